@@ -0,0 +1,30 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Embeds what `--version` and the document's `meta` section report: the git
+/// commit, the build time and the target triple.
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible.
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+
+    println!("cargo:rustc-env=SALTBOX_FACTS_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=SALTBOX_FACTS_BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rustc-env=SALTBOX_FACTS_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+    // Rebuilt on a new commit or checkout; otherwise the build time is that of the
+    // last such rebuild.
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
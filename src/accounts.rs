@@ -0,0 +1,175 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+use crate::facts::Truncation;
+use crate::mmap::{self, Contents};
+
+pub const GROUP_FILE_PATH: &str = "/etc/group";
+pub const PASSWD_FILE_PATH: &str = "/etc/passwd";
+
+/// Fields in a passwd line; group lines have four.
+const MAX_FIELDS: usize = 7;
+
+/// A user's passwd fields, keyed by name in the users section.
+// Fields borrow from the mapped file.
+#[derive(Serialize, JsonSchema)]
+pub struct User<'a> {
+    pub uid: &'a str,
+    pub gid: &'a str,
+    pub comment: &'a str,
+    pub home: &'a str,
+    pub shell: &'a str,
+}
+
+/// A group's gid and members, keyed by name in the groups section.
+#[derive(Serialize, JsonSchema)]
+pub struct Group<'a> {
+    pub gid: &'a str,
+    #[serde(rename = "group-list")]
+    pub group_list: Vec<&'a str>,
+}
+
+/// Entries by name, borrowing from the file they were parsed from, and the
+/// truncation when `max_entries` left some out.
+pub type Entries<'a, T> = (BTreeMap<&'a str, T>, Option<Truncation>);
+
+/// The users or groups section: the mapped file, parsed again as it's serialized so
+/// entries go from the file into the output without another copy.
+pub struct Listing {
+    contents: Contents,
+    file_path: &'static str,
+    max_entries: usize,
+    groups: bool,
+}
+
+impl Listing {
+    /// The users in PASSWD_FILE_PATH, and the truncation when `max_entries` left
+    /// some out.
+    pub fn users(max_entries: usize) -> Result<(Listing, Option<Truncation>), Box<dyn std::error::Error>> {
+        let contents = read(PASSWD_FILE_PATH)?;
+        let (_, truncated) = users(&contents, PASSWD_FILE_PATH, max_entries)?;
+        Ok((Listing { contents, file_path: PASSWD_FILE_PATH, max_entries, groups: false }, truncated))
+    }
+
+    /// The groups in GROUP_FILE_PATH, and the truncation when `max_entries` left
+    /// some out.
+    pub fn groups(max_entries: usize) -> Result<(Listing, Option<Truncation>), Box<dyn std::error::Error>> {
+        let contents = read(GROUP_FILE_PATH)?;
+        let (_, truncated) = groups(&contents, GROUP_FILE_PATH, max_entries)?;
+        Ok((Listing { contents, file_path: GROUP_FILE_PATH, max_entries, groups: true }, truncated))
+    }
+}
+
+impl Serialize for Listing {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let custom = |e: Box<dyn std::error::Error>| serde::ser::Error::custom(e);
+        match self.groups {
+            true => groups(&self.contents, self.file_path, self.max_entries).map_err(custom)?.0.serialize(serializer),
+            false => users(&self.contents, self.file_path, self.max_entries).map_err(custom)?.0.serialize(serializer),
+        }
+    }
+}
+
+/// Maps a passwd- or group-format file for `users` or `groups` to parse in place.
+pub fn read(file_path: &str) -> Result<Contents, Box<dyn std::error::Error>> {
+    tracing::debug!("Reading {}", file_path);
+    Ok(mmap::read(file_path)?)
+}
+
+/// The users in a passwd-format file, at most `max_entries` (0 for no limit).
+pub fn users<'a>(contents: &'a [u8], file_path: &str, max_entries: usize) -> Result<Entries<'a, User<'a>>, Box<dyn std::error::Error>> {
+    parse(contents, file_path, 7, max_entries, |tokens, _| User {
+        uid: tokens[2],
+        gid: tokens[3],
+        comment: tokens[4],
+        home: tokens[5],
+        shell: tokens[6],
+    })
+}
+
+/// The groups in a group-format file, at most `max_entries` (0 for no limit).
+pub fn groups<'a>(contents: &'a [u8], file_path: &str, max_entries: usize) -> Result<Entries<'a, Group<'a>>, Box<dyn std::error::Error>> {
+    parse(contents, file_path, 3, max_entries, |tokens, count| Group {
+        gid: tokens[2],
+        group_list: if count > 3 { tokens[3].split(',').collect() } else { Vec::new() },
+    })
+}
+
+/// Splits the lines of a passwd- or group-format file in place and builds an entry
+/// from each with at least `min_tokens` fields, keyed by name. Lines past
+/// `max_entries` are still counted so the truncation can be reported.
+fn parse<'a, T>(
+    contents: &'a [u8],
+    file_path: &str,
+    min_tokens: usize,
+    max_entries: usize,
+    entry: impl Fn(&[&'a str; MAX_FIELDS], usize) -> T,
+) -> Result<Entries<'a, T>, Box<dyn std::error::Error>> {
+    let mut data: BTreeMap<&str, T> = BTreeMap::new();
+    let mut entries = 0;
+    let mut omitted = 0;
+
+    for line in contents.split(|&byte| byte == b'\n') {
+        let line = std::str::from_utf8(line).map_err(|e| format!("Cannot read {}: {}", file_path, e))?;
+        let mut tokens = [""; MAX_FIELDS];
+        let mut count = 0;
+        for (index, token) in line.trim_end_matches('\r').split(':').enumerate() {
+            if index < MAX_FIELDS {
+                tokens[index] = token;
+            }
+            count = index + 1;
+        }
+        if count < min_tokens {
+            continue;
+        }
+        entries += 1;
+        if max_entries > 0 && data.len() >= max_entries && !data.contains_key(tokens[0]) {
+            omitted += 1;
+            continue;
+        }
+        data.insert(tokens[0], entry(&tokens, count));
+    }
+
+    let truncation = (omitted > 0).then_some(Truncation {
+        limit: max_entries,
+        entries,
+    });
+    Ok((data, truncation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::Section;
+    use serde_json::json;
+
+    const PASSWD: &[u8] = b"root:x:0:0:root:/root:/bin/bash\nseed:x:1000:1000:Seed,,,:/home/seed:/bin/zsh\r\nshort:x:1\n";
+    const GROUP: &[u8] = b"root:x:0:\ndocker:x:998:seed,plex\n";
+
+    #[test]
+    fn entries_are_parsed_in_place_and_typed_sections_serialize_as_json() {
+        let (users, truncated) = users(PASSWD, "passwd", 0).unwrap();
+        assert!(truncated.is_none());
+        let expected = json!({
+            "root": { "uid": "0", "gid": "0", "comment": "root", "home": "/root", "shell": "/bin/bash" },
+            "seed": { "uid": "1000", "gid": "1000", "comment": "Seed,,,", "home": "/home/seed", "shell": "/bin/zsh" }
+        });
+        assert_eq!(serde_json::to_value(&users).unwrap(), expected);
+
+        let (groups, _) = groups(GROUP, "group", 0).unwrap();
+        let section = Section::typed(groups);
+        assert_eq!(
+            serde_json::to_string(&section.value).unwrap(),
+            json!({ "docker": { "gid": "998", "group-list": ["seed", "plex"] }, "root": { "gid": "0", "group-list": [""] } }).to_string()
+        );
+    }
+
+    #[test]
+    fn max_entries_truncates_and_counts_every_entry() {
+        let (users, truncated) = users(PASSWD, "passwd", 1).unwrap();
+        assert_eq!(users.keys().copied().collect::<Vec<_>>(), ["root"]);
+        let truncated = truncated.unwrap();
+        assert_eq!((truncated.limit, truncated.entries), (1, 2));
+    }
+}
@@ -0,0 +1,116 @@
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::binaries::find_in_path;
+use crate::errors::{Code, Error};
+use crate::tool_versions::{extract_version, run_version};
+
+const SALTBOX_VENV_ANSIBLE_PATH: &str = "/srv/ansible/venv/bin/ansible";
+const SALTBOX_CONFIG_PATH: &str = "/srv/git/saltbox/ansible.cfg";
+const SYSTEM_CONFIG_PATH: &str = "/etc/ansible/ansible.cfg";
+const SYSTEM_INVENTORY_PATH: &str = "/etc/ansible/hosts";
+/// `ansible --version` starts a Python interpreter and imports the whole of ansible-core.
+const ANSIBLE_VERSION_TIMEOUT: u64 = 10;
+
+/// Detects whether this host carries an Ansible installation that manages hosts
+/// (itself included, as Saltbox does), along with its version and config file.
+pub async fn get_ansible_controller() -> Value {
+    let saltbox_venv = Path::new(SALTBOX_VENV_ANSIBLE_PATH).exists();
+    let ansible_path = if saltbox_venv {
+        Some(PathBuf::from(SALTBOX_VENV_ANSIBLE_PATH))
+    } else {
+        find_in_path("ansible")
+    };
+
+    let mut version = None;
+    let mut reported_config = None;
+    let mut error = None;
+    if let Some(path) = &ansible_path {
+        match timeout(Duration::from_secs(ANSIBLE_VERSION_TIMEOUT), run_version(path)).await {
+            Ok(Ok(output)) => {
+                version = output.lines().next().and_then(extract_version);
+                reported_config = output
+                    .lines()
+                    .find_map(|line| line.trim().strip_prefix("config file = "))
+                    .map(str::trim)
+                    .filter(|path| *path != "None")
+                    .map(PathBuf::from);
+            }
+            Ok(Err(e)) => error = Some(Error::run("ansible --version", &e)),
+            Err(_) => error = Some(Error::new(Code::Timeout, "ansible --version", format!("ansible --version timed out after {}s", ANSIBLE_VERSION_TIMEOUT))),
+        }
+    }
+
+    let config_file = reported_config.or_else(find_config_file);
+    let inventory_files = find_inventory_files(config_file.as_deref());
+    let is_controller = ansible_path.is_some() && (config_file.is_some() || !inventory_files.is_empty());
+
+    json!({
+        "is_controller": is_controller,
+        "ansible_path": ansible_path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+        "version": version,
+        "config_file": config_file.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+        "inventory_files": inventory_files,
+        "saltbox_venv": saltbox_venv,
+        "error": error
+    })
+}
+
+/// Ansible's own search order, with the Saltbox checkout consulted before the system file.
+fn find_config_file() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(path) = env::var("ANSIBLE_CONFIG") {
+        candidates.push(PathBuf::from(path));
+    }
+    candidates.push(PathBuf::from("ansible.cfg"));
+    if let Ok(home) = env::var("HOME") {
+        candidates.push(Path::new(&home).join(".ansible.cfg"));
+    }
+    candidates.push(PathBuf::from(SALTBOX_CONFIG_PATH));
+    candidates.push(PathBuf::from(SYSTEM_CONFIG_PATH));
+
+    candidates
+        .into_iter()
+        .find(|path| path.is_file())
+        .map(|path| fs::canonicalize(&path).unwrap_or(path))
+}
+
+/// Collects existing inventories named by `inventory =` in the config's `[defaults]`,
+/// falling back to /etc/ansible/hosts.
+fn find_inventory_files(config_file: Option<&Path>) -> Vec<String> {
+    let mut inventories = Vec::new();
+
+    if let Some(config_file) = config_file {
+        if let Ok(contents) = fs::read_to_string(config_file) {
+            let base = config_file.parent().unwrap_or(Path::new("/"));
+            let mut in_defaults = false;
+            for line in contents.lines().map(str::trim) {
+                if line.starts_with('[') {
+                    in_defaults = line == "[defaults]";
+                    continue;
+                }
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                if in_defaults && matches!(key.trim(), "inventory" | "hostfile") {
+                    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+                        let path = base.join(entry);
+                        if path.exists() {
+                            inventories.push(path.to_string_lossy().into_owned());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if inventories.is_empty() && Path::new(SYSTEM_INVENTORY_PATH).exists() {
+        inventories.push(SYSTEM_INVENTORY_PATH.to_string());
+    }
+
+    inventories
+}
@@ -0,0 +1,89 @@
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::cli::BenchArgs;
+use crate::collectors::{self, Context};
+use crate::exec;
+use crate::output::ReportFormat;
+
+/// Collectors left out unless named with `--collectors`, since repeating them
+/// queries third-party services.
+const NETWORK_COLLECTORS: &[&str] = &["ip"];
+
+/// Runs each collector `--iterations` times on this host and reports min, median
+/// and max wall time and how many subprocesses a run starts.
+pub async fn run(args: BenchArgs) -> Result<(), Box<dyn Error>> {
+    let names: Vec<String> = match &args.collectors {
+        Some(names) => {
+            if let Some(unknown) = names.iter().find(|name| collectors::find(name).is_none()) {
+                return Err(format!("Unknown collector: {} (expected one of {})", unknown, collectors::names().join(", ")).into());
+            }
+            names.clone()
+        }
+        None => collectors::names()
+            .into_iter()
+            .filter(|name| !NETWORK_COLLECTORS.contains(name))
+            .map(|name| name.to_string())
+            .collect(),
+    };
+    let context = Context::new(&args.gather)?;
+
+    let mut results = Map::new();
+    for name in &names {
+        let mut durations = Vec::with_capacity(args.iterations);
+        let mut subprocesses = 0;
+        let mut error = None;
+        for _ in 0..args.iterations {
+            let spawned_before = exec::spawned();
+            let started = Instant::now();
+            if let Err(e) = collectors::collect(name, &context).await {
+                error = Some(e.to_string());
+            }
+            durations.push(started.elapsed());
+            subprocesses = subprocesses.max(exec::spawned() - spawned_before);
+        }
+        durations.sort();
+        results.insert(
+            name.clone(),
+            json!({
+                "min_ms": millis(durations[0]),
+                "median_ms": millis(durations[durations.len() / 2]),
+                "max_ms": millis(durations[durations.len() - 1]),
+                "subprocesses": subprocesses,
+                "error": error
+            }),
+        );
+    }
+
+    match args.format {
+        ReportFormat::Json => println!("{}", json!({ "iterations": args.iterations, "collectors": results })),
+        ReportFormat::Text => {
+            println!(
+                "{:<20} {:>10} {:>10} {:>10} {:>13}",
+                "collector", "min", "median", "max", "subprocesses"
+            );
+            for name in &names {
+                let result = &results[name.as_str()];
+                let cell = |key: &str| format!("{:.1}ms", result[key].as_f64().unwrap_or_default());
+                println!(
+                    "{:<20} {:>10} {:>10} {:>10} {:>13}",
+                    name,
+                    cell("min_ms"),
+                    cell("median_ms"),
+                    cell("max_ms"),
+                    result["subprocesses"]
+                );
+                if let Some(error) = result["error"].as_str() {
+                    println!("  error: {}", error);
+                }
+            }
+            println!("{} iteration{} each", args.iterations, if args.iterations == 1 { "" } else { "s" });
+        }
+    }
+    Ok(())
+}
+
+fn millis(duration: Duration) -> Value {
+    json!((duration.as_secs_f64() * 10_000.0).round() / 10.0)
+}
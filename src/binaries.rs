@@ -0,0 +1,71 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Reports, for each requested binary, whether it is on PATH and where it resolves to.
+pub fn get_binaries(names: &[String]) -> Value {
+    let mut binaries = BTreeMap::new();
+
+    for name in names {
+        let value = match find_in_path(name) {
+            Some(path) => {
+                let resolved = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                json!({
+                    "present": true,
+                    "path": path.to_string_lossy(),
+                    "resolved": resolved.to_string_lossy()
+                })
+            }
+            None => json!({
+                "present": false,
+                "path": "",
+                "resolved": ""
+            }),
+        };
+        binaries.insert(name.clone(), value);
+    }
+
+    json!(binaries)
+}
+
+/// Equivalent of `command -v`: the first executable regular file named `name` on PATH.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    if name.contains(std::path::is_separator) {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    let path_var = env::var_os("PATH")?;
+    env::split_paths(&path_var)
+        .flat_map(|dir| candidates(&dir, name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    vec![dir.join(name)]
+}
+
+/// On Windows `docker` is found as docker.exe, or with any other PATHEXT extension.
+#[cfg(not(unix))]
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    std::iter::once(dir.join(name))
+        .chain(extensions.split(';').filter(|ext| !ext.is_empty()).map(|ext| dir.join(format!("{}{}", name, ext))))
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).map(|metadata| metadata.is_file()).unwrap_or(false)
+}
@@ -0,0 +1,27 @@
+use serde_json::{json, Value};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::timestamp::format_rfc3339;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Abbreviated commit the binary was built from, or `unknown` outside a git checkout.
+pub const GIT_COMMIT: &str = env!("SALTBOX_FACTS_GIT_COMMIT");
+pub const TARGET: &str = env!("SALTBOX_FACTS_TARGET");
+const BUILD_TIMESTAMP: &str = env!("SALTBOX_FACTS_BUILD_TIMESTAMP");
+
+pub fn build_time() -> String {
+    format_rfc3339(UNIX_EPOCH + Duration::from_secs(BUILD_TIMESTAMP.parse().unwrap_or(0)))
+}
+
+/// The `meta` section: exactly which binary produced a document.
+pub fn meta() -> Value {
+    json!({ "version": VERSION, "git_commit": GIT_COMMIT, "build_time": build_time(), "target": TARGET })
+}
+
+/// Prints `--version`.
+pub fn print_version() {
+    println!("saltbox-facts {}", VERSION);
+    println!("commit: {}", GIT_COMMIT);
+    println!("built:  {}", build_time());
+    println!("target: {}", TARGET);
+}
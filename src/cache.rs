@@ -0,0 +1,188 @@
+use serde_json::{json, Value};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{CacheAction, CacheArgs};
+use crate::output::ReportFormat;
+use crate::platform;
+use crate::timestamp::now_secs;
+
+const SYSTEM_CACHE_DIR: &str = "/var/cache/ansible-facts";
+
+/// Small JSON entries persisted between runs, one file per entry.
+///
+/// The cache is best-effort: if the directory can't be created or written, every
+/// read misses and every write is dropped, and collection proceeds uncached.
+pub struct Cache {
+    dir: Option<PathBuf>,
+}
+
+impl Cache {
+    /// Opens `dir`, or the default: /var/cache/ansible-facts for root, otherwise
+    /// `$XDG_CACHE_HOME/ansible-facts` (falling back to ~/.cache/ansible-facts).
+    pub fn open(dir: Option<&str>) -> Cache {
+        let dir = dir.map(PathBuf::from).or_else(default_dir);
+        let dir = dir.filter(|dir| match fs::create_dir_all(dir) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Cache disabled: cannot create {}: {}", dir.display(), e);
+                false
+            }
+        });
+        Cache { dir }
+    }
+
+    /// The directory in use, or None if no cache directory could be created.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Returns the entry and the unix time it was stored at.
+    pub fn read(&self, name: &str) -> Option<(u64, Value)> {
+        let contents = fs::read_to_string(self.path(name)?).ok()?;
+        let entry: Value = serde_json::from_str(&contents).ok()?;
+        Some((entry.get("stored_at")?.as_u64()?, entry.get("value")?.clone()))
+    }
+
+    /// Writes via a temporary file and rename so concurrent runs never read a torn entry.
+    pub fn write(&self, name: &str, value: &Value) {
+        let Some(path) = self.path(name) else {
+            return;
+        };
+        let entry = json!({ "stored_at": now_secs(), "value": value });
+        let temp = path.with_extension(format!("tmp.{}", std::process::id()));
+        if fs::write(&temp, entry.to_string()).is_ok() && fs::rename(&temp, &path).is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+    }
+
+    /// Every entry's name, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".json").map(String::from))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Removes an entry; a missing one is not an error.
+    pub fn remove(&self, name: &str) -> std::io::Result<()> {
+        match self.path(name).map(fs::remove_file) {
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.json", name)))
+    }
+}
+
+fn default_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("LOCALAPPDATA").map(|dir| Path::new(&dir).join("ansible-facts"));
+    }
+    if platform::is_root() {
+        return Some(PathBuf::from(SYSTEM_CACHE_DIR));
+    }
+    match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(Path::new(&dir).join("ansible-facts")),
+        _ => env::var_os("HOME").map(|home| Path::new(&home).join(".cache").join("ansible-facts")),
+    }
+}
+
+/// `cache list|clear|dir`.
+pub fn run(args: CacheArgs) -> Result<(), Box<dyn Error>> {
+    let cache = Cache::open(args.cache_dir.as_deref());
+    let Some(dir) = cache.dir() else {
+        return Err("No cache directory could be created; pass --cache-dir".into());
+    };
+    match args.action {
+        CacheAction::Dir => println!("{}", dir.display()),
+        CacheAction::List => {
+            let now = now_secs();
+            let entries: Vec<Value> = cache
+                .names()
+                .into_iter()
+                .map(|name| {
+                    let bytes = cache.path(&name).and_then(|path| fs::metadata(path).ok()).map(|metadata| metadata.len());
+                    let stored_at = cache.read(&name).map(|(stored_at, _)| stored_at);
+                    json!({ "name": name, "stored_at": stored_at, "age_secs": stored_at.map(|stored_at| now.saturating_sub(stored_at)), "bytes": bytes })
+                })
+                .collect();
+            match args.format {
+                ReportFormat::Json => println!("{}", json!({ "dir": dir.display().to_string(), "entries": entries })),
+                ReportFormat::Text => {
+                    println!("{}", dir.display());
+                    for entry in &entries {
+                        let age = entry["age_secs"].as_u64().map_or_else(|| "unreadable".to_string(), |age| format!("stored {} s ago", age));
+                        println!("  {:<32} {:>8} bytes  {}", entry["name"].as_str().unwrap_or_default(), entry["bytes"].as_u64().unwrap_or(0), age);
+                    }
+                }
+            }
+        }
+        CacheAction::Clear => {
+            let names = args.names;
+            let existing = cache.names();
+            if let Some(unknown) = names.iter().find(|name| !existing.contains(name)) {
+                return Err(format!("No cache entry named {} in {}", unknown, dir.display()).into());
+            }
+            let names = if names.is_empty() { existing } else { names };
+            for name in &names {
+                cache.remove(name).map_err(|e| format!("Cannot remove {} from {}: {}", name, dir.display(), e))?;
+            }
+            eprintln!("Removed {} entries from {}", names.len(), dir.display());
+        }
+    }
+    Ok(())
+}
+
+/// Caps outbound requests to external services across all runs on this host,
+/// tracking request times in the cache.
+pub struct RateLimiter<'a> {
+    cache: &'a Cache,
+    limit: usize,
+    window_secs: u64,
+    requests: Vec<u64>,
+}
+
+const RATE_LIMIT_ENTRY: &str = "outbound_requests";
+
+impl<'a> RateLimiter<'a> {
+    pub fn load(cache: &'a Cache, limit: usize, window_secs: u64) -> RateLimiter<'a> {
+        let requests = cache
+            .read(RATE_LIMIT_ENTRY)
+            .and_then(|(_, value)| value.as_array().map(|items| items.iter().filter_map(Value::as_u64).collect()))
+            .unwrap_or_default();
+        RateLimiter {
+            cache,
+            limit,
+            window_secs,
+            requests,
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        let now = now_secs();
+        self.requests.iter().filter(|&&time| time + self.window_secs > now && time <= now).count() >= self.limit
+    }
+
+    /// Records a request and returns true, or returns false if the budget is spent.
+    pub fn try_acquire(&mut self) -> bool {
+        let now = now_secs();
+        self.requests.retain(|&time| time + self.window_secs > now && time <= now);
+        if self.requests.len() >= self.limit {
+            return false;
+        }
+        self.requests.push(now);
+        self.cache.write(RATE_LIMIT_ENTRY, &json!(self.requests));
+        true
+    }
+}
@@ -0,0 +1,150 @@
+use std::io;
+use std::sync::Mutex;
+
+use crate::errors::{Code, Error};
+
+/// What a collector may do, as declared in the registry.
+#[derive(Clone, Copy)]
+pub struct Capabilities {
+    /// Runs external commands.
+    pub exec: bool,
+    /// Opens network connections.
+    pub network: bool,
+    /// Reads data only root can access; without root the section is incomplete.
+    /// Declarative only: unlike exec and network, it can't be observed at runtime.
+    pub root: bool,
+}
+
+/// Declarations are enforced for the collector currently running. Collectors run
+/// one at a time, so a single slot is enough.
+struct State {
+    current: Option<(&'static str, Capabilities)>,
+    no_exec: bool,
+    offline: bool,
+    violations: Vec<String>,
+    /// Commands started and endpoints contacted since the last `take_usage`.
+    commands: Vec<String>,
+    endpoints: Vec<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    current: None,
+    no_exec: false,
+    offline: false,
+    violations: Vec::new(),
+    commands: Vec::new(),
+    endpoints: Vec::new(),
+});
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Applies `--no-exec` / `--offline` for the rest of the process.
+pub fn restrict(no_exec: bool, offline: bool) {
+    let mut state = state();
+    state.no_exec = no_exec;
+    state.offline = offline;
+}
+
+pub fn enter(collector: &'static str, capabilities: Capabilities) {
+    state().current = Some((collector, capabilities));
+}
+
+pub fn leave() {
+    state().current = None;
+}
+
+/// Violations recorded so far: collectors that tried to exec or use the network
+/// without declaring it.
+pub fn take_violations() -> Vec<String> {
+    std::mem::take(&mut state().violations)
+}
+
+/// Adds violations recorded elsewhere, e.g. in a worker process.
+pub fn record_violations(violations: impl IntoIterator<Item = String>) {
+    state().violations.extend(violations);
+}
+
+/// Commands run and endpoints contacted since the last call, for `--profile`.
+pub fn take_usage() -> (Vec<String>, Vec<String>) {
+    let mut state = state();
+    (std::mem::take(&mut state.commands), std::mem::take(&mut state.endpoints))
+}
+
+/// Adds usage recorded elsewhere, e.g. in a worker process.
+pub fn record_usage(commands: impl IntoIterator<Item = String>, endpoints: impl IntoIterator<Item = String>) {
+    let mut state = state();
+    for command in commands {
+        push_unique(&mut state.commands, command);
+    }
+    for endpoint in endpoints {
+        push_unique(&mut state.endpoints, endpoint);
+    }
+}
+
+/// Records an endpoint contacted: a local one that isn't subject to `--offline`, like
+/// the Docker socket, or one of a group checked with [`check_network_group`].
+pub fn record_endpoint(endpoint: &str) {
+    push_unique(&mut state().endpoints, endpoint.to_string());
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+/// Called before any subprocess is started; refuses when the running collector
+/// didn't declare exec or `--no-exec` is in effect.
+pub fn check_exec(program: &str) -> io::Result<()> {
+    let mut state = state();
+    if state.no_exec {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("not running {}: --no-exec is set", program)));
+    }
+    match state.current {
+        Some((collector, capabilities)) if !capabilities.exec => {
+            let message = format!("{} ran {} without declaring exec", collector, program);
+            tracing::warn!("Capability violation: {}", message);
+            state.violations.push(message.clone());
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, message))
+        }
+        _ => {
+            push_unique(&mut state.commands, program.to_string());
+            Ok(())
+        }
+    }
+}
+
+/// Called before any outbound connection; refuses when the running collector
+/// didn't declare network access or `--offline` is in effect.
+pub fn check_network(target: &str) -> Result<(), Error> {
+    check_network_for(target, true)
+}
+
+/// As [`check_network`], for a group of targets named by `label`; the collector
+/// records each target it actually contacts with [`record_endpoint`].
+pub fn check_network_group(label: &str) -> Result<(), Error> {
+    check_network_for(label, false)
+}
+
+fn check_network_for(target: &str, record: bool) -> Result<(), Error> {
+    let mut state = state();
+    if state.offline {
+        return Err(Error::new(Code::Disabled, target, format!("not connecting to {}: --offline is set", target)));
+    }
+    match state.current {
+        Some((collector, capabilities)) if !capabilities.network => {
+            let message = format!("{} connected to {} without declaring network access", collector, target);
+            tracing::warn!("Capability violation: {}", message);
+            state.violations.push(message.clone());
+            Err(Error::new(Code::PermissionDenied, target, message))
+        }
+        _ => {
+            if record {
+                push_unique(&mut state.endpoints, target.to_string());
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,1062 @@
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::env;
+use std::iter;
+use std::net::SocketAddr;
+use std::process;
+use tracing::level_filters::LevelFilter;
+
+use crate::build_info;
+use crate::collectors;
+use crate::compare::Severity;
+use crate::config;
+use crate::container_restarts::{DEFAULT_FLAP_RESTARTS, DEFAULT_FLAP_WINDOW_MINUTES};
+use crate::coredumps::{DEFAULT_CRASH_THRESHOLD, DEFAULT_CRASH_WINDOW_DAYS};
+use crate::derived::{self, Definition};
+use crate::diff::DiffFormat;
+use crate::dir_sizes;
+use crate::drop_ins::DEFAULT_DROP_IN_DIR;
+use crate::dns;
+use crate::docker_networks::Ipv4Net;
+use crate::download_clients::{self, DownloadClient, DEFAULT_GLUETUN_API};
+use crate::echo;
+use crate::http::{self, HttpOptions};
+use crate::ip::DEFAULT_HTTP_TIMEOUT;
+use crate::logging;
+use crate::media_servers::{DEFAULT_EMBY_URL, DEFAULT_JELLYFIN_URL, DEFAULT_PLEX_PREFERENCES_PATH, DEFAULT_PLEX_URL};
+use crate::nagios::{self, Check};
+use crate::output::{self, OutputFormat, ReportFormat};
+use crate::ownership;
+use crate::policy::{self, Rule};
+use crate::processes::{DEFAULT_CPU_THRESHOLD, DEFAULT_MEMORY_THRESHOLD_MB};
+use crate::query::Query;
+use crate::schema_version;
+use crate::section_cache::{self, Ttl};
+use crate::sso::DEFAULT_AUTHELIA_CONFIG_PATH;
+use crate::static_facts::Precedence;
+use crate::traefik::{DEFAULT_ACME_PATH, DEFAULT_TRAEFIK_API};
+use crate::wireguard::DEFAULT_STALE_AFTER_SECS;
+use crate::worker;
+
+/// The program name command lines are parsed with.
+pub const BIN: &str = "saltbox-facts";
+
+const GATHER_AFTER_HELP: &str = "\
+Config file:
+  Keys are long options without the dashes (`_` may stand for `-`), e.g.
+  `format = \"yaml\"` or `log_path = [\"/var/log\"]`; the keys of a [table] get its
+  name as a prefix, so `timeout` under [http] is --http-timeout. `true` sets a flag
+  and `false` turns it off.
+
+Environment:
+  ANSIBLE_FACTS_<OPTION> sets any option too, e.g. ANSIBLE_FACTS_HTTP_TIMEOUT=10 or
+  ANSIBLE_FACTS_NO_IPV6=true; `false` turns a flag off, and a repeatable option
+  takes one value per line. ANSIBLE_FACTS_CONFIG and ANSIBLE_FACTS_NO_CONFIG=true
+  stand in for --config and --no-config. Other ANSIBLE_FACTS_ variables are ignored.
+
+  The config file is applied first, then the environment, then the command line, so
+  the command line wins over the environment, which wins over the file and the
+  defaults. An option given in a later layer replaces the earlier value, lists and
+  repeatable options included.
+
+Exit status:
+  0  Every collector that ran succeeded
+  1  Fatal error, including a collector failure with --strict
+  2  Invalid arguments
+  3  Some collectors failed; their errors are listed in the failed section
+  4  Every collector that ran failed";
+
+/// Variables reported when `--env-vars` is not given.
+pub const DEFAULT_ENV_VARS: &[&str] = &[
+    "TZ",
+    "LANG",
+    "LANGUAGE",
+    "LC_*",
+    "http_proxy",
+    "https_proxy",
+    "ftp_proxy",
+    "all_proxy",
+    "no_proxy",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "FTP_PROXY",
+    "ALL_PROXY",
+    "NO_PROXY",
+    "XDG_*",
+];
+
+/// Binaries checked when `--binaries` is not given.
+pub const DEFAULT_BINARIES: &[&str] = &["docker", "python3", "git", "curl", "unzip"];
+
+/// Commands probed when `--tool-versions` is not given.
+pub const DEFAULT_TOOL_VERSIONS: &[&str] = &["docker", "python3", "git", "curl"];
+
+/// Collects facts about this host for Saltbox's Ansible roles
+///
+/// Without a subcommand, or with `gather`, facts are collected from this host.
+#[derive(Parser)]
+#[command(name = BIN, args_conflicts_with_subcommands = true, disable_help_subcommand = true, disable_version_flag = true, args_override_self = true, after_help = GATHER_AFTER_HELP)]
+struct Cli {
+    #[command(flatten)]
+    args: Args,
+    /// Print the version, git commit, build time and target
+    #[arg(short = 'V', long)]
+    version: bool,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+/// The options of a gather run on their own, as given after `--` to bench, serve
+/// and remote.
+#[derive(Parser)]
+#[command(name = BIN, args_override_self = true)]
+struct GatherLine {
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Collect facts from this host (the default)
+    #[command(args_override_self = true, after_help = GATHER_AFTER_HELP)]
+    Gather(Box<Args>),
+    /// Print one value from a gather run
+    ///
+    /// Prints one value, e.g. `saltbox-facts get ip.public_ip`: strings raw, anything
+    /// else as compact JSON. PATH is a --query expression; when it starts with a
+    /// collector's name only that collector runs. The options are those of a normal
+    /// run.
+    #[command(args_override_self = true)]
+    Get(Box<GetArgs>),
+    /// Collect facts from hosts over SSH
+    ///
+    /// Collects facts from each host over SSH and aggregates them into one document.
+    /// Arguments after `--` are passed to the collector on every host.
+    Remote(RemoteArgs),
+    /// Report the differences between two fact documents
+    ///
+    /// Reports the differences between two fact documents, e.g. before and after
+    /// maintenance or between two hosts, most severe first.
+    Compare(CompareArgs),
+    /// Check a saved document against a schema and policies
+    ///
+    /// Checks a saved facts document and exits non-zero if it is malformed or out of
+    /// policy.
+    Validate(ValidateArgs),
+    /// Time each collector on this host
+    ///
+    /// Runs each collector repeatedly on this host and prints min/median/max durations
+    /// and the subprocesses each run starts. Arguments after `--` configure the
+    /// collectors as they would for a normal run.
+    Bench(Box<BenchArgs>),
+    /// Check what the collectors need
+    ///
+    /// Checks what the collectors need (files, sockets, commands, privileges, cache
+    /// and outbound connectivity) and suggests fixes. Exits non-zero if a required
+    /// prerequisite is missing.
+    Doctor(DoctorArgs),
+    /// Print the JSON Schema of the document
+    ///
+    /// Prints a JSON Schema describing the document a gather run prints, including
+    /// optional sections and error fields, for validating fact payloads downstream.
+    Schema {
+        /// Describe the shape of an earlier schema version, as a gather run with the
+        /// same option prints it
+        #[arg(long, value_name = "N", value_parser = schema_version::parse, default_value_t = schema_version::CURRENT)]
+        schema_version: u32,
+    },
+    /// List or clear the cache
+    ///
+    /// Manages the cache of lookups, incremental sections and the outbound request
+    /// budget.
+    Cache(CacheArgs),
+    /// Serve facts over HTTP
+    ///
+    /// Serves facts over HTTP, collecting afresh on each request: `GET /facts` returns
+    /// the document and `GET /facts/<PATH>` the value `get <PATH>` prints. Up to
+    /// --max-connections requests are answered at once; later ones wait. Arguments
+    /// after `--` configure the collectors as they would for a normal run.
+    Serve(Box<ServeArgs>),
+    /// Print a shell completion script
+    ///
+    /// Prints a completion script for bash, zsh, fish, elvish or powershell, covering
+    /// every subcommand, option and collector name, e.g.:
+    ///   saltbox-facts completions bash > /etc/bash_completion.d/saltbox-facts
+    ///   saltbox-facts completions zsh > "${fpath[1]}/_saltbox-facts"
+    ///   saltbox-facts completions fish > ~/.config/fish/completions/saltbox-facts.fish
+    #[command(verbatim_doc_comment)]
+    Completions { shell: clap_complete::Shell },
+}
+
+pub enum Command {
+    Gather(Box<Args>),
+    Remote(RemoteArgs),
+    Compare(CompareArgs),
+    Validate(ValidateArgs),
+    Bench(Box<BenchArgs>),
+    Doctor(DoctorArgs),
+    Schema(u32),
+    Cache(CacheArgs),
+    Serve(Box<ServeArgs>),
+    Completions(clap_complete::Shell),
+    /// Internal: collects one section in a worker process.
+    Worker(String, Box<Args>),
+}
+
+impl Command {
+    pub fn parse() -> Result<Command, clap::Error> {
+        Command::parse_from(env::args().skip(1).collect())
+    }
+
+    /// Parses the arguments after the program name; gather runs keep them in
+    /// [`Args::argv`].
+    fn parse_from(argv: Vec<String>) -> Result<Command, clap::Error> {
+        // `__worker <COLLECTOR> <GATHER ARGS>...` is internal, so it stays out of
+        // the help and the completion scripts clap derives from `Cli`.
+        if let Some((worker::SUBCOMMAND, rest)) = argv.split_first().map(|(first, rest)| (first.as_str(), rest)) {
+            return match rest.split_first() {
+                Some((collector, argv)) => match Command::parse_from(argv.to_vec())? {
+                    Command::Gather(args) => Ok(Command::Worker(collector.clone(), args)),
+                    _ => Err(invalid("a worker takes the arguments of a gather run")),
+                },
+                None => Err(invalid("a worker takes a collector and the arguments of a gather run")),
+            };
+        }
+        let matches = Cli::command().try_get_matches_from(iter::once(BIN.to_string()).chain(argv.iter().cloned()))?;
+        let cli = Cli::from_arg_matches(&matches)?;
+        if cli.version {
+            build_info::print_version();
+            process::exit(0);
+        }
+        let subcommand_matches = || matches.subcommand().map(|(_, matches)| matches.clone()).unwrap_or_default();
+        Ok(match cli.command {
+            None => Command::Gather(Box::new(Args::layered(matches.clone(), argv)?)),
+            Some(CliCommand::Gather(_)) => Command::Gather(Box::new(Args::layered(subcommand_matches(), argv)?)),
+            Some(CliCommand::Get(get)) => Command::Gather(Box::new(select(get.path, Args::layered(subcommand_matches(), argv)?)?)),
+            Some(CliCommand::Remote(args)) => {
+                // Validate up front rather than failing identically on every host.
+                Args::parse_from(args.collector_args.clone())?;
+                Command::Remote(args)
+            }
+            Some(CliCommand::Compare(args)) => Command::Compare(args),
+            Some(CliCommand::Validate(args)) => Command::Validate(args),
+            Some(CliCommand::Bench(mut args)) => {
+                args.gather = Args::parse_from(args.collector_args.clone())?;
+                Command::Bench(args)
+            }
+            Some(CliCommand::Doctor(args)) => Command::Doctor(args),
+            Some(CliCommand::Schema { schema_version }) => Command::Schema(schema_version),
+            Some(CliCommand::Cache(args)) if !args.names.is_empty() && !matches!(args.action, CacheAction::Clear) => {
+                return Err(invalid("only cache clear takes entry names"))
+            }
+            Some(CliCommand::Cache(args)) => Command::Cache(args),
+            Some(CliCommand::Serve(mut args)) => {
+                args.gather = Args::parse_from(args.collector_args.clone())?;
+                Command::Serve(args)
+            }
+            Some(CliCommand::Completions { shell }) => Command::Completions(shell),
+        })
+    }
+}
+
+/// The whole command line as clap describes it, which `completions` generates its
+/// scripts from.
+pub fn command() -> clap::Command {
+    Cli::command()
+}
+
+/// The options of a gather run as the config file and the environment give them:
+/// a flag also takes `=true` or `=false`, so a layer can turn off a flag an earlier
+/// one set.
+pub fn layer_command() -> clap::Command {
+    GatherLine::command().mut_args(|arg| match arg.get_action() {
+        ArgAction::SetTrue => arg.num_args(0..=1).require_equals(true).default_missing_value("true"),
+        _ => arg,
+    })
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Comma-separated environment variables to report; a leading or trailing `*`
+    /// matches a suffix or prefix (default: TZ, LANG, LANGUAGE, LC_*, proxy
+    /// variables, XDG_*)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = list_item, default_values = DEFAULT_ENV_VARS, hide_default_value = true)]
+    pub env_vars: Vec<String>,
+    /// Comma-separated binaries to look up on PATH (default:
+    /// docker,python3,git,curl,unzip)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = list_item, default_values = DEFAULT_BINARIES, hide_default_value = true)]
+    pub binaries: Vec<String>,
+    /// Comma-separated commands whose `--version` is probed (default:
+    /// docker,python3,git,curl)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = list_item, default_values = DEFAULT_TOOL_VERSIONS, hide_default_value = true)]
+    pub tool_versions: Vec<String>,
+    /// Extra salt mixed into host_fingerprint, so fingerprints can't be correlated
+    /// across fleets using different salts
+    #[arg(long, value_name = "SALT")]
+    pub fingerprint_salt: Option<String>,
+    /// Skip the public IPv4 lookup
+    #[arg(long)]
+    pub no_ipv4: bool,
+    /// Skip the public IPv6 lookup
+    #[arg(long)]
+    pub no_ipv6: bool,
+    /// Self-hosted echo endpoint preferred for the public IPv4 lookup, falling back
+    /// to public services
+    #[arg(long, value_name = "URL", value_parser = echo::parse_url)]
+    pub echo_url_ipv4: Option<String>,
+    /// Self-hosted echo endpoint preferred for the public IPv6 lookup
+    #[arg(long, value_name = "URL", value_parser = echo::parse_url)]
+    pub echo_url_ipv6: Option<String>,
+    /// File holding the bearer token sent to the self-hosted endpoints
+    #[arg(long, value_name = "FILE")]
+    pub echo_token_file: Option<String>,
+    /// PEM bundle of additional trusted CA certificates
+    #[arg(long, value_name = "FILE")]
+    pub ca_bundle: Option<String>,
+    /// PEM client certificate (may include the key) for HTTP requests
+    #[arg(long, value_name = "FILE")]
+    pub client_cert: Option<String>,
+    /// PEM private key for --client-cert
+    #[arg(long, value_name = "FILE")]
+    pub client_key: Option<String>,
+    /// Extra header sent with every HTTP request, including public echo services
+    /// (repeatable)
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = http::parse_header)]
+    pub headers: Vec<(String, String)>,
+    /// User-Agent for HTTP requests
+    #[arg(long, value_name = "UA")]
+    pub user_agent: Option<String>,
+    /// Resolve HTTP hostnames through this nameserver instead of the system resolver
+    /// (repeatable)
+    #[arg(long = "dns-server", value_name = "IP[:PORT]", value_parser = dns::parse_server)]
+    pub dns_servers: Vec<SocketAddr>,
+    /// Disable TLS certificate verification (dangerous)
+    #[arg(long)]
+    pub insecure: bool,
+    /// Keep at most this many users and groups each; the full counts are reported
+    /// under truncated (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    pub max_entries: usize,
+    /// Comma-separated collectors to run, or all; the rest are left out of the
+    /// output entirely (default: ip, groups, users, timezone; see --list-collectors)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = collector_or_all)]
+    pub facts: Option<Vec<String>>,
+    /// Compare running containers' image digests with their registries (HEAD
+    /// manifest requests) and report update_available
+    #[arg(long)]
+    pub docker_check_updates: bool,
+    /// Containers that exited more than N times within --flap-window are listed as
+    /// flapping_containers
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FLAP_RESTARTS)]
+    pub flap_restarts: u64,
+    /// Window for --flap-restarts
+    #[arg(long, value_name = "MINUTES", default_value_t = DEFAULT_FLAP_WINDOW_MINUTES)]
+    pub flap_window: u64,
+    /// Traefik API to read routers and services from (credentials may be given in
+    /// the URL)
+    #[arg(long, value_name = "URL", default_value = DEFAULT_TRAEFIK_API)]
+    pub traefik_api: String,
+    /// Traefik ACME store summarized under traefik.acme
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_ACME_PATH)]
+    pub traefik_acme: String,
+    /// Authelia configuration read for its session domains
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_AUTHELIA_CONFIG_PATH)]
+    pub authelia_config: String,
+    /// cloudflared configuration read besides /etc/cloudflared and /root/.cloudflared
+    /// (repeatable)
+    #[arg(long = "cloudflared-config", value_name = "FILE")]
+    pub cloudflared_configs: Vec<String>,
+    /// WireGuard peers without a handshake for longer are listed as stale_peers
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_STALE_AFTER_SECS)]
+    pub wireguard_stale_after: u64,
+    /// Application data root whose directories the ownership collector audits
+    /// (repeatable; default: /opt and /srv)
+    #[arg(long = "ownership-root", value_name = "DIR")]
+    pub ownership_roots: Vec<String>,
+    /// Owner expected throughout the roots instead of each application directory's
+    /// own owner and group
+    #[arg(long, value_name = "USER")]
+    pub ownership_user: Option<String>,
+    /// Entries walked per ownership root before it's reported as incomplete (0 for
+    /// no limit)
+    #[arg(long, value_name = "N", default_value_t = ownership::DEFAULT_MAX_FILES)]
+    pub ownership_max_files: usize,
+    /// Directory the dir_sizes collector measures (repeatable; default:
+    /// /var/lib/docker, /var/log and /mnt/local/transcodes)
+    #[arg(long = "dir-size", value_name = "DIR")]
+    pub dir_sizes: Vec<String>,
+    /// Time the dir_sizes collector may spend in all; directories it doesn't finish
+    /// are reported incomplete
+    #[arg(long, value_name = "SECS", default_value_t = dir_sizes::DEFAULT_BUDGET_SECS)]
+    pub dir_size_budget: u64,
+    /// Log file or directory whose growth since the previous run log_growth reports
+    /// (repeatable; default: /var/log and /var/lib/docker/containers)
+    #[arg(long = "log-path", value_name = "PATH")]
+    pub log_paths: Vec<String>,
+    /// Binaries with at least this many core dumps in the window are listed as
+    /// frequent_crashers
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CRASH_THRESHOLD)]
+    pub crash_threshold: u64,
+    /// Days of core dumps and crash reports counted
+    #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_CRASH_WINDOW_DAYS)]
+    pub crash_window: u64,
+    /// The processes collector lists processes using at least this much of one CPU
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_CPU_THRESHOLD)]
+    pub process_cpu: u64,
+    /// ...and those with at least this much resident memory
+    #[arg(long, value_name = "MB", default_value_t = DEFAULT_MEMORY_THRESHOLD_MB)]
+    pub process_memory: u64,
+    /// Plex server for the plex collector
+    #[arg(long, value_name = "URL", default_value = DEFAULT_PLEX_URL)]
+    pub plex_url: String,
+    /// Plex Preferences.xml holding the server's token
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_PLEX_PREFERENCES_PATH)]
+    pub plex_preferences: String,
+    /// Jellyfin server for the jellyfin collector
+    #[arg(long, value_name = "URL", default_value = DEFAULT_JELLYFIN_URL)]
+    pub jellyfin_url: String,
+    /// File holding a Jellyfin API key, needed for transcoding settings and libraries
+    #[arg(long, value_name = "FILE")]
+    pub jellyfin_token_file: Option<String>,
+    /// Emby server for the emby collector
+    #[arg(long, value_name = "URL", default_value = DEFAULT_EMBY_URL)]
+    pub emby_url: String,
+    /// File holding an Emby API key, needed for transcoding settings and libraries
+    #[arg(long, value_name = "FILE")]
+    pub emby_token_file: Option<String>,
+    /// Download client for the download_clients collector: qbittorrent=URL or
+    /// transmission=URL, with any credentials in the URL (repeatable)
+    #[arg(long = "download-client", value_name = "KIND=URL", value_parser = download_clients::parse_client)]
+    pub download_clients: Vec<DownloadClient>,
+    /// gluetun control server asked for the forwarded port, and by vpn_gateways when
+    /// a gluetun container's own address doesn't answer
+    #[arg(long, value_name = "URL", default_value = DEFAULT_GLUETUN_API)]
+    pub gluetun_api: String,
+    /// File holding the forwarded port instead, e.g. gluetun's
+    /// /tmp/gluetun/forwarded_port bind-mounted on the host
+    #[arg(long, value_name = "FILE")]
+    pub forwarded_port_file: Option<String>,
+    /// IPv4 range used by a VPN (e.g. 100.64.0.0/10) that Docker networks must not
+    /// overlap, beyond host routes (repeatable)
+    #[arg(long = "vpn-subnet", value_name = "CIDR", value_parser = Ipv4Net::parse)]
+    pub vpn_subnets: Vec<Ipv4Net>,
+    /// Comma-separated opt-in collectors to run as well: region, plex, jellyfin,
+    /// emby, download_clients, ownership, dir_sizes, processes
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = opt_in_name)]
+    pub enable: Vec<String>,
+    /// Skip collectors that run external commands
+    #[arg(long)]
+    pub no_exec: bool,
+    /// Skip collectors that use the network
+    #[arg(long)]
+    pub offline: bool,
+    /// Report each collector as it runs on stderr: text or json (one event object per
+    /// line)
+    #[arg(long, value_name = "FORMAT")]
+    pub progress: Option<ReportFormat>,
+    /// Log diagnostics to stderr: URLs tried, files read, cache decisions and
+    /// fallbacks taken; -vv and -vvv for more detail
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Print nothing on stderr but a fatal error, for cron and Ansible runs (same as
+    /// --log-level off)
+    #[arg(short, long, conflicts_with = "log_level")]
+    pub quiet: bool,
+    /// Diagnostics to print: off, error, warn (default), info, debug or trace
+    #[arg(long, value_name = "LEVEL", value_parser = logging::parse_level)]
+    pub log_level: Option<LevelFilter>,
+    /// List collectors with what they declare they do (exec, network, root) and exit
+    #[arg(long)]
+    pub list_collectors: bool,
+    /// Run collectors that can hang (mounts) in a resource-limited worker process,
+    /// killed after this long (0 runs them in-process)
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub worker_timeout: u64,
+    /// Limit for the whole run: collectors still running when it expires, and those
+    /// not yet started, are reported as timed out (0 for no limit)
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub timeout: u64,
+    /// Reuse cached users, groups and locales while their source files are unchanged
+    /// (compared by size, inode and change times)
+    #[arg(long)]
+    pub incremental: bool,
+    /// Reuse a collector's section for this long, e.g. ip=10m or
+    /// host_fingerprint=forever; 0 never caches (repeatable)
+    #[arg(long = "cache-ttl", value_name = "COLLECTOR=DURATION", value_parser = section_cache::parse_ttl)]
+    pub cache_ttls: Vec<(String, Ttl)>,
+    /// Comma-separated collectors (or all) to collect afresh, ignoring cached
+    /// sections and lookups
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = collector_or_all)]
+    pub refresh: Vec<String>,
+    /// Cache directory (default: /var/cache/ansible-facts as root, otherwise
+    /// $XDG_CACHE_HOME/ansible-facts)
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<String>,
+    /// Reuse a public IP lookup younger than this (0 always queries)
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub min_requery_interval: u64,
+    /// Timeout for each request to an echo service during the public IP lookup
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_HTTP_TIMEOUT)]
+    pub http_timeout: u64,
+    /// Maximum requests per hour to external echo services across all runs on this
+    /// host
+    #[arg(long, value_name = "N", default_value_t = 30)]
+    pub rate_limit: usize,
+    /// Directory of *.json/*.yaml files merged under their stem names
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_DROP_IN_DIR)]
+    pub drop_in_dir: String,
+    /// JSON or YAML file deep-merged over the collected facts
+    #[arg(long, value_name = "FILE")]
+    pub static_facts: Option<String>,
+    /// override: static values win; fill-missing: only fill keys that are missing,
+    /// null or empty strings
+    #[arg(long, value_name = "MODE", default_value = "override")]
+    pub static_precedence: Precedence,
+    /// Add a derived fact computed from collected values, e.g.
+    /// 'low_disk = mounts["/"].available_gb < 10' (repeatable)
+    #[arg(long, value_name = "NAME=EXPR", value_parser = derived::parse_definition)]
+    pub derive: Vec<Definition>,
+    /// JSON or YAML mapping of derived fact names to expressions
+    #[arg(long, value_name = "FILE")]
+    pub derived_facts: Option<String>,
+    /// Check an expression after collection, e.g. 'ip.failed_ipv4 == false';
+    /// failures are listed in the assertions section (repeatable)
+    #[arg(long = "assert", value_name = "EXPR", value_parser = policy::parse_rule)]
+    pub assertions: Vec<Rule>,
+    /// JSON or YAML mapping of assertion names to expressions, or to {assert: EXPR,
+    /// message: TEXT}
+    #[arg(long = "assertions", value_name = "FILE")]
+    pub assertions_file: Option<String>,
+    /// Fail without printing anything when any collector fails, and exit non-zero
+    /// when any assertion fails
+    #[arg(long)]
+    pub strict: bool,
+    /// Omit volatile values (timestamps, latencies, disk usage) so an unchanged host
+    /// produces byte-identical output
+    #[arg(long)]
+    pub deterministic: bool,
+    /// Print the document in this schema version's shape; 1 is the version and the
+    /// sections alone, with plain-string errors, as the first releases printed it
+    #[arg(long, value_name = "N", value_parser = schema_version::parse, default_value_t = schema_version::CURRENT)]
+    pub schema_version: u32,
+    /// Print the changes from a previously saved facts document instead of the facts
+    /// themselves
+    #[arg(long, value_name = "FILE")]
+    pub diff: Option<String>,
+    /// Diff output: changes or json-patch (RFC 6902)
+    #[arg(long, value_name = "FORMAT", default_value = "changes")]
+    pub diff_format: DiffFormat,
+    /// Print only the part of the output selected by a JMESPath expression (subset:
+    /// a.b, "quoted-key", [0], *, [*], {key: a.b}, [a, b], a | b); strings are printed
+    /// raw
+    #[arg(long, value_name = "EXPR", value_parser = Query::parse)]
+    pub query: Option<Query>,
+    /// Print a single-level map with dotted keys (users.plex.uid); keys holding a dot
+    /// are quoted (traefik.domains."a.example")
+    #[arg(long)]
+    pub flatten: bool,
+    /// Use facter's names for facts it also has (networking.ip, timezone,
+    /// mountpoints), keeping the whole document under saltbox, to serve as a Puppet
+    /// external fact
+    #[arg(long)]
+    pub facter: bool,
+    /// Nest the whole output under KEY, to merge it into a larger fact dictionary;
+    /// --diff, --query and --flatten see it nested
+    #[arg(long, value_name = "KEY", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    pub namespace: Option<String>,
+    /// Add a timings section: each collector's duration and status, the commands it
+    /// ran, endpoints it contacted and files it reads
+    #[arg(long)]
+    pub profile: bool,
+    /// Print JSON on a single line even on a terminal, where it is otherwise indented
+    /// (files and pipes always get a single line)
+    #[arg(long)]
+    pub compact: bool,
+    /// Output format: env prints shell KEY='VALUE' lines (e.g. SALTBOX_IP_PUBLIC_IP),
+    /// ndjson one {"category", "status", "facts"} line per collector as it finishes,
+    /// then the envelope
+    #[arg(long, value_name = "FORMAT", default_value = "json")]
+    pub format: OutputFormat,
+    /// Write the output to FILE instead of stdout, atomically (a temporary file
+    /// renamed into place)
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<String>,
+    /// Install the facts as an Ansible local fact, e.g.
+    /// /etc/ansible/facts.d/saltbox.fact (ansible_local.saltbox): like --output, but
+    /// JSON only and the directory is created
+    #[arg(long, value_name = "FILE", value_parser = fact_file)]
+    pub facts_d: Option<String>,
+    /// Section to export with --format csv: users, groups or mounts (default: the one
+    /// of them named in --facts); only that collector runs unless --facts says
+    /// otherwise
+    #[arg(long, value_name = "SECTION")]
+    pub only: Option<String>,
+    /// With --format nagios, a check to report: disk:MOUNT:WARN[:CRIT] (used
+    /// percent), ip:ipv4|ipv6 or connectivity:ipv4|ipv6 (repeatable); exits 0-3 as a
+    /// monitoring plugin. The collector a check reads (mounts, ip, connectivity) is
+    /// collected with it
+    #[arg(long = "check", value_name = "CHECK", value_parser = nagios::parse_check)]
+    pub checks: Vec<Check>,
+    /// TOML file of default options (default: /etc/ansible-facts.toml, if present);
+    /// see Config file below
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+    /// Don't read a config file
+    #[arg(long, conflicts_with = "config")]
+    pub no_config: bool,
+    /// The arguments these were parsed from, passed on to worker processes.
+    #[arg(skip)]
+    pub argv: Vec<String>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        GatherLine::parse_from([BIN]).args
+    }
+}
+
+impl Args {
+    /// Parses the options of a gather run given without a subcommand, e.g. those
+    /// after `--` of bench, serve and remote.
+    pub fn parse_from(argv: Vec<String>) -> Result<Args, clap::Error> {
+        let matches = GatherLine::command().try_get_matches_from(iter::once(BIN.to_string()).chain(argv.iter().cloned()))?;
+        Args::layered(matches, argv)
+    }
+
+    /// The options given in `matches` over those the environment and the config file
+    /// set, over the defaults.
+    fn layered(matches: ArgMatches, argv: Vec<String>) -> Result<Args, clap::Error> {
+        Args::layered_over(matches, argv, config::from_env())
+    }
+
+    /// `layered` with the options `environment` sets in place of the process's.
+    fn layered_over(mut matches: ArgMatches, argv: Vec<String>, environment: Vec<(String, Vec<String>)>) -> Result<Args, clap::Error> {
+        let given = Args::from_arg_matches(&matches)?;
+        let mut parsed = Args::default();
+        // Lowest precedence first: the config file, the environment, then the command line.
+        if let Some((path, required)) = config::path(given.config.as_deref(), given.no_config) {
+            for (key, args) in config::load(&path, required).map_err(invalid)? {
+                parsed.apply(args).map_err(|e| in_layer(&format!("{}: {}", path, key), e))?;
+            }
+        }
+        for (variable, args) in environment {
+            parsed.apply(args).map_err(|e| in_layer(&variable, e))?;
+        }
+        clear_defaults(&mut matches);
+        parsed.update_from_arg_matches(&matches)?;
+        parsed.validate()?;
+        parsed.argv = argv;
+        Ok(parsed)
+    }
+
+    /// Sets the options `argv` gives, leaving the rest as they are.
+    fn apply(&mut self, argv: Vec<String>) -> Result<(), clap::Error> {
+        let mut matches = layer_command().try_get_matches_from(iter::once(BIN.to_string()).chain(argv))?;
+        clear_defaults(&mut matches);
+        self.update_from_arg_matches(&matches)
+    }
+
+    /// The checks that span options, run once every layer is applied.
+    fn validate(&mut self) -> Result<(), clap::Error> {
+        for list in [&mut self.env_vars, &mut self.binaries, &mut self.tool_versions] {
+            list.retain(|item| !item.is_empty());
+        }
+        if (self.quiet || self.log_level.is_some()) && self.verbose > 0 {
+            return Err(invalid("-v can't be combined with --quiet or --log-level"));
+        }
+        match (&self.format, &self.only) {
+            // `--facts users` names the table when it's the one tabular section asked for.
+            (OutputFormat::Csv, None) => {
+                let tables: Vec<String> = self.facts.iter().flatten().filter(|name| output::csv_section_names().contains(&name.as_str())).cloned().collect();
+                match &tables[..] {
+                    [table] => self.only = Some(table.clone()),
+                    _ => {
+                        return Err(invalid(format!(
+                            "--format csv requires --only <{}>, or --facts naming exactly one of them",
+                            output::csv_section_names().join("|")
+                        )))
+                    }
+                }
+            }
+            // Nothing else is printed, so nothing else needs collecting.
+            (OutputFormat::Csv, Some(only)) if self.facts.is_none() => self.facts = Some(vec![only.clone()]),
+            (OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Msgpack | OutputFormat::Env | OutputFormat::Ndjson | OutputFormat::Nagios, Some(_)) => {
+                return Err(invalid("--only is only supported with --format csv"))
+            }
+            _ => {}
+        }
+        if self.facts_d.is_some() {
+            if !matches!(self.format, OutputFormat::Json) || self.query.is_some() || self.diff.is_some() {
+                return Err(invalid("--facts-d writes the JSON document; it can't be combined with --format, --query or --diff"));
+            }
+            if self.output.is_some() {
+                return Err(invalid("--facts-d and --output are mutually exclusive"));
+            }
+        }
+        match self.format {
+            OutputFormat::Nagios if self.checks.is_empty() => return Err(invalid("--format nagios requires at least one --check")),
+            OutputFormat::Nagios if self.diff.is_some() || self.query.is_some() || self.flatten || self.facter || self.namespace.is_some() || self.output.is_some() => {
+                return Err(invalid("--format nagios can't be combined with --diff, --query, --flatten, --facter, --namespace or --output"))
+            }
+            // Sections are printed as they're collected, before anything reshapes them.
+            OutputFormat::Ndjson
+                if self.diff.is_some()
+                    || self.query.is_some()
+                    || self.flatten
+                    || self.facter
+                    || self.namespace.is_some()
+                    || self.static_facts.is_some()
+                    || self.output.is_some() =>
+            {
+                return Err(invalid("--format ndjson can't be combined with --diff, --query, --flatten, --facter, --namespace, --static-facts or --output"))
+            }
+            OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Msgpack | OutputFormat::Env | OutputFormat::Ndjson | OutputFormat::Csv if !self.checks.is_empty() => {
+                return Err(invalid("--check is only supported with --format nagios"))
+            }
+            _ => {}
+        }
+        if let OutputFormat::Nagios = self.format {
+            // Each check collects the section it reads, as get does for its path;
+            // without --facts nothing else is printed, so nothing else is collected.
+            let names = self.facts.get_or_insert_with(Vec::new);
+            for check in &self.checks {
+                if !names.iter().any(|name| name == check.collector()) {
+                    names.push(check.collector().to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `collector` runs: named in `--facts`, or every collector with
+    /// `--facts all`; without it the baseline ones and those in `--enable`.
+    pub fn selects(&self, collector: &collectors::Collector) -> bool {
+        match &self.facts {
+            Some(names) => self.named(collector.name) || names.iter().any(|name| name == "all"),
+            None => collector.baseline || self.enable.iter().any(|name| name == collector.name),
+        }
+    }
+
+    /// Whether `--facts` names `collector` itself rather than through `all`.
+    pub fn named(&self, collector: &str) -> bool {
+        self.facts.iter().flatten().any(|name| name == collector)
+    }
+
+    /// The diagnostics level from `--quiet`, `--log-level` or `-v`.
+    pub fn log_level(&self) -> LevelFilter {
+        match self.log_level {
+            _ if self.quiet => LevelFilter::OFF,
+            Some(level) => level,
+            None => logging::verbosity_level(self.verbose),
+        }
+    }
+
+    /// Whether `--refresh` asks for `collector` to bypass its caches.
+    pub fn refreshes(&self, collector: &str) -> bool {
+        self.refresh.iter().any(|name| name == collector || name == "all")
+    }
+
+    /// The options of the HTTP client the collectors share.
+    pub fn http(&self) -> HttpOptions {
+        HttpOptions {
+            ca_bundle: self.ca_bundle.clone(),
+            client_cert: self.client_cert.clone(),
+            client_key: self.client_key.clone(),
+            insecure: self.insecure,
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.clone(),
+            dns_servers: self.dns_servers.clone(),
+        }
+    }
+}
+
+/// `get <PATH> [OPTIONS]`: a gather run whose `--query` is PATH.
+#[derive(clap::Args)]
+struct GetArgs {
+    /// A --query expression, e.g. ip.public_ip
+    #[arg(value_name = "PATH", value_parser = Query::parse)]
+    path: Query,
+    #[command(flatten)]
+    args: Args,
+}
+
+/// Makes `args` print the value at `path`, limited to the collector it starts
+/// with.
+fn select(path: Query, mut args: Args) -> Result<Args, clap::Error> {
+    if args.query.is_some() || args.diff.is_some() || args.flatten || args.facter || args.namespace.is_some() || args.facts_d.is_some() {
+        return Err(invalid("get prints the value at PATH; it can't be combined with --query, --diff, --flatten, --facter, --namespace or --facts-d"));
+    }
+    if !matches!(args.format, OutputFormat::Json) {
+        return Err(invalid("get prints the value at PATH; it can't be combined with --format"));
+    }
+    if args.facts.is_none() {
+        // The build metadata needs no collector; other envelope keys, drop-ins and
+        // derived facts may draw on any of them.
+        args.facts = Some(match path.first_field() {
+            Some("meta" | "saltbox_facts_version" | "schema_version") => Vec::new(),
+            Some(name) if collectors::find(name).is_some() => vec![name.to_string()],
+            _ => vec!["all".to_string()],
+        });
+    }
+    args.query = Some(path);
+    Ok(args)
+}
+
+#[derive(clap::Args)]
+pub struct RemoteArgs {
+    /// Hosts to collect from, one SSH destination per line
+    #[arg(long, value_name = "FILE")]
+    pub inventory: String,
+    /// Write the aggregated document here instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<String>,
+    /// Hosts collected at once
+    #[arg(long, value_name = "N", default_value_t = 8)]
+    pub parallel: usize,
+    /// Per-host limit for copying and collecting
+    #[arg(long, value_name = "SECS", default_value_t = 120)]
+    pub timeout: u64,
+    /// Binary copied to hosts (default: this executable)
+    #[arg(long, value_name = "FILE")]
+    pub binary: Option<String>,
+    /// Where the binary lives on hosts, relative to the login directory unless
+    /// absolute
+    #[arg(long, value_name = "PATH", default_value = ".cache/ansible-facts/bin/saltbox-facts")]
+    pub remote_path: String,
+    /// Never copy; run the binary already installed at --remote-path
+    #[arg(long, conflicts_with = "binary")]
+    pub no_copy: bool,
+    /// Extra `ssh -o` option, e.g. User=deploy (repeatable)
+    #[arg(long = "ssh-option", value_name = "OPT")]
+    pub ssh_options: Vec<String>,
+    /// Options for the collector on every host
+    #[arg(last = true, value_name = "COLLECTOR ARGS")]
+    pub collector_args: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct CompareArgs {
+    /// The earlier document
+    #[arg(value_name = "OLD")]
+    pub old: String,
+    /// The later document
+    #[arg(value_name = "NEW")]
+    pub new: String,
+    /// Report format
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub format: ReportFormat,
+    /// Hide differences below this severity
+    #[arg(long, value_name = "LEVEL", default_value = "info")]
+    pub min_severity: Severity,
+}
+
+#[derive(clap::Args)]
+pub struct ValidateArgs {
+    /// The saved facts document
+    #[arg(value_name = "FILE")]
+    pub file: String,
+    /// JSON Schema (JSON or YAML) the document must conform to
+    #[arg(long, value_name = "FILE")]
+    pub schema: Option<String>,
+    /// JSON or YAML mapping of rule names to expressions that must hold, e.g.
+    /// 'root_space: mounts["/"].available_gb >= 20', or to {assert: EXPR, message:
+    /// TEXT} (repeatable)
+    #[arg(long = "policy", value_name = "FILE")]
+    pub policies: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Runs per collector
+    #[arg(long, value_name = "N", default_value_t = 5, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub iterations: usize,
+    /// Comma-separated collectors to run (default: all except ip, which queries
+    /// external services)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = collector_name)]
+    pub collectors: Option<Vec<String>>,
+    /// Report format
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub format: ReportFormat,
+    /// Options configuring the collectors, as for a normal run
+    #[arg(last = true, value_name = "COLLECTOR ARGS")]
+    collector_args: Vec<String>,
+    #[arg(skip)]
+    pub gather: Args,
+}
+
+#[derive(clap::Args)]
+pub struct DoctorArgs {
+    /// Report format
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub format: ReportFormat,
+    /// Skip the DNS and connectivity checks
+    #[arg(long)]
+    pub offline: bool,
+    /// Cache directory to check instead of the default
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<String>,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum CacheAction {
+    /// Show each entry and when it was stored
+    List,
+    /// Remove every entry, or only those named, so the next run starts cold
+    Clear,
+    /// Print the cache directory in use
+    Dir,
+}
+
+#[derive(clap::Args)]
+pub struct CacheArgs {
+    #[arg(value_name = "ACTION")]
+    pub action: CacheAction,
+    /// Entries for clear to remove (default: all)
+    #[arg(value_name = "NAME")]
+    pub names: Vec<String>,
+    /// Report format for list
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub format: ReportFormat,
+    /// Cache directory to use instead of the default
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on; the document describes the host, so expose it with care
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9761")]
+    pub listen: SocketAddr,
+    /// Connections answered at once, each by its own run
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    pub max_connections: usize,
+    /// Options configuring the collectors, as for a normal run
+    #[arg(last = true, value_name = "COLLECTOR ARGS")]
+    collector_args: Vec<String>,
+    #[arg(skip)]
+    pub gather: Args,
+}
+
+/// An error in the arguments as a whole rather than one value.
+fn invalid(message: impl std::fmt::Display) -> clap::Error {
+    Cli::command().error(ErrorKind::ArgumentConflict, message)
+}
+
+/// Names the config key or environment variable an error in its arguments came
+/// from.
+fn in_layer(source: &str, e: clap::Error) -> clap::Error {
+    let rendered = e.to_string();
+    let message = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+    Cli::command().error(e.kind(), format!("{}: {}", source, message))
+}
+
+/// Drops the options `matches` only has defaults for, so applying it leaves them
+/// as an earlier layer set them.
+fn clear_defaults(matches: &mut ArgMatches) {
+    let defaults: Vec<String> = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::DefaultValue))
+        .map(|id| id.to_string())
+        .collect();
+    for id in defaults {
+        let _ = matches.try_clear_id(&id);
+    }
+}
+
+fn list_item(value: &str) -> Result<String, String> {
+    Ok(value.trim().to_string())
+}
+
+fn collector_name(value: &str) -> Result<String, String> {
+    let name = value.trim();
+    match collectors::find(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err(format!("unknown collector (expected one of {})", collectors::names().join(", "))),
+    }
+}
+
+fn opt_in_name(value: &str) -> Result<String, String> {
+    let name = value.trim();
+    let opt_in = collectors::opt_in_names();
+    match opt_in.contains(&name) {
+        true => Ok(name.to_string()),
+        false => Err(format!("not an opt-in collector (expected one of {})", opt_in.join(", "))),
+    }
+}
+
+fn collector_or_all(value: &str) -> Result<String, String> {
+    match value.trim() {
+        "all" => Ok("all".to_string()),
+        _ => collector_name(value).map_err(|_| format!("unknown collector (expected all or one of {})", collectors::names().join(", "))),
+    }
+}
+
+fn fact_file(value: &str) -> Result<String, String> {
+    match value.ends_with(".fact") {
+        true => Ok(value.to_string()),
+        false => Err("Ansible only loads files ending in .fact".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A gather run's options from `argv` over the config file `config` and the
+    /// environment `variables`.
+    fn layered(config: &str, variables: &[(&str, &str)], argv: &[&str]) -> Result<Args, clap::Error> {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!("saltbox-facts-cli-{}-{}.toml", process::id(), FILES.fetch_add(1, Ordering::Relaxed)));
+        std::fs::write(&path, config).unwrap();
+        let mut argv: Vec<String> = argv.iter().map(|arg| arg.to_string()).collect();
+        argv.extend(["--config".to_string(), path.display().to_string()]);
+        let matches = GatherLine::command().try_get_matches_from(iter::once(BIN.to_string()).chain(argv.iter().cloned())).unwrap();
+        let environment = config::options_from(variables.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+        let args = Args::layered_over(matches, argv, environment);
+        std::fs::remove_file(path).unwrap();
+        args
+    }
+
+    fn gather(config: &str, variables: &[(&str, &str)], argv: &[&str]) -> Args {
+        layered(config, variables, argv).unwrap()
+    }
+
+    #[test]
+    fn command_line_over_environment_over_config() {
+        let config = "http_timeout = 5\nbinaries = [\"git\"]\n";
+        assert_eq!(gather(config, &[], &[]).http_timeout, 5);
+        assert_eq!(gather(config, &[("ANSIBLE_FACTS_HTTP_TIMEOUT", "10")], &[]).http_timeout, 10);
+        assert_eq!(gather(config, &[("ANSIBLE_FACTS_HTTP_TIMEOUT", "10")], &["--http-timeout", "20"]).http_timeout, 20);
+        // A list given in a later layer replaces the earlier one.
+        assert_eq!(gather(config, &[], &[]).binaries, ["git"]);
+        assert_eq!(gather(config, &[("ANSIBLE_FACTS_BINARIES", "curl,sh")], &[]).binaries, ["curl", "sh"]);
+        assert_eq!(gather(config, &[("ANSIBLE_FACTS_BINARIES", "curl")], &["--binaries", "jq"]).binaries, ["jq"]);
+    }
+
+    #[test]
+    fn false_turns_off_a_flag_an_earlier_layer_set() {
+        assert!(gather("no_ipv4 = true\n", &[], &[]).no_ipv4);
+        assert!(!gather("no_ipv4 = true\n", &[("ANSIBLE_FACTS_NO_IPV4", "false")], &[]).no_ipv4);
+        assert!(gather("no_ipv4 = false\n", &[("ANSIBLE_FACTS_NO_IPV4", "true")], &[]).no_ipv4);
+        assert!(gather("", &[("ANSIBLE_FACTS_NO_IPV4", "false")], &["--no-ipv4"]).no_ipv4);
+        // An option not given in a layer keeps what the one before set.
+        assert!(gather("no_ipv4 = true\n", &[("ANSIBLE_FACTS_HTTP_TIMEOUT", "10")], &["--no-ipv6"]).no_ipv4);
+    }
+
+    #[test]
+    fn environment_sets_values_and_output_options() {
+        let args = gather("", &[("ANSIBLE_FACTS_USER_AGENT", "true"), ("ANSIBLE_FACTS_FORMAT", "yaml")], &[]);
+        assert_eq!(args.user_agent.as_deref(), Some("true"));
+        assert!(matches!(args.format, OutputFormat::Yaml));
+    }
+
+    #[test]
+    fn invalid_layer_values_name_their_source() {
+        let error = layered("", &[("ANSIBLE_FACTS_NO_IPV4", "maybe")], &[]).err().unwrap();
+        assert!(error.to_string().contains("ANSIBLE_FACTS_NO_IPV4: invalid value 'maybe'"), "{}", error);
+        let error = layered("http_timeout = \"ten\"\n", &[], &[]).err().unwrap();
+        assert!(error.to_string().contains("http-timeout: invalid type"), "{}", error);
+    }
+}
@@ -0,0 +1,148 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{Code, Error};
+use crate::exec;
+
+pub const ADJTIME_FILE_PATH: &str = "/etc/adjtime";
+const RTC_CLASS_PATH: &str = "/sys/class/rtc";
+pub const CLOCKSOURCE_PATH: &str = "/sys/devices/system/clocksource/clocksource0";
+const CPUINFO_FILE_PATH: &str = "/proc/cpuinfo";
+
+/// Clocksources that are only selected when nothing better is usable.
+const FALLBACK_CLOCKSOURCES: &[&str] = &["jiffies", "refined-jiffies", "pit"];
+
+/// Reports whether the hardware clock keeps local time or UTC, and whether an RTC exists.
+///
+/// `timedatectl` is authoritative when it can be queried; `/etc/adjtime` is the fallback
+/// and absent both, the kernel default of UTC is assumed.
+pub async fn get_rtc() -> Value {
+    let (device, name) = find_rtc_device();
+    let adjtime_mode = read_adjtime_mode();
+    let (timedatectl_local_rtc, timedatectl_error) = query_timedatectl_local_rtc().await;
+
+    let (local_rtc, source) = match (timedatectl_local_rtc, adjtime_mode.as_deref()) {
+        (Some(local), _) => (local, "timedatectl"),
+        (None, Some(mode)) => (mode == "LOCAL", "adjtime"),
+        (None, None) => (false, "default"),
+    };
+
+    let mismatch = match (timedatectl_local_rtc, adjtime_mode.as_deref()) {
+        (Some(local), Some(mode)) => local != (mode == "LOCAL"),
+        _ => false,
+    };
+
+    json!({
+        "available": device.is_some(),
+        "device": device.unwrap_or_default(),
+        "name": name.unwrap_or_default(),
+        "local_rtc": local_rtc,
+        "source": source,
+        "adjtime_mode": adjtime_mode,
+        "timedatectl_local_rtc": timedatectl_local_rtc,
+        "mismatch": mismatch,
+        "error": timedatectl_error
+    })
+}
+
+fn find_rtc_device() -> (Option<String>, Option<String>) {
+    let mut devices: Vec<String> = match fs::read_dir(RTC_CLASS_PATH) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("rtc"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    devices.sort();
+
+    match devices.into_iter().next() {
+        Some(device) => {
+            let name = fs::read_to_string(Path::new(RTC_CLASS_PATH).join(&device).join("name"))
+                .ok()
+                .map(|s| s.trim().to_string());
+            (Some(device), name)
+        }
+        None if Path::new("/dev/rtc").exists() => (Some("rtc".to_string()), None),
+        None => (None, None),
+    }
+}
+
+/// The third line of /etc/adjtime is either `UTC` or `LOCAL`.
+fn read_adjtime_mode() -> Option<String> {
+    let contents = fs::read_to_string(ADJTIME_FILE_PATH).ok()?;
+    contents
+        .lines()
+        .nth(2)
+        .map(|line| line.trim().to_uppercase())
+        .filter(|mode| mode == "UTC" || mode == "LOCAL")
+}
+
+async fn query_timedatectl_local_rtc() -> (Option<bool>, Option<Error>) {
+    match exec::output("timedatectl", ["show", "--property=LocalRTC"]).await {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let value = stdout
+                .lines()
+                .find_map(|line| line.strip_prefix("LocalRTC="))
+                .map(|value| value.trim() == "yes");
+            (value, None)
+        }
+        Ok(output) => (None, Some(Error::exited("timedatectl", &output))),
+        Err(e) => (None, Some(Error::run("timedatectl", &e))),
+    }
+}
+
+/// Reports the active and available kernel clocksources and flags known-bad choices.
+pub fn get_clocksource() -> Value {
+    let base = Path::new(CLOCKSOURCE_PATH);
+    let current = match fs::read_to_string(base.join("current_clocksource")) {
+        Ok(contents) => contents.trim().to_string(),
+        Err(e) => {
+            return json!({
+                "current": "",
+                "available": Vec::<String>::new(),
+                "known_bad": false,
+                "reason": Value::Null,
+                "error": Error::new(Code::of_io(&e), CLOCKSOURCE_PATH, format!("Error reading clocksource: {}", e))
+            });
+        }
+    };
+    let available: Vec<String> = fs::read_to_string(base.join("available_clocksource"))
+        .map(|contents| contents.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+
+    let reason = if FALLBACK_CLOCKSOURCES.contains(&current.as_str()) {
+        Some(format!("{} is a low-resolution fallback clocksource", current))
+    } else if current == "tsc" && !has_invariant_tsc() {
+        Some("tsc is active but the CPU does not advertise constant_tsc/nonstop_tsc".to_string())
+    } else if current != "tsc" && !available.iter().any(|source| source == "tsc") && has_invariant_tsc() {
+        Some(format!("tsc is missing from available clocksources (likely marked unstable), using {}", current))
+    } else {
+        None
+    };
+
+    json!({
+        "current": current,
+        "available": available,
+        "known_bad": reason.is_some(),
+        "reason": reason,
+        "error": Value::Null
+    })
+}
+
+fn has_invariant_tsc() -> bool {
+    let cpuinfo = match fs::read_to_string(CPUINFO_FILE_PATH) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("flags"))
+        .map(|line| {
+            let flags: Vec<&str> = line.split_whitespace().collect();
+            flags.contains(&"constant_tsc") && flags.contains(&"nonstop_tsc")
+        })
+        .unwrap_or(false)
+}
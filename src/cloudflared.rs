@@ -0,0 +1,152 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::time::Duration;
+
+use crate::binaries::find_in_path;
+use crate::docker;
+use crate::errors::Error;
+use crate::http::Client;
+
+/// Where cloudflared looks for its configuration when run as a service, and where
+/// `cloudflared tunnel login` leaves it for root.
+pub const CONFIG_PATHS: &[&str] = &[
+    "/etc/cloudflared/config.yml",
+    "/etc/cloudflared/config.yaml",
+    "/usr/local/etc/cloudflared/config.yml",
+    "/root/.cloudflared/config.yml",
+    "/root/.cloudflared/config.yaml",
+];
+/// The first of the ports cloudflared picks for metrics when none is configured.
+const DEFAULT_METRICS_ADDRESS: &str = "127.0.0.1:20241";
+const METRICS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// cloudflared installs (binary, containers), the tunnels configured in local
+/// config files, and what each metrics endpoint reports: open edge connections and,
+/// for remotely managed tunnels, the ingress rules pushed from the dashboard.
+/// `hostnames` lists every hostname routed through a tunnel from either source.
+pub async fn get_cloudflared(client: &Client, extra_configs: &[String]) -> Value {
+    let binary = find_in_path("cloudflared").map(|path| path.display().to_string());
+    let (containers, docker_error) = match docker::get("/containers/json").await {
+        Ok(containers) => (tunnel_containers(&containers), None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+
+    let mut hostnames = BTreeSet::new();
+    let mut metrics_addresses = BTreeSet::new();
+    let mut tunnels = Vec::new();
+    for path in CONFIG_PATHS.iter().copied().chain(extra_configs.iter().map(String::as_str)) {
+        let config = match fs::read_to_string(path) {
+            Ok(config) => config,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                tunnels.push(json!({ "config_file": path, "error": Error::read(path, &e) }));
+                continue;
+            }
+        };
+        let config: Value = match serde_yaml::from_str(&config) {
+            Ok(config) => config,
+            Err(e) => {
+                tunnels.push(json!({ "config_file": path, "error": Error::parse(path, e) }));
+                continue;
+            }
+        };
+        let ingress = ingress_rules(config.get("ingress"));
+        hostnames.extend(ingress.iter().filter_map(|rule| rule.get("hostname").and_then(Value::as_str).map(String::from)));
+        let metrics = config.get("metrics").and_then(Value::as_str);
+        metrics_addresses.extend(metrics.map(String::from));
+        tunnels.push(json!({
+            "config_file": path,
+            "tunnel": config.get("tunnel"),
+            "credentials_file": config.get("credentials-file"),
+            "metrics": metrics,
+            "ingress": ingress,
+            "error": Value::Null
+        }));
+    }
+    if metrics_addresses.is_empty() {
+        metrics_addresses.insert(DEFAULT_METRICS_ADDRESS.to_string());
+    }
+
+    let mut metrics = Map::new();
+    for address in &metrics_addresses {
+        let report = query_metrics(client, address).await;
+        hostnames.extend(
+            report
+                .get("remote_ingress")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|rule| rule.get("hostname").and_then(Value::as_str).map(String::from)),
+        );
+        metrics.insert(address.clone(), report);
+    }
+
+    // A reachable metrics endpoint also counts: cloudflared may run from a path
+    // that isn't on PATH, e.g. a package's /usr/local/bin on a minimal sudo PATH.
+    let running = metrics.values().any(|report| report.get("reachable") == Some(&Value::Bool(true)));
+    json!({
+        "installed": binary.is_some() || !containers.is_empty() || running,
+        "binary": binary,
+        "containers": containers,
+        "docker_error": docker_error,
+        "tunnels": tunnels,
+        "metrics": metrics,
+        "hostnames": hostnames
+    })
+}
+
+fn tunnel_containers(containers: &Value) -> Vec<Value> {
+    containers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|container| container.get("Image").and_then(Value::as_str).is_some_and(|image| image.contains("cloudflared")))
+        .map(|container| {
+            let name = container.pointer("/Names/0").and_then(Value::as_str).unwrap_or_default().trim_start_matches('/');
+            json!({ "name": name, "image": container.get("Image"), "state": container.get("State") })
+        })
+        .collect()
+}
+
+/// `[{hostname, service, path}]`; the catch-all rule without a hostname is kept
+/// so the fallback service is visible.
+fn ingress_rules(ingress: Option<&Value>) -> Vec<Value> {
+    ingress
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|rule| json!({ "hostname": rule.get("hostname"), "service": rule.get("service"), "path": rule.get("path") }))
+        .collect()
+}
+
+/// Reads `/metrics` (Prometheus text) for `cloudflared_tunnel_ha_connections`, and
+/// `/config`, which only remotely managed tunnels serve with their ingress rules.
+async fn query_metrics(client: &Client, address: &str) -> Value {
+    let base = format!("http://{}", address);
+    let metrics = match get_text(client, &format!("{}/metrics", base)).await {
+        Ok(metrics) => metrics,
+        Err(e) => return json!({ "reachable": false, "ha_connections": Value::Null, "remote_ingress": [], "error": e }),
+    };
+    let ha_connections = metrics
+        .lines()
+        .filter(|line| line.starts_with("cloudflared_tunnel_ha_connections"))
+        .find_map(|line| line.split_whitespace().last()?.parse::<f64>().ok())
+        .map(|connections| connections as u64);
+    let remote_ingress = match get_text(client, &format!("{}/config", base)).await {
+        Ok(config) => serde_json::from_str::<Value>(&config)
+            .ok()
+            .map(|config| ingress_rules(config.pointer("/config/ingress")))
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    json!({ "reachable": true, "ha_connections": ha_connections, "remote_ingress": remote_ingress, "error": Value::Null })
+}
+
+async fn get_text(client: &Client, url: &str) -> Result<String, Error> {
+    let response = client.get(url)?.timeout(METRICS_TIMEOUT).send().await.map_err(|e| Error::http(url, &e))?;
+    if !response.status().is_success() {
+        return Err(Error::status(url, response.status()));
+    }
+    response.text().await.map_err(|e| Error::http(url, &e))
+}
@@ -0,0 +1,379 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::accounts::{self, GROUP_FILE_PATH, PASSWD_FILE_PATH};
+use crate::cache::Cache;
+use crate::capability::{self, Capabilities};
+use crate::cli::Args;
+use crate::echo::{self, EchoEndpoint};
+use crate::facts::Section;
+use crate::http::Client;
+use crate::ip::{self, IpOptions};
+use crate::locale::{LOCALE_ARCHIVE_PATH, LOCALE_DIR_PATH};
+use crate::section_cache::{self, Ttl};
+use crate::{ansible, binaries, clock, cloudflared, connectivity, container_restarts, coredumps, databases, dbus, dhcp_leases, dir_sizes, docker_daemon, docker_images, docker_networks, docker_stats, download_clients, environment, fingerprint, gpu, http, listen_ports, locale, log_growth, media_servers, mounts, netplan, network_managers, output, ownership, processes, region, shares, sso, swap, systemd_mounts, timezone, tool_versions, traefik, vpn_gateways, wake_on_lan, wireguard, worker};
+
+pub struct Collector {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub capabilities: Capabilities,
+    /// Files that fully determine the section; with `--incremental` the section is
+    /// reused while none of them change.
+    pub sources: &'static [&'static str],
+    /// Runs in a worker process with resource limits and `--worker-timeout`, because
+    /// it can hang on a broken subsystem in a way that can't be cancelled. Hangs in a
+    /// command don't need this: `exec::output` kills commands that run too long.
+    pub isolated: bool,
+    /// Also runs off Linux; everything else is reported as unsupported there.
+    pub portable: bool,
+    /// Only runs when named in `--enable` or `--facts`, not with `--facts all`.
+    pub opt_in: bool,
+    /// Runs without `--facts`: the sections every release has printed.
+    pub baseline: bool,
+}
+
+const fn collector(name: &'static str, description: &'static str, exec: bool, network: bool, root: bool) -> Collector {
+    Collector {
+        name,
+        description,
+        capabilities: Capabilities { exec, network, root },
+        sources: &[],
+        isolated: false,
+        portable: false,
+        opt_in: false,
+        baseline: false,
+    }
+}
+
+impl Collector {
+    const fn reading(self, sources: &'static [&'static str]) -> Collector {
+        Collector { sources, ..self }
+    }
+
+    const fn isolated(self) -> Collector {
+        Collector { isolated: true, ..self }
+    }
+
+    const fn portable(self) -> Collector {
+        Collector { portable: true, ..self }
+    }
+
+    const fn opt_in(self) -> Collector {
+        Collector { opt_in: true, ..self }
+    }
+
+    const fn baseline(self) -> Collector {
+        Collector { baseline: true, ..self }
+    }
+}
+
+/// Every collector, in the order sections are collected, with what it declares it
+/// does. Exec and network use are checked while it runs.
+pub const COLLECTORS: &[Collector] = &[
+    // name, description, exec, network, root
+    collector("ip", "Public IPv4/IPv6 addresses from echo services", true, true, false).portable().baseline(),
+    collector("connectivity", "Outbound TCP reachability per address family", false, true, false).portable(),
+    collector("region", "Coarse region hint from connect latency to regional anchors (opt-in)", false, true, false)
+        .portable()
+        .opt_in(),
+    collector("groups", "Groups from /etc/group", false, false, false).reading(&[GROUP_FILE_PATH]).baseline(),
+    collector("users", "Users from /etc/passwd", false, false, false).reading(&[PASSWD_FILE_PATH]).baseline(),
+    collector("timezone", "System timezone", true, false, false).portable().baseline(),
+    collector("mounts", "Mounted filesystems and their usage", false, false, false).isolated(),
+    collector("swap", "Swap devices and files with sizes and priorities, zram devices and swap usage", false, false, false),
+    collector("systemd_mounts", "systemd mount and automount units: state, origin (fstab or unit file) and idle timeouts", true, false, false),
+    collector("network_managers", "Whether NetworkManager, systemd-networkd or ifupdown manages each interface", true, false, false),
+    collector("netplan", "Netplan renderer and per-interface DHCP and static settings, with drift from the live addresses", false, false, true),
+    collector("dhcp_leases", "Current DHCP leases from networkd, NetworkManager and dhclient, with server, DNS, routers and expiry", false, false, false),
+    collector("wake_on_lan", "Wake-on-LAN modes each physical interface supports and has armed, from ethtool", true, false, false),
+    collector("dbus", "Key system D-Bus services (NetworkManager, resolved, timedated, logind) running or activatable", false, false, false),
+    collector("rtc", "Hardware clock and whether it keeps local time", true, false, false),
+    collector("clocksource", "Kernel clocksource", false, false, false),
+    collector("locales", "Installed locales", false, false, false).reading(&[LOCALE_ARCHIVE_PATH, LOCALE_DIR_PATH]),
+    collector("environment", "Whitelisted environment variables", false, false, false).portable(),
+    collector("binaries", "Presence of common binaries on PATH", false, false, false).portable(),
+    collector("tool_versions", "Versions reported by `<tool> --version`", true, false, false).portable(),
+    collector("docker_daemon", "dockerd settings from daemon.json and systemd drop-ins", false, false, false),
+    collector("docker_images", "Local Docker images and, with --docker-check-updates, registry updates", false, true, false),
+    collector("docker_networks", "Docker network subnets and overlaps with host LAN/VPN routes", false, false, false),
+    collector("docker_stats", "Point-in-time CPU, memory and block I/O per running container", false, false, false),
+    collector("container_restarts", "Containers in a restart loop, from recent Docker die events", false, false, false),
+    collector("databases", "PostgreSQL, MariaDB/MySQL and Redis servers on the host: versions, data directories, ports and sockets", false, false, true),
+    collector("shares", "Samba shares and NFS exports with their paths and access options, flagging hand-edited files", false, false, false),
+    collector("listen_ports", "Listening TCP and UDP sockets with their process and Docker container", false, false, true),
+    collector("processes", "Processes above CPU or memory thresholds with their user and cgroup (opt-in)", false, false, false).opt_in(),
+    collector("coredumps", "Recent core dumps and crash reports per binary, and kernel.core_pattern", false, false, false),
+    collector("gpu_container", "Whether containers can use the NVIDIA GPU (driver, toolkit, runtime or CDI)", false, false, false),
+    collector("traefik", "Traefik routers, services and per-domain routing from its API; ACME domains", false, true, false),
+    collector("sso", "SSO provider (Authelia/Authentik), its domains and Traefik middlewares using it", false, true, false),
+    collector("cloudflared", "cloudflared installs, tunnels and their ingress hostnames", false, true, false),
+    collector("vpn_gateways", "VPN gateway containers (gluetun), their egress IP, forwarded port and routed containers", false, true, false),
+    collector("wireguard", "WireGuard interfaces, peers and handshake ages, flagging stale peers", false, false, true),
+    collector("log_growth", "Log file and container log sizes and growth rates since the previous run", false, false, true),
+    collector("plex", "Plex version, claim state, transcoder settings and library sizes (opt-in)", false, true, false).opt_in(),
+    collector("jellyfin", "Jellyfin version, setup state, transcoding and library sizes (opt-in)", false, true, false).opt_in(),
+    collector("emby", "Emby version, setup state, transcoding and library sizes (opt-in)", false, true, false).opt_in(),
+    collector("download_clients", "Download client listening ports versus the VPN's forwarded port (opt-in)", false, true, false).opt_in(),
+    collector("ownership", "Owner and group consistency of application data under /opt and /srv (opt-in)", false, false, true).opt_in(),
+    collector("dir_sizes", "Disk usage of Docker's data root, logs and transcodes within a time budget (opt-in)", false, false, true).opt_in(),
+    collector("ansible_controller", "Whether this host is an Ansible controller", true, false, false),
+    collector("host_fingerprint", "Salted hash of stable hardware identifiers", false, false, true),
+];
+
+pub fn find(name: &str) -> Option<&'static Collector> {
+    COLLECTORS.iter().find(|collector| collector.name == name)
+}
+
+pub fn names() -> Vec<&'static str> {
+    COLLECTORS.iter().map(|collector| collector.name).collect()
+}
+
+/// Collectors deliberately not ported off Linux, and why. Users and groups would
+/// need WinAPI account enumeration (NetUserEnum / NetLocalGroupEnum) and the
+/// interface collectors GetAdaptersAddresses; neither is implemented, so they're
+/// reported as skipped with this reason rather than as failures.
+const NOT_PORTED: &[(&str, &str)] = &[
+    ("groups", "not implemented off Linux (local groups need WinAPI)"),
+    ("users", "not implemented off Linux (local users need WinAPI)"),
+    ("network_managers", "not implemented off Linux (network interfaces need WinAPI)"),
+    ("netplan", "not implemented off Linux (network interfaces need WinAPI)"),
+    ("dhcp_leases", "not implemented off Linux (network interfaces need WinAPI)"),
+    ("wake_on_lan", "not implemented off Linux (network interfaces need WinAPI)"),
+];
+
+pub fn opt_in_names() -> Vec<&'static str> {
+    COLLECTORS.iter().filter(|collector| collector.opt_in).map(|collector| collector.name).collect()
+}
+
+/// Why a selected collector won't run on this platform or under `--no-exec` / `--offline`,
+/// if it won't.
+pub fn skip_reason(collector: &Collector, args: &Args) -> Option<&'static str> {
+    if !collector.portable && !cfg!(target_os = "linux") {
+        Some(not_ported_reason(collector.name))
+    } else if collector.opt_in && !args.enable.iter().any(|name| name == collector.name) && !args.named(collector.name) {
+        Some("opt-in (--enable)")
+    } else if args.no_exec && collector.capabilities.exec {
+        Some("runs commands (--no-exec)")
+    } else if args.offline && collector.capabilities.network {
+        Some("uses the network (--offline)")
+    } else {
+        None
+    }
+}
+
+fn not_ported_reason(name: &str) -> &'static str {
+    NOT_PORTED
+        .iter()
+        .find(|(not_ported, _)| *not_ported == name)
+        .map_or("not supported on this platform", |(_, reason)| reason)
+}
+
+/// Prints the `--list-collectors` table through a locked stdout; a reader that
+/// stops early, such as `head`, ends it without an error.
+pub fn print_list() -> io::Result<()> {
+    output::closed_pipe_is_ok(write_list(&mut io::stdout().lock()))
+}
+
+fn write_list(out: &mut impl Write) -> io::Result<()> {
+    let mark = |declared: bool| if declared { "yes" } else { "-" };
+    writeln!(out, "{:<20} {:<5} {:<8} {:<5} description", "collector", "exec", "network", "root")?;
+    for collector in COLLECTORS {
+        let capabilities = &collector.capabilities;
+        writeln!(
+            out,
+            "{:<20} {:<5} {:<8} {:<5} {}",
+            collector.name,
+            mark(capabilities.exec),
+            mark(capabilities.network),
+            mark(capabilities.root),
+            collector.description
+        )?;
+    }
+    out.flush()
+}
+
+/// What collectors share for one run.
+pub struct Context<'a> {
+    pub args: &'a Args,
+    pub client: Client,
+    pub cache: Cache,
+    pub ip_options: IpOptions,
+    /// Whether isolated collectors run in worker processes; false inside a worker.
+    pub isolate: bool,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(args: &'a Args) -> Result<Context<'a>, Box<dyn Error>> {
+        let echo_token = args.echo_token_file.as_deref().map(echo::read_token).transpose()?;
+        let endpoint = |url: &Option<String>| url.clone().map(|url| EchoEndpoint { url, token: echo_token.clone() });
+        Ok(Context {
+            args,
+            client: http::build_client(&args.http())?,
+            cache: Cache::open(args.cache_dir.as_deref()),
+            ip_options: IpOptions {
+                min_requery_interval: if args.refreshes("ip") { 0 } else { args.min_requery_interval },
+                rate_limit: args.rate_limit,
+                ipv4: !args.no_ipv4,
+                ipv6: !args.no_ipv6,
+                echo_ipv4: endpoint(&args.echo_url_ipv4),
+                echo_ipv6: endpoint(&args.echo_url_ipv6),
+                timeout: args.http_timeout,
+            },
+            isolate: args.worker_timeout > 0,
+        })
+    }
+}
+
+/// Runs one collector, holding it to its declared capabilities.
+pub async fn collect(name: &str, context: &Context<'_>) -> Result<Section, Box<dyn Error>> {
+    let collector = find(name).ok_or_else(|| format!("Unknown collector: {}", name))?;
+    let args = context.args;
+    let ttl = args.cache_ttls.iter().rev().find(|(collector, _)| collector == name).map(|(_, ttl)| *ttl);
+    let incremental = args.incremental && !collector.sources.is_empty();
+    // Taken before collecting, so a change while parsing is picked up next run.
+    let fingerprint = incremental.then(|| section_cache::fingerprint(collector.sources));
+    let cached = ttl != Some(Ttl::Secs(0)) && (ttl.is_some() || fingerprint.is_some());
+    let options = if cached { options(collector, args) } else { String::new() };
+    if cached && !args.refreshes(name) {
+        if let Some(section) = section_cache::lookup(&context.cache, name, &options, fingerprint.as_ref(), ttl) {
+            return Ok(section);
+        }
+    }
+
+    let result = if collector.isolated && context.isolate {
+        worker::collect(name, context).await
+    } else {
+        capability::enter(collector.name, collector.capabilities);
+        let result = run_collector(name, context).await;
+        capability::leave();
+        result
+    };
+
+    match &result {
+        Ok(section) if cached => section_cache::store(&context.cache, name, &options, fingerprint.as_ref(), section),
+        _ => {}
+    }
+    result
+}
+
+/// The options `collector`'s section depends on, which a cached section must have
+/// been collected with; those of the HTTP client count for every collector using
+/// the network. Keep in step with `run_collector`.
+fn options(collector: &Collector, args: &Args) -> String {
+    let own = match collector.name {
+        "ip" => format!("{:?}", (args.no_ipv4, args.no_ipv6, &args.echo_url_ipv4, &args.echo_url_ipv6, &args.echo_token_file)),
+        "connectivity" => format!("{:?}", (args.no_ipv4, args.no_ipv6)),
+        "groups" | "users" => format!("{:?}", args.max_entries),
+        "environment" => format!("{:?}", args.env_vars),
+        "binaries" => format!("{:?}", args.binaries),
+        "tool_versions" => format!("{:?}", args.tool_versions),
+        "docker_images" => format!("{:?}", args.docker_check_updates),
+        "docker_networks" => format!("{:?}", args.vpn_subnets),
+        "container_restarts" => format!("{:?}", (args.flap_restarts, args.flap_window)),
+        "processes" => format!("{:?}", (args.process_cpu, args.process_memory)),
+        "coredumps" => format!("{:?}", (args.crash_threshold, args.crash_window)),
+        "traefik" => format!("{:?}", (&args.traefik_api, &args.traefik_acme)),
+        "sso" => format!("{:?}", (&args.traefik_api, &args.authelia_config)),
+        "cloudflared" => format!("{:?}", args.cloudflared_configs),
+        "vpn_gateways" => format!("{:?}", args.gluetun_api),
+        "wireguard" => format!("{:?}", args.wireguard_stale_after),
+        "log_growth" => format!("{:?}", args.log_paths),
+        "plex" => format!("{:?}", (&args.plex_url, &args.plex_preferences)),
+        "jellyfin" => format!("{:?}", (&args.jellyfin_url, &args.jellyfin_token_file)),
+        "emby" => format!("{:?}", (&args.emby_url, &args.emby_token_file)),
+        "download_clients" => format!("{:?}", (&args.download_clients, &args.gluetun_api, &args.forwarded_port_file)),
+        "ownership" => format!("{:?}", (&args.ownership_roots, &args.ownership_user, args.ownership_max_files)),
+        "dir_sizes" => format!("{:?}", (&args.dir_sizes, args.dir_size_budget)),
+        "host_fingerprint" => format!("{:?}", args.fingerprint_salt),
+        _ => String::new(),
+    };
+    match collector.capabilities.network {
+        true => format!("{} {:?}", own, args.http()),
+        false => own,
+    }
+}
+
+async fn run_collector(name: &str, context: &Context<'_>) -> Result<Section, Box<dyn Error>> {
+    let args = context.args;
+    let value = match name {
+        "ip" => return Ok(ip::get_ip_facts(&context.client, &context.cache, &context.ip_options).await),
+        "connectivity" => connectivity::get_connectivity(!args.no_ipv4, !args.no_ipv6).await,
+        "region" => region::get_region_hint().await,
+        // Typed entries, written straight from the mapped file when the document is
+        // streamed; anything needing the assembled document gets their JSON tree.
+        "groups" => {
+            let (groups, truncated) = accounts::Listing::groups(args.max_entries)?;
+            return Ok(Section { truncated, ..Section::typed(groups) });
+        }
+        "users" => {
+            let (users, truncated) = accounts::Listing::users(args.max_entries)?;
+            return Ok(Section { truncated, ..Section::typed(users) });
+        }
+        "timezone" => timezone::get_timezone().await?,
+        "mounts" => mounts::get_mounts()?,
+        "swap" => swap::get_swap(),
+        "systemd_mounts" => systemd_mounts::get_systemd_mounts().await,
+        "network_managers" => network_managers::get_network_managers().await,
+        "netplan" => netplan::get_netplan(),
+        "dhcp_leases" => dhcp_leases::get_dhcp_leases(),
+        "wake_on_lan" => wake_on_lan::get_wake_on_lan().await,
+        "dbus" => dbus::get_dbus().await,
+        "rtc" => clock::get_rtc().await,
+        "clocksource" => clock::get_clocksource(),
+        "locales" => locale::get_locales(),
+        "environment" => environment::get_environment(&args.env_vars),
+        "binaries" => binaries::get_binaries(&args.binaries),
+        "tool_versions" => tool_versions::get_tool_versions(&args.tool_versions).await,
+        "docker_daemon" => docker_daemon::get_docker_daemon(),
+        "docker_images" => return Ok(docker_images::get_docker_images(&context.client, args.docker_check_updates).await.into()),
+        "docker_networks" => return Ok(docker_networks::get_docker_networks(&args.vpn_subnets).await.into()),
+        "docker_stats" => return Ok(docker_stats::get_docker_stats().await.into()),
+        "container_restarts" => return Ok(container_restarts::get_container_restarts(args.flap_restarts, args.flap_window).await.into()),
+        "databases" => databases::get_databases(),
+        "shares" => shares::get_shares(),
+        "listen_ports" => listen_ports::get_listen_ports().await,
+        "processes" => processes::get_processes(args.process_cpu, args.process_memory).await,
+        "coredumps" => coredumps::get_coredumps(args.crash_threshold, args.crash_window),
+        "gpu_container" => gpu::get_gpu_container().await,
+        "traefik" => return Ok(traefik::get_traefik(&context.client, &args.traefik_api, &args.traefik_acme).await.into()),
+        "sso" => sso::get_sso(&context.client, &args.traefik_api, &args.authelia_config).await,
+        "cloudflared" => cloudflared::get_cloudflared(&context.client, &args.cloudflared_configs).await,
+        "vpn_gateways" => vpn_gateways::get_vpn_gateways(&context.client, &args.gluetun_api).await,
+        "wireguard" => wireguard::get_wireguard(args.wireguard_stale_after),
+        "log_growth" => {
+            let paths: Vec<String> = if args.log_paths.is_empty() {
+                log_growth::DEFAULT_LOG_PATHS.iter().map(|path| path.to_string()).collect()
+            } else {
+                args.log_paths.clone()
+            };
+            log_growth::get_log_growth(&context.cache, &paths)
+        }
+        "plex" => return Ok(media_servers::get_plex(&context.client, &args.plex_url, &args.plex_preferences).await.into()),
+        "jellyfin" => return Ok(media_servers::get_jellyfin(&context.client, &args.jellyfin_url, args.jellyfin_token_file.as_deref()).await.into()),
+        "emby" => return Ok(media_servers::get_emby(&context.client, &args.emby_url, args.emby_token_file.as_deref()).await.into()),
+        "download_clients" => {
+            download_clients::get_download_clients(&context.client, &args.download_clients, &args.gluetun_api, args.forwarded_port_file.as_deref()).await
+        }
+        "ownership" => {
+            let roots: Vec<String> = if args.ownership_roots.is_empty() {
+                ownership::DEFAULT_OWNERSHIP_ROOTS.iter().map(|root| root.to_string()).collect()
+            } else {
+                args.ownership_roots.clone()
+            };
+            ownership::get_ownership(&roots, args.ownership_user.as_deref(), args.ownership_max_files)?
+        }
+        "dir_sizes" => {
+            let directories: Vec<String> = if args.dir_sizes.is_empty() {
+                dir_sizes::DEFAULT_DIRECTORIES.iter().map(|directory| directory.to_string()).collect()
+            } else {
+                args.dir_sizes.clone()
+            };
+            dir_sizes::get_dir_sizes(&directories, Duration::from_secs(args.dir_size_budget))
+        }
+        "ansible_controller" => ansible::get_ansible_controller().await,
+        "host_fingerprint" => fingerprint::get_host_fingerprint(args.fingerprint_salt.as_deref()),
+        _ => return Err(format!("Unknown collector: {}", name).into()),
+    };
+    Ok(Section::new(value))
+}
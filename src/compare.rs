@@ -0,0 +1,207 @@
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fs;
+
+use crate::cli::CompareArgs;
+use crate::diff::{self, Change};
+use crate::output::ReportFormat;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Severity hints keyed by JSON Pointer pattern (see `diff::pointer_matches`); the
+/// first match wins. Anything unmatched is a warning.
+const RULES: &[(&str, Severity, &str)] = &[
+    ("/ip/public_ip", Severity::Critical, "public IPv4 address changed"),
+    ("/ip/public_ipv6", Severity::Critical, "public IPv6 address changed"),
+    ("/ip/**", Severity::Info, "lookup details vary between runs"),
+    ("/connectivity/verdict", Severity::Critical, "outbound connectivity changed"),
+    ("/connectivity/**", Severity::Info, "probe details vary between runs"),
+    ("/host_fingerprint/fingerprint", Severity::Critical, "different host or replaced hardware"),
+    ("/mounts/*/used_gb", Severity::Info, "disk usage fluctuates"),
+    ("/mounts/*/available_gb", Severity::Info, "disk usage fluctuates"),
+    ("/mounts/*/used_percent", Severity::Info, "disk usage fluctuates"),
+    ("/mounts/**", Severity::Critical, "mount layout changed"),
+    ("/users/*", Severity::Critical, "account added or removed"),
+    ("/timezone", Severity::Critical, "timezone changed"),
+    ("/locales/has_en_us_utf8", Severity::Critical, "en_US.UTF-8 availability changed"),
+    ("/ansible_controller/is_controller", Severity::Critical, "controller role changed"),
+    ("/tool_versions/**", Severity::Info, "routine upgrade"),
+    ("/saltbox_facts_version", Severity::Info, "collector upgraded"),
+    ("/schema_version", Severity::Warning, "document shape changed"),
+    ("/drop_ins/**", Severity::Info, "drop-in bookkeeping"),
+];
+
+/// Compares two fact documents and prints a report of the differences, most
+/// severe first.
+pub fn run(args: CompareArgs) -> Result<(), Box<dyn Error>> {
+    let old = load(&args.old)?;
+    let new = load(&args.new)?;
+    let classified = classify_changes(&old, &new, args.min_severity);
+
+    let count = |severity: Severity| classified.iter().filter(|(s, _)| *s == severity).count();
+    let summary = json!({
+        "critical": count(Severity::Critical),
+        "warning": count(Severity::Warning),
+        "info": count(Severity::Info)
+    });
+    let entries: Vec<Value> = classified.into_iter().map(|(_, entry)| entry).collect();
+
+    match args.format {
+        ReportFormat::Json => {
+            let report = json!({ "changed": !entries.is_empty(), "summary": summary, "changes": entries });
+            println!("{}", serde_json::to_string(&report)?);
+        }
+        ReportFormat::Text => {
+            for entry in &entries {
+                let detail = match entry["kind"].as_str() {
+                    Some("added") => format!("+ {}", preview(&entry["new"])),
+                    Some("removed") => format!("- {}", preview(&entry["old"])),
+                    _ => format!("{} -> {}", preview(&entry["old"]), preview(&entry["new"])),
+                };
+                println!(
+                    "{:<8}  {:<8}  {}: {}  ({})",
+                    entry["severity"].as_str().unwrap_or_default().to_uppercase(),
+                    entry["kind"].as_str().unwrap_or_default(),
+                    entry["path"].as_str().unwrap_or_default(),
+                    detail,
+                    entry["reason"].as_str().unwrap_or_default()
+                );
+            }
+            match entries.len() {
+                0 => println!("No differences"),
+                total => println!(
+                    "{} difference{}: {} critical, {} warning, {} info",
+                    total,
+                    if total == 1 { "" } else { "s" },
+                    summary["critical"],
+                    summary["warning"],
+                    summary["info"]
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The changes from `old` to `new` at `min_severity` or above, most severe first,
+/// each with its severity and the report entry for it.
+fn classify_changes(old: &Value, new: &Value, min_severity: Severity) -> Vec<(Severity, Value)> {
+    let mut classified: Vec<(Severity, Value)> = diff::diff(old, new)
+        .into_iter()
+        .filter_map(|change| {
+            let (kind, path, old, new) = match change {
+                Change::Add { path, value } => ("added", path, Value::Null, value),
+                Change::Remove { path, old } => ("removed", path, old, Value::Null),
+                Change::Replace { path, old, value } => ("modified", path, old, value),
+            };
+            let (severity, reason) = classify(&path);
+            let entry = json!({
+                "path": path,
+                "kind": kind,
+                "severity": severity.name(),
+                "reason": reason,
+                "old": old,
+                "new": new
+            });
+            (severity >= min_severity).then_some((severity, entry))
+        })
+        .collect();
+    // Changes already come out in path order, so a stable sort keeps it within a severity.
+    classified.sort_by_key(|(severity, _)| Reverse(*severity));
+    classified
+}
+
+fn load(path: &str) -> Result<Value, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Cannot parse {}: {}", path, e))
+}
+
+fn classify(path: &str) -> (Severity, &'static str) {
+    RULES
+        .iter()
+        .find(|(pattern, _, _)| diff::pointer_matches(pattern, path))
+        .map(|&(_, severity, reason)| (severity, reason))
+        .unwrap_or((Severity::Warning, "unclassified change"))
+}
+
+/// Compact JSON, shortened so one change stays on one line.
+fn preview(value: &Value) -> String {
+    const MAX_CHARS: usize = 60;
+    let text = value.to_string();
+    if text.chars().count() <= MAX_CHARS {
+        return text;
+    }
+    let mut short: String = text.chars().take(MAX_CHARS - 3).collect();
+    short.push_str("...");
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(classified: &[(Severity, Value)]) -> Vec<(&'static str, &str, &str)> {
+        classified.iter().map(|(severity, entry)| (severity.name(), entry["kind"].as_str().unwrap(), entry["path"].as_str().unwrap())).collect()
+    }
+
+    #[test]
+    fn identical_documents_have_no_changes() {
+        let document = json!({ "ip": { "public_ip": "203.0.113.7" }, "timezone": "Etc/UTC" });
+        assert!(classify_changes(&document, &document, Severity::Info).is_empty());
+    }
+
+    #[test]
+    fn changes_are_classified_most_severe_first() {
+        let old = json!({ "ip": { "public_ip": "203.0.113.7", "source": "a" }, "timezone": "Etc/UTC", "users": { "root": {} }, "custom": 1 });
+        let new = json!({ "ip": { "public_ip": "203.0.113.8", "source": "b" }, "users": { "root": {}, "seed": { "uid": "1000" } }, "custom": 2 });
+        let classified = classify_changes(&old, &new, Severity::Info);
+        assert_eq!(
+            paths(&classified),
+            [
+                ("critical", "modified", "/ip/public_ip"),
+                ("critical", "removed", "/timezone"),
+                ("critical", "added", "/users/seed"),
+                ("warning", "modified", "/custom"),
+                ("info", "modified", "/ip/source"),
+            ]
+        );
+        assert_eq!(
+            classified[0].1,
+            json!({ "path": "/ip/public_ip", "kind": "modified", "severity": "critical", "reason": "public IPv4 address changed", "old": "203.0.113.7", "new": "203.0.113.8" })
+        );
+        assert_eq!((&classified[1].1["old"], &classified[1].1["new"]), (&json!("Etc/UTC"), &Value::Null));
+        assert_eq!(classified[3].1["reason"], json!("unclassified change"));
+
+        let critical = classify_changes(&old, &new, Severity::Critical);
+        assert_eq!(critical.len(), 3);
+    }
+
+    #[test]
+    fn unreadable_documents_are_errors() {
+        assert!(load("/nonexistent/facts.json").unwrap_err().starts_with("Cannot read /nonexistent/facts.json"));
+    }
+
+    #[test]
+    fn long_values_are_shortened() {
+        assert_eq!(preview(&json!("short")), "\"short\"");
+        let long = preview(&json!("x".repeat(100)));
+        assert_eq!(long.chars().count(), 60);
+        assert!(long.ends_with("..."));
+    }
+}
@@ -0,0 +1,49 @@
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, Command, ValueHint};
+use clap_complete::Shell;
+use std::error::Error;
+use std::io::{self, Write};
+
+use crate::cli;
+use crate::collectors;
+use crate::output;
+
+/// `completions <SHELL>`: the completion script clap_complete generates from the
+/// parser itself, so it lists exactly the subcommands and options it accepts. It's
+/// generated into a buffer and written at once, as clap_complete panics on write
+/// errors, so a reader that stops early ends it without an error.
+pub fn run(shell: Shell) -> Result<(), Box<dyn Error>> {
+    let mut command = hint(cli::command());
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, cli::BIN, &mut script);
+    let mut stdout = io::stdout().lock();
+    output::closed_pipe_is_ok(stdout.write_all(&script).and_then(|()| stdout.flush()))?;
+    Ok(())
+}
+
+/// Gives the completion script what the value parsers can't tell it: collector
+/// names for the lists of collectors and files where a path is expected. Parsing
+/// never sees these; they only exist for the generator.
+fn hint(command: Command) -> Command {
+    command.mut_args(hint_arg).mut_subcommands(hint)
+}
+
+fn hint_arg(arg: Arg) -> Arg {
+    let collectors = collectors::names();
+    let words = match arg.get_id().as_str() {
+        "facts" | "enable" | "collectors" | "path" => Some(collectors),
+        "refresh" => Some([vec!["all"], collectors].concat()),
+        "only" => Some(output::csv_section_names()),
+        _ => None,
+    };
+    if let Some(words) = words {
+        return arg.value_parser(PossibleValuesParser::new(words));
+    }
+    let value_names = arg.get_value_names().unwrap_or_default();
+    match value_names.first().map(|name| name.as_str()) {
+        Some("FILE") => arg.value_hint(ValueHint::FilePath),
+        Some("DIR") => arg.value_hint(ValueHint::DirPath),
+        Some("PATH") if arg.get_long().is_some() => arg.value_hint(ValueHint::AnyPath),
+        _ => arg,
+    }
+}
@@ -0,0 +1,399 @@
+use clap::{Arg, ArgAction};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs;
+
+use crate::cli;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/ansible-facts.toml";
+pub const ENV_PREFIX: &str = "ANSIBLE_FACTS_";
+/// Options only the command line and `ANSIBLE_FACTS_CONFIG`/`_NO_CONFIG` give,
+/// besides `--help`.
+const NOT_LAYERED: &[&str] = &["config", "no-config", "help"];
+/// The config file picked by `--config` and `--no-config` or, failing those, the
+/// environment: the one given, which must exist, else [`DEFAULT_CONFIG_PATH`] when
+/// it does; none with `--no-config`.
+pub fn path(config: Option<&str>, disabled: bool) -> Option<(String, bool)> {
+    if disabled {
+        return None;
+    }
+    let mut chosen = config.map(String::from);
+    if chosen.is_none() {
+        if env_flag("NO_CONFIG") {
+            return None;
+        }
+        chosen = env::var(format!("{}CONFIG", ENV_PREFIX)).ok().filter(|path| !path.is_empty());
+    }
+    Some(match chosen {
+        Some(path) => (path, true),
+        None => (DEFAULT_CONFIG_PATH.to_string(), false),
+    })
+}
+
+/// Options set by `ANSIBLE_FACTS_<OPTION>` variables, e.g. `ANSIBLE_FACTS_HTTP_TIMEOUT=10`
+/// for `--http-timeout 10`, each as its variable and the arguments it stands for.
+/// Every long option of a gather run may be set. A flag takes `true` or `false`,
+/// which turns it off even when the config file set it; a repeatable option takes
+/// one value per line, and an empty value leaves an option as it is. Variables
+/// naming no option are ignored: Ansible's own settings (`ANSIBLE_FACTS_MODULES`)
+/// share the prefix.
+pub fn from_env() -> Vec<(String, Vec<String>)> {
+    options_from(env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?))))
+}
+
+pub fn options_from(variables: impl Iterator<Item = (String, String)>) -> Vec<(String, Vec<String>)> {
+    let command = cli::layer_command();
+    let mut options: Vec<(String, Vec<String>)> = variables
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase().replace('_', "-");
+            let arg = option(&command, &key)?;
+            let flag = format!("--{}", key);
+            let args = match arg.get_action() {
+                _ if value.is_empty() => Vec::new(),
+                ArgAction::SetTrue => vec![format!("{}={}", flag, value.to_ascii_lowercase())],
+                ArgAction::Count => match value.parse::<usize>() {
+                    Ok(count) => vec![flag; count],
+                    Err(_) => vec![format!("{}={}", flag, value)],
+                },
+                _ if is_comma_list(arg) => vec![format!("{}={}", flag, value)],
+                _ => value.lines().map(|line| format!("{}={}", flag, line)).collect(),
+            };
+            Some((name, args))
+        })
+        .collect();
+    // The environment's order isn't meaningful; this keeps runs reproducible.
+    options.sort();
+    options
+}
+
+/// The long option `long` of `command`, unless it can't be set by a layer.
+fn option<'a>(command: &'a clap::Command, long: &str) -> Option<&'a Arg> {
+    command.get_arguments().find(|arg| arg.get_long() == Some(long)).filter(|_| !NOT_LAYERED.contains(&long))
+}
+
+/// Whether the option takes one comma-separated list, which an array is joined into.
+fn is_comma_list(arg: &Arg) -> bool {
+    arg.get_value_delimiter() == Some(',')
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(format!("{}{}", ENV_PREFIX, name)).is_ok_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// The options set in the TOML file at `path`, each as its key and the arguments it
+/// stands for. A key is a long option without its dashes, `_` standing for `-`; keys
+/// in a `[table]` get the table's name as a prefix, so `timeout` under `[http]` is
+/// `--http-timeout`. `true` sets a flag, `false` turns it off.
+///
+/// Nothing when the file is optional and missing.
+pub fn load(path: &str, required: bool) -> Result<Vec<(String, Vec<String>)>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Cannot read {}: {}", path, e)),
+    };
+    parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn parse(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let table: toml::Table = toml::from_str(text).map_err(|e| e.to_string().trim_end().to_string())?;
+    let mut options = toml::Table::new();
+    flatten("", table, &mut options)?;
+    if let Some(key) = ["config", "no-config"].into_iter().find(|key| options.contains_key(*key)) {
+        return Err(format!("{} can only be given on the command line", key));
+    }
+    let config: Config = serde_path_to_error::deserialize(toml::Value::Table(options)).map_err(|e| {
+        // serde lists every field it expected after an unknown one; --help says it better.
+        let message = e.inner().message().split(", expected one of").next().unwrap_or_default().to_string();
+        match message.starts_with("unknown field") {
+            true => format!("{} (keys are the long options of --help)", message),
+            false => format!("{}: {}", e.path(), message),
+        }
+    })?;
+    let Value::Object(options) = serde_json::to_value(config).map_err(|e| e.to_string())? else {
+        return Ok(Vec::new());
+    };
+    let command = cli::layer_command();
+    Ok(options
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| {
+            let comma_list = option(&command, &key).is_some_and(is_comma_list);
+            (key.clone(), args(&key, value, comma_list))
+        })
+        .collect())
+}
+
+/// Moves the keys of `table` into `options` as option names: `_` becomes `-` and
+/// a table's keys get its name as a prefix.
+fn flatten(prefix: &str, table: toml::Table, options: &mut toml::Table) -> Result<(), String> {
+    for (key, value) in table {
+        let key = format!("{}{}", prefix, key.replace('_', "-"));
+        match value {
+            toml::Value::Table(table) => flatten(&format!("{}-", key), table, options)?,
+            value => {
+                if options.insert(key.clone(), value).is_some() {
+                    return Err(format!("{} is set twice", key));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The arguments an option's value stands for.
+fn args(key: &str, value: Value, comma_list: bool) -> Vec<String> {
+    let flag = format!("--{}", key);
+    let scalar = |value: Value| match value {
+        Value::String(value) => value,
+        value => value.to_string(),
+    };
+    match value {
+        Value::Bool(true) => vec![flag],
+        Value::Bool(false) => vec![format!("{}=false", flag)],
+        // A count, given as often as it says.
+        Value::Number(count) if key == "verbose" => vec![flag; count.as_u64().unwrap_or_default() as usize],
+        Value::Array(items) if comma_list => {
+            vec![format!("{}={}", flag, items.into_iter().map(scalar).collect::<Vec<_>>().join(","))]
+        }
+        Value::Array(items) => items.into_iter().map(|item| format!("{}={}", flag, scalar(item))).collect(),
+        value => vec![format!("{}={}", flag, scalar(value))],
+    }
+}
+
+/// The options a config file may set, one field per long option of a gather run
+/// (but `--config` and `--no-config`). The types only say what TOML value a key
+/// takes; the value itself is checked when it's parsed as the option's argument.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Config {
+    env_vars: Option<List>,
+    binaries: Option<List>,
+    tool_versions: Option<List>,
+    fingerprint_salt: Option<String>,
+    no_ipv4: Option<bool>,
+    no_ipv6: Option<bool>,
+    echo_url_ipv4: Option<String>,
+    echo_url_ipv6: Option<String>,
+    echo_token_file: Option<String>,
+    ca_bundle: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    header: Option<List>,
+    user_agent: Option<String>,
+    dns_server: Option<List>,
+    insecure: Option<bool>,
+    max_entries: Option<u64>,
+    facts: Option<List>,
+    docker_check_updates: Option<bool>,
+    flap_restarts: Option<u64>,
+    flap_window: Option<u64>,
+    traefik_api: Option<String>,
+    traefik_acme: Option<String>,
+    authelia_config: Option<String>,
+    cloudflared_config: Option<List>,
+    wireguard_stale_after: Option<u64>,
+    ownership_root: Option<List>,
+    ownership_user: Option<String>,
+    ownership_max_files: Option<u64>,
+    dir_size: Option<List>,
+    dir_size_budget: Option<u64>,
+    log_path: Option<List>,
+    crash_threshold: Option<u64>,
+    crash_window: Option<u64>,
+    process_cpu: Option<u64>,
+    process_memory: Option<u64>,
+    plex_url: Option<String>,
+    plex_preferences: Option<String>,
+    jellyfin_url: Option<String>,
+    jellyfin_token_file: Option<String>,
+    emby_url: Option<String>,
+    emby_token_file: Option<String>,
+    download_client: Option<List>,
+    gluetun_api: Option<String>,
+    forwarded_port_file: Option<String>,
+    vpn_subnet: Option<List>,
+    enable: Option<List>,
+    no_exec: Option<bool>,
+    offline: Option<bool>,
+    progress: Option<String>,
+    verbose: Option<u8>,
+    quiet: Option<bool>,
+    log_level: Option<String>,
+    list_collectors: Option<bool>,
+    worker_timeout: Option<u64>,
+    timeout: Option<u64>,
+    incremental: Option<bool>,
+    cache_ttl: Option<List>,
+    refresh: Option<List>,
+    cache_dir: Option<String>,
+    min_requery_interval: Option<u64>,
+    http_timeout: Option<u64>,
+    rate_limit: Option<u64>,
+    drop_in_dir: Option<String>,
+    static_facts: Option<String>,
+    static_precedence: Option<String>,
+    derive: Option<List>,
+    derived_facts: Option<String>,
+    assert: Option<List>,
+    assertions: Option<String>,
+    strict: Option<bool>,
+    deterministic: Option<bool>,
+    schema_version: Option<u32>,
+    diff: Option<String>,
+    diff_format: Option<String>,
+    query: Option<String>,
+    flatten: Option<bool>,
+    facter: Option<bool>,
+    namespace: Option<String>,
+    profile: Option<bool>,
+    compact: Option<bool>,
+    format: Option<String>,
+    output: Option<String>,
+    facts_d: Option<String>,
+    only: Option<String>,
+    check: Option<List>,
+}
+
+/// The value of a list or repeatable option: an array, or one string as the
+/// command line would take it.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged, expecting = "expected a string or an array of strings")]
+enum List {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(text: &str) -> Vec<(String, Vec<String>)> {
+        parse(text).unwrap()
+    }
+
+    #[test]
+    fn tables_prefix_their_keys() {
+        let text = "no_ipv6 = true\n\n[http]\ntimeout = 10\n";
+        assert_eq!(
+            options(text),
+            vec![
+                ("http-timeout".to_string(), vec!["--http-timeout=10".to_string()]),
+                ("no-ipv6".to_string(), vec!["--no-ipv6".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_and_flags() {
+        let text = r#"
+            facts = ["ip", "users"]
+            log-path = ["/var/log", "/opt/logs"]
+            header = "X-Token: 1"
+            strict = false
+            verbose = 2
+        "#;
+        assert_eq!(
+            options(text),
+            vec![
+                ("facts".to_string(), vec!["--facts=ip,users".to_string()]),
+                ("header".to_string(), vec!["--header=X-Token: 1".to_string()]),
+                ("log-path".to_string(), vec!["--log-path=/var/log".to_string(), "--log-path=/opt/logs".to_string()]),
+                ("strict".to_string(), vec!["--strict=false".to_string()]),
+                ("verbose".to_string(), vec!["--verbose".to_string(), "--verbose".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let error = parse("colour = \"auto\"\n").unwrap_err();
+        assert!(error.starts_with("unknown field `colour`"), "{}", error);
+        let error = parse("[htttp]\ntimeout = 10\n").unwrap_err();
+        assert!(error.starts_with("unknown field `htttp-timeout`"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        let error = parse("http_timeout = \"ten\"\n").unwrap_err();
+        assert_eq!(error, "http-timeout: invalid type: string \"ten\", expected u64");
+        assert!(parse("strict = 1\n").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_toml_and_repeated_keys() {
+        assert!(parse("timeout = \n").unwrap_err().contains("line 1"));
+        assert_eq!(parse("http_timeout = 1\n[http]\ntimeout = 2\n").unwrap_err(), "http-timeout is set twice");
+    }
+
+    #[test]
+    fn every_option_is_a_key() {
+        let command = crate::cli::command();
+        for long in command.get_arguments().filter_map(|arg| arg.get_long()).filter(|long| !matches!(*long, "version" | "help" | "config" | "no-config")) {
+            if let Err(error) = parse(&format!("{} = []", long)) {
+                assert!(!error.starts_with("unknown field"), "{}", error);
+            }
+        }
+    }
+
+    fn environment(variables: &[(&str, &str)]) -> Vec<(String, Vec<String>)> {
+        options_from(variables.iter().map(|(name, value)| (name.to_string(), value.to_string())))
+    }
+
+    #[test]
+    fn environment_sets_options_by_their_kind() {
+        let options = environment(&[
+            ("ANSIBLE_FACTS_HTTP_TIMEOUT", "10"),
+            ("ANSIBLE_FACTS_NO_IPV6", "TRUE"),
+            ("ANSIBLE_FACTS_STRICT", "false"),
+            ("ANSIBLE_FACTS_USER_AGENT", "true"),
+            ("ANSIBLE_FACTS_LOG_PATH", "/var/log\n/opt/logs"),
+            ("ANSIBLE_FACTS_FACTS", "ip,users"),
+            ("ANSIBLE_FACTS_FORMAT", "yaml"),
+            ("ANSIBLE_FACTS_VERBOSE", "2"),
+            ("ANSIBLE_FACTS_CACHE_DIR", ""),
+        ]);
+        assert_eq!(
+            options,
+            vec![
+                ("ANSIBLE_FACTS_CACHE_DIR".to_string(), Vec::new()),
+                ("ANSIBLE_FACTS_FACTS".to_string(), vec!["--facts=ip,users".to_string()]),
+                ("ANSIBLE_FACTS_FORMAT".to_string(), vec!["--format=yaml".to_string()]),
+                ("ANSIBLE_FACTS_HTTP_TIMEOUT".to_string(), vec!["--http-timeout=10".to_string()]),
+                ("ANSIBLE_FACTS_LOG_PATH".to_string(), vec!["--log-path=/var/log".to_string(), "--log-path=/opt/logs".to_string()]),
+                ("ANSIBLE_FACTS_NO_IPV6".to_string(), vec!["--no-ipv6=true".to_string()]),
+                ("ANSIBLE_FACTS_STRICT".to_string(), vec!["--strict=false".to_string()]),
+                ("ANSIBLE_FACTS_USER_AGENT".to_string(), vec!["--user-agent=true".to_string()]),
+                ("ANSIBLE_FACTS_VERBOSE".to_string(), vec!["--verbose".to_string(), "--verbose".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn environment_ignores_what_names_no_option() {
+        let options = environment(&[
+            ("ANSIBLE_FACTS_FOO", "1"),
+            ("ANSIBLE_FACTS_MODULES", "setup"),
+            ("ANSIBLE_FACTS_CONFIG", "/etc/other.toml"),
+            ("ANSIBLE_FACTS_NO_CONFIG", "true"),
+            ("ANSIBLE_FACTS_HELP", "true"),
+            ("HTTP_TIMEOUT", "5"),
+        ]);
+        assert_eq!(options, Vec::new());
+    }
+
+    #[test]
+    fn every_option_is_an_environment_variable() {
+        let command = cli::layer_command();
+        for long in command.get_arguments().filter_map(|arg| arg.get_long()).filter(|long| !NOT_LAYERED.contains(long)) {
+            let variable = format!("{}{}", ENV_PREFIX, long.to_ascii_uppercase().replace('-', "_"));
+            assert_eq!(environment(&[(&variable, "1")]).len(), 1, "{}", variable);
+        }
+    }
+
+    #[test]
+    fn config_only_on_the_command_line() {
+        assert_eq!(parse("config = \"/etc/other.toml\"\n").unwrap_err(), "config can only be given on the command line");
+    }
+}
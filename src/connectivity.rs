@@ -0,0 +1,98 @@
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout};
+
+use crate::capability;
+use crate::errors::{Code, Error};
+
+/// Anycast resolvers reachable on 443 from practically anywhere. IP literals keep the
+/// verdict independent of DNS, which is diagnosed separately.
+const IPV4_TARGETS: &[&str] = &["1.1.1.1:443", "8.8.8.8:443", "9.9.9.9:443"];
+const IPV6_TARGETS: &[&str] = &["[2606:4700:4700::1111]:443", "[2001:4860:4860::8888]:443", "[2620:fe::fe]:443"];
+
+/// RFC 8305's recommended delay between starting successive connection attempts.
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Actively tests outbound TCP connectivity per address family. Interface state
+/// (`ip -6 addr`) only says an address exists, not that packets get anywhere.
+pub async fn get_connectivity(ipv4_enabled: bool, ipv6_enabled: bool) -> Value {
+    let (ipv4, ipv6) = tokio::join!(check_family(IPV4_TARGETS, ipv4_enabled), check_family(IPV6_TARGETS, ipv6_enabled));
+    let ipv4_ok = ipv4["verdict"] == "ok";
+    let ipv6_ok = ipv6["verdict"] == "ok";
+
+    json!({
+        "ipv4": ipv4,
+        "ipv6": ipv6,
+        "verdict": match (ipv4_ok, ipv6_ok) {
+            (true, true) => "dual-stack",
+            (true, false) => "ipv4-only",
+            (false, true) => "ipv6-only",
+            (false, false) => "offline",
+        }
+    })
+}
+
+/// Races connections to `targets`, starting one every `ATTEMPT_DELAY` until one
+/// succeeds; the first success wins and the rest are cancelled.
+async fn check_family(targets: &[&str], enabled: bool) -> Value {
+    if !enabled {
+        return json!({
+            "verdict": "disabled",
+            "target": Value::Null,
+            "latency_ms": Value::Null,
+            "errors": Vec::<Error>::new()
+        });
+    }
+
+    if let Err(e) = capability::check_network_group(&targets.join(", ")) {
+        return json!({
+            "verdict": "unreachable",
+            "target": Value::Null,
+            "latency_ms": Value::Null,
+            "errors": [e]
+        });
+    }
+
+    let mut attempts = JoinSet::new();
+    for (index, target) in targets.iter().enumerate() {
+        capability::record_endpoint(target);
+        let target = target.to_string();
+        attempts.spawn(async move {
+            sleep(ATTEMPT_DELAY * index as u32).await;
+            let address: SocketAddr = target.parse().map_err(|e| Error::new(Code::InvalidData, &target, format!("{}: {}", target, e)))?;
+            let started = Instant::now();
+            match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+                Ok(Ok(_)) => Ok((target, started.elapsed())),
+                Ok(Err(e)) => Err(Error::new(Code::of_io(&e), &target, format!("{}: {}", target, e))),
+                Err(_) => Err(Error::new(Code::Timeout, &target, format!("{}: timed out after {}s", target, CONNECT_TIMEOUT.as_secs()))),
+            }
+        });
+    }
+
+    let mut errors = Vec::new();
+    while let Some(result) = attempts.join_next().await {
+        match result {
+            Ok(Ok((target, elapsed))) => {
+                return json!({
+                    "verdict": "ok",
+                    "target": target,
+                    "latency_ms": elapsed.as_millis() as u64,
+                    "errors": errors
+                });
+            }
+            Ok(Err(e)) => errors.push(e),
+            Err(e) => errors.push(Error::new(Code::Unknown, "connectivity", format!("connection attempt failed: {}", e))),
+        }
+    }
+
+    json!({
+        "verdict": "unreachable",
+        "target": Value::Null,
+        "latency_ms": Value::Null,
+        "errors": errors
+    })
+}
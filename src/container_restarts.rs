@@ -0,0 +1,69 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::docker;
+use crate::errors::Error;
+use crate::timestamp::now_secs;
+
+pub const DEFAULT_FLAP_RESTARTS: u64 = 3;
+pub const DEFAULT_FLAP_WINDOW_MINUTES: u64 = 10;
+
+/// `{"type":["container"],"event":["die"]}`, URL-encoded. A restart policy brings a
+/// container back after every exit, so each die in the window is one restart.
+const DIE_EVENTS_FILTER: &str = "%7B%22type%22%3A%5B%22container%22%5D%2C%22event%22%3A%5B%22die%22%5D%7D";
+
+/// How often each container exited in the last `window_minutes`, and which exited
+/// more than `threshold` times: those are in a restart loop.
+pub async fn get_container_restarts(threshold: u64, window_minutes: u64) -> (Value, Option<Error>) {
+    let until = now_secs();
+    let since = until.saturating_sub(window_minutes * 60);
+    let path = format!("/events?since={}&until={}&filters={}", since, until, DIE_EVENTS_FILTER);
+    let events = match docker::get_stream(&path).await {
+        Ok(events) => events,
+        Err(e) => {
+            let failure = e.as_failure();
+            let value = json!({
+                "available": false,
+                "error": e,
+                "window_minutes": window_minutes,
+                "threshold": threshold,
+                "restarts": {},
+                "flapping_containers": []
+            });
+            return (value, failure);
+        }
+    };
+
+    // Keyed by name rather than ID: a container recreated by compose mid-loop keeps
+    // its name.
+    let mut restarts: BTreeMap<&str, (u64, Option<&str>, Option<&str>)> = BTreeMap::new();
+    for event in &events {
+        let attributes = event.pointer("/Actor/Attributes");
+        let Some(name) = attributes.and_then(|attributes| attributes.get("name")).and_then(Value::as_str) else {
+            continue;
+        };
+        let entry = restarts.entry(name).or_default();
+        entry.0 += 1;
+        // Events arrive oldest first.
+        entry.1 = attributes.and_then(|attributes| attributes.get("exitCode")).and_then(Value::as_str);
+        entry.2 = attributes.and_then(|attributes| attributes.get("image")).and_then(Value::as_str);
+    }
+
+    let flapping: Vec<&str> = restarts.iter().filter(|(_, (count, _, _))| *count > threshold).map(|(name, _)| *name).collect();
+    let restarts: Map<String, Value> = restarts
+        .iter()
+        .map(|(name, (count, exit_code, image))| {
+            let exit_code = exit_code.and_then(|code| code.parse::<i64>().ok());
+            (name.to_string(), json!({ "restarts": count, "last_exit_code": exit_code, "image": image }))
+        })
+        .collect();
+    let value = json!({
+        "available": true,
+        "error": Value::Null,
+        "window_minutes": window_minutes,
+        "threshold": threshold,
+        "restarts": restarts,
+        "flapping_containers": flapping
+    });
+    (value, None)
+}
@@ -0,0 +1,151 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::errors::Error;
+use crate::timestamp::{format_rfc3339, now_secs};
+
+pub const CORE_PATTERN_PATH: &str = "/proc/sys/kernel/core_pattern";
+pub const COREDUMP_DIR: &str = "/var/lib/systemd/coredump";
+pub const APPORT_DIR: &str = "/var/crash";
+pub const DEFAULT_CRASH_THRESHOLD: u64 = 3;
+pub const DEFAULT_CRASH_WINDOW_DAYS: u64 = 7;
+/// Crashes listed under `recent`, newest first.
+const RECENT: usize = 20;
+
+struct Crash {
+    binary: String,
+    uid: Option<u32>,
+    pid: Option<u32>,
+    time: u64,
+    size: u64,
+    source: &'static str,
+}
+
+/// Crashes in the last `window_days` from systemd-coredump's store and apport's
+/// reports, per binary, and the binaries that crashed at least `threshold` times:
+/// a service that keeps dying. Also how the kernel hands off core dumps.
+///
+/// Only file names and metadata are read; the dumps themselves aren't opened.
+pub fn get_coredumps(threshold: u64, window_days: u64) -> Value {
+    let core_pattern = fs::read_to_string(CORE_PATTERN_PATH).ok().map(|pattern| pattern.trim().to_string());
+    let handler = core_pattern.as_deref().map(|pattern| match pattern.strip_prefix('|') {
+        Some(command) if command.contains("systemd-coredump") => "systemd-coredump",
+        Some(command) if command.contains("apport") => "apport",
+        Some(_) => "pipe",
+        None => "file",
+    });
+
+    let since = now_secs().saturating_sub(window_days * 86_400);
+    let mut crashes = Vec::new();
+    let mut errors = Map::new();
+    for (dir, parse) in [(COREDUMP_DIR, parse_coredump as fn(&str) -> Option<Crash>), (APPORT_DIR, parse_apport)] {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                errors.insert(dir.to_string(), json!(Error::read(dir, &e)));
+                continue;
+            }
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Some(mut crash) = parse(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            crash.size = metadata.len();
+            if crash.time == 0 {
+                crash.time = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |age| age.as_secs());
+            }
+            if crash.time >= since {
+                crashes.push(crash);
+            }
+        }
+    }
+    crashes.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.binary.cmp(&b.binary)));
+
+    let time = |secs: u64| format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
+    let mut binaries: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for crash in &crashes {
+        // Newest first, so the first crash seen is the last one.
+        let entry = binaries.entry(&crash.binary).or_insert((0, crash.time));
+        entry.0 += 1;
+    }
+    let frequent: Vec<&str> = binaries.iter().filter(|(_, (count, _))| *count >= threshold).map(|(binary, _)| *binary).collect();
+    let binaries: Map<String, Value> =
+        binaries.iter().map(|(binary, (count, last))| (binary.to_string(), json!({ "crashes": count, "last_crash": time(*last) }))).collect();
+    let recent: Vec<Value> = crashes
+        .iter()
+        .take(RECENT)
+        .map(|crash| {
+            json!({
+                "binary": crash.binary,
+                "uid": crash.uid,
+                "pid": crash.pid,
+                "time": time(crash.time),
+                "size_bytes": crash.size,
+                "source": crash.source
+            })
+        })
+        .collect();
+
+    json!({
+        "core_pattern": core_pattern,
+        "handler": handler,
+        "errors": errors,
+        "window_days": window_days,
+        "threshold": threshold,
+        "count": crashes.len(),
+        "binaries": binaries,
+        "frequent_crashers": frequent,
+        "recent": recent
+    })
+}
+
+/// `core.COMM.UID.BOOT_ID.PID.USEC[.zst|.xz|.lz4]`, as systemd-coredump names its
+/// files. COMM may itself hold dots, so the fields are taken from the right.
+fn parse_coredump(name: &str) -> Option<Crash> {
+    let rest = name.strip_prefix("core.")?;
+    let rest = [".zst", ".xz", ".lz4"].iter().find_map(|extension| rest.strip_suffix(extension)).unwrap_or(rest);
+    let mut fields = rest.rsplitn(5, '.');
+    let usec: u64 = fields.next()?.parse().ok()?;
+    let pid = fields.next()?.parse().ok()?;
+    let _boot_id = fields.next()?;
+    let uid = fields.next()?.parse().ok()?;
+    let comm = fields.next()?;
+    Some(Crash { binary: unescape(comm), uid: Some(uid), pid: Some(pid), time: usec / 1_000_000, size: 0, source: "systemd-coredump" })
+}
+
+/// `_usr_bin_foo.UID.crash`: apport replaces the slashes of the executable's path
+/// with underscores. The report's time is its modification time.
+fn parse_apport(name: &str) -> Option<Crash> {
+    let (path, uid) = name.strip_suffix(".crash")?.rsplit_once('.')?;
+    Some(Crash { binary: path.replace('_', "/"), uid: uid.parse().ok(), pid: None, time: 0, size: 0, source: "apport" })
+}
+
+/// Undoes systemd's `\xNN` escaping of unusual characters in COMM.
+fn unescape(comm: &str) -> String {
+    let mut bytes = Vec::with_capacity(comm.len());
+    let raw = comm.as_bytes();
+    let mut index = 0;
+    while index < raw.len() {
+        let escaped = raw.get(index..index + 4).filter(|chunk| chunk.starts_with(b"\\x")).and_then(|chunk| {
+            let hex = std::str::from_utf8(&chunk[2..]).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                index += 4;
+            }
+            None => {
+                bytes.push(raw[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
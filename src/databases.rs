@@ -0,0 +1,434 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::docker;
+use crate::processes::{self, PROC_PATH};
+
+/// Debian's package database, for versions the server's own files don't give.
+const DPKG_STATUS_PATH: &str = "/var/lib/dpkg/status";
+
+const POSTGRES_PROCESS: &str = "postgres";
+/// Debian's per-cluster configuration, `<version>/<cluster>/postgresql.conf`.
+const POSTGRES_DEBIAN_CONFIG_DIR: &str = "/etc/postgresql";
+/// RHEL's data directories: `data` for the distribution's server, `<version>/data`
+/// for the PGDG packages.
+const POSTGRES_RHEL_DIR: &str = "/var/lib/pgsql";
+/// Debian's versioned install root and the distribution binary; PGDG packages
+/// install under `/usr/pgsql-<version>`.
+const POSTGRES_BINARIES: &[&str] = &["/usr/lib/postgresql", "/usr/bin/postgres"];
+const POSTGRES_SOCKET_DIR: &str = "/var/run/postgresql";
+const POSTGRES_PORT: u16 = 5432;
+
+const MARIADB_PROCESSES: &[&str] = &["mariadbd", "mysqld"];
+const MARIADB_BINARIES: &[&str] = &["/usr/sbin/mariadbd", "/usr/sbin/mysqld", "/usr/libexec/mysqld"];
+const MARIADB_CONFIG_FILES: &[&str] = &["/etc/my.cnf", "/etc/mysql/my.cnf"];
+const MARIADB_DATA_DIR: &str = "/var/lib/mysql";
+/// Default socket paths: Debian's, then RHEL's.
+const MARIADB_SOCKETS: &[&str] = &["/run/mysqld/mysqld.sock", "/var/lib/mysql/mysql.sock"];
+const MARIADB_PORT: u16 = 3306;
+
+const REDIS_PROCESS: &str = "redis-server";
+const REDIS_BINARIES: &[&str] = &["/usr/bin/redis-server", "/usr/sbin/redis-server"];
+/// `redis.conf` and, on Debian, `redis-<instance>.conf` for extra instances.
+const REDIS_CONFIG_DIR: &str = "/etc/redis";
+const REDIS_CONFIG_FILE: &str = "/etc/redis.conf";
+const REDIS_PORT: u16 = 6379;
+
+/// Nested includes followed before giving up on a loop.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// PostgreSQL, MariaDB/MySQL and Redis servers on the host, so a role can choose
+/// between a bundled database container and the host's own: whether each is
+/// installed, and per instance its version, data directory, port, socket and
+/// whether it is running.
+///
+/// Instances come from the configuration and the standard data directories, and
+/// from running server processes; servers running inside containers are left out.
+/// Data directories and most server configuration are readable only by the
+/// database's user, so without root versions and ports may fall back to package
+/// versions and defaults. `socket` is set when the socket file exists.
+pub fn get_databases() -> Value {
+    let servers = server_processes();
+    let packages = dpkg_versions();
+    json!({
+        "postgresql": postgresql(&servers),
+        "mariadb": mariadb(&servers, &packages),
+        "redis": redis(&servers, &packages)
+    })
+}
+
+struct Process {
+    pid: u32,
+    comm: String,
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+}
+
+impl Process {
+    /// The value of `--name=value` or `--name value`, or of `-D value` style flags.
+    fn option(&self, names: &[&str]) -> Option<String> {
+        self.args.iter().enumerate().find_map(|(index, arg)| {
+            names.iter().find_map(|name| match arg.strip_prefix(name) {
+                Some("") => self.args.get(index + 1).cloned(),
+                Some(value) => value.strip_prefix('=').map(String::from),
+                None => None,
+            })
+        })
+    }
+}
+
+/// Database server processes outside containers. Only the postmaster counts for
+/// PostgreSQL, not the backends it forks.
+fn server_processes() -> Vec<Process> {
+    let mut candidates = Vec::new();
+    let mut postgres_pids = Vec::new();
+    for entry in fs::read_dir(PROC_PATH).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(comm) = fs::read_to_string(entry.path().join("comm")) else {
+            continue;
+        };
+        let comm = comm.trim().to_string();
+        if comm == POSTGRES_PROCESS {
+            postgres_pids.push(pid);
+        }
+        if comm != POSTGRES_PROCESS && comm != REDIS_PROCESS && !MARIADB_PROCESSES.contains(&comm.as_str()) {
+            continue;
+        }
+        if processes::cgroup(pid).is_some_and(|cgroup| docker::container_id(&cgroup).is_some()) {
+            continue;
+        }
+        let stat = fs::read_to_string(entry.path().join("stat")).unwrap_or_default();
+        // `pid (comm) state ppid ...`; comm may itself hold spaces and parentheses.
+        let ppid = stat.rsplit_once(')').and_then(|(_, rest)| rest.split_whitespace().nth(1)?.parse::<u32>().ok());
+        let args = fs::read(entry.path().join("cmdline"))
+            .unwrap_or_default()
+            .split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        candidates.push((ppid, Process { pid, comm, args, cwd: fs::read_link(entry.path().join("cwd")).ok() }));
+    }
+    candidates
+        .into_iter()
+        .filter(|(ppid, process)| process.comm != POSTGRES_PROCESS || !ppid.is_some_and(|ppid| postgres_pids.contains(&ppid)))
+        .map(|(_, process)| process)
+        .collect()
+}
+
+#[derive(Default)]
+struct PostgresCluster {
+    name: Option<String>,
+    version: Option<String>,
+    config_file: Option<String>,
+    port: Option<u16>,
+    listen_addresses: Option<String>,
+    socket_dir: Option<String>,
+    pid: Option<u32>,
+}
+
+fn postgresql(servers: &[Process]) -> Value {
+    let mut clusters: BTreeMap<String, PostgresCluster> = BTreeMap::new();
+
+    // Debian: /etc/postgresql/<version>/<cluster>/postgresql.conf, readable by all.
+    for version in subdirectories(Path::new(POSTGRES_DEBIAN_CONFIG_DIR)) {
+        for cluster in subdirectories(&version) {
+            let config_file = cluster.join("postgresql.conf");
+            let Ok(contents) = fs::read_to_string(&config_file) else {
+                continue;
+            };
+            let settings = postgres_settings(&contents);
+            let version = file_name(&version);
+            let name = file_name(&cluster);
+            let data_directory = settings.get("data_directory").cloned().unwrap_or_else(|| format!("/var/lib/postgresql/{}/{}", version, name));
+            clusters.insert(
+                data_directory,
+                PostgresCluster {
+                    port: settings.get("port").and_then(|port| port.parse().ok()),
+                    listen_addresses: settings.get("listen_addresses").cloned(),
+                    socket_dir: settings.get("unix_socket_directories").and_then(|dirs| dirs.split(',').next()).map(|dir| dir.trim().to_string()),
+                    name: Some(name),
+                    version: Some(version),
+                    config_file: Some(config_file.display().to_string()),
+                    pid: None,
+                },
+            );
+        }
+    }
+
+    // RHEL keeps postgresql.conf in the data directory.
+    let rhel = Path::new(POSTGRES_RHEL_DIR);
+    for data_directory in std::iter::once(rhel.join("data")).chain(subdirectories(rhel).into_iter().map(|dir| dir.join("data"))) {
+        if data_directory.is_dir() {
+            let config_file = data_directory.join("postgresql.conf");
+            let settings = fs::read_to_string(&config_file).map(|contents| postgres_settings(&contents)).unwrap_or_default();
+            clusters.entry(data_directory.display().to_string()).or_insert_with(|| PostgresCluster {
+                port: settings.get("port").and_then(|port| port.parse().ok()),
+                listen_addresses: settings.get("listen_addresses").cloned(),
+                socket_dir: settings.get("unix_socket_directories").and_then(|dirs| dirs.split(',').next()).map(|dir| dir.trim().to_string()),
+                config_file: Some(config_file.display().to_string()).filter(|_| config_file.exists()),
+                ..PostgresCluster::default()
+            });
+        }
+    }
+
+    // A postmaster runs in its data directory; -D names it when cwd is unreadable.
+    for server in servers.iter().filter(|server| server.comm == POSTGRES_PROCESS) {
+        let Some(data_directory) = server.cwd.as_ref().map(|cwd| cwd.display().to_string()).or_else(|| server.option(&["-D", "--pgdata"])) else {
+            continue;
+        };
+        let cluster = clusters.entry(data_directory).or_default();
+        cluster.pid = Some(server.pid);
+        if let Some(port) = server.option(&["-p", "--port"]).and_then(|port| port.parse().ok()) {
+            cluster.port = Some(port);
+        }
+    }
+
+    let instances: Vec<Value> = clusters
+        .into_iter()
+        .map(|(data_directory, mut cluster)| {
+            let data = Path::new(&data_directory);
+            if let Some(version) = fs::read_to_string(data.join("PG_VERSION")).ok().map(|version| version.trim().to_string()) {
+                cluster.version = Some(version);
+            }
+            // postmaster.pid: pid, data directory, start time, port, socket
+            // directory, listen address, ...; present while the server runs.
+            if let Ok(pidfile) = fs::read_to_string(data.join("postmaster.pid")) {
+                let lines: Vec<&str> = pidfile.lines().map(str::trim).collect();
+                let pid = lines.first().and_then(|pid| pid.parse::<u32>().ok());
+                if pid.is_some_and(|pid| Path::new(PROC_PATH).join(pid.to_string()).exists()) {
+                    cluster.pid = cluster.pid.or(pid);
+                }
+                cluster.port = lines.get(3).and_then(|port| port.parse().ok()).or(cluster.port);
+                cluster.socket_dir = lines.get(4).filter(|dir| !dir.is_empty()).map(|dir| dir.to_string()).or(cluster.socket_dir.take());
+                cluster.listen_addresses = lines.get(5).filter(|address| !address.is_empty()).map(|address| address.to_string()).or(cluster.listen_addresses.take());
+            }
+            let port = cluster.port.unwrap_or(POSTGRES_PORT);
+            let socket_dir = cluster.socket_dir.unwrap_or_else(|| POSTGRES_SOCKET_DIR.to_string());
+            let socket = Path::new(&socket_dir).join(format!(".s.PGSQL.{}", port));
+            let socket = socket.exists().then(|| socket.display().to_string());
+            json!({
+                "cluster": cluster.name,
+                "version": cluster.version,
+                "data_directory": data_directory,
+                "config_file": cluster.config_file,
+                "port": port,
+                "listen_addresses": cluster.listen_addresses.unwrap_or_else(|| "localhost".to_string()),
+                "socket": socket,
+                "running": cluster.pid.is_some() || socket.is_some(),
+                "pid": cluster.pid
+            })
+        })
+        .collect();
+
+    let installed = POSTGRES_BINARIES.iter().any(|path| Path::new(path).exists()) || subdirectories(Path::new("/usr")).iter().any(|dir| file_name(dir).starts_with("pgsql-"));
+    json!({ "installed": installed || !instances.is_empty(), "instances": instances })
+}
+
+/// `name = value` settings of a postgresql.conf, unquoted, without comments.
+fn postgres_settings(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.split('#').next()?.trim();
+            let (key, value) = line.split_once('=').or_else(|| line.split_once(char::is_whitespace))?;
+            Some((key.trim().to_ascii_lowercase(), value.trim().trim_matches('\'').to_string()))
+        })
+        .collect()
+}
+
+fn mariadb(servers: &[Process], packages: &BTreeMap<String, String>) -> Value {
+    let mut config_files = Vec::new();
+    let mut settings = BTreeMap::new();
+    for path in MARIADB_CONFIG_FILES {
+        read_my_cnf(Path::new(path), 0, &mut config_files, &mut settings);
+    }
+    let server = servers.iter().find(|server| MARIADB_PROCESSES.contains(&server.comm.as_str()));
+    let setting = |name: &str| server.and_then(|server| server.option(&[&format!("--{}", name)])).or_else(|| settings.get(name).cloned());
+
+    let data_directory = setting("datadir").unwrap_or_else(|| MARIADB_DATA_DIR.to_string());
+    let socket = setting("socket").or_else(|| MARIADB_SOCKETS.iter().find(|path| Path::new(path).exists()).map(|path| path.to_string()));
+    let socket = socket.filter(|socket| Path::new(socket).exists());
+    // Written by mariadb-upgrade (mysql_upgrade) after each upgrade: `10.11.6-MariaDB`.
+    let upgrade_info = ["mariadb_upgrade_info", "mysql_upgrade_info"]
+        .iter()
+        .find_map(|file| fs::read_to_string(Path::new(&data_directory).join(file)).ok())
+        .map(|info| info.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string());
+    let package = ["mariadb-server", "mariadb-server-core", "mysql-server", "mysql-server-core"].iter().find_map(|name| packages.get(*name).map(|version| (*name, version)));
+    let flavor = match (server.map(|server| server.comm.as_str()), &upgrade_info, package) {
+        (Some("mariadbd"), _, _) => Some("mariadb"),
+        (_, Some(info), _) if info.contains("MariaDB") => Some("mariadb"),
+        (_, _, Some((name, _))) if name.starts_with("mariadb") => Some("mariadb"),
+        (_, _, Some(_)) | (Some(_), _, _) => Some("mysql"),
+        _ if Path::new("/usr/sbin/mariadbd").exists() => Some("mariadb"),
+        _ => None,
+    };
+    let version = upgrade_info.map(|info| info.split('-').next().unwrap_or_default().to_string()).or_else(|| package.map(|(_, version)| version.clone()));
+    let installed = MARIADB_BINARIES.iter().any(|path| Path::new(path).exists()) || server.is_some();
+
+    let instances: Vec<Value> = if installed || Path::new(&data_directory).is_dir() {
+        vec![json!({
+            "flavor": flavor,
+            "version": version,
+            "data_directory": data_directory,
+            "port": setting("port").and_then(|port| port.parse::<u16>().ok()).unwrap_or(MARIADB_PORT),
+            "bind_address": setting("bind-address"),
+            "socket": socket,
+            "running": server.is_some(),
+            "pid": server.map(|server| server.pid)
+        })]
+    } else {
+        Vec::new()
+    };
+    json!({ "installed": installed, "config_files": config_files, "instances": instances })
+}
+
+/// Reads the server's settings from an option file: the `[mysqld]`, `[mariadb]`,
+/// `[mariadbd]` and `[server]` groups, following `!include` and `!includedir`.
+/// Names are normalized to dashes, later values winning.
+fn read_my_cnf(path: &Path, depth: usize, files: &mut Vec<String>, settings: &mut BTreeMap<String, String>) {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return;
+    };
+    files.push(path.display().to_string());
+    let mut server_group = false;
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with(['#', ';'])) {
+        if let Some(include) = line.strip_prefix("!includedir") {
+            if depth < MAX_INCLUDE_DEPTH {
+                let mut included: Vec<PathBuf> = fs::read_dir(include.trim())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "cnf"))
+                    .collect();
+                included.sort();
+                for include in included {
+                    read_my_cnf(&include, depth + 1, files, settings);
+                }
+            }
+        } else if let Some(include) = line.strip_prefix("!include") {
+            if depth < MAX_INCLUDE_DEPTH {
+                read_my_cnf(Path::new(include.trim()), depth + 1, files, settings);
+            }
+        } else if let Some(group) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            server_group = matches!(group.trim(), "mysqld" | "mariadb" | "mariadbd" | "server");
+        } else if server_group {
+            let (key, value) = line.split_once('=').unwrap_or((line, ""));
+            let value = value.split('#').next().unwrap_or_default().trim().trim_matches(['"', '\'']);
+            settings.insert(key.trim().replace('_', "-").to_ascii_lowercase(), value.to_string());
+        }
+    }
+}
+
+fn redis(servers: &[Process], packages: &BTreeMap<String, String>) -> Value {
+    let mut config_files: Vec<PathBuf> = fs::read_dir(REDIS_CONFIG_DIR)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("redis") && name.ends_with(".conf")))
+        .collect();
+    config_files.sort();
+    if Path::new(REDIS_CONFIG_FILE).exists() {
+        config_files.insert(0, PathBuf::from(REDIS_CONFIG_FILE));
+    }
+
+    // The process title is `redis-server <bind>:<port>`, or `*:<port>`.
+    let mut running: Vec<(u16, u32)> = servers
+        .iter()
+        .filter(|server| server.comm == REDIS_PROCESS)
+        .filter_map(|server| Some((server.args.last()?.rsplit_once(':')?.1.split_whitespace().next()?.parse().ok()?, server.pid)))
+        .collect();
+    let version = packages.get("redis-server").or_else(|| packages.get("redis")).cloned();
+
+    let mut instances = Vec::new();
+    for config_file in config_files {
+        let mut settings = BTreeMap::new();
+        let readable = read_redis_conf(&config_file, 0, &mut settings);
+        let port = settings.get("port").and_then(|port| port.parse::<u16>().ok()).unwrap_or(REDIS_PORT);
+        let pid = running.iter().position(|(running_port, _)| *running_port == port).map(|index| running.remove(index).1);
+        let socket = settings.get("unixsocket").filter(|socket| Path::new(socket).exists()).cloned();
+        instances.push(json!({
+            "config_file": config_file.display().to_string(),
+            "config_readable": readable,
+            "version": version,
+            "data_directory": settings.get("dir"),
+            // Port 0 turns TCP off.
+            "port": (port != 0).then_some(port),
+            "bind": settings.get("bind"),
+            "socket": socket,
+            "running": pid.is_some(),
+            "pid": pid
+        }));
+    }
+    for (port, pid) in running {
+        instances.push(json!({
+            "config_file": null,
+            "config_readable": false,
+            "version": version,
+            "data_directory": null,
+            "port": (port != 0).then_some(port),
+            "bind": null,
+            "socket": null,
+            "running": true,
+            "pid": pid
+        }));
+    }
+
+    let installed = REDIS_BINARIES.iter().any(|path| Path::new(path).exists()) || !instances.is_empty();
+    json!({ "installed": installed, "instances": instances })
+}
+
+/// `directive arguments` lines of a redis.conf, following `include`; false when
+/// the file can't be read.
+fn read_redis_conf(path: &Path, depth: usize, settings: &mut BTreeMap<String, String>) -> bool {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return false;
+    };
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let (directive, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let directive = directive.to_ascii_lowercase();
+        let value = value.trim().trim_matches('"').to_string();
+        if directive == "include" {
+            if depth < MAX_INCLUDE_DEPTH {
+                read_redis_conf(Path::new(&value), depth + 1, settings);
+            }
+        } else {
+            settings.insert(directive, value);
+        }
+    }
+    true
+}
+
+/// Upstream versions of installed packages from dpkg's status file, without the
+/// epoch and Debian revision (`5:7.0.15-1~deb12u1` is `7.0.15`).
+fn dpkg_versions() -> BTreeMap<String, String> {
+    let status = fs::read_to_string(DPKG_STATUS_PATH).unwrap_or_default();
+    status
+        .split("\n\n")
+        .filter_map(|stanza| {
+            let field = |name: &str| stanza.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':')).map(str::trim);
+            if !field("Status")?.ends_with(" installed") {
+                return None;
+            }
+            let version = field("Version")?;
+            let version = version.split_once(':').map_or(version, |(_, version)| version);
+            let version = version.rsplit_once('-').map_or(version, |(upstream, _)| upstream);
+            Some((field("Package")?.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir).into_iter().flatten().filter_map(|entry| entry.ok()).map(|entry| entry.path()).filter(|path| path.is_dir()).collect();
+    dirs.sort();
+    dirs
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,147 @@
+use serde_json::{json, Map, Value};
+use std::env;
+use std::time::Duration;
+
+pub const SYSTEM_BUS_SOCKET: &str = "/run/dbus/system_bus_socket";
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// The services probed, by the key they're reported under.
+const SERVICES: &[(&str, &str)] = &[
+    ("network_manager", "org.freedesktop.NetworkManager"),
+    ("resolved", "org.freedesktop.resolve1"),
+    ("timedated", "org.freedesktop.timedate1"),
+    ("logind", "org.freedesktop.login1"),
+    ("systemd", "org.freedesktop.systemd1"),
+];
+
+/// Which key services are on the system bus (`running`) or can be started by it on
+/// first use (`activatable`, as timedated usually is), so roles and collectors know
+/// which control planes exist before choosing how to query the host.
+pub async fn get_dbus() -> Value {
+    let address = bus_address();
+    let (result, error) = match bus::list_names(&address).await {
+        Ok(names) => (Some(names), None),
+        Err(e) => (None, Some(e)),
+    };
+    let services: Map<String, Value> = SERVICES
+        .iter()
+        .map(|(key, name)| {
+            let (running, activatable) = result.as_ref().map_or((false, false), |(names, activatable)| {
+                (names.iter().any(|running| running == name), activatable.iter().any(|activatable| activatable == name))
+            });
+            (key.to_string(), json!({ "name": name, "running": running, "activatable": activatable, "available": running || activatable }))
+        })
+        .collect();
+    json!({ "address": address, "connected": result.is_some(), "error": error, "services": services })
+}
+
+/// The socket from `DBUS_SYSTEM_BUS_ADDRESS` (`unix:path=...`) when set, else the
+/// standard one.
+fn bus_address() -> String {
+    env::var("DBUS_SYSTEM_BUS_ADDRESS").ok().and_then(|address| socket_path(&address)).unwrap_or_else(|| SYSTEM_BUS_SOCKET.to_string())
+}
+
+/// The path of the first `unix:path=` address in a `;`-separated list.
+fn socket_path(addresses: &str) -> Option<String> {
+    addresses.split(';').find_map(|address| address.strip_prefix("unix:")?.split(',').find_map(|part| part.strip_prefix("path=")).map(String::from))
+}
+
+/// The bus daemon's own methods, over zbus.
+#[cfg(unix)]
+mod bus {
+    use zbus::connection::Builder;
+    use zbus::fdo::DBusProxy;
+
+    use super::TIMEOUT;
+    use crate::errors::{Code, Error};
+
+    /// The names on the bus and the names it can activate.
+    pub async fn list_names(path: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        let names = async {
+            let address = format!("unix:path={}", path);
+            let connection = Builder::address(address.as_str())?.build().await?;
+            let proxy = DBusProxy::new(&connection).await?;
+            let names = proxy.list_names().await?;
+            let activatable = proxy.list_activatable_names().await?;
+            let strings = |names: Vec<zbus::names::OwnedBusName>| names.into_iter().map(|name| name.to_string()).collect();
+            Ok::<_, zbus::Error>((strings(names), strings(activatable)))
+        };
+        match tokio::time::timeout(TIMEOUT, names).await {
+            Ok(names) => names.map_err(|e| error(path, e)),
+            Err(_) => Err(Error::new(Code::Timeout, path, format!("{}: timed out after {}s", path, TIMEOUT.as_secs()))),
+        }
+    }
+
+    fn error(path: &str, error: zbus::Error) -> Error {
+        let code = match &error {
+            zbus::Error::InputOutput(e) | zbus::Error::Connection(e, _) => Code::of_io(e),
+            zbus::Error::Handshake(_) => Code::Unauthorized,
+            zbus::Error::Address(_) | zbus::Error::Unsupported => Code::Unsupported,
+            _ => Code::Unknown,
+        };
+        Error::new(code, path, format!("{}: {}", path, error))
+    }
+}
+
+#[cfg(not(unix))]
+mod bus {
+    use crate::errors::{Code, Error};
+
+    pub async fn list_names(path: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+        Err(Error::new(Code::Unsupported, path, "D-Bus is only supported on Unix"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_path_from_addresses() {
+        assert_eq!(socket_path("unix:path=/run/dbus/system_bus_socket").as_deref(), Some("/run/dbus/system_bus_socket"));
+        assert_eq!(socket_path("tcp:host=localhost,port=1;unix:guid=abc,path=/tmp/bus").as_deref(), Some("/tmp/bus"));
+        assert_eq!(socket_path("unix:abstract=/tmp/dbus-x"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn missing_socket_is_an_error() {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-no-bus", std::process::id()));
+        let error = bus::list_names(path.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(error.code, crate::errors::Code::NotFound);
+        assert_eq!(error.source, path.to_str().unwrap());
+    }
+
+    /// Against a private bus daemon, where there's one to start.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn lists_the_names_on_a_bus() {
+        let dir = std::env::temp_dir().join(format!("saltbox-facts-{}-bus", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("socket");
+        let config = dir.join("bus.conf");
+        std::fs::write(
+            &config,
+            format!(
+                "<busconfig><listen>unix:path={}</listen><auth>EXTERNAL</auth><policy context=\"default\"><allow send_destination=\"*\"/><allow receive_sender=\"*\"/><allow own=\"*\"/></policy></busconfig>",
+                socket.display()
+            ),
+        )
+        .unwrap();
+        let Ok(mut daemon) = std::process::Command::new("dbus-daemon").arg("--nofork").arg("--config-file").arg(&config).stderr(std::process::Stdio::null()).spawn() else {
+            return;
+        };
+        for _ in 0..50 {
+            if socket.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let names = bus::list_names(socket.to_str().unwrap()).await;
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (names, activatable) = names.unwrap();
+        assert!(names.iter().any(|name| name == "org.freedesktop.DBus"), "{:?}", names);
+        assert!(activatable.iter().any(|name| name == "org.freedesktop.DBus"), "{:?}", activatable);
+    }
+}
@@ -0,0 +1,77 @@
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::expr::Expr;
+use crate::static_facts::load_file;
+
+#[derive(Clone)]
+pub struct Definition {
+    pub name: String,
+    pub expr: Expr,
+}
+
+/// Parses `NAME = EXPR` as given to `--derive`.
+pub fn parse_definition(source: &str) -> Result<Definition, String> {
+    let name_end = source
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(source.len());
+    let name = &source[..name_end];
+    let rest = source[name_end..].trim_start();
+    match rest.strip_prefix('=') {
+        Some(expr) if !name.is_empty() && !expr.starts_with('=') => Ok(Definition {
+            name: name.to_string(),
+            expr: Expr::parse(expr).map_err(|e| format!("Derived fact {}: {}", name, e))?,
+        }),
+        _ => Err(format!("Invalid derived fact {:?}: expected NAME = EXPRESSION", source)),
+    }
+}
+
+/// Loads a JSON/YAML mapping of derived fact names to expression strings.
+pub fn load_definitions(path: &Path) -> Result<Vec<Definition>, String> {
+    let value = load_file(path)?;
+    let map = value
+        .as_object()
+        .ok_or_else(|| format!("{} must contain a mapping of names to expressions", path.display()))?;
+    map.iter()
+        .map(|(name, expr)| {
+            let source = expr
+                .as_str()
+                .ok_or_else(|| format!("{}: derived fact {} must be an expression string", path.display(), name))?;
+            Ok(Definition {
+                name: name.clone(),
+                expr: Expr::parse(source).map_err(|e| format!("{}: derived fact {}: {}", path.display(), name, e))?,
+            })
+        })
+        .collect()
+}
+
+/// Evaluates the definitions into the `derived` section, and their failures into
+/// `derived_errors`. Definitions may refer to each other (`derived.low_disk`) in any
+/// order: evaluation is repeated until no value changes.
+pub fn apply(document: &mut Value, definitions: &[Definition]) {
+    document["derived"] = json!({});
+    let mut errors = Map::new();
+
+    for _ in 0..=definitions.len() {
+        let mut values = Map::new();
+        errors.clear();
+        for definition in definitions {
+            match definition.expr.evaluate(document) {
+                Ok(value) => {
+                    values.insert(definition.name.clone(), value);
+                }
+                Err(e) => {
+                    values.insert(definition.name.clone(), Value::Null);
+                    errors.insert(definition.name.clone(), json!(e));
+                }
+            }
+        }
+        let values = Value::Object(values);
+        if document["derived"] == values {
+            break;
+        }
+        document["derived"] = values;
+    }
+
+    document["derived_errors"] = Value::Object(errors);
+}
@@ -0,0 +1,69 @@
+use serde_json::Value;
+
+use crate::diff::{escape_pointer, pointer_matches};
+
+/// Values that differ between runs on an unchanged host: collection times,
+/// latencies and sampled usage, and which of several equivalent endpoints happened
+/// to answer first.
+const VOLATILE_PATHS: &[&str] = &[
+    "/freshness",
+    "/connectivity/*/latency_ms",
+    "/connectivity/*/target",
+    "/connectivity/*/errors",
+    "/dhcp_leases/leases/*/expires_in_secs",
+    "/dir_sizes/directories/*/size_bytes",
+    "/dir_sizes/directories/*/files",
+    "/dir_sizes/directories/*/elapsed_ms",
+    "/dir_sizes/directories/*/largest",
+    "/docker_stats/containers/*/cpu_percent",
+    "/docker_stats/containers/*/memory_usage_mb",
+    "/docker_stats/containers/*/memory_percent",
+    "/docker_stats/containers/*/block_read_mb",
+    "/docker_stats/containers/*/block_write_mb",
+    "/docker_stats/containers/*/pids",
+    "/ip/source_ipv4",
+    "/ip/source_ipv6",
+    "/log_growth/previous_run",
+    "/log_growth/interval_secs",
+    "/log_growth/paths/*/size_bytes",
+    "/log_growth/paths/*/growth_bytes",
+    "/log_growth/paths/*/bytes_per_hour",
+    "/log_growth/paths/*/fastest_growing",
+    "/mounts/*/used_gb",
+    "/mounts/*/available_gb",
+    "/mounts/*/used_percent",
+    "/processes/processes",
+    "/processes/sample_ms",
+    "/processes/scanned",
+    "/region/latency_ms",
+    "/swap/used_bytes",
+    "/swap/devices/*/used_bytes",
+    "/swap/zram/*/orig_data_bytes",
+    "/swap/zram/*/compr_data_bytes",
+    "/swap/zram/*/mem_used_bytes",
+    "/region/errors",
+    "/timings/*/duration_ms",
+    "/wireguard/interfaces/*/peers/*/handshake_age_secs",
+    "/wireguard/interfaces/*/peers/*/rx_bytes",
+    "/wireguard/interfaces/*/peers/*/tx_bytes",
+];
+
+/// Removes volatile values so repeated runs on an unchanged host print
+/// byte-identical output. Keys are already sorted, so only values need handling.
+pub fn normalize(document: &mut Value) {
+    strip(document, "");
+}
+
+/// Whether `--deterministic` removes the value at this JSON Pointer.
+pub fn is_volatile(path: &str) -> bool {
+    VOLATILE_PATHS.iter().any(|pattern| pointer_matches(pattern, path))
+}
+
+fn strip(value: &mut Value, path: &str) {
+    if let Value::Object(map) = value {
+        map.retain(|key, _| !is_volatile(&format!("{}/{}", path, escape_pointer(key))));
+        for (key, child) in map.iter_mut() {
+            strip(child, &format!("{}/{}", path, escape_pointer(key)));
+        }
+    }
+}
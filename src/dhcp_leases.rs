@@ -0,0 +1,313 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::errors::Error;
+use crate::timestamp::{format_rfc3339, now_secs, secs_from_civil};
+
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+/// systemd-networkd's leases, one file per interface index.
+pub const NETWORKD_LEASES_PATH: &str = "/run/systemd/netif/leases";
+/// NetworkManager's state directory: `internal-<uuid>-<iface>.lease` files from its
+/// built-in client, `dhclient-<uuid>-<iface>.lease` when it runs dhclient.
+const NETWORK_MANAGER_PATH: &str = "/var/lib/NetworkManager";
+/// Where dhclient keeps its lease database on Debian/Ubuntu and RHEL.
+const DHCLIENT_DIRS: &[&str] = &["/var/lib/dhcp", "/var/lib/dhclient"];
+
+/// The DHCP lease each interface holds: address, server, routers, DNS servers and
+/// when the lease was obtained, renews and expires, so "my IP changed overnight"
+/// shows up as a lease close to expiry or recently renewed with a new address.
+///
+/// Leases are read from systemd-networkd, NetworkManager's internal client and
+/// dhclient. networkd-format leases don't record when they were obtained, so the
+/// file's modification time (written at every renewal) stands in for it.
+pub fn get_dhcp_leases() -> Value {
+    let now = now_secs();
+    let mut leases = Vec::new();
+    let mut errors = Vec::new();
+
+    let names = interface_names();
+    for (path, index) in files(Path::new(NETWORKD_LEASES_PATH), |name| name.parse::<u32>().ok()) {
+        let interface = names.get(&index).cloned();
+        match networkd_lease(&path, interface, "networkd", now) {
+            Ok(lease) => leases.push(lease),
+            Err(e) => errors.push(e),
+        }
+    }
+    for (path, interface) in files(Path::new(NETWORK_MANAGER_PATH), |name| {
+        let stem = name.strip_prefix("internal-")?.strip_suffix(".lease")?;
+        // The connection's UUID is 36 characters, then `-<iface>`.
+        stem.get(37..).filter(|interface| !interface.is_empty()).map(String::from)
+    }) {
+        match networkd_lease(&path, Some(interface), "network_manager", now) {
+            Ok(lease) => leases.push(lease),
+            Err(e) => errors.push(e),
+        }
+    }
+    let dhclient_files = DHCLIENT_DIRS
+        .iter()
+        .flat_map(|dir| files(Path::new(dir), |name| (name.ends_with(".leases") || name.ends_with(".lease")).then_some(())))
+        .chain(files(Path::new(NETWORK_MANAGER_PATH), |name| (name.starts_with("dhclient-") && name.ends_with(".lease")).then_some(())));
+    for (path, _) in dhclient_files {
+        match fs::read_to_string(&path) {
+            Ok(contents) => leases.extend(dhclient_leases(&contents, &path, now)),
+            Err(e) => errors.push(Error::read(path.display(), &e)),
+        }
+    }
+
+    leases.sort_by(|a, b| a["interface"].as_str().cmp(&b["interface"].as_str()).then_with(|| a["file"].as_str().cmp(&b["file"].as_str())));
+    json!({ "errors": errors, "leases": leases })
+}
+
+/// Files in `dir` whose names `select` accepts, with what it extracted, sorted.
+fn files<T>(dir: &Path, select: impl Fn(&str) -> Option<T>) -> Vec<(std::path::PathBuf, T)> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.path(), select(entry.file_name().to_str()?)?)))
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+fn interface_names() -> HashMap<u32, String> {
+    fs::read_dir(SYS_CLASS_NET_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let index = fs::read_to_string(entry.path().join("ifindex")).ok()?.trim().parse().ok()?;
+            Some((index, entry.file_name().to_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// A lease in the `KEY=value` format systemd's DHCP client saves, which networkd
+/// and NetworkManager's internal client share.
+fn networkd_lease(path: &Path, interface: Option<String>, client: &str, now: u64) -> Result<Value, Error> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::read(path.display(), &e))?;
+    let value = |key: &str| contents.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('=')).map(str::trim).filter(|value| !value.is_empty());
+    let list = |key: &str| value(key).map_or_else(Vec::new, |value| value.split_whitespace().map(String::from).collect::<Vec<_>>());
+    let obtained = fs::metadata(path).and_then(|metadata| metadata.modified()).ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|age| age.as_secs());
+    let lifetime = value("LIFETIME").and_then(|lifetime| lifetime.parse::<u64>().ok());
+    let renew = value("T1").and_then(|t1| t1.parse::<u64>().ok());
+    Ok(lease(
+        interface,
+        client,
+        path,
+        Fields {
+            address: value("ADDRESS").map(String::from),
+            prefix_length: value("NETMASK").and_then(prefix_length),
+            server: value("SERVER_ADDRESS").map(String::from),
+            routers: list("ROUTER"),
+            dns: list("DNS"),
+            domain: value("DOMAINNAME").map(String::from),
+            lease_time_secs: lifetime,
+            obtained_at: obtained,
+            renew_at: obtained.zip(renew).map(|(obtained, renew)| obtained + renew),
+            expires_at: obtained.zip(lifetime).map(|(obtained, lifetime)| obtained + lifetime),
+        },
+        now,
+    ))
+}
+
+/// The most recent lease per interface in a dhclient lease database, which
+/// appends a `lease { ... }` block at every renewal.
+fn dhclient_leases(contents: &str, path: &Path, now: u64) -> Vec<Value> {
+    let mut latest: Vec<(String, Fields)> = Vec::new();
+    for block in contents.split("lease {").skip(1) {
+        let block = block.split('}').next().unwrap_or_default();
+        let mut interface = None;
+        let mut fields = Fields::default();
+        for statement in block.split(';').map(str::trim) {
+            let (key, rest) = statement.split_once(char::is_whitespace).unwrap_or((statement, ""));
+            let rest = rest.trim();
+            let unquoted = rest.trim_matches('"').to_string();
+            match key {
+                "interface" => interface = Some(unquoted),
+                "fixed-address" => fields.address = Some(unquoted),
+                "renew" => fields.renew_at = dhclient_time(rest),
+                "expire" => fields.expires_at = dhclient_time(rest),
+                "option" => {
+                    let (option, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    let value = value.trim();
+                    let list = || value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect::<Vec<_>>();
+                    match option {
+                        "subnet-mask" => fields.prefix_length = prefix_length(value),
+                        "routers" => fields.routers = list(),
+                        "domain-name-servers" => fields.dns = list(),
+                        "domain-name" => fields.domain = Some(value.trim_matches('"').to_string()),
+                        "dhcp-server-identifier" => fields.server = Some(value.to_string()),
+                        "dhcp-lease-time" => fields.lease_time_secs = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        fields.obtained_at = fields.expires_at.zip(fields.lease_time_secs).and_then(|(expires, lifetime)| expires.checked_sub(lifetime));
+        let Some(interface) = interface else {
+            continue;
+        };
+        latest.retain(|(existing, _)| *existing != interface);
+        latest.push((interface, fields));
+    }
+    latest.into_iter().map(|(interface, fields)| lease(Some(interface), "dhclient", path, fields, now)).collect()
+}
+
+/// `4 2026/10/15 03:12:44` (weekday, then UTC) or `epoch 1760497964`; none for
+/// `never`.
+fn dhclient_time(value: &str) -> Option<u64> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    match words[..] {
+        ["epoch", secs, ..] => secs.parse().ok(),
+        [_, date, time, ..] => {
+            let date: Vec<&str> = date.split('/').collect();
+            let time: Vec<u64> = time.split(':').filter_map(|part| part.parse().ok()).collect();
+            match (&date[..], &time[..]) {
+                ([year, month, day], [hour, minute, second]) => secs_from_civil(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?, *hour, *minute, *second),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn prefix_length(netmask: &str) -> Option<u32> {
+    netmask.parse::<std::net::Ipv4Addr>().ok().map(|mask| u32::from(mask).count_ones())
+}
+
+#[derive(Default)]
+struct Fields {
+    address: Option<String>,
+    prefix_length: Option<u32>,
+    server: Option<String>,
+    routers: Vec<String>,
+    dns: Vec<String>,
+    domain: Option<String>,
+    lease_time_secs: Option<u64>,
+    obtained_at: Option<u64>,
+    renew_at: Option<u64>,
+    expires_at: Option<u64>,
+}
+
+fn lease(interface: Option<String>, client: &str, path: &Path, fields: Fields, now: u64) -> Value {
+    let time = |secs: Option<u64>| secs.map(|secs| format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs)));
+    json!({
+        "interface": interface,
+        "client": client,
+        "file": path.display().to_string(),
+        "address": fields.address,
+        "prefix_length": fields.prefix_length,
+        "server": fields.server,
+        "routers": fields.routers,
+        "dns": fields.dns,
+        "domain": fields.domain,
+        "lease_time_secs": fields.lease_time_secs,
+        "obtained_at": time(fields.obtained_at),
+        "renew_at": time(fields.renew_at),
+        "expires_at": time(fields.expires_at),
+        "expires_in_secs": fields.expires_at.map(|expires| expires.saturating_sub(now)),
+        "expired": fields.expires_at.map(|expires| expires <= now)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DHCLIENT_LEASES: &str = r#"
+lease {
+  interface "eth0";
+  fixed-address 192.168.1.40;
+  option dhcp-lease-time 86400;
+  expire 3 2026/10/14 15:12:44;
+}
+lease {
+  interface "eth0";
+  fixed-address 192.168.1.50;
+  option subnet-mask 255.255.255.0;
+  option routers 192.168.1.1;
+  option domain-name-servers 192.168.1.1, 1.1.1.1;
+  option domain-name "lan";
+  option dhcp-server-identifier 192.168.1.1;
+  option dhcp-lease-time 86400;
+  renew 4 2026/10/15 03:12:44;
+  rebind 4 2026/10/15 12:12:44;
+  expire 4 2026/10/15 15:12:44;
+}
+lease {
+  interface "wlan0";
+  fixed-address 10.0.0.7;
+  renew never;
+  expire epoch 1760000000;
+}
+"#;
+
+    #[test]
+    fn dhclient_keeps_the_latest_lease_per_interface() {
+        let expires = secs_from_civil(2026, 10, 15, 15, 12, 44).unwrap();
+        let leases = dhclient_leases(DHCLIENT_LEASES, Path::new("/var/lib/dhcp/dhclient.leases"), expires - 3600);
+        assert_eq!(leases.len(), 2);
+        assert_eq!(
+            leases[0],
+            json!({
+                "interface": "eth0",
+                "client": "dhclient",
+                "file": "/var/lib/dhcp/dhclient.leases",
+                "address": "192.168.1.50",
+                "prefix_length": 24,
+                "server": "192.168.1.1",
+                "routers": ["192.168.1.1"],
+                "dns": ["192.168.1.1", "1.1.1.1"],
+                "domain": "lan",
+                "lease_time_secs": 86400,
+                "obtained_at": "2026-10-14T15:12:44Z",
+                "renew_at": "2026-10-15T03:12:44Z",
+                "expires_at": "2026-10-15T15:12:44Z",
+                "expires_in_secs": 3600,
+                "expired": false
+            })
+        );
+        assert_eq!((&leases[1]["interface"], &leases[1]["renew_at"], &leases[1]["obtained_at"]), (&json!("wlan0"), &Value::Null, &Value::Null));
+        assert_eq!((&leases[1]["expires_at"], &leases[1]["expired"]), (&json!("2025-10-09T08:53:20Z"), &json!(true)));
+    }
+
+    #[test]
+    fn dhclient_times_and_netmasks() {
+        assert_eq!(dhclient_time("4 2026/10/15 03:12:44"), secs_from_civil(2026, 10, 15, 3, 12, 44));
+        assert_eq!(dhclient_time("epoch 1760497964"), Some(1760497964));
+        assert_eq!(dhclient_time("never"), None);
+        assert_eq!(dhclient_time("4 2026/10/15"), None);
+        assert_eq!(prefix_length("255.255.240.0"), Some(20));
+        assert_eq!(prefix_length("24"), None);
+    }
+
+    #[test]
+    fn networkd_leases_are_timed_from_the_file() {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-networkd-lease", std::process::id()));
+        fs::write(
+            &path,
+            "# This is private data. Do not parse.\nADDRESS=192.168.1.50\nNETMASK=255.255.255.0\nROUTER=192.168.1.1\nSERVER_ADDRESS=192.168.1.1\nT1=43200\nLIFETIME=86400\nDNS=192.168.1.1 1.1.1.1\nDOMAINNAME=lan\nNTP=\n",
+        )
+        .unwrap();
+        let lease = networkd_lease(&path, Some("eth0".to_string()), "networkd", now_secs());
+        fs::remove_file(&path).unwrap();
+        let lease = lease.unwrap();
+
+        assert_eq!((&lease["address"], &lease["prefix_length"], &lease["server"]), (&json!("192.168.1.50"), &json!(24), &json!("192.168.1.1")));
+        assert_eq!((&lease["routers"], &lease["dns"], &lease["domain"]), (&json!(["192.168.1.1"]), &json!(["192.168.1.1", "1.1.1.1"]), &json!("lan")));
+        assert_eq!((&lease["interface"], &lease["client"], &lease["lease_time_secs"]), (&json!("eth0"), &json!("networkd"), &json!(86400)));
+        assert_eq!(lease["expired"], json!(false));
+        assert!(lease["expires_in_secs"].as_u64().is_some_and(|secs| secs > 86000));
+        assert!(lease["renew_at"].is_string() && lease["obtained_at"].is_string());
+    }
+
+    #[test]
+    fn unreadable_networkd_leases_are_errors() {
+        assert!(networkd_lease(Path::new("/nonexistent/lease"), None, "networkd", 0).is_err());
+    }
+}
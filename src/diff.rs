@@ -0,0 +1,195 @@
+use serde_json::{json, Map, Value};
+
+/// Top-level sections that change on every run and would drown out real changes.
+const VOLATILE_SECTIONS: &[&str] = &["freshness"];
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// `{changed, added, removed, modified}` keyed by JSON Pointer.
+    Changes,
+    /// An RFC 6902 JSON Patch that turns the previous document into the current one.
+    JsonPatch,
+}
+
+pub enum Change {
+    Add { path: String, value: Value },
+    Remove { path: String, old: Value },
+    Replace { path: String, old: Value, value: Value },
+}
+
+/// Computes the changes from `old` to `new`, skipping volatile top-level sections.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let strip = |map: &Map<String, Value>| -> Map<String, Value> {
+                map.iter()
+                    .filter(|(key, _)| !VOLATILE_SECTIONS.contains(&key.as_str()))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
+            };
+            diff_values("", &Value::Object(strip(old)), &Value::Object(strip(new)), &mut changes);
+        }
+        _ => diff_values("", old, new, &mut changes),
+    }
+    changes
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = format!("{}/{}", path, escape_pointer(key));
+                match new_map.get(key) {
+                    Some(new_value) => diff_values(&child, old_value, new_value, changes),
+                    None => changes.push(Change::Remove {
+                        path: child,
+                        old: old_value.clone(),
+                    }),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    changes.push(Change::Add {
+                        path: format!("{}/{}", path, escape_pointer(key)),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        _ if old != new => changes.push(Change::Replace {
+            path: path.to_string(),
+            old: old.clone(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Matches a JSON Pointer against a pattern in which `*` matches one segment and a
+/// trailing `**` any remainder; otherwise the whole path must match.
+pub fn pointer_matches(pattern: &str, path: &str) -> bool {
+    let mut segments = path.split('/').skip(1);
+    for part in pattern.split('/').skip(1) {
+        if part == "**" {
+            return true;
+        }
+        match segments.next() {
+            Some(segment) if part == "*" || part == segment => {}
+            _ => return false,
+        }
+    }
+    segments.next().is_none()
+}
+
+/// RFC 6901 escaping of a single reference token.
+pub fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+pub fn render(changes: &[Change], format: &DiffFormat) -> Value {
+    match format {
+        DiffFormat::JsonPatch => Value::Array(
+            changes
+                .iter()
+                .map(|change| match change {
+                    Change::Add { path, value } => json!({ "op": "add", "path": path, "value": value }),
+                    Change::Remove { path, .. } => json!({ "op": "remove", "path": path }),
+                    Change::Replace { path, value, .. } => json!({ "op": "replace", "path": path, "value": value }),
+                })
+                .collect(),
+        ),
+        DiffFormat::Changes => {
+            let mut added = Map::new();
+            let mut removed = Map::new();
+            let mut modified = Map::new();
+            for change in changes {
+                match change {
+                    Change::Add { path, value } => {
+                        added.insert(path.clone(), value.clone());
+                    }
+                    Change::Remove { path, old } => {
+                        removed.insert(path.clone(), old.clone());
+                    }
+                    Change::Replace { path, old, value } => {
+                        modified.insert(path.clone(), json!({ "old": old, "new": value }));
+                    }
+                }
+            }
+            json!({
+                "changed": !changes.is_empty(),
+                "added": added,
+                "removed": removed,
+                "modified": modified
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn objects_are_diffed_key_by_key() {
+        let old = json!({ "ip": { "public_ip": "203.0.113.7", "failed_ipv6": true }, "timezone": { "timezone": "UTC" } });
+        let new = json!({ "ip": { "public_ip": "203.0.113.8", "public_ipv6": "2001:db8::1" }, "timezone": { "timezone": "UTC" } });
+        assert_eq!(
+            render(&diff(&old, &new), &DiffFormat::JsonPatch),
+            json!([
+                { "op": "remove", "path": "/ip/failed_ipv6" },
+                { "op": "replace", "path": "/ip/public_ip", "value": "203.0.113.8" },
+                { "op": "add", "path": "/ip/public_ipv6", "value": "2001:db8::1" }
+            ])
+        );
+    }
+
+    #[test]
+    fn arrays_are_replaced_whole() {
+        let old = json!({ "groups": { "docker": { "group-list": ["a", "b"] } } });
+        let new = json!({ "groups": { "docker": { "group-list": ["a", "b", "c"] } } });
+        assert_eq!(
+            render(&diff(&old, &new), &DiffFormat::JsonPatch),
+            json!([{ "op": "replace", "path": "/groups/docker/group-list", "value": ["a", "b", "c"] }])
+        );
+        assert!(diff(&json!([1, 2]), &json!([1, 2])).is_empty());
+    }
+
+    #[test]
+    fn keys_are_escaped_as_pointer_tokens() {
+        let old = json!({ "mounts": {} });
+        let new = json!({ "mounts": { "/mnt/a~b": 1 } });
+        assert_eq!(render(&diff(&old, &new), &DiffFormat::JsonPatch), json!([{ "op": "add", "path": "/mounts/~1mnt~1a~0b", "value": 1 }]));
+    }
+
+    #[test]
+    fn volatile_sections_and_type_changes() {
+        let old = json!({ "freshness": { "ip": 1 }, "timezone": "UTC" });
+        let new = json!({ "freshness": { "ip": 2 }, "timezone": { "timezone": "UTC" } });
+        assert_eq!(
+            render(&diff(&old, &new), &DiffFormat::Changes),
+            json!({
+                "changed": true,
+                "added": {},
+                "removed": {},
+                "modified": { "/timezone": { "old": "UTC", "new": { "timezone": "UTC" } } }
+            })
+        );
+        assert_eq!(render(&diff(&json!(1), &json!("1")), &DiffFormat::JsonPatch), json!([{ "op": "replace", "path": "", "value": "1" }]));
+    }
+
+    #[test]
+    fn pointer_patterns() {
+        assert!(pointer_matches("/users/*/uid", "/users/root/uid"));
+        assert!(!pointer_matches("/users/*/uid", "/users/root"));
+        assert!(pointer_matches("/mounts/**", "/mounts/~1/size_gb"));
+        assert!(!pointer_matches("/ip", "/ip/public_ip"));
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        assert!(DiffFormat::from_str("json-patch", false).is_ok());
+        assert!(DiffFormat::from_str("merge-patch", false).is_err());
+    }
+}
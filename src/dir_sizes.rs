@@ -0,0 +1,148 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::errors::{Code, Error};
+
+/// Docker's data root, logs and Saltbox's transcode directory.
+pub const DEFAULT_DIRECTORIES: &[&str] = &["/var/lib/docker", "/var/log", "/mnt/local/transcodes"];
+pub const DEFAULT_BUDGET_SECS: u64 = 10;
+/// Children of each directory listed under `largest`.
+const LARGEST: usize = 10;
+
+/// Disk space used under each directory, from file metadata alone, and its largest
+/// children. A cleanup role gets "what's eating the disk" without waiting on `du`.
+///
+/// `budget` is shared: each directory may use what's left of it divided by the
+/// directories still to scan. A directory that runs out is reported with
+/// `complete: false` and the sizes found so far, a lower bound. Sizes are the
+/// blocks allocated, hard links counted once; the walk doesn't follow symlinks or
+/// cross into other filesystems (so a mount below a directory isn't included).
+pub fn get_dir_sizes(directories: &[String], budget: Duration) -> Value {
+    let deadline = Instant::now() + budget;
+    let mut reports = Map::new();
+    for (index, directory) in directories.iter().enumerate() {
+        let started = Instant::now();
+        let share = deadline.saturating_duration_since(started) / (directories.len() - index) as u32;
+        let mut report = json!({
+            "exists": false,
+            "error": Value::Null,
+            "complete": false,
+            "size_bytes": 0,
+            "files": 0,
+            "elapsed_ms": 0,
+            "largest": {}
+        });
+        match metadata::entry(Path::new(directory)) {
+            Ok(root) if root.dir => {
+                let mut scan = Scan { deadline: started + share, dev: root.dev, seen: HashSet::new(), files: 0, complete: true };
+                let mut size = root.bytes;
+                let mut children: Vec<(String, u64)> = Vec::new();
+                for child in read_dir(Path::new(directory)) {
+                    let Ok(entry) = metadata::entry(&child) else {
+                        continue;
+                    };
+                    let bytes = scan.size(&child, &entry);
+                    size += bytes;
+                    children.push((child.file_name().unwrap_or_default().to_string_lossy().into_owned(), bytes));
+                    if !scan.complete {
+                        break;
+                    }
+                }
+                children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                let largest: Map<String, Value> = children.into_iter().take(LARGEST).map(|(name, bytes)| (name, json!(bytes))).collect();
+                report["exists"] = json!(true);
+                report["complete"] = json!(scan.complete);
+                report["size_bytes"] = json!(size);
+                report["files"] = json!(scan.files);
+                report["largest"] = Value::Object(largest);
+            }
+            Ok(_) => {
+                report["exists"] = json!(true);
+                report["error"] = json!(Error::new(Code::NotFound, directory, format!("{} is not a directory", directory)));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => report["error"] = json!(Error::read(directory, &e)),
+        }
+        report["elapsed_ms"] = json!(started.elapsed().as_millis() as u64);
+        reports.insert(directory.clone(), report);
+    }
+    json!({ "budget_secs": budget.as_secs(), "directories": reports })
+}
+
+struct Scan {
+    deadline: Instant,
+    dev: u64,
+    /// (device, inode) of files with more than one link, so each is counted once.
+    seen: HashSet<(u64, u64)>,
+    files: u64,
+    complete: bool,
+}
+
+impl Scan {
+    /// Bytes allocated to `path` and, for a directory on the same filesystem,
+    /// everything below it, stopping at the deadline.
+    fn size(&mut self, path: &Path, entry: &metadata::Entry) -> u64 {
+        if entry.links > 1 && !entry.dir && !self.seen.insert((entry.dev, entry.inode)) {
+            return 0;
+        }
+        if !entry.dir {
+            self.files += 1;
+            return entry.bytes;
+        }
+        let mut size = entry.bytes;
+        if entry.dev != self.dev {
+            return size;
+        }
+        for child in read_dir(path) {
+            if Instant::now() >= self.deadline {
+                self.complete = false;
+                break;
+            }
+            if let Ok(child_entry) = metadata::entry(&child) {
+                size += self.size(&child, &child_entry);
+            }
+        }
+        size
+    }
+}
+
+/// The paths in `dir`, sorted, so a hard-linked file is always counted under the
+/// same child; an unreadable directory has none.
+fn read_dir(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir).into_iter().flatten().filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    paths.sort();
+    paths
+}
+
+mod metadata {
+    use std::io;
+    use std::path::Path;
+
+    pub struct Entry {
+        pub bytes: u64,
+        pub dev: u64,
+        pub inode: u64,
+        pub links: u64,
+        pub dir: bool,
+    }
+
+    /// `path` itself (symlinks aren't followed) and the space allocated to it.
+    #[cfg(unix)]
+    pub fn entry(path: &Path) -> io::Result<Entry> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::symlink_metadata(path)?;
+        // st_blocks counts 512-byte units whatever the filesystem's block size.
+        Ok(Entry { bytes: metadata.blocks() * 512, dev: metadata.dev(), inode: metadata.ino(), links: metadata.nlink(), dir: metadata.is_dir() })
+    }
+
+    /// Without allocation data, the apparent size.
+    #[cfg(not(unix))]
+    pub fn entry(path: &Path) -> io::Result<Entry> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        Ok(Entry { bytes: metadata.len(), dev: 0, inode: 0, links: 1, dir: metadata.is_dir() })
+    }
+}
@@ -0,0 +1,59 @@
+use std::net::{IpAddr, SocketAddr};
+
+#[cfg(feature = "custom-dns")]
+pub use resolver::CustomResolver;
+
+const DNS_PORT: u16 = 53;
+
+/// Only built with the `custom-dns` feature, which pulls in hickory-resolver.
+#[cfg(feature = "custom-dns")]
+mod resolver {
+    use hickory_resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts};
+    use hickory_resolver::TokioAsyncResolver;
+    use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    /// Resolves the tool's own HTTP lookups through fixed nameservers, bypassing
+    /// /etc/resolv.conf so a broken system resolver doesn't also break the IP facts.
+    pub struct CustomResolver {
+        resolver: Arc<TokioAsyncResolver>,
+    }
+
+    impl CustomResolver {
+        pub fn new(servers: &[SocketAddr]) -> CustomResolver {
+            let mut group = NameServerConfigGroup::with_capacity(servers.len() * 2);
+            for &server in servers {
+                // TCP as well as UDP, for networks that drop UDP/53 to anything but the local resolver.
+                group.push(NameServerConfig::new(server, Protocol::Udp));
+                group.push(NameServerConfig::new(server, Protocol::Tcp));
+            }
+            let config = ResolverConfig::from_parts(None, Vec::new(), group);
+            CustomResolver {
+                resolver: Arc::new(TokioAsyncResolver::tokio(config, ResolverOpts::default())),
+            }
+        }
+    }
+
+    impl Resolve for CustomResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            let resolver = self.resolver.clone();
+            Box::pin(async move {
+                let lookup = resolver.lookup_ip(name.as_str()).await?;
+                // reqwest replaces the port with the one from the URL.
+                let addrs: Addrs = Box::new(lookup.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+                Ok(addrs)
+            })
+        }
+    }
+}
+
+/// Parses `IP`, `IP:PORT` or `[IPv6]:PORT` as given to `--dns-server`.
+pub fn parse_server(source: &str) -> Result<SocketAddr, String> {
+    if let Ok(ip) = source.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DNS_PORT));
+    }
+    source
+        .parse::<SocketAddr>()
+        .map_err(|_| format!("Invalid DNS server {:?}: expected an IP address, optionally with a port", source))
+}
@@ -0,0 +1,196 @@
+//! A client for the Docker Engine API on its Unix socket, over hyper: GET requests
+//! returning JSON or a finite stream of JSON objects, which is all the docker
+//! collectors need.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use crate::errors::{Code, Error};
+
+pub const DOCKER_SOCKET_PATH: &str = "/var/run/docker.sock";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Generous for /containers/json on hosts with hundreds of containers.
+const MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// The daemon socket: `DOCKER_HOST` when it names a unix:// socket, otherwise the
+/// default path.
+pub fn socket_path() -> String {
+    match env::var("DOCKER_HOST") {
+        Ok(host) if host.starts_with("unix://") => host["unix://".len()..].to_string(),
+        _ => DOCKER_SOCKET_PATH.to_string(),
+    }
+}
+
+/// GETs `path` (e.g. `/images/json`) from the daemon and parses the JSON body.
+pub async fn get(path: &str) -> Result<Value, Error> {
+    let (status, body) = send(path).await?;
+    let body: Value = serde_json::from_slice(&body).map_err(|e| invalid(path, e))?;
+    check_status(path, status, &body)?;
+    Ok(body)
+}
+
+/// GETs a streaming endpoint bounded in time, such as `/events?until=...`, and
+/// parses the concatenated JSON objects it returns.
+pub async fn get_stream(path: &str) -> Result<Vec<Value>, Error> {
+    let (status, body) = send(path).await?;
+    let values = serde_json::Deserializer::from_slice(&body)
+        .into_iter::<Value>()
+        .collect::<Result<Vec<Value>, _>>()
+        .map_err(|e| invalid(path, e))?;
+    check_status(path, status, values.first().unwrap_or(&Value::Null))?;
+    Ok(values)
+}
+
+/// The id of the container a cgroup path belongs to: `.../docker-<id>.scope` with
+/// the systemd cgroup driver, `.../docker/<id>` with cgroupfs.
+pub fn container_id(cgroup: &str) -> Option<&str> {
+    let parts: Vec<&str> = cgroup.split('/').collect();
+    parts.iter().enumerate().rev().find_map(|(index, part)| {
+        let id = part
+            .strip_prefix("docker-")
+            .and_then(|part| part.strip_suffix(".scope"))
+            .or_else(|| (index > 0 && parts[index - 1] == "docker").then_some(*part))?;
+        (id.len() == 64 && id.bytes().all(|byte| byte.is_ascii_hexdigit())).then_some(id)
+    })
+}
+
+/// Container names by id from a `/containers/json` listing.
+pub fn container_names(containers: &Value) -> HashMap<String, String> {
+    containers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|container| {
+            let id = container.get("Id")?.as_str()?;
+            let name = container.pointer("/Names/0")?.as_str()?.trim_start_matches('/');
+            Some((id.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+async fn send(path: &str) -> Result<(u16, Vec<u8>), Error> {
+    let socket = socket_path();
+    tracing::debug!("GET {} on {}", path, socket);
+    tokio::time::timeout(REQUEST_TIMEOUT, request(&socket, path))
+        .await
+        .map_err(|_| Error::new(Code::Timeout, path, format!("{}: timed out after {}s", path, REQUEST_TIMEOUT.as_secs())))?
+}
+
+fn check_status(path: &str, status: u16, body: &Value) -> Result<(), Error> {
+    if status != 200 {
+        let message = body.get("message").and_then(Value::as_str).unwrap_or("no message");
+        return Err(Error::new(Code::of_status(status), path, format!("{}: HTTP {}: {}", path, status, message)));
+    }
+    Ok(())
+}
+
+fn invalid(path: &str, error: impl std::fmt::Display) -> Error {
+    Error::new(Code::InvalidData, path, format!("{}: invalid JSON from the daemon: {}", path, error))
+}
+
+/// Sends the request with hyper over the socket, reading at most
+/// MAX_RESPONSE_BYTES of the body.
+#[cfg(unix)]
+async fn request(socket: &str, path: &str) -> Result<(u16, Vec<u8>), Error> {
+    use http_body_util::{BodyExt, Empty, LengthLimitError, Limited};
+    use hyper::body::Bytes;
+    use hyper_util::client::legacy::Client;
+    use hyperlocal::{UnixClientExt, UnixConnector, Uri};
+
+    crate::capability::record_endpoint(&format!("unix:{}", socket));
+    let client: Client<UnixConnector, Empty<Bytes>> = Client::unix();
+    let request = hyper::Request::get(Uri::new(socket, path))
+        .header("Accept", "application/json")
+        .body(Empty::new())
+        .map_err(|e| Error::new(Code::InvalidData, path, format!("{}: {}", path, e)))?;
+    let response = client.request(request).await.map_err(|e| {
+        let action = if e.is_connect() { "Cannot connect to" } else { "Cannot read from" };
+        Error::new(io_error(&e).map_or(Code::Unknown, Code::of_io), socket, format!("{} {}: {}", action, socket, io_error(&e).map_or(e.to_string(), |e| e.to_string())))
+    })?;
+    let status = response.status().as_u16();
+    let body = Limited::new(response.into_body(), MAX_RESPONSE_BYTES).collect().await.map_err(|e| match e.downcast_ref::<LengthLimitError>() {
+        Some(_) => Error::new(Code::TooLarge, path, format!("{}: the daemon's response is larger than {} MiB", path, MAX_RESPONSE_BYTES / 1024 / 1024)),
+        None => Error::new(io_error(e.as_ref()).map_or(Code::Unknown, Code::of_io), socket, format!("Cannot read from {}: {}", socket, e)),
+    })?;
+    Ok((status, body.to_bytes().to_vec()))
+}
+
+/// The I/O error at the bottom of a hyper error, if any.
+#[cfg(unix)]
+fn io_error<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a std::io::Error> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            return Some(error);
+        }
+        source = error.source();
+    }
+    None
+}
+
+#[cfg(not(unix))]
+async fn request(socket: &str, _path: &str) -> Result<(u16, Vec<u8>), Error> {
+    Err(Error::new(Code::Unsupported, socket, "The Docker socket is only supported on Unix"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A daemon that answers one request with `head` and a body of `body`.
+    fn daemon(head: &'static str, body: Vec<u8>) -> String {
+        let socket = std::env::temp_dir().join(format!("saltbox-facts-{}-docker-{}.sock", std::process::id(), SOCKETS.fetch_add(1, Ordering::Relaxed)));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0u8; 1];
+                stream.read_exact(&mut byte).await.unwrap();
+                request.push(byte[0]);
+            }
+            let _ = stream.write_all(format!("{}\r\nContent-Length: {}\r\n\r\n", head, body.len()).as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+        });
+        socket.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn reads_the_status_and_body() {
+        let socket = daemon("HTTP/1.1 200 OK", br#"[{"Id":"abc"}]"#.to_vec());
+        assert_eq!(request(&socket, "/containers/json").await.unwrap(), (200, br#"[{"Id":"abc"}]"#.to_vec()));
+        std::fs::remove_file(socket).unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_responses_are_too_large() {
+        let socket = daemon("HTTP/1.1 200 OK", vec![b' '; MAX_RESPONSE_BYTES + 1]);
+        let error = request(&socket, "/containers/json").await.unwrap_err();
+        assert_eq!(error.code, Code::TooLarge, "{}", error.message);
+        std::fs::remove_file(socket).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_socket_is_not_found() {
+        let socket = std::env::temp_dir().join(format!("saltbox-facts-{}-no-docker.sock", std::process::id()));
+        let error = request(socket.to_str().unwrap(), "/info").await.unwrap_err();
+        assert_eq!(error.code, Code::NotFound, "{}", error.message);
+    }
+
+    #[test]
+    fn unsuccessful_statuses_carry_the_daemon_message() {
+        let error = check_status("/containers/x/json", 404, &serde_json::json!({ "message": "No such container: x" })).unwrap_err();
+        assert_eq!(error.code, Code::NotFound);
+        assert_eq!(error.message, "/containers/x/json: HTTP 404: No such container: x");
+        assert!(check_status("/info", 200, &Value::Null).is_ok());
+    }
+}
@@ -0,0 +1,168 @@
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{Code, Error};
+use crate::timestamp::format_rfc3339;
+
+pub const DAEMON_JSON_PATH: &str = "/etc/docker/daemon.json";
+pub const DROP_IN_DIR_PATH: &str = "/etc/systemd/system/docker.service.d";
+
+/// daemon.json keys reported, with the dockerd flag that sets the same option.
+/// List-valued options take the flag once per entry.
+const SETTINGS: &[(&str, &str)] = &[
+    ("log-driver", "--log-driver"),
+    ("log-opts", "--log-opt"),
+    ("default-address-pools", "--default-address-pool"),
+    ("dns", "--dns"),
+    ("dns-search", "--dns-search"),
+    ("dns-opts", "--dns-opt"),
+    ("storage-driver", "--storage-driver"),
+    ("storage-opts", "--storage-opt"),
+    ("data-root", "--data-root"),
+];
+
+/// dockerd's configuration from daemon.json and the ExecStart of systemd drop-ins.
+///
+/// The SHA-256 and modification time of daemon.json let a role that templates it
+/// notice manual edits before overwriting them. An option set both in the file and
+/// as a flag is listed under `conflicts`: dockerd refuses to start in that case.
+pub fn get_docker_daemon() -> Value {
+    let (drop_ins, exec_start) = read_drop_ins(Path::new(DROP_IN_DIR_PATH));
+    let flags = exec_start.as_deref().map(parse_flags).unwrap_or_default();
+    let config_path = flags
+        .get("--config-file")
+        .and_then(|values| values.as_array()?.last()?.as_str())
+        .unwrap_or(DAEMON_JSON_PATH)
+        .to_string();
+
+    let (config, file, error) = match fs::read(&config_path) {
+        Ok(data) => {
+            let modified = fs::metadata(&config_path).and_then(|metadata| metadata.modified()).ok().map(format_rfc3339);
+            let file = json!({
+                "sha256": Sha256::digest(&data).iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+                "modified": modified,
+                "size": data.len(),
+            });
+            match serde_json::from_slice::<Value>(&data) {
+                Ok(Value::Object(config)) => (config, file, None),
+                Ok(_) => (Map::new(), file, Some(Error::new(Code::InvalidData, &config_path, format!("{} is not a JSON object", config_path)))),
+                Err(e) => (Map::new(), file, Some(Error::parse(&config_path, e))),
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Map::new(), Value::Null, None),
+        Err(e) => (Map::new(), Value::Null, Some(Error::read(&config_path, &e))),
+    };
+
+    let mut settings = Map::new();
+    let mut conflicts = Vec::new();
+    for (key, flag) in SETTINGS {
+        let from_flags = flags.get(*flag);
+        let value = match (config.get(*key), from_flags) {
+            (Some(value), Some(_)) => {
+                conflicts.push(*key);
+                value.clone()
+            }
+            (Some(value), None) => value.clone(),
+            (None, Some(values)) => flag_value(key, values),
+            (None, None) => Value::Null,
+        };
+        settings.insert(key.replace('-', "_"), value);
+    }
+
+    json!({
+        "config_file": config_path,
+        "file": file,
+        "error": error,
+        "settings": settings,
+        "drop_ins": drop_ins,
+        "exec_start": exec_start,
+        "conflicts": conflicts,
+    })
+}
+
+/// The drop-in files, sorted as systemd applies them, and the ExecStart they leave
+/// in effect. An empty `ExecStart=` clears the unit's own, as systemd requires
+/// before a service's command can be replaced.
+fn read_drop_ins(dir: &Path) -> (Vec<String>, Option<String>) {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "conf"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+
+    let mut exec_start = None;
+    for path in &paths {
+        let Ok(contents) = fs::read_to_string(path) else {
+            continue;
+        };
+        let mut in_service = false;
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_service = line == "[Service]";
+            } else if let Some(command) = line.strip_prefix("ExecStart=").filter(|_| in_service) {
+                exec_start = (!command.trim().is_empty()).then(|| command.trim().to_string());
+            }
+        }
+    }
+    (paths.iter().map(|path| path.display().to_string()).collect(), exec_start)
+}
+
+/// dockerd's `--flag value` and `--flag=value` arguments, by flag. Boolean flags
+/// without a value aren't needed here and may swallow the next word; that only
+/// matters for flags this module doesn't look at.
+fn parse_flags(command: &str) -> Map<String, Value> {
+    let mut flags = Map::new();
+    let mut words = command.split_whitespace().skip(1).peekable();
+    while let Some(word) = words.next() {
+        if !word.starts_with("--") {
+            continue;
+        }
+        let (flag, value) = match word.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (word, words.next_if(|next| !next.starts_with('-')).map(str::to_string)),
+        };
+        if let Some(value) = value {
+            let values = flags.entry(flag.to_string()).or_insert_with(|| json!([]));
+            if let Value::Array(values) = values {
+                values.push(json!(value.trim_matches('"')));
+            }
+        }
+    }
+    flags
+}
+
+/// A flag's values in daemon.json's shape for `key`.
+fn flag_value(key: &str, values: &Value) -> Value {
+    let values: Vec<&str> = values.as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+    match key {
+        "dns" | "dns-search" | "dns-opts" | "storage-opts" => json!(values),
+        "log-opts" => values
+            .iter()
+            .filter_map(|option| option.split_once('='))
+            .map(|(name, value)| (name.to_string(), json!(value)))
+            .collect::<Map<_, _>>()
+            .into(),
+        // base=172.80.0.0/16,size=24
+        "default-address-pools" => values
+            .iter()
+            .map(|pool| {
+                let fields: Map<String, Value> = pool
+                    .split(',')
+                    .filter_map(|field| field.split_once('='))
+                    .map(|(name, value)| {
+                        let value = value.parse::<u64>().map_or_else(|_| json!(value), |size| json!(size));
+                        (name.to_string(), value)
+                    })
+                    .collect();
+                Value::Object(fields)
+            })
+            .collect(),
+        _ => values.last().map_or(Value::Null, |value| json!(value)),
+    }
+}
@@ -0,0 +1,231 @@
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::docker;
+use crate::errors::{Code, Error};
+use crate::http::Client;
+use crate::timestamp::format_rfc3339;
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(10);
+const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+/// Manifest lists and OCI indexes first: a multi-arch pull records the index
+/// digest in RepoDigests, and that is what the registry must be compared against.
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.docker.distribution.manifest.v2+json";
+
+/// Local images keyed by short ID and, with `check_updates`, whether each running
+/// container's image has a newer digest in its registry.
+pub async fn get_docker_images(client: &Client, check_updates: bool) -> (Value, Option<Error>) {
+    let images = match docker::get("/images/json").await {
+        Ok(images) => images,
+        Err(e) => return (json!({ "available": false, "error": e, "images": {}, "updates": {}, "updates_error": Value::Null }), e.as_failure()),
+    };
+
+    let mut inventory = Map::new();
+    let mut digests_by_id: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for image in images.as_array().into_iter().flatten() {
+        let id = image.get("Id").and_then(Value::as_str).unwrap_or_default();
+        let tags = strings(image.get("RepoTags"));
+        let digests = strings(image.get("RepoDigests"));
+        let dangling = tags.iter().all(|tag| *tag == "<none>:<none>");
+        let created = image
+            .get("Created")
+            .and_then(Value::as_u64)
+            .map(|secs| format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs)));
+        let size_mb = image.get("Size").and_then(Value::as_u64).map(|bytes| (bytes as f64 / BYTES_PER_MB * 100.0).round() / 100.0);
+        inventory.insert(
+            short_id(id).to_string(),
+            json!({
+                "tags": if dangling { Vec::new() } else { tags.clone() },
+                "digests": digests.clone(),
+                "size_mb": size_mb,
+                "created": created,
+                "dangling": dangling
+            }),
+        );
+        digests_by_id.insert(id, digests);
+    }
+
+    let (updates, updates_error) = match check_updates {
+        true => match check_containers(client, &digests_by_id).await {
+            Ok(updates) => (updates, None),
+            Err(e) => (Map::new(), Some(e)),
+        },
+        false => (Map::new(), None),
+    };
+    let value = json!({
+        "available": true,
+        "error": Value::Null,
+        "images": inventory,
+        "updates": updates,
+        "updates_error": updates_error
+    });
+    (value, None)
+}
+
+/// Compares each running container's local image digest with the registry's
+/// current digest for the tag it was started from.
+async fn check_containers(client: &Client, digests_by_id: &BTreeMap<&str, Vec<&str>>) -> Result<Map<String, Value>, Error> {
+    let mut updates = Map::new();
+    let containers = docker::get("/containers/json").await?;
+
+    let mut remote_digests: BTreeMap<String, Result<String, Error>> = BTreeMap::new();
+    for container in containers.as_array().into_iter().flatten() {
+        let name = strings(container.get("Names")).first().map(|name| name.trim_start_matches('/')).unwrap_or_default().to_string();
+        let image = container.get("Image").and_then(Value::as_str).unwrap_or_default();
+        let image_id = container.get("ImageID").and_then(Value::as_str).unwrap_or_default();
+        let local = digests_by_id.get(image_id).cloned().unwrap_or_default();
+
+        let result = match parse_reference(image) {
+            None => Err(Error::new(Code::Unsupported, image, "started from an image ID or digest, nothing to compare")),
+            Some(_) if local.is_empty() => Err(Error::new(Code::Unsupported, image, "image has no registry digest (built locally?)")),
+            Some(reference) => {
+                if !remote_digests.contains_key(image) {
+                    remote_digests.insert(image.to_string(), remote_digest(client, &reference).await);
+                }
+                remote_digests[image].clone()
+            }
+        };
+        let local_digest = local.first().and_then(|digest| digest.split_once('@')).map(|(_, digest)| digest);
+        let value = match result {
+            Ok(remote) => json!({
+                "image": image,
+                "update_available": !local.iter().any(|digest| digest.ends_with(&format!("@{}", remote))),
+                "local_digest": local_digest,
+                "remote_digest": remote,
+                "error": Value::Null
+            }),
+            Err(e) => json!({
+                "image": image,
+                "update_available": Value::Null,
+                "local_digest": local_digest,
+                "remote_digest": Value::Null,
+                "error": e
+            }),
+        };
+        updates.insert(name, value);
+    }
+    Ok(updates)
+}
+
+/// An image reference split into registry, repository and tag.
+struct Reference {
+    registry: String,
+    repository: String,
+    tag: String,
+}
+
+/// Parses `[registry/]repository[:tag]` the way the Docker CLI does. References
+/// pinned by digest or bare image IDs return None: they can't go out of date.
+fn parse_reference(image: &str) -> Option<Reference> {
+    if image.contains('@') || image.starts_with("sha256:") {
+        return None;
+    }
+    let (registry, rest) = match image.split_once('/') {
+        Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => (first.to_string(), rest),
+        _ => (DOCKER_HUB_REGISTRY.to_string(), image),
+    };
+    let (repository, tag) = match rest.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+        _ => (rest, "latest"),
+    };
+    let repository = if registry == DOCKER_HUB_REGISTRY && !repository.contains('/') {
+        format!("library/{}", repository)
+    } else {
+        repository.to_string()
+    };
+    let registry = if registry == "docker.io" { DOCKER_HUB_REGISTRY.to_string() } else { registry };
+    Some(Reference {
+        registry,
+        repository,
+        tag: tag.to_string(),
+    })
+}
+
+/// The registry's digest for the reference, from a HEAD request for its manifest.
+/// Registries that challenge for a bearer token get an anonymous one.
+async fn remote_digest(client: &Client, reference: &Reference) -> Result<String, Error> {
+    let url = format!("https://{}/v2/{}/manifests/{}", reference.registry, reference.repository, reference.tag);
+    let head = |token: Option<String>| {
+        let mut request = client.head(&url)?.header(ACCEPT, MANIFEST_TYPES).timeout(REGISTRY_TIMEOUT);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        Ok::<_, Error>(request.send())
+    };
+
+    let mut response = head(None)?.await.map_err(|e| Error::http(&url, &e))?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Error::new(Code::Unauthorized, &url, format!("{}: HTTP 401 without an authentication challenge", url)))?;
+        let token = anonymous_token(client, challenge).await?;
+        response = head(Some(token))?.await.map_err(|e| Error::http(&url, &e))?;
+    }
+    if !response.status().is_success() {
+        return Err(Error::status(&url, response.status()));
+    }
+    response
+        .headers()
+        .get("docker-content-digest")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .ok_or_else(|| Error::new(Code::InvalidData, &url, format!("{}: no Docker-Content-Digest header", url)))
+}
+
+/// Answers a `Bearer realm="...",service="...",scope="..."` challenge without
+/// credentials, which is enough for public images.
+async fn anonymous_token(client: &Client, challenge: &str) -> Result<String, Error> {
+    let parameters = challenge
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| Error::new(Code::Unsupported, challenge, format!("Unsupported registry authentication: {}", challenge)))?;
+    let mut realm = None;
+    let mut query = Vec::new();
+    for parameter in parameters.split(',') {
+        if let Some((key, value)) = parameter.trim().split_once('=') {
+            let value = value.trim_matches('"').to_string();
+            match key {
+                "realm" => realm = Some(value),
+                "service" | "scope" => query.push((key.to_string(), value)),
+                _ => {}
+            }
+        }
+    }
+    let realm = realm.ok_or_else(|| Error::new(Code::InvalidData, challenge, format!("Registry challenge without a realm: {}", challenge)))?;
+    let response = client
+        .get(&realm)?
+        .query(&query)
+        .timeout(REGISTRY_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| Error::http(&realm, &e))?;
+    if !response.status().is_success() {
+        return Err(Error::status(&realm, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| Error::http(&realm, &e))?;
+    let body: Value = serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, &realm, format!("{}: {}", realm, e)))?;
+    body.get("token")
+        .or_else(|| body.get("access_token"))
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| Error::new(Code::InvalidData, &realm, format!("{}: no token in response", realm)))
+}
+
+fn strings(value: Option<&Value>) -> Vec<&str> {
+    value.and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).collect()
+}
+
+/// The 12-character ID the Docker CLI shows.
+fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    &id[..id.len().min(12)]
+}
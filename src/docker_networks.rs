@@ -0,0 +1,141 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::net::Ipv4Addr;
+
+use crate::docker;
+use crate::errors::Error;
+
+pub const ROUTE_FILE_PATH: &str = "/proc/net/route";
+
+/// Interfaces Docker creates for its own networks; their routes are the networks
+/// themselves, not something they could conflict with.
+const DOCKER_INTERFACE_PREFIXES: &[&str] = &["docker", "br-", "veth"];
+
+/// An IPv4 network in CIDR form.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ipv4Net {
+    address: u32,
+    prefix: u8,
+}
+
+impl Ipv4Net {
+    pub fn parse(source: &str) -> Result<Ipv4Net, String> {
+        let invalid = || format!("Invalid IPv4 subnet {:?}: expected ADDRESS/PREFIX", source);
+        let (address, prefix) = source.split_once('/').ok_or_else(invalid)?;
+        let address: Ipv4Addr = address.parse().map_err(|_| invalid())?;
+        let prefix: u8 = prefix.parse().ok().filter(|prefix| *prefix <= 32).ok_or_else(invalid)?;
+        Ok(Ipv4Net::new(u32::from(address), prefix))
+    }
+
+    fn new(address: u32, prefix: u8) -> Ipv4Net {
+        Ipv4Net {
+            address: address & mask(prefix),
+            prefix,
+        }
+    }
+
+    /// Two networks overlap exactly when one contains the other.
+    fn overlaps(&self, other: &Ipv4Net) -> bool {
+        let prefix = self.prefix.min(other.prefix);
+        self.address & mask(prefix) == other.address & mask(prefix)
+    }
+}
+
+impl std::fmt::Display for Ipv4Net {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", Ipv4Addr::from(self.address), self.prefix)
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+/// Docker networks with their subnets, and any IPv4 subnet that overlaps a route
+/// on a non-Docker interface (LAN, VPN) or one of `vpn_subnets`.
+pub async fn get_docker_networks(vpn_subnets: &[Ipv4Net]) -> (Value, Option<Error>) {
+    let networks = match docker::get("/networks").await {
+        Ok(networks) => networks,
+        Err(e) => return (json!({ "available": false, "error": e, "networks": {}, "host_subnets": [], "conflicts": [] }), e.as_failure()),
+    };
+
+    let mut host_subnets: Vec<(Ipv4Net, String)> = host_routes();
+    host_subnets.extend(vpn_subnets.iter().map(|subnet| (*subnet, "--vpn-subnet".to_string())));
+
+    let mut inventory = Map::new();
+    let mut conflicts = Vec::new();
+    for network in networks.as_array().into_iter().flatten() {
+        let name = network.get("Name").and_then(Value::as_str).unwrap_or_default();
+        let subnets: Vec<&str> = network
+            .pointer("/IPAM/Config")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|config| config.get("Subnet").and_then(Value::as_str))
+            .collect();
+        for subnet in &subnets {
+            // IPv6 subnets are listed but not checked.
+            let Ok(parsed) = Ipv4Net::parse(subnet) else {
+                continue;
+            };
+            for (host, source) in host_subnets.iter().filter(|(host, _)| host.overlaps(&parsed)) {
+                conflicts.push(json!({
+                    "network": name,
+                    "subnet": subnet,
+                    "conflicts_with": host.to_string(),
+                    "source": source
+                }));
+            }
+        }
+        inventory.insert(
+            name.to_string(),
+            json!({
+                "driver": network.get("Driver").and_then(Value::as_str),
+                "subnets": subnets
+            }),
+        );
+    }
+
+    let host_subnets: Vec<Value> = host_subnets
+        .iter()
+        .map(|(subnet, source)| json!({ "subnet": subnet.to_string(), "source": source }))
+        .collect();
+    let value = json!({
+        "available": true,
+        "error": Value::Null,
+        "networks": inventory,
+        "host_subnets": host_subnets,
+        "conflicts": conflicts
+    });
+    (value, None)
+}
+
+/// Non-default IPv4 routes on interfaces Docker didn't create, as (subnet, "dev IFACE").
+fn host_routes() -> Vec<(Ipv4Net, String)> {
+    let Ok(contents) = fs::read_to_string(ROUTE_FILE_PATH) else {
+        return Vec::new();
+    };
+    let mut routes = Vec::new();
+    for line in contents.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [interface, destination, _, _, _, _, _, mask, ..] = fields[..] else {
+            continue;
+        };
+        if DOCKER_INTERFACE_PREFIXES.iter().any(|prefix| interface.starts_with(prefix)) {
+            continue;
+        }
+        // The kernel prints addresses as native-endian integers of network-order bytes.
+        let parse = |hex: &str| u32::from_str_radix(hex, 16).ok().map(|value| u32::from_be_bytes(value.to_ne_bytes()));
+        let (Some(destination), Some(mask)) = (parse(destination), parse(mask)) else {
+            continue;
+        };
+        if mask == 0 {
+            continue;
+        }
+        let route = (Ipv4Net::new(destination, mask.leading_ones() as u8), format!("dev {}", interface));
+        if !routes.contains(&route) {
+            routes.push(route);
+        }
+    }
+    routes
+}
@@ -0,0 +1,95 @@
+use serde_json::{json, Map, Value};
+use tokio::task::JoinSet;
+
+use crate::docker;
+use crate::errors::Error;
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// A point-in-time resource snapshot of each running container: CPU percent,
+/// memory usage and limit, and block I/O totals since the container started.
+///
+/// The daemon computes the CPU figure from two samples about a second apart, so
+/// containers are sampled concurrently to keep the collector close to that second.
+pub async fn get_docker_stats() -> (Value, Option<Error>) {
+    let containers = match docker::get("/containers/json").await {
+        Ok(containers) => containers,
+        Err(e) => return (json!({ "available": false, "error": e, "containers": {} }), e.as_failure()),
+    };
+
+    let mut samples = JoinSet::new();
+    for container in containers.as_array().into_iter().flatten() {
+        let Some(id) = container.get("Id").and_then(Value::as_str).map(String::from) else {
+            continue;
+        };
+        let name = container
+            .get("Names")
+            .and_then(|names| names.get(0))
+            .and_then(Value::as_str)
+            .map_or_else(|| id.clone(), |name| name.trim_start_matches('/').to_string());
+        samples.spawn(async move { (name, docker::get(&format!("/containers/{}/stats?stream=false", id)).await) });
+    }
+
+    let mut snapshot = Map::new();
+    while let Some(sample) = samples.join_next().await {
+        let Ok((name, stats)) = sample else {
+            continue;
+        };
+        let value = match stats {
+            Ok(stats) => summarize(&stats),
+            Err(e) => json!({ "error": e }),
+        };
+        snapshot.insert(name, value);
+    }
+    (json!({ "available": true, "error": Value::Null, "containers": snapshot }), None)
+}
+
+fn summarize(stats: &Value) -> Value {
+    let number = |pointer: &str| stats.pointer(pointer).and_then(Value::as_u64);
+    let mb = |bytes: u64| (bytes as f64 / BYTES_PER_MB * 100.0).round() / 100.0;
+
+    // As `docker stats` computes it: the container's share of all CPU time between
+    // the two samples, scaled to the number of CPUs (so 200% is two full cores).
+    let cpu_percent = match (
+        number("/cpu_stats/cpu_usage/total_usage").zip(number("/precpu_stats/cpu_usage/total_usage")),
+        number("/cpu_stats/system_cpu_usage").zip(number("/precpu_stats/system_cpu_usage")),
+    ) {
+        (Some((total, previous_total)), Some((system, previous_system))) if system > previous_system => {
+            let cpus = number("/cpu_stats/online_cpus").unwrap_or(1);
+            let percent = total.saturating_sub(previous_total) as f64 / (system - previous_system) as f64 * cpus as f64 * 100.0;
+            Some((percent * 100.0).round() / 100.0)
+        }
+        _ => None,
+    };
+
+    // Page cache is reclaimable, so `docker stats` leaves it out: `cache` on cgroup
+    // v1, `inactive_file` on v2.
+    let cache = number("/memory_stats/stats/inactive_file").or_else(|| number("/memory_stats/stats/cache")).unwrap_or(0);
+    let memory_usage = number("/memory_stats/usage").map(|usage| usage.saturating_sub(cache));
+    let memory_limit = number("/memory_stats/limit");
+    let memory_percent = memory_usage
+        .zip(memory_limit)
+        .filter(|(_, limit)| *limit > 0)
+        .map(|(usage, limit)| (usage as f64 / limit as f64 * 10000.0).round() / 100.0);
+
+    let (mut read, mut write) = (0, 0);
+    for entry in stats.pointer("/blkio_stats/io_service_bytes_recursive").and_then(Value::as_array).into_iter().flatten() {
+        let value = entry.get("value").and_then(Value::as_u64).unwrap_or(0);
+        match entry.get("op").and_then(Value::as_str).map(str::to_ascii_lowercase).as_deref() {
+            Some("read") => read += value,
+            Some("write") => write += value,
+            _ => {}
+        }
+    }
+
+    json!({
+        "cpu_percent": cpu_percent,
+        "memory_usage_mb": memory_usage.map(mb),
+        "memory_limit_mb": memory_limit.map(mb),
+        "memory_percent": memory_percent,
+        "block_read_mb": mb(read),
+        "block_write_mb": mb(write),
+        "pids": number("/pids_stats/current"),
+        "error": Value::Null
+    })
+}
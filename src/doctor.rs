@@ -0,0 +1,218 @@
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::accounts::{GROUP_FILE_PATH, PASSWD_FILE_PATH};
+use crate::binaries::find_in_path;
+use crate::cache::Cache;
+use crate::cli::DoctorArgs;
+use crate::clock::{ADJTIME_FILE_PATH, CLOCKSOURCE_PATH};
+use crate::collectors::COLLECTORS;
+use crate::connectivity;
+#[cfg(unix)]
+use crate::docker;
+use crate::fingerprint::{DMI_SERIAL_PATHS, MACHINE_ID_PATHS};
+use crate::locale::LOCALE_ARCHIVE_PATH;
+use crate::mounts::MOUNTS_FILE_PATH;
+use crate::output::ReportFormat;
+use crate::platform;
+
+const DBUS_SOCKET_PATH: &str = "/run/dbus/system_bus_socket";
+/// Resolved to check DNS, since the IP lookups need it.
+const DNS_PROBE_HOST: &str = "ipify.saltbox.dev:443";
+const DNS_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+    Skipped,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+    hint: Option<&'static str>,
+}
+
+fn check(name: &str, status: Status, detail: impl Into<String>, hint: Option<&'static str>) -> Check {
+    Check {
+        name: name.to_string(),
+        status,
+        detail: detail.into(),
+        hint,
+    }
+}
+
+/// Checks the prerequisites of the collectors and prints what is missing and how
+/// to fix it. Fails if anything required is missing.
+pub async fn run(args: DoctorArgs) -> Result<(), Box<dyn Error>> {
+    let mut checks = Vec::new();
+
+    for path in [PASSWD_FILE_PATH, GROUP_FILE_PATH, MOUNTS_FILE_PATH] {
+        checks.push(match File::open(path) {
+            Ok(_) => check(path, Status::Ok, "readable", None),
+            Err(e) => check(path, Status::Fail, e.to_string(), Some("The users, groups and mounts collectors need this file")),
+        });
+    }
+    for (path, hint) in [
+        (ADJTIME_FILE_PATH, "rtc falls back to timedatectl and defaults; run `hwclock --systohc` to create it"),
+        (LOCALE_ARCHIVE_PATH, "locales only sees directories under /usr/lib/locale; install the locales package"),
+    ] {
+        checks.push(match File::open(path) {
+            Ok(_) => check(path, Status::Ok, "readable", None),
+            Err(e) => check(path, Status::Warn, e.to_string(), Some(hint)),
+        });
+    }
+    let clocksource = Path::new(CLOCKSOURCE_PATH).join("current_clocksource");
+    checks.push(match fs::read_to_string(&clocksource) {
+        Ok(_) => check(CLOCKSOURCE_PATH, Status::Ok, "readable", None),
+        Err(e) => check(CLOCKSOURCE_PATH, Status::Warn, e.to_string(), Some("clocksource needs sysfs mounted at /sys")),
+    });
+    checks.push(match MACHINE_ID_PATHS.iter().find(|path| fs::read_to_string(path).is_ok()) {
+        Some(path) => check("machine-id", Status::Ok, format!("{} readable", path), None),
+        None => check(
+            "machine-id",
+            Status::Warn,
+            "no readable machine-id",
+            Some("host_fingerprint is less stable without it; run `systemd-machine-id-setup`"),
+        ),
+    });
+
+    #[cfg(unix)]
+    for (name, path, hint) in [
+        ("docker socket", docker::socket_path(), "Docker facts need the daemon running and this user in the docker group"),
+        ("D-Bus socket", DBUS_SOCKET_PATH.to_string(), "Service facts need the system bus; is dbus running?"),
+    ] {
+        checks.push(match std::os::unix::net::UnixStream::connect(&path) {
+            Ok(_) => check(name, Status::Ok, format!("{} accepts connections", path), None),
+            Err(e) => check(name, Status::Warn, format!("{}: {}", path, e), Some(hint)),
+        });
+    }
+
+    for (program, hint) in [
+        ("sh", "timezone detection falls back to Etc/UTC without it"),
+        ("ip", "the public IPv6 lookup is skipped without iproute2"),
+        ("timedatectl", "rtc can't ask systemd whether the RTC keeps local time"),
+    ] {
+        checks.push(match find_in_path(program) {
+            Some(path) => check(program, Status::Ok, path.display().to_string(), None),
+            None => check(program, Status::Warn, "not found on PATH", Some(hint)),
+        });
+    }
+
+    let is_root = platform::is_root();
+    for collector in COLLECTORS.iter().filter(|collector| collector.capabilities.root) {
+        let name = format!("{} privileges", collector.name);
+        checks.push(if is_root || DMI_SERIAL_PATHS.iter().any(|path| fs::read_to_string(path).is_ok()) {
+            check(&name, Status::Ok, "privileged sources readable", None)
+        } else {
+            check(
+                &name,
+                Status::Warn,
+                "not running as root; DMI serials are unreadable",
+                Some("run as root for complete results"),
+            )
+        });
+    }
+
+    let cache = Cache::open(args.cache_dir.as_deref());
+    checks.push(match cache.dir() {
+        Some(dir) => {
+            let probe = dir.join(format!(".doctor.{}", std::process::id()));
+            match fs::write(&probe, b"") {
+                Ok(()) => {
+                    let _ = fs::remove_file(&probe);
+                    check("cache", Status::Ok, format!("{} writable", dir.display()), None)
+                }
+                Err(e) => check(
+                    "cache",
+                    Status::Warn,
+                    format!("{}: {}", dir.display(), e),
+                    Some("lookups aren't cached and the rate limit can't be tracked; pass --cache-dir"),
+                ),
+            }
+        }
+        None => check(
+            "cache",
+            Status::Warn,
+            "no cache directory could be created",
+            Some("lookups aren't cached and the rate limit can't be tracked; pass --cache-dir"),
+        ),
+    });
+
+    if args.offline {
+        checks.push(check("dns", Status::Skipped, "--offline", None));
+        checks.push(check("connectivity", Status::Skipped, "--offline", None));
+    } else {
+        checks.push(match timeout(DNS_TIMEOUT, tokio::net::lookup_host(DNS_PROBE_HOST)).await {
+            Ok(Ok(addresses)) => match addresses.count() {
+                0 => check("dns", Status::Fail, format!("{}: no addresses", DNS_PROBE_HOST), Some(DNS_HINT)),
+                _ => check("dns", Status::Ok, format!("{} resolves", DNS_PROBE_HOST), None),
+            },
+            Ok(Err(e)) => check("dns", Status::Fail, format!("{}: {}", DNS_PROBE_HOST, e), Some(DNS_HINT)),
+            Err(_) => check("dns", Status::Fail, format!("{}: timed out", DNS_PROBE_HOST), Some(DNS_HINT)),
+        });
+        let connectivity = connectivity::get_connectivity(true, true).await;
+        let verdict = connectivity["verdict"].as_str().unwrap_or_default().to_string();
+        checks.push(match verdict.as_str() {
+            "dual-stack" => check("connectivity", Status::Ok, verdict, None),
+            "ipv4-only" | "ipv6-only" => check(
+                "connectivity",
+                Status::Warn,
+                verdict,
+                Some("one address family can't reach the internet; pass --no-ipv4/--no-ipv6 to skip its lookup"),
+            ),
+            _ => check(
+                "connectivity",
+                Status::Fail,
+                verdict,
+                Some("no outbound TCP on port 443; check the firewall or proxy, or run with --offline"),
+            ),
+        });
+    }
+
+    let count = |status: Status| checks.iter().filter(|check| check.status == status).count();
+    let (failures, warnings) = (count(Status::Fail), count(Status::Warn));
+    match args.format {
+        ReportFormat::Json => {
+            let checks: Vec<Value> = checks
+                .iter()
+                .map(|check| json!({ "name": check.name, "status": check.status.name(), "detail": check.detail, "hint": check.hint }))
+                .collect();
+            println!("{}", json!({ "checks": checks, "ok": failures == 0, "failures": failures, "warnings": warnings }));
+        }
+        ReportFormat::Text => {
+            for check in &checks {
+                println!("[{:^7}] {}: {}", check.status.name(), check.name, check.detail);
+                if let (Some(hint), Status::Warn | Status::Fail) = (check.hint, check.status) {
+                    println!("          hint: {}", hint);
+                }
+            }
+            println!("{} ok, {} warnings, {} failures", count(Status::Ok), warnings, failures);
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{} check{} failed", failures, if failures == 1 { "" } else { "s" }).into());
+    }
+    Ok(())
+}
+
+const DNS_HINT: &str = "the system resolver is broken; fix /etc/resolv.conf or pass --dns-server";
@@ -0,0 +1,951 @@
+//! The JSON Schema printed by `saltbox-facts schema`, describing the document a
+//! gather run prints.
+//!
+//! What has a Rust type is derived from it with schemars: error objects and the
+//! users and groups entries. The other sections are assembled as JSON values, so
+//! their shapes are maintained by hand in `section` and have to follow changes to
+//! their collectors. Every key a section always emits is `required`; error fields
+//! are written there as nullable strings, their version 1 shape, and turned into
+//! error objects for version 2 by `structure_errors`. Sections themselves are
+//! optional: `--facts`, platform and capability skips leave them out.
+
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::accounts::{Group, User};
+use crate::collectors::COLLECTORS;
+use crate::deterministic;
+use crate::errors;
+use crate::schema_version;
+
+pub fn document_schema(version: &str, schema_version: u32) -> Value {
+    let mut properties = Map::new();
+    properties.insert("saltbox_facts_version".to_string(), json!({ "const": version }));
+    for collector in COLLECTORS {
+        let mut schema = section(collector.name);
+        if schema_version >= 2 {
+            structure_errors(&mut schema);
+        }
+        schema["description"] = json!(collector.description);
+        properties.insert(collector.name.to_string(), schema);
+    }
+
+    let collected_at = object(&[("collected_at", string()), ("cache_hit", boolean())]);
+    let reasons = map_of(string());
+    let failed = map_of(error_object());
+    let envelope = [
+        (
+            "meta",
+            object(&[("version", string()), ("git_commit", string()), ("build_time", string()), ("target", string())]),
+            "The binary that produced the document",
+        ),
+        ("freshness", map_of(collected_at), "When each section was collected, and whether it came from the cache"),
+        (
+            "truncated",
+            map_of(object(&[("limit", integer()), ("entries", integer())])),
+            "Sections cut short by --max-entries, with the limit and how many entries exist",
+        ),
+        ("skipped", reasons.clone(), "Collectors not run, with the reason"),
+        ("cancelled", reasons.clone(), "Collectors stopped by a signal, with its name"),
+        ("timed_out", reasons.clone(), "Collectors whose worker was killed, with the reason"),
+        ("failed", failed, "Collectors that ran but couldn't collect their facts, with the error"),
+        ("capability_violations", array_of(string()), "Collectors caught doing something they didn't declare"),
+        (
+            "drop_ins",
+            object(&[("directory", string()), ("loaded", array_of(string())), ("errors", map_of(string()))]),
+            "Drop-in files merged from --drop-in-dir",
+        ),
+        ("derived", map_of(json!({})), "Values of --derive / --derived-facts expressions"),
+        ("derived_errors", map_of(string()), "Derived facts that failed to evaluate"),
+        (
+            "assertions",
+            object(&[("passed", array_of(string())), ("failed", map_of(string()))]),
+            "Results of --assert / --assertions, when any are given",
+        ),
+        (
+            "timings",
+            map_of(object(&[
+                ("status", string()),
+                ("duration_ms", integer()),
+                ("commands", array_of(string())),
+                ("endpoints", array_of(string())),
+                ("files", array_of(string())),
+            ])),
+            "With --profile, how long each collector took and the commands, endpoints and files it used",
+        ),
+    ];
+    // Version 1 is the sections alone; what options add keeps to `additionalProperties`.
+    let required = if schema_version >= 2 {
+        properties.insert("schema_version".to_string(), schema_version::property(schema_version));
+        for (name, mut schema, description) in envelope {
+            schema["description"] = json!(description);
+            properties.insert(name.to_string(), schema);
+        }
+        json!([
+            "saltbox_facts_version", "schema_version", "meta", "freshness", "truncated", "skipped", "cancelled", "timed_out",
+            "failed", "capability_violations", "drop_ins", "derived", "derived_errors"
+        ])
+    } else {
+        json!(["saltbox_facts_version"])
+    };
+
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "saltbox-facts document",
+        "type": "object",
+        "required": required,
+        "properties": properties,
+        // --static-facts and drop-ins add keys of their own.
+        "additionalProperties": true
+    });
+    relax_volatile(&mut schema, "");
+    schema
+}
+
+/// Drops values that `--deterministic` removes from `required`, so the schema also
+/// describes normalized documents. `path` is the instance path the schema applies
+/// to, with `*` for any key or index.
+fn relax_volatile(schema: &mut Value, path: &str) {
+    let Some(schema) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        for (name, property) in properties.iter_mut() {
+            relax_volatile(property, &format!("{}/{}", path, name));
+        }
+    }
+    for keyword in ["additionalProperties", "items"] {
+        if let Some(child) = schema.get_mut(keyword) {
+            relax_volatile(child, &format!("{}/*", path));
+        }
+    }
+    if let Some(Value::Array(required)) = schema.get_mut("required") {
+        required.retain(|name| !name.as_str().is_some_and(|name| deterministic::is_volatile(&format!("{}/{}", path, name))));
+    }
+}
+
+/// An `errors::Error`.
+fn error_object() -> Value {
+    derived::<errors::Error>()
+}
+
+/// The schema of `T`, with any it refers to inlined, to embed in the document's.
+fn derived<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2020_12().with(|settings| settings.inline_subschemas = true).into_generator();
+    let mut schema = generator.into_root_schema_for::<T>().to_value();
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+        schema.remove("title");
+    }
+    schema
+}
+
+/// Rewrites the error fields of a section schema from their version 1 shape, the
+/// reverse of `errors::messages_in_section`: string (or nullable string) `error`,
+/// `*_error` and `error_*` properties and the items of `errors` lists and maps
+/// become error objects.
+fn structure_errors(schema: &mut Value) {
+    let error = error_object();
+    let Some(schema) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        for (name, property) in properties.iter_mut() {
+            if name == "errors" {
+                for keyword in ["items", "additionalProperties"] {
+                    if property.get(keyword) == Some(&string()) {
+                        property[keyword] = error.clone();
+                    }
+                }
+            } else if errors::is_error_key(name) && (*property == string() || *property == nullable("string")) {
+                let mut replacement = error.clone();
+                if *property == nullable("string") {
+                    replacement["type"] = json!(["object", "null"]);
+                }
+                *property = replacement;
+            } else {
+                structure_errors(property);
+            }
+        }
+    }
+    for keyword in ["additionalProperties", "items"] {
+        if let Some(child) = schema.get_mut(keyword) {
+            structure_errors(child);
+        }
+    }
+}
+
+fn section(name: &str) -> Value {
+    let unavailable = [("available", boolean()), ("error", nullable("string"))];
+    let media_library = object(&[("title", nullable("string")), ("type", nullable("string")), ("items", nullable("integer"))]);
+    match name {
+        "ip" => object(&[
+            ("public_ip", string()),
+            ("public_ipv6", string()),
+            ("enabled_ipv4", boolean()),
+            ("enabled_ipv6", boolean()),
+            ("failed_ipv4", boolean()),
+            ("failed_ipv6", boolean()),
+            ("error_ipv4", nullable("string")),
+            ("error_ipv6", nullable("string")),
+            ("ipv6_check_error", nullable("string")),
+            ("source_ipv4", nullable("string")),
+            ("source_ipv6", nullable("string")),
+            ("asn_ipv4", nullable("integer")),
+            ("asn_ipv6", nullable("integer")),
+        ]),
+        "connectivity" => {
+            let family = object(&[
+                ("verdict", string()),
+                ("target", nullable("string")),
+                ("latency_ms", nullable("integer")),
+                ("errors", array_of(string())),
+            ]);
+            object(&[("verdict", string()), ("ipv4", family.clone()), ("ipv6", family)])
+        }
+        "region" => object(&[
+            ("hint", nullable("string")),
+            ("latency_ms", map_of(nullable("integer"))),
+            ("errors", array_of(string())),
+        ]),
+        "groups" => map_of(derived::<Group>()),
+        "users" => map_of(derived::<User>()),
+        "timezone" => object(&[("timezone", string())]),
+        "mounts" => map_of(json!({
+            "type": "object",
+            "properties": {
+                "device": string(),
+                "fstype": string(),
+                "options": array_of(string()),
+                "size_gb": { "type": "number" },
+                "used_gb": { "type": "number" },
+                "available_gb": { "type": "number" },
+                "used_percent": { "type": "number" },
+                "error": nullable("string")
+            },
+            // Usage is missing when statvfs fails, with the error set instead.
+            "required": ["device", "fstype", "options", "error"]
+        })),
+        "swap" => object(&[
+            ("error", nullable("string")),
+            ("total_bytes", nullable("integer")),
+            ("used_bytes", nullable("integer")),
+            ("swappiness", nullable("integer")),
+            ("zram_only", boolean()),
+            (
+                "devices",
+                map_of(object(&[
+                    ("type", string()),
+                    ("size_bytes", nullable("integer")),
+                    ("used_bytes", nullable("integer")),
+                    ("priority", nullable("integer")),
+                    ("zram", boolean()),
+                ])),
+            ),
+            (
+                "zram",
+                map_of(object(&[
+                    ("disksize_bytes", nullable("integer")),
+                    ("algorithm", nullable("string")),
+                    ("orig_data_bytes", nullable("integer")),
+                    ("compr_data_bytes", nullable("integer")),
+                    ("mem_used_bytes", nullable("integer")),
+                    ("swap", boolean()),
+                ])),
+            ),
+            ("zram_generator_config", nullable("string")),
+        ]),
+        "systemd_mounts" => object(&[
+            ("error", nullable("string")),
+            (
+                "units",
+                map_of(object(&[
+                    ("kind", json!({"enum": ["mount", "automount"]})),
+                    ("load_state", nullable("string")),
+                    ("active_state", nullable("string")),
+                    ("sub_state", nullable("string")),
+                    ("unit_file_state", nullable("string")),
+                    ("origin", json!({"enum": ["fstab", "generator", "unit_file", "runtime"]})),
+                    ("fragment_path", nullable("string")),
+                    ("source_path", nullable("string")),
+                    ("where", nullable("string")),
+                    ("what", nullable("string")),
+                    ("type", nullable("string")),
+                    ("options", nullable("string")),
+                    ("idle_timeout_secs", nullable("integer")),
+                    ("triggered_by", array_of(string())),
+                    ("triggers", array_of(string())),
+                ])),
+            ),
+        ]),
+        "network_managers" => object(&[
+            (
+                "managers",
+                object(&[
+                    ("network_manager", object(&[("installed", boolean()), ("running", boolean()), ("error", nullable("string"))])),
+                    ("networkd", object(&[("running", boolean())])),
+                    ("ifupdown", object(&[("installed", boolean()), ("configured", boolean()), ("errors", array_of(string()))])),
+                ]),
+            ),
+            (
+                "interfaces",
+                map_of(object(&[
+                    ("managed_by", array_of(json!({ "enum": ["network_manager", "networkd", "ifupdown"] }))),
+                    ("network_manager", nullable_object(&[("type", string()), ("state", string()), ("connection", nullable("string"))])),
+                    ("networkd", nullable_object(&[("admin_state", nullable("string")), ("oper_state", nullable("string")), ("network_file", nullable("string"))])),
+                    (
+                        "ifupdown",
+                        nullable_object(&[("methods", array_of(string())), ("auto", boolean()), ("hotplug", boolean()), ("up", boolean()), ("file", string())]),
+                    ),
+                ])),
+            ),
+            ("conflicts", array_of(string())),
+        ]),
+        "netplan" => object(&[
+            ("files", array_of(string())),
+            ("errors", array_of(string())),
+            ("renderer", nullable("string")),
+            (
+                "interfaces",
+                map_of(object(&[
+                    ("kind", string()),
+                    ("name", nullable("string")),
+                    ("renderer", nullable("string")),
+                    ("dhcp4", boolean()),
+                    ("dhcp6", boolean()),
+                    ("addresses", array_of(string())),
+                    ("gateway4", nullable("string")),
+                    ("gateway6", nullable("string")),
+                    ("routes", array_of(object(&[("to", nullable("string")), ("via", nullable("string")), ("metric", nullable("integer"))]))),
+                    ("nameservers", object(&[("addresses", array_of(string())), ("search", array_of(string()))])),
+                    ("present", nullable("boolean")),
+                    ("actual_addresses", json!({ "type": ["array", "null"], "items": string() })),
+                ])),
+            ),
+            (
+                "drift",
+                array_of(object(&[
+                    ("interface", string()),
+                    ("kind", json!({ "enum": ["missing_interface", "missing_address", "unexpected_address"] })),
+                    ("address", nullable("string")),
+                ])),
+            ),
+        ]),
+        "dhcp_leases" => object(&[
+            ("errors", array_of(string())),
+            (
+                "leases",
+                array_of(object(&[
+                    ("interface", nullable("string")),
+                    ("client", json!({ "enum": ["networkd", "network_manager", "dhclient"] })),
+                    ("file", string()),
+                    ("address", nullable("string")),
+                    ("prefix_length", nullable("integer")),
+                    ("server", nullable("string")),
+                    ("routers", array_of(string())),
+                    ("dns", array_of(string())),
+                    ("domain", nullable("string")),
+                    ("lease_time_secs", nullable("integer")),
+                    ("obtained_at", nullable("string")),
+                    ("renew_at", nullable("string")),
+                    ("expires_at", nullable("string")),
+                    ("expires_in_secs", nullable("integer")),
+                    ("expired", nullable("boolean")),
+                ])),
+            ),
+        ]),
+        "wake_on_lan" => object(&[
+            ("ethtool_installed", boolean()),
+            (
+                "interfaces",
+                map_of(object(&[
+                    ("supported_flags", nullable("string")),
+                    ("flags", nullable("string")),
+                    ("supported", array_of(string())),
+                    ("enabled", array_of(string())),
+                    ("magic_packet", nullable("boolean")),
+                    ("device_wakeup", nullable("string")),
+                    ("error", nullable("string")),
+                ])),
+            ),
+            ("armed", array_of(string())),
+        ]),
+        "dbus" => object(&[
+            ("address", string()),
+            ("connected", boolean()),
+            ("error", nullable("string")),
+            ("services", map_of(object(&[("name", string()), ("running", boolean()), ("activatable", boolean()), ("available", boolean())]))),
+        ]),
+        "rtc" => object(&[
+            ("available", boolean()),
+            ("device", string()),
+            ("name", string()),
+            ("local_rtc", boolean()),
+            ("mismatch", boolean()),
+            ("source", string()),
+            ("adjtime_mode", nullable("string")),
+            ("timedatectl_local_rtc", nullable("boolean")),
+            ("error", nullable("string")),
+        ]),
+        "clocksource" => object(&[
+            ("current", string()),
+            ("available", array_of(string())),
+            ("known_bad", boolean()),
+            ("reason", nullable("string")),
+            ("error", nullable("string")),
+        ]),
+        "locales" => object(&[
+            ("available", array_of(string())),
+            ("has_en_us_utf8", boolean()),
+            ("error", nullable("string")),
+        ]),
+        "environment" => map_of(string()),
+        "binaries" => map_of(object(&[("present", boolean()), ("path", string()), ("resolved", string())])),
+        "tool_versions" => map_of(object(&[
+            ("raw", string()),
+            ("version", nullable("string")),
+            ("major", nullable("integer")),
+            ("minor", nullable("integer")),
+            ("patch", nullable("integer")),
+            ("error", nullable("string")),
+        ])),
+        "docker_daemon" => object(&[
+            ("config_file", string()),
+            ("file", json!({ "type": ["object", "null"] })),
+            ("error", nullable("string")),
+            ("settings", map_of(json!({}))),
+            ("drop_ins", array_of(string())),
+            ("exec_start", nullable("string")),
+            ("conflicts", array_of(string())),
+        ]),
+        "docker_images" => with(
+            &unavailable,
+            &[
+                (
+                    "images",
+                    map_of(object(&[
+                        ("tags", array_of(string())),
+                        ("digests", array_of(string())),
+                        ("size_mb", nullable("number")),
+                        ("created", nullable("string")),
+                        ("dangling", boolean()),
+                    ])),
+                ),
+                (
+                    "updates",
+                    map_of(object(&[
+                        ("image", string()),
+                        ("update_available", nullable("boolean")),
+                        ("local_digest", nullable("string")),
+                        ("remote_digest", nullable("string")),
+                        ("error", nullable("string")),
+                    ])),
+                ),
+                ("updates_error", nullable("string")),
+            ],
+        ),
+        "docker_networks" => with(
+            &unavailable,
+            &[
+                ("networks", map_of(object(&[("driver", string()), ("subnets", array_of(string()))]))),
+                ("host_subnets", array_of(object(&[("subnet", string()), ("source", string())]))),
+                (
+                    "conflicts",
+                    array_of(object(&[
+                        ("network", string()),
+                        ("subnet", string()),
+                        ("conflicts_with", string()),
+                        ("source", string()),
+                    ])),
+                ),
+            ],
+        ),
+        "docker_stats" => with(
+            &unavailable,
+            &[(
+                "containers",
+                map_of(json!({
+                    "type": "object",
+                    "properties": {
+                        "cpu_percent": nullable("number"),
+                        "memory_usage_mb": nullable("number"),
+                        "memory_limit_mb": nullable("number"),
+                        "memory_percent": nullable("number"),
+                        "block_read_mb": { "type": "number" },
+                        "block_write_mb": { "type": "number" },
+                        "pids": nullable("integer"),
+                        "error": nullable("string")
+                    },
+                    // A container whose sample failed has only the error.
+                    "required": ["error"]
+                })),
+            )],
+        ),
+        "container_restarts" => with(
+            &unavailable,
+            &[
+                ("window_minutes", integer()),
+                ("threshold", integer()),
+                (
+                    "restarts",
+                    map_of(object(&[
+                        ("restarts", integer()),
+                        ("last_exit_code", nullable("integer")),
+                        ("image", nullable("string")),
+                    ])),
+                ),
+                ("flapping_containers", array_of(string())),
+            ],
+        ),
+        "databases" => {
+            let server = |extra: &[(&str, Value)], instance: &[(&str, Value)]| {
+                with(extra, &[("installed", boolean()), ("instances", array_of(object(instance)))])
+            };
+            let mariadb = server(
+                &[("config_files", array_of(string()))],
+                &[
+                    ("flavor", json!({ "enum": ["mariadb", "mysql", null] })),
+                    ("version", nullable("string")),
+                    ("data_directory", string()),
+                    ("port", integer()),
+                    ("bind_address", nullable("string")),
+                    ("socket", nullable("string")),
+                    ("running", boolean()),
+                    ("pid", nullable("integer")),
+                ],
+            );
+            object(&[
+                (
+                    "postgresql",
+                    server(
+                        &[],
+                        &[
+                            ("cluster", nullable("string")),
+                            ("version", nullable("string")),
+                            ("data_directory", string()),
+                            ("config_file", nullable("string")),
+                            ("port", integer()),
+                            ("listen_addresses", string()),
+                            ("socket", nullable("string")),
+                            ("running", boolean()),
+                            ("pid", nullable("integer")),
+                        ],
+                    ),
+                ),
+                ("mariadb", mariadb),
+                (
+                    "redis",
+                    server(
+                        &[],
+                        &[
+                            ("config_file", nullable("string")),
+                            ("config_readable", boolean()),
+                            ("version", nullable("string")),
+                            ("data_directory", nullable("string")),
+                            ("port", nullable("integer")),
+                            ("bind", nullable("string")),
+                            ("socket", nullable("string")),
+                            ("running", boolean()),
+                            ("pid", nullable("integer")),
+                        ],
+                    ),
+                ),
+            ])
+        }
+        "shares" => {
+            let files = array_of(object(&[("path", string()), ("ansible_managed", boolean())]));
+            object(&[
+                (
+                    "samba",
+                    object(&[
+                        ("installed", boolean()),
+                        ("files", files.clone()),
+                        ("errors", array_of(string())),
+                        ("global", map_of(string())),
+                        (
+                            "shares",
+                            map_of(object(&[
+                                ("file", string()),
+                                ("path", nullable("string")),
+                                ("comment", nullable("string")),
+                                ("read_only", boolean()),
+                                ("browseable", boolean()),
+                                ("guest_ok", boolean()),
+                                ("valid_users", array_of(string())),
+                                ("write_list", array_of(string())),
+                                ("options", map_of(string())),
+                            ])),
+                        ),
+                    ]),
+                ),
+                (
+                    "nfs",
+                    object(&[
+                        ("installed", boolean()),
+                        ("files", files),
+                        ("errors", array_of(string())),
+                        (
+                            "exports",
+                            array_of(object(&[
+                                ("path", string()),
+                                ("file", string()),
+                                ("clients", array_of(object(&[("client", string()), ("options", array_of(string()))]))),
+                            ])),
+                        ),
+                    ]),
+                ),
+            ])
+        }
+        "listen_ports" => object(&[
+            ("errors", array_of(string())),
+            (
+                "ports",
+                array_of(object(&[
+                    ("protocol", json!({"enum": ["tcp", "udp"]})),
+                    ("address", string()),
+                    ("port", integer()),
+                    ("pid", nullable("integer")),
+                    ("process", nullable("string")),
+                    ("container", nullable("string")),
+                    ("mapped_by", json!({"enum": ["cgroup", "published", null]})),
+                ])),
+            ),
+        ]),
+        "processes" => object(&[
+            ("error", nullable("string")),
+            ("sample_ms", integer()),
+            ("cpu_threshold_percent", integer()),
+            ("memory_threshold_bytes", integer()),
+            ("scanned", integer()),
+            (
+                "processes",
+                array_of(object(&[
+                    ("pid", integer()),
+                    ("name", string()),
+                    ("uid", nullable("integer")),
+                    ("user", nullable("string")),
+                    ("cgroup", nullable("string")),
+                    ("container", nullable("string")),
+                    ("rss_bytes", integer()),
+                    ("memory_percent", nullable("number")),
+                    ("cpu_percent", json!({"type": "number"})),
+                ])),
+            ),
+        ]),
+        "coredumps" => object(&[
+            ("core_pattern", nullable("string")),
+            ("handler", json!({ "enum": ["systemd-coredump", "apport", "pipe", "file", null] })),
+            ("errors", map_of(string())),
+            ("window_days", integer()),
+            ("threshold", integer()),
+            ("count", integer()),
+            ("binaries", map_of(object(&[("crashes", integer()), ("last_crash", string())]))),
+            ("frequent_crashers", array_of(string())),
+            (
+                "recent",
+                array_of(object(&[
+                    ("binary", string()),
+                    ("uid", nullable("integer")),
+                    ("pid", nullable("integer")),
+                    ("time", string()),
+                    ("size_bytes", integer()),
+                    ("source", json!({ "enum": ["systemd-coredump", "apport"] })),
+                ])),
+            ),
+        ]),
+        "gpu_container" => object(&[
+            ("gpu_container_ready", boolean()),
+            ("reasons", array_of(string())),
+            ("driver_version", nullable("string")),
+            ("toolkit_binaries", array_of(string())),
+            ("toolkit_config", boolean()),
+            ("cdi_specs", array_of(string())),
+            ("docker_runtimes", array_of(string())),
+            ("docker_runtimes_source", string()),
+            ("nvidia_runtime", boolean()),
+        ]),
+        "traefik" => with(
+            &unavailable,
+            &[
+                ("routers", map_of(json!({ "type": "object" }))),
+                ("services", map_of(json!({ "type": "object" }))),
+                ("middlewares", array_of(string())),
+                (
+                    "domains",
+                    map_of(object(&[("routed", boolean()), ("routes", array_of(json!({ "type": "object" })))])),
+                ),
+                (
+                    "acme",
+                    object(&[("path", string()), ("error", nullable("string")), ("resolvers", map_of(json!({ "type": "object" })))]),
+                ),
+            ],
+        ),
+        "sso" => object(&[
+            ("provider", json!({ "enum": ["authelia", "authentik", null] })),
+            (
+                "containers",
+                array_of(object(&[("name", string()), ("image", string()), ("provider", string()), ("state", nullable("string"))])),
+            ),
+            ("authelia_config", string()),
+            ("domains", array_of(string())),
+            (
+                "middlewares",
+                array_of(object(&[
+                    ("name", nullable("string")),
+                    ("provider", string()),
+                    ("address", string()),
+                    ("status", nullable("string")),
+                ])),
+            ),
+            ("docker_error", nullable("string")),
+            ("traefik_error", nullable("string")),
+            ("config_error", nullable("string")),
+        ]),
+        "cloudflared" => {
+            let ingress = array_of(object(&[("hostname", nullable("string")), ("service", nullable("string")), ("path", nullable("string"))]));
+            object(&[
+                ("installed", boolean()),
+                ("binary", nullable("string")),
+                ("containers", array_of(object(&[("name", string()), ("image", nullable("string")), ("state", nullable("string"))]))),
+                ("docker_error", nullable("string")),
+                (
+                    "tunnels",
+                    array_of(json!({
+                        "type": "object",
+                        "properties": {
+                            "config_file": string(),
+                            "tunnel": nullable("string"),
+                            "credentials_file": nullable("string"),
+                            "metrics": nullable("string"),
+                            "ingress": ingress.clone(),
+                            "error": nullable("string")
+                        },
+                        // A file that can't be read or parsed has only the error.
+                        "required": ["config_file", "error"]
+                    })),
+                ),
+                (
+                    "metrics",
+                    map_of(object(&[
+                        ("reachable", boolean()),
+                        ("ha_connections", nullable("integer")),
+                        ("remote_ingress", ingress),
+                        ("error", nullable("string")),
+                    ])),
+                ),
+                ("hostnames", array_of(string())),
+            ])
+        }
+        "vpn_gateways" => object(&[
+            ("docker_error", nullable("string")),
+            (
+                "gateways",
+                map_of(object(&[
+                    ("image", string()),
+                    ("state", nullable("string")),
+                    ("control_api", nullable("string")),
+                    ("api_error", nullable("string")),
+                    ("public_ip", nullable("string")),
+                    ("country", nullable("string")),
+                    ("region", nullable("string")),
+                    ("vpn_status", nullable("string")),
+                    ("forwarded_port", nullable("integer")),
+                    ("routed_containers", array_of(string())),
+                ])),
+            ),
+            ("routed_containers", map_of(string())),
+        ]),
+        "wireguard" => object(&[
+            (
+                "interfaces",
+                map_of(object(&[
+                    ("source", json!({ "enum": ["netlink", "uapi"] })),
+                    ("up", nullable("boolean")),
+                    ("public_key", nullable("string")),
+                    ("listen_port", nullable("integer")),
+                    ("error", nullable("string")),
+                    (
+                        "peers",
+                        map_of(object(&[
+                            ("endpoint", nullable("string")),
+                            ("allowed_ips", array_of(string())),
+                            ("last_handshake", nullable("string")),
+                            ("handshake_age_secs", nullable("integer")),
+                            ("stale", boolean()),
+                            ("persistent_keepalive", nullable("integer")),
+                            ("rx_bytes", integer()),
+                            ("tx_bytes", integer()),
+                        ])),
+                    ),
+                ])),
+            ),
+            ("stale_after_secs", integer()),
+            ("stale_peers", array_of(string())),
+        ]),
+        "log_growth" => object(&[
+            ("previous_run", nullable("string")),
+            ("interval_secs", nullable("integer")),
+            (
+                "paths",
+                map_of(object(&[
+                    ("exists", boolean()),
+                    ("error", nullable("string")),
+                    ("complete", boolean()),
+                    ("files", integer()),
+                    ("size_bytes", integer()),
+                    ("growth_bytes", nullable("integer")),
+                    ("bytes_per_hour", nullable("integer")),
+                    ("fastest_growing", map_of(integer())),
+                ])),
+            ),
+        ]),
+        "plex" => with(
+            &unavailable,
+            &[
+                ("url", string()),
+                ("version", nullable("string")),
+                ("claimed", nullable("boolean")),
+                ("machine_identifier", nullable("string")),
+                ("transcoder", json!({ "type": ["object", "null"] })),
+                ("libraries", json!({ "type": ["array", "null"], "items": media_library.clone() })),
+                ("auth_error", nullable("string")),
+            ],
+        ),
+        "jellyfin" | "emby" => with(
+            &unavailable,
+            &[
+                ("url", string()),
+                ("product", nullable("string")),
+                ("server_name", nullable("string")),
+                ("server_id", nullable("string")),
+                ("version", nullable("string")),
+                ("setup_complete", nullable("boolean")),
+                ("transcoder", json!({ "type": ["object", "null"] })),
+                ("libraries", json!({ "type": ["array", "null"], "items": media_library })),
+                ("auth_error", nullable("string")),
+            ],
+        ),
+        "download_clients" => object(&[
+            ("forwarded_port", nullable("integer")),
+            ("forwarded_port_source", nullable("string")),
+            ("forwarded_port_error", nullable("string")),
+            (
+                "clients",
+                array_of(object(&[
+                    ("kind", json!({ "enum": ["qbittorrent", "transmission"] })),
+                    ("url", string()),
+                    ("available", boolean()),
+                    ("error", nullable("string")),
+                    ("listen_port", nullable("integer")),
+                    ("connection_status", nullable("string")),
+                    ("port_forward_ok", nullable("boolean")),
+                ])),
+            ),
+            ("port_forward_ok", nullable("boolean")),
+        ]),
+        "ownership" => object(&[
+            ("expected_user", nullable("string")),
+            (
+                "roots",
+                map_of(object(&[
+                    ("error", nullable("string")),
+                    (
+                        "apps",
+                        map_of(object(&[
+                            ("owner", string()),
+                            ("group", string()),
+                            ("expected_owner", string()),
+                            ("expected_group", string()),
+                            ("entries", integer()),
+                            ("mismatched_owner", integer()),
+                            ("mismatched_group", integer()),
+                            ("unexpected_owners", map_of(integer())),
+                            ("samples", array_of(string())),
+                        ])),
+                    ),
+                    ("entries", integer()),
+                    ("complete", boolean()),
+                    ("mismatched_owner", integer()),
+                ])),
+            ),
+        ]),
+        "dir_sizes" => object(&[
+            ("budget_secs", integer()),
+            (
+                "directories",
+                map_of(object(&[
+                    ("exists", boolean()),
+                    ("error", nullable("string")),
+                    ("complete", boolean()),
+                    ("size_bytes", integer()),
+                    ("files", integer()),
+                    ("elapsed_ms", integer()),
+                    ("largest", map_of(integer())),
+                ])),
+            ),
+        ]),
+        "ansible_controller" => object(&[
+            ("is_controller", boolean()),
+            ("ansible_path", string()),
+            ("version", nullable("string")),
+            ("config_file", string()),
+            ("inventory_files", array_of(string())),
+            ("saltbox_venv", boolean()),
+            ("error", nullable("string")),
+        ]),
+        "host_fingerprint" => object(&[
+            ("fingerprint", nullable("string")),
+            ("sources", array_of(string())),
+            ("error", nullable("string")),
+        ]),
+        // A collector added without a shape here is still listed, unconstrained.
+        _ => json!({ "type": "object" }),
+    }
+}
+
+/// An object with exactly these keys, all required.
+fn object(properties: &[(&str, Value)]) -> Value {
+    with(&[], properties)
+}
+
+fn with(common: &[(&str, Value)], properties: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = common
+        .iter()
+        .chain(properties)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let required: Vec<&String> = properties.keys().collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn map_of(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+/// An object whose properties are all required, or null in its place.
+fn nullable_object(properties: &[(&str, Value)]) -> Value {
+    let mut schema = object(properties);
+    schema["type"] = json!(["object", "null"]);
+    schema
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
@@ -0,0 +1,210 @@
+use reqwest::header::{CONTENT_TYPE, COOKIE, REFERER, SET_COOKIE};
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Duration;
+
+use crate::errors::{Code, Error};
+use crate::http::Client;
+
+pub const DEFAULT_GLUETUN_API: &str = "http://127.0.0.1:8000";
+
+const API_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Qbittorrent,
+    Transmission,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Qbittorrent => "qbittorrent",
+            Kind::Transmission => "transmission",
+        }
+    }
+}
+
+/// A download client given with `--download-client`.
+#[derive(Clone, Debug)]
+pub struct DownloadClient {
+    pub kind: Kind,
+    pub url: Url,
+}
+
+/// Parses `qbittorrent=URL` or `transmission=URL`; credentials go in the URL.
+pub fn parse_client(source: &str) -> Result<DownloadClient, String> {
+    let invalid = || format!("Invalid download client {:?}: expected qbittorrent=URL or transmission=URL", source);
+    let (kind, url) = source.split_once('=').ok_or_else(invalid)?;
+    let kind = match kind {
+        "qbittorrent" => Kind::Qbittorrent,
+        "transmission" => Kind::Transmission,
+        _ => return Err(invalid()),
+    };
+    let url = Url::parse(url).map_err(|e| format!("Invalid download client URL {}: {}", url, e))?;
+    Ok(DownloadClient { kind, url })
+}
+
+/// The port the VPN forwards (from `forwarded_port_file`, else gluetun's control
+/// server), each client's listening port, and whether they match.
+///
+/// `port_forward_ok` is true when every reachable client listens on the forwarded
+/// port, false when one doesn't, and null when either side is unknown.
+pub async fn get_download_clients(client: &Client, clients: &[DownloadClient], gluetun_api: &str, forwarded_port_file: Option<&str>) -> Value {
+    let (forwarded_port, source, forwarded_port_error) = match forwarded_port_file {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(port) => match port.trim().parse::<u16>() {
+                Ok(port) => (Some(port), Some(path.to_string()), None),
+                Err(_) => (None, Some(path.to_string()), Some(Error::new(Code::InvalidData, path, format!("{} doesn't hold a port number", path)))),
+            },
+            Err(e) => (None, Some(path.to_string()), Some(Error::read(path, &e))),
+        },
+        None => match gluetun_forwarded_port(client, gluetun_api.trim_end_matches('/')).await {
+            Ok(port) => (port, Some("gluetun".to_string()), None),
+            Err(e) => (None, Some("gluetun".to_string()), Some(e)),
+        },
+    };
+
+    let mut reports = Vec::new();
+    for download_client in clients {
+        let listen_port = match download_client.kind {
+            Kind::Qbittorrent => qbittorrent_port(client, &download_client.url).await,
+            Kind::Transmission => transmission_port(client, &download_client.url).await,
+        };
+        let (listen_port, connection_status, error) = match listen_port {
+            Ok((port, status)) => (port, status, None),
+            Err(e) => (None, None, Some(e)),
+        };
+        let port_forward_ok = forwarded_port.zip(listen_port).map(|(forwarded, listening)| forwarded == listening);
+        reports.push(json!({
+            "kind": download_client.kind.name(),
+            "url": redacted(&download_client.url),
+            "available": error.is_none(),
+            "error": error,
+            "listen_port": listen_port,
+            "connection_status": connection_status,
+            "port_forward_ok": port_forward_ok
+        }));
+    }
+    let verdicts: Vec<bool> = reports.iter().filter_map(|report| report["port_forward_ok"].as_bool()).collect();
+    let port_forward_ok = (!verdicts.is_empty()).then(|| verdicts.iter().all(|ok| *ok));
+
+    json!({
+        "forwarded_port": forwarded_port,
+        "forwarded_port_source": source,
+        "forwarded_port_error": forwarded_port_error,
+        "clients": reports,
+        "port_forward_ok": port_forward_ok
+    })
+}
+
+/// gluetun 3.40+ serves `/v1/portforward`; older versions only the OpenVPN one.
+/// Port 0 means forwarding is off or not yet negotiated.
+pub async fn gluetun_forwarded_port(client: &Client, api: &str) -> Result<Option<u16>, Error> {
+    let mut result = Ok(None);
+    for path in ["/v1/portforward", "/v1/openvpn/portforwarded"] {
+        let url = format!("{}{}", api, path);
+        // A 404 is an older (or newer) gluetun; anything else is the answer.
+        match json_body(client.get(&url)?, &url).await {
+            Ok(body) => {
+                let port = body.get("port").and_then(Value::as_u64).and_then(|port| u16::try_from(port).ok());
+                return Ok(port.filter(|port| *port != 0));
+            }
+            Err(e) if e.code == Code::NotFound => result = Err(e),
+            Err(e) => return Err(e),
+        }
+    }
+    result
+}
+
+/// Logs in when the URL has credentials (qBittorrent lets localhost skip this when
+/// "Bypass authentication for clients on localhost" is set), then reads the
+/// preferences' `listen_port` and the transfer info's `connection_status`.
+async fn qbittorrent_port(client: &Client, url: &Url) -> Result<(Option<u16>, Option<String>), Error> {
+    let base = redacted(url);
+    let base = base.trim_end_matches('/');
+    let mut cookie = None;
+    if !url.username().is_empty() {
+        let login = format!("{}/api/v2/auth/login", base);
+        let form = format!("username={}&password={}", url.username(), url.password().unwrap_or_default());
+        // qBittorrent rejects a login whose Referer doesn't match its own origin.
+        let request = client.post(&login)?.header(REFERER, base).header(CONTENT_TYPE, "application/x-www-form-urlencoded").body(form);
+        let response = send(request, &login).await?;
+        cookie = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| value.split(';').next().filter(|pair| pair.starts_with("SID=")).map(String::from));
+        if cookie.is_none() {
+            return Err(Error::new(Code::Unauthorized, &login, format!("{}: login rejected", login)));
+        }
+    }
+    let get = |path: &str| {
+        let request = client.get(&format!("{}{}", base, path))?;
+        Ok::<_, Error>(match &cookie {
+            Some(cookie) => request.header(COOKIE, cookie),
+            None => request,
+        })
+    };
+    let preferences = json_body(get("/api/v2/app/preferences")?, &format!("{}/api/v2/app/preferences", base)).await?;
+    let port = preferences.get("listen_port").and_then(Value::as_u64).and_then(|port| u16::try_from(port).ok());
+    let status = json_body(get("/api/v2/transfer/info")?, &format!("{}/api/v2/transfer/info", base))
+        .await
+        .ok()
+        .and_then(|info| info.get("connection_status").and_then(Value::as_str).map(String::from));
+    Ok((port, status))
+}
+
+/// `session-get` over Transmission's RPC, answering its 409 with the session id it
+/// hands out. Basic auth is taken from the URL.
+async fn transmission_port(client: &Client, url: &Url) -> Result<(Option<u16>, Option<String>), Error> {
+    let mut rpc = url.clone();
+    if rpc.path() == "/" {
+        rpc.set_path("/transmission/rpc");
+    }
+    let body = json!({ "method": "session-get", "arguments": { "fields": ["peer-port"] } }).to_string();
+    let mut session_id = None;
+    for _ in 0..2 {
+        let mut request = client.post(rpc.as_str())?.header(CONTENT_TYPE, "application/json").body(body.clone());
+        if let Some(session_id) = &session_id {
+            request = request.header("X-Transmission-Session-Id", session_id);
+        }
+        let response = send(request, &redacted(&rpc)).await?;
+        if response.status() == StatusCode::CONFLICT {
+            session_id = response.headers().get("X-Transmission-Session-Id").cloned();
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(Error::status(redacted(&rpc), response.status()));
+        }
+        let body = response.bytes().await.map_err(|e| Error::http(redacted(&rpc), &e))?;
+        let body: Value = serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, redacted(&rpc), format!("{}: {}", redacted(&rpc), e)))?;
+        let port = body.pointer("/arguments/peer-port").and_then(Value::as_u64).and_then(|port| u16::try_from(port).ok());
+        return Ok((port, None));
+    }
+    Err(Error::new(Code::Unauthorized, redacted(&rpc), format!("{}: no session id accepted", redacted(&rpc))))
+}
+
+async fn json_body(request: RequestBuilder, url: &str) -> Result<Value, Error> {
+    let response = send(request, url).await?;
+    if !response.status().is_success() {
+        return Err(Error::status(url, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| Error::http(url, &e))?;
+    serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: {}", url, e)))
+}
+
+/// Sends `request`; `url` is how it's named in errors, without credentials.
+async fn send(request: RequestBuilder, url: &str) -> Result<Response, Error> {
+    request.timeout(API_TIMEOUT).send().await.map_err(|e| Error::http(url, &e))
+}
+
+/// The URL as reported: without the credentials it may carry.
+fn redacted(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.to_string()
+}
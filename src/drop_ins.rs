@@ -0,0 +1,78 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::static_facts::load_file;
+
+pub const DEFAULT_DROP_IN_DIR: &str = "/etc/ansible-facts/facts.d";
+/// Drop-ins are meant for small bits of local data, not for shipping inventories.
+const MAX_DROP_IN_BYTES: u64 = 1024 * 1024;
+
+/// Merges `<dir>/*.json|*.yaml|*.yml` into `document` under each file's stem, and
+/// returns the `drop_ins` report of what was loaded.
+///
+/// A drop-in is rejected (and reported under `errors`, keyed by file name)
+/// if it doesn't parse, isn't a mapping, is too large, has a stem that isn't a plain
+/// identifier, or would shadow a collected section.
+pub fn apply(document: &mut Value, dir: &Path) -> Value {
+    let mut loaded = Vec::new();
+    let mut errors = Map::new();
+
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            errors.insert(dir.display().to_string(), json!(format!("Cannot read directory: {}", e)));
+            Vec::new()
+        }
+    };
+    paths.sort();
+
+    for path in paths {
+        let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        if !matches!(extension, "json" | "yaml" | "yml") {
+            continue;
+        }
+        let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+
+        match load_drop_in(&path, &stem, document) {
+            Ok(value) => {
+                tracing::debug!("Merged drop-in {} as {}", path.display(), stem);
+                document[stem.as_str()] = value;
+                loaded.push(stem);
+            }
+            Err(e) => {
+                tracing::warn!("Rejected drop-in {}: {}", path.display(), e);
+                errors.insert(file_name, json!(e));
+            }
+        }
+    }
+
+    json!({
+        "directory": dir.display().to_string(),
+        "loaded": loaded,
+        "errors": errors
+    })
+}
+
+fn load_drop_in(path: &Path, stem: &str, document: &Value) -> Result<Value, String> {
+    if stem.is_empty() || !stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("Invalid name {:?}: use letters, digits, '_' and '-' only", stem));
+    }
+    if document.get(stem).is_some() || stem == "drop_ins" {
+        return Err(format!("{} would shadow an existing section", stem));
+    }
+    let size = fs::metadata(path).map_err(|e| format!("Cannot stat file: {}", e))?.len();
+    if size > MAX_DROP_IN_BYTES {
+        return Err(format!("File is {} bytes, exceeding the {} byte limit", size, MAX_DROP_IN_BYTES));
+    }
+
+    let value = load_file(path)?;
+    if !value.is_object() {
+        return Err("Top level must be a mapping".to_string());
+    }
+    Ok(value)
+}
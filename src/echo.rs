@@ -0,0 +1,129 @@
+//! Authenticated echo protocol for self-hosted public IP endpoints.
+//!
+//! Request: `GET <url>` with `Accept: application/json` and, when a token is
+//! configured, `Authorization: Bearer <token>`.
+//!
+//! Response: `200 OK` with a JSON object
+//!
+//! ```json
+//! {"ip": "203.0.113.7", "asn": 64496, "timestamp": 1719835200}
+//! ```
+//!
+//! `ip` is the client address as seen by the server, `asn` the origin AS number
+//! (optional, may be null) and `timestamp` the unix time the server answered.
+//! Answers whose timestamp is more than `MAX_CLOCK_SKEW_SECS` away from the local
+//! clock are rejected, so a replayed or cached response isn't taken as current.
+
+use reqwest::header::ACCEPT;
+use reqwest::Url;
+use serde_json::Value;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::errors::{Code, Error};
+use crate::http::Client;
+use crate::timestamp::now_secs;
+
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+const MAX_RESPONSE_BYTES: usize = 4096;
+
+pub struct EchoEndpoint {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+pub struct EchoAnswer {
+    pub ip: String,
+    pub asn: Option<u32>,
+}
+
+/// Checks an `--echo-url-*` value; plain HTTP is only accepted for loopback, where
+/// the token can't be sniffed off the network.
+pub fn parse_url(source: &str) -> Result<String, String> {
+    let url = Url::parse(source).map_err(|e| format!("Invalid echo URL {:?}: {}", source, e))?;
+    let host = url.host_str().ok_or_else(|| format!("Invalid echo URL {:?}: missing host", source))?;
+    let loopback = host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    match url.scheme() {
+        "https" => Ok(source.to_string()),
+        "http" if loopback => Ok(source.to_string()),
+        _ => Err(format!("Invalid echo URL {:?}: must use https", source)),
+    }
+}
+
+pub fn read_token(path: &str) -> Result<String, String> {
+    let token = fs::read_to_string(path).map_err(|e| format!("Cannot read echo token file {}: {}", path, e))?;
+    match token.trim() {
+        "" => Err(format!("Echo token file {} is empty", path)),
+        token => Ok(token.to_string()),
+    }
+}
+
+pub async fn query(client: &Client, endpoint: &EchoEndpoint, timeout_secs: u64, is_ipv6: bool) -> Result<EchoAnswer, Error> {
+    let mut request = client.get(&endpoint.url)?.header(ACCEPT, "application/json");
+    if let Some(token) = &endpoint.token {
+        request = request.bearer_auth(token);
+    }
+
+    let url = &endpoint.url;
+    let timed_out = || Error::new(Code::Timeout, url, format!("{}: timed out after {}s", url, timeout_secs));
+    let response = match timeout(Duration::from_secs(timeout_secs), request.send()).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(Error::http(url, &e)),
+        Err(_) => return Err(timed_out()),
+    };
+    if !response.status().is_success() {
+        return Err(Error::new(Code::of_status(response.status().as_u16()), url, format!("HTTP {} received from {}", response.status(), url)));
+    }
+    let body = match timeout(Duration::from_secs(timeout_secs), response.bytes()).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return Err(Error::http(url, &e)),
+        Err(_) => return Err(timed_out()),
+    };
+    if body.len() > MAX_RESPONSE_BYTES {
+        return Err(Error::new(Code::InvalidData, url, format!("{}: response larger than {} bytes", url, MAX_RESPONSE_BYTES)));
+    }
+    let body: Value = serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: invalid JSON: {}", url, e)))?;
+    validate(&body, is_ipv6, now_secs()).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: {}", url, e)))
+}
+
+/// Validates a response body against the protocol for the family that was asked for.
+pub fn validate(body: &Value, is_ipv6: bool, now: u64) -> Result<EchoAnswer, String> {
+    let ip = body.get("ip").and_then(Value::as_str).ok_or("missing \"ip\"")?;
+    let family_matches = if is_ipv6 {
+        ip.parse::<Ipv6Addr>().is_ok()
+    } else {
+        ip.parse::<Ipv4Addr>().is_ok()
+    };
+    if !family_matches {
+        return Err(format!("invalid {} address {:?}", if is_ipv6 { "IPv6" } else { "IPv4" }, ip));
+    }
+    // Normalises e.g. zero-padded or uppercase IPv6 forms.
+    let ip = ip.parse::<IpAddr>().map_err(|e| e.to_string())?.to_string();
+
+    let asn = match body.get("asn") {
+        None | Some(Value::Null) => None,
+        Some(value) => Some(
+            value
+                .as_u64()
+                .and_then(|asn| u32::try_from(asn).ok())
+                .ok_or_else(|| format!("invalid \"asn\" {}", value))?,
+        ),
+    };
+
+    let timestamp = body.get("timestamp").and_then(Value::as_u64).ok_or("missing or invalid \"timestamp\"")?;
+    if timestamp.abs_diff(now) > MAX_CLOCK_SKEW_SECS {
+        return Err(format!(
+            "timestamp {} is more than {}s from the local clock",
+            timestamp, MAX_CLOCK_SKEW_SECS
+        ));
+    }
+
+    Ok(EchoAnswer { ip, asn })
+}
@@ -0,0 +1,239 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io;
+use std::process::Output;
+
+use crate::collectors;
+
+/// What went wrong, as a stable code playbooks can branch on. Codes are stable:
+/// new ones may be added, but an existing one is never renamed or reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Code {
+    /// Every endpoint tried for a lookup failed.
+    AllRequestsFailed,
+    /// A request budget (--rate-limit) is spent.
+    RateLimited,
+    /// An operation timed out.
+    Timeout,
+    /// Access needs more privileges, usually root.
+    PermissionDenied,
+    /// A command the collector runs isn't installed.
+    CommandNotFound,
+    /// A command ran but exited unsuccessfully.
+    CommandFailed,
+    /// Nothing is listening on a socket or port.
+    ConnectionRefused,
+    /// A host couldn't be resolved or reached.
+    Unreachable,
+    /// A service rejected the credentials (HTTP 401 or 403).
+    Unauthorized,
+    /// A service answered with another unsuccessful HTTP status.
+    HttpError,
+    /// A file, directory or resource doesn't exist (HTTP 404 included).
+    NotFound,
+    /// Something was read but couldn't be parsed or understood.
+    InvalidData,
+    /// A response was larger than the limit it's read with.
+    TooLarge,
+    /// Not supported on this host or platform.
+    Unsupported,
+    /// Ruled out by an option such as --offline.
+    Disabled,
+    /// Anything else.
+    Unknown,
+}
+
+impl Code {
+    /// The code for an I/O error.
+    pub fn of_io(error: &io::Error) -> Code {
+        match error.kind() {
+            io::ErrorKind::NotFound => Code::NotFound,
+            io::ErrorKind::PermissionDenied => Code::PermissionDenied,
+            io::ErrorKind::TimedOut => Code::Timeout,
+            io::ErrorKind::ConnectionRefused => Code::ConnectionRefused,
+            io::ErrorKind::NetworkUnreachable | io::ErrorKind::HostUnreachable | io::ErrorKind::AddrNotAvailable => Code::Unreachable,
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Code::InvalidData,
+            io::ErrorKind::Unsupported => Code::Unsupported,
+            _ => Code::Unknown,
+        }
+    }
+
+    /// The code for a failed HTTP request, from the I/O error behind it when it
+    /// didn't get as far as a response.
+    pub fn of_http(error: &reqwest::Error) -> Code {
+        if error.is_timeout() {
+            return Code::Timeout;
+        }
+        if let Some(status) = error.status() {
+            return Code::of_status(status.as_u16());
+        }
+        if error.is_decode() || error.is_body() {
+            return Code::InvalidData;
+        }
+        let mut cause = std::error::Error::source(error);
+        while let Some(error) = cause {
+            if let Some(code) = error.downcast_ref::<io::Error>().map(Code::of_io).filter(|code| *code != Code::Unknown) {
+                return code;
+            }
+            cause = error.source();
+        }
+        // Name resolution and TLS failures carry no I/O error.
+        if error.is_connect() {
+            Code::Unreachable
+        } else {
+            Code::Unknown
+        }
+    }
+
+    /// The code for an unsuccessful HTTP status.
+    pub fn of_status(status: u16) -> Code {
+        match status {
+            401 | 403 => Code::Unauthorized,
+            404 => Code::NotFound,
+            _ => Code::HttpError,
+        }
+    }
+}
+
+/// An error in a section: `{code, message, source}` from schema version 2, its
+/// message alone before. `source` names what failed, a file, command, URL or
+/// socket, or the collector when nothing more specific did.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Error {
+    pub code: Code,
+    pub message: String,
+    pub source: String,
+}
+
+impl Error {
+    pub fn new(code: Code, source: impl Into<String>, message: impl Into<String>) -> Error {
+        Error {
+            code,
+            message: message.into(),
+            source: source.into(),
+        }
+    }
+
+    /// `Cannot read <path>: <error>`.
+    pub fn read(path: impl fmt::Display, error: &io::Error) -> Error {
+        Error::new(Code::of_io(error), path.to_string(), format!("Cannot read {}: {}", path, error))
+    }
+
+    /// `Cannot parse <path>: <error>`.
+    pub fn parse(path: impl fmt::Display, error: impl fmt::Display) -> Error {
+        Error::new(Code::InvalidData, path.to_string(), format!("Cannot parse {}: {}", path, error))
+    }
+
+    /// `Error running <program>: <error>`, for a command that couldn't be started
+    /// or didn't finish.
+    pub fn run(program: impl fmt::Display, error: &io::Error) -> Error {
+        let code = match error.kind() {
+            io::ErrorKind::NotFound => Code::CommandNotFound,
+            _ => Code::of_io(error),
+        };
+        Error::new(code, program.to_string(), format!("Error running {}: {}", program, error))
+    }
+
+    /// `<program> exited with <status>: <stderr>`.
+    pub fn exited(program: impl fmt::Display, output: &Output) -> Error {
+        let message = format!("{} exited with {}: {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim());
+        Error::new(Code::CommandFailed, program.to_string(), message)
+    }
+
+    /// `<url>: <error>`, for a request that failed.
+    pub fn http(url: impl fmt::Display, error: &reqwest::Error) -> Error {
+        Error::new(Code::of_http(error), url.to_string(), format!("{}: {}", url, error))
+    }
+
+    /// `<url>: HTTP <status>`, for an unsuccessful response.
+    pub fn status(url: impl fmt::Display, status: reqwest::StatusCode) -> Error {
+        Error::new(Code::of_status(status.as_u16()), url.to_string(), format!("{}: HTTP {}", url, status))
+    }
+
+    /// Several errors as one, their messages joined with `; `. The code and source
+    /// are those of the last, the attempt that was given up on.
+    pub fn joined(errors: Vec<Error>) -> Option<Error> {
+        let last = errors.last()?;
+        let message = errors.iter().map(|error| error.message.as_str()).collect::<Vec<_>>().join("; ");
+        Some(Error::new(last.code, last.source.clone(), message))
+    }
+
+    /// The error as the failure of a collector for an optional service, unless it
+    /// only says the service isn't there: no such file or socket, nothing listening,
+    /// HTTP 404. Those are reported in the section without failing.
+    pub fn as_failure(&self) -> Option<Error> {
+        (!matches!(self.code, Code::NotFound | Code::ConnectionRefused)).then(|| self.clone())
+    }
+
+    /// The error with `context: ` in front of its message.
+    pub fn context(mut self, context: impl fmt::Display) -> Error {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Turns the error objects of a document back into their messages, the shape of
+/// schema version 1: in the collector sections (see `messages_in_section`) and in
+/// `failed`.
+pub fn to_messages(document: &mut Value) {
+    let Some(document) = document.as_object_mut() else {
+        return;
+    };
+    for (name, section) in document.iter_mut() {
+        if collectors::find(name).is_some() {
+            messages_in_section(section);
+        } else if name == "failed" {
+            section.as_object_mut().into_iter().flat_map(|failed| failed.values_mut()).for_each(to_message);
+        }
+    }
+}
+
+/// `to_messages` for one section: `error` and `*_error`/`error_*` fields, and the
+/// entries of `errors` lists and maps.
+pub fn messages_in_section(section: &mut Value) {
+    match section {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if key == "errors" {
+                    match value {
+                        Value::Array(items) => items.iter_mut().for_each(to_message),
+                        Value::Object(items) => items.values_mut().for_each(to_message),
+                        _ => {}
+                    }
+                } else if is_error_key(key) {
+                    to_message(value);
+                } else {
+                    messages_in_section(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(messages_in_section),
+        _ => {}
+    }
+}
+
+pub fn is_error_key(key: &str) -> bool {
+    key == "error" || key.ends_with("_error") || key.starts_with("error_")
+}
+
+/// The message of an error, whether an object or a version 1 string.
+pub fn message(error: &Value) -> Option<&str> {
+    error.as_str().or_else(|| error.get("message")?.as_str())
+}
+
+fn to_message(value: &mut Value) {
+    if let Some(message) = value.get("message").and_then(Value::as_str) {
+        *value = Value::String(message.to_string());
+    }
+}
@@ -0,0 +1,129 @@
+use std::ffi::OsStr;
+use std::io;
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::capability;
+
+/// Longest a command run through `output` may take before it is killed.
+const COMMAND_TIMEOUT: u64 = 10;
+
+/// Subprocesses started by collectors so far; `bench` reports the per-run delta.
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+/// A command for a collector to run. Collectors build every subprocess through here
+/// (or `output`) so spawning can be counted and checked against the collector's
+/// declared capabilities in one place.
+pub fn async_command<S: AsRef<OsStr>>(program: S) -> io::Result<tokio::process::Command> {
+    capability::check_exec(&program.as_ref().to_string_lossy())?;
+    SPAWNED.fetch_add(1, Ordering::Relaxed);
+    let mut command = tokio::process::Command::new(program);
+    // A collector cancelled by a signal must not leave its subprocess behind; in a
+    // group of its own, the subprocess's children are killed with it through
+    // `ProcessGroup`.
+    command.kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+    Ok(command)
+}
+
+/// The process group of a command spawned from `async_command`, killed when dropped
+/// unless `disarm`ed. `kill_on_drop` only reaches the direct child, so without it
+/// a cancelled `sh -c` would leave the commands it started running.
+pub struct ProcessGroup {
+    id: Option<u32>,
+}
+
+impl ProcessGroup {
+    pub fn of(child: &tokio::process::Child) -> ProcessGroup {
+        ProcessGroup { id: child.id() }
+    }
+
+    /// Kills every process in the group now.
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        if let Some(id) = self.id {
+            // SAFETY: killpg has no memory-safety preconditions; the group is the
+            // child's own, set up by async_command.
+            unsafe { libc::killpg(id as libc::pid_t, libc::SIGKILL) };
+        }
+        self.id = None;
+    }
+
+    /// Leaves the group alone once the command has finished by itself.
+    pub fn disarm(&mut self) {
+        self.id = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Runs `program` to completion and collects its output, like
+/// `std::process::Command::output` but without blocking the runtime: the command is
+/// killed after `COMMAND_TIMEOUT`, or as soon as the collector running it is
+/// cancelled by a signal or `--timeout`.
+pub async fn output<S, I, A>(program: S, args: I) -> io::Result<Output>
+where
+    S: AsRef<OsStr>,
+    I: IntoIterator<Item = A>,
+    A: AsRef<OsStr>,
+{
+    let name = program.as_ref().to_string_lossy().into_owned();
+    let mut command = async_command(program)?;
+    command.args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let child = command.spawn()?;
+    // Dropped with the future when the collector is cancelled, killing the group.
+    let mut group = ProcessGroup::of(&child);
+    match tokio::time::timeout(Duration::from_secs(COMMAND_TIMEOUT), child.wait_with_output()).await {
+        Ok(output) => {
+            group.disarm();
+            output
+        }
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} did not finish within {} s", name, COMMAND_TIMEOUT))),
+    }
+}
+
+pub fn spawned() -> usize {
+    SPAWNED.load(Ordering::Relaxed)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Whether `pid` is still running: present in /proc and not a zombie.
+    fn running(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok_and(|stat| stat.rsplit_once(") ").is_some_and(|(_, rest)| !rest.starts_with('Z')))
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_command_kills_what_it_started() {
+        let pid_file = std::env::temp_dir().join(format!("saltbox-facts-{}-grandchild", std::process::id()));
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let run = output("sh", ["-c", &script]);
+        assert!(tokio::time::timeout(Duration::from_millis(500), run).await.is_err());
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::fs::remove_file(&pid_file).unwrap();
+        let pid = pid.trim();
+        for _ in 0..50 {
+            if !running(pid) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("sleep {} outlived its cancelled shell", pid);
+    }
+
+    #[tokio::test]
+    async fn finished_commands_return_their_output() {
+        let output = output("sh", ["-c", "echo out; echo err >&2; exit 3"]).await.unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+        assert_eq!(output.status.code(), Some(3));
+    }
+}
@@ -0,0 +1,589 @@
+//! A small expression language evaluated against the facts document.
+//!
+//! ```text
+//! mounts["/"].available_gb < 10
+//! ip.failed_ipv4 == false && len(users) > 1
+//! contains(binaries.docker.path, "/usr/bin") or not exists(ansible_controller.version)
+//! ```
+//!
+//! Paths start at the document root; `.name` and `["key"]`/`[0]` index into objects
+//! and arrays, and a missing key is `null` rather than an error. Supported operators,
+//! loosest first: `or`/`||`, `and`/`&&`, `not`/`!`, comparisons, `+ -`, `* / %`,
+//! unary `-`. Functions: `len`, `exists`, `contains`, `starts_with`, `ends_with`,
+//! `lower`, `upper`, and `number` for string fields such as `users.root.uid`.
+
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+
+#[derive(Clone)]
+pub struct Expr {
+    root: Node,
+}
+
+#[derive(Clone)]
+enum Node {
+    Literal(Value),
+    Root(String),
+    Field(Box<Node>, String),
+    Index(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64, bool),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "(", ")", "[", "]", ".", ",", "<", ">", "!", "+", "-", "*", "/", "%",
+];
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("Unexpected {} in {:?}", describe(token), source));
+        }
+        Ok(Expr { root })
+    }
+
+    pub fn evaluate(&self, document: &Value) -> Result<Value, String> {
+        evaluate(&self.root, document)
+    }
+}
+
+/// JMESPath-style truthiness: null, false, 0, "" and empty collections are false.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(true),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n, _) => format!("number {}", n),
+        Token::Str(s) => format!("string {:?}", s),
+        Token::Ident(name) => format!("identifier {}", name),
+        Token::Punct(p) => format!("'{}'", p),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    'outer: while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let mut integer = true;
+            if pos + 1 < chars.len() && chars[pos] == '.' && chars[pos + 1].is_ascii_digit() {
+                integer = false;
+                pos += 1;
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            let text: String = chars[start..pos].iter().collect();
+            let number = text.parse::<f64>().map_err(|e| format!("Invalid number {}: {}", text, e))?;
+            tokens.push(Token::Number(number, integer));
+        } else if c == '"' || c == '\'' {
+            let quote = c;
+            let mut value = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    Some(&ch) if ch == quote => break,
+                    Some('\\') => {
+                        let escaped = chars.get(pos + 1).ok_or("Unterminated escape in string")?;
+                        value.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => *other,
+                        });
+                        pos += 2;
+                    }
+                    Some(&ch) => {
+                        value.push(ch);
+                        pos += 1;
+                    }
+                    None => return Err(format!("Unterminated string in {:?}", source)),
+                }
+            }
+            pos += 1;
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else {
+            for punct in PUNCTUATION {
+                let len = punct.len();
+                if pos + len <= chars.len() && chars[pos..pos + len].iter().collect::<String>() == *punct {
+                    tokens.push(Token::Punct(punct));
+                    pos += len;
+                    continue 'outer;
+                }
+            }
+            return Err(format!("Unexpected character {:?} in {:?}", c, source));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(name)) if name == keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(match self.peek() {
+                Some(token) => format!("Expected '{}' but found {}", punct, describe(token)),
+                None => format!("Expected '{}' but the expression ended", punct),
+            })
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node, String> {
+        let mut left = self.parse_and()?;
+        while self.eat_punct("||") || self.eat_keyword("or") {
+            left = Node::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Node, String> {
+        let mut left = self.parse_not()?;
+        while self.eat_punct("&&") || self.eat_keyword("and") {
+            left = Node::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Node, String> {
+        if self.eat_punct("!") || self.eat_keyword("not") {
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Node, String> {
+        let left = self.parse_additive()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => BinaryOp::Eq,
+            Some(Token::Punct("!=")) => BinaryOp::Ne,
+            Some(Token::Punct("<")) => BinaryOp::Lt,
+            Some(Token::Punct("<=")) => BinaryOp::Le,
+            Some(Token::Punct(">")) => BinaryOp::Gt,
+            Some(Token::Punct(">=")) => BinaryOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_additive()?;
+        Ok(Node::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Node, String> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = if self.eat_punct("+") {
+                BinaryOp::Add
+            } else if self.eat_punct("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Node::Binary(op, Box::new(left), Box::new(self.parse_multiplicative()?));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Node, String> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = if self.eat_punct("*") {
+                BinaryOp::Mul
+            } else if self.eat_punct("/") {
+                BinaryOp::Div
+            } else if self.eat_punct("%") {
+                BinaryOp::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Node::Binary(op, Box::new(left), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        if self.eat_punct("-") {
+            return Ok(Node::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_primary()?;
+        loop {
+            if self.eat_punct(".") {
+                match self.tokens.get(self.pos).cloned() {
+                    Some(Token::Ident(name)) => {
+                        self.pos += 1;
+                        node = Node::Field(Box::new(node), name);
+                    }
+                    Some(token) => return Err(format!("Expected a field name after '.' but found {}", describe(&token))),
+                    None => return Err("Expected a field name after '.'".to_string()),
+                }
+            } else if self.eat_punct("[") {
+                let index = self.parse_or()?;
+                self.expect_punct("]")?;
+                node = Node::Index(Box::new(node), Box::new(index));
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Number(n, true) if n.abs() < i64::MAX as f64 => Ok(Node::Literal(json!(n as i64))),
+            Token::Number(n, _) => Ok(Node::Literal(json!(n))),
+            Token::Str(s) => Ok(Node::Literal(Value::String(s))),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Node::Literal(Value::Bool(true))),
+                "false" => Ok(Node::Literal(Value::Bool(false))),
+                "null" => Ok(Node::Literal(Value::Null)),
+                _ if self.eat_punct("(") => {
+                    let mut args = Vec::new();
+                    if !self.eat_punct(")") {
+                        loop {
+                            args.push(self.parse_or()?);
+                            if self.eat_punct(")") {
+                                break;
+                            }
+                            self.expect_punct(",")?;
+                        }
+                    }
+                    Ok(Node::Call(name, args))
+                }
+                _ => Ok(Node::Root(name)),
+            },
+            Token::Punct("(") => {
+                let inner = self.parse_or()?;
+                self.expect_punct(")")?;
+                Ok(inner)
+            }
+            token => Err(format!("Unexpected {}", describe(&token))),
+        }
+    }
+}
+
+fn evaluate(node: &Node, document: &Value) -> Result<Value, String> {
+    match node {
+        Node::Literal(value) => Ok(value.clone()),
+        Node::Root(name) => Ok(document.get(name).cloned().unwrap_or(Value::Null)),
+        Node::Field(base, name) => Ok(evaluate(base, document)?.get(name).cloned().unwrap_or(Value::Null)),
+        Node::Index(base, index) => {
+            let base = evaluate(base, document)?;
+            match evaluate(index, document)? {
+                Value::String(key) => Ok(base.get(&key).cloned().unwrap_or(Value::Null)),
+                Value::Number(n) => {
+                    let items = match base.as_array() {
+                        Some(items) => items,
+                        None => return Ok(Value::Null),
+                    };
+                    let index = n.as_i64().ok_or_else(|| format!("Index {} is not an integer", n))?;
+                    let resolved = if index < 0 { items.len() as i64 + index } else { index };
+                    Ok(usize::try_from(resolved)
+                        .ok()
+                        .and_then(|i| items.get(i))
+                        .cloned()
+                        .unwrap_or(Value::Null))
+                }
+                other => Err(format!("Cannot index with {}", other)),
+            }
+        }
+        Node::Not(inner) => Ok(Value::Bool(!is_truthy(&evaluate(inner, document)?))),
+        Node::Negate(inner) => match evaluate(inner, document)? {
+            Value::Number(n) => Ok(n.as_i64().map(|i| json!(-i)).unwrap_or_else(|| json!(-n.as_f64().unwrap_or(0.0)))),
+            other => Err(format!("Cannot negate {}", other)),
+        },
+        Node::And(left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, document)?) && is_truthy(&evaluate(right, document)?),
+        )),
+        Node::Or(left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, document)?) || is_truthy(&evaluate(right, document)?),
+        )),
+        Node::Binary(op, left, right) => binary(*op, evaluate(left, document)?, evaluate(right, document)?),
+        Node::Call(name, args) => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, document))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(name, &args)
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+    match op {
+        BinaryOp::Eq => Ok(Value::Bool(values_equal(&left, &right))),
+        BinaryOp::Ne => Ok(Value::Bool(!values_equal(&left, &right))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (&left, &right) {
+                (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => None,
+            }
+            .ok_or_else(|| format!("Cannot compare {} with {}", left, right))?;
+            Ok(Value::Bool(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        BinaryOp::Add => match (&left, &right) {
+            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+            _ => arithmetic(op, &left, &right),
+        },
+        _ => arithmetic(op, &left, &right),
+    }
+}
+
+fn arithmetic(op: BinaryOp, left: &Value, right: &Value) -> Result<Value, String> {
+    let (Value::Number(a), Value::Number(b)) = (left, right) else {
+        return Err(format!("Arithmetic needs numbers, got {} and {}", left, right));
+    };
+
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        let result = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Sub => a.checked_sub(b),
+            BinaryOp::Mul => a.checked_mul(b),
+            BinaryOp::Rem => a.checked_rem(b),
+            BinaryOp::Div if a.checked_rem(b) == Some(0) => a.checked_div(b),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(json!(result));
+        }
+    }
+
+    let (a, b) = (a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+    if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0.0 {
+        return Err("Division by zero".to_string());
+    }
+    Ok(json!(match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div => a / b,
+        _ => a % b,
+    }))
+}
+
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let arity = |expected: usize| -> Result<(), String> {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(format!("{}() takes {} argument(s), got {}", name, expected, args.len()))
+        }
+    };
+    let string_arg = |index: usize| -> Result<&str, String> {
+        args[index]
+            .as_str()
+            .ok_or_else(|| format!("{}() expects a string, got {}", name, args[index]))
+    };
+
+    match name {
+        "len" => {
+            arity(1)?;
+            match &args[0] {
+                Value::String(s) => Ok(json!(s.chars().count())),
+                Value::Array(items) => Ok(json!(items.len())),
+                Value::Object(map) => Ok(json!(map.len())),
+                Value::Null => Ok(json!(0)),
+                other => Err(format!("len() of {}", other)),
+            }
+        }
+        "exists" => {
+            arity(1)?;
+            Ok(Value::Bool(!args[0].is_null()))
+        }
+        "contains" => {
+            arity(2)?;
+            match &args[0] {
+                Value::String(s) => Ok(Value::Bool(s.contains(string_arg(1)?))),
+                Value::Array(items) => Ok(Value::Bool(items.iter().any(|item| values_equal(item, &args[1])))),
+                Value::Object(map) => Ok(Value::Bool(map.contains_key(string_arg(1)?))),
+                Value::Null => Ok(Value::Bool(false)),
+                other => Err(format!("contains() on {}", other)),
+            }
+        }
+        "starts_with" => {
+            arity(2)?;
+            Ok(Value::Bool(string_arg(0)?.starts_with(string_arg(1)?)))
+        }
+        "ends_with" => {
+            arity(2)?;
+            Ok(Value::Bool(string_arg(0)?.ends_with(string_arg(1)?)))
+        }
+        "lower" => {
+            arity(1)?;
+            Ok(Value::String(string_arg(0)?.to_lowercase()))
+        }
+        "number" => {
+            arity(1)?;
+            match &args[0] {
+                Value::Number(_) => Ok(args[0].clone()),
+                Value::String(s) => {
+                    let s = s.trim();
+                    s.parse::<i64>()
+                        .map(|i| json!(i))
+                        .or_else(|_| s.parse::<f64>().map(|f| json!(f)))
+                        .map_err(|_| format!("number() cannot parse {:?}", s))
+                }
+                other => Err(format!("number() of {}", other)),
+            }
+        }
+        "upper" => {
+            arity(1)?;
+            Ok(Value::String(string_arg(0)?.to_uppercase()))
+        }
+        _ => Err(format!("Unknown function {}()", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, document: &Value) -> Result<Value, String> {
+        Expr::parse(source)?.evaluate(document)
+    }
+
+    #[test]
+    fn integer_arithmetic_stays_integral() {
+        let document = json!({});
+        assert_eq!(eval("7 % 3", &document), Ok(json!(1)));
+        assert_eq!(eval("6 / 3", &document), Ok(json!(2)));
+        assert_eq!(eval("7 / 2", &document), Ok(json!(3.5)));
+        assert_eq!(eval("-2 * 3 + 1", &document), Ok(json!(-5)));
+    }
+
+    #[test]
+    fn overflowing_arithmetic_falls_back_to_floats() {
+        let document = json!({});
+        assert_eq!(eval("(-4611686018427387904 * 2) % -1", &document), Ok(json!(-0.0)));
+        assert_eq!(eval("(-4611686018427387904 * 2) / -1", &document), Ok(json!(9223372036854775808.0)));
+        assert_eq!(eval("9223372036854775807 + 1", &document), Ok(json!(9223372036854775808.0)));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let document = json!({});
+        assert_eq!(eval("1 / 0", &document), Err("Division by zero".to_string()));
+        assert_eq!(eval("1 % 0", &document), Err("Division by zero".to_string()));
+    }
+
+    #[test]
+    fn paths_index_into_the_document() {
+        let document = json!({ "mounts": { "/": { "available_gb": 5 } }, "users": { "root": { "uid": "0" } }, "list": [1, 2] });
+        assert_eq!(eval("mounts[\"/\"].available_gb < 10", &document), Ok(json!(true)));
+        assert_eq!(eval("number(users.root.uid) == 0", &document), Ok(json!(true)));
+        assert_eq!(eval("list[1]", &document), Ok(json!(2)));
+        assert_eq!(eval("missing.key", &document), Ok(Value::Null));
+    }
+
+    #[test]
+    fn boolean_operators_and_functions() {
+        let document = json!({ "users": { "root": {}, "saltbox": {} }, "path": "/usr/bin/docker" });
+        assert_eq!(eval("len(users) > 1 && not exists(nothing)", &document), Ok(json!(true)));
+        assert_eq!(eval("starts_with(path, \"/usr\") or false", &document), Ok(json!(true)));
+        assert_eq!(eval("upper(\"a\") + lower(\"B\")", &document), Ok(json!("Ab")));
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("(1").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert!(Expr::parse("\"unterminated").is_err());
+        assert!(eval("nope(1)", &json!({})).is_err());
+        assert!(eval("len(1, 2)", &json!({})).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::{json, Map, Value};
+
+const BYTES_PER_GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Renames the facts that have a facter core equivalent to facter's names and
+/// shapes (`networking.ip`, `timezone`, `mountpoints`), so the output can serve as
+/// a Puppet external fact. Everything, mapped or not, is also kept under `saltbox`.
+///
+/// The public addresses stand in for facter's `networking.ip` / `ip6`; facter
+/// itself reports the primary interface's address, which differs behind NAT.
+pub fn to_facter(document: &Value) -> Value {
+    let mut facts = Map::new();
+
+    let mut networking = Map::new();
+    for (source, target) in [("public_ip", "ip"), ("public_ipv6", "ip6")] {
+        if let Some(address) = document.pointer(&format!("/ip/{}", source)).and_then(Value::as_str).filter(|address| !address.is_empty()) {
+            networking.insert(target.to_string(), json!(address));
+        }
+    }
+    if !networking.is_empty() {
+        facts.insert("networking".to_string(), Value::Object(networking));
+    }
+
+    if let Some(timezone) = document.pointer("/timezone/timezone") {
+        facts.insert("timezone".to_string(), timezone.clone());
+    }
+
+    if let Some(mounts) = document.get("mounts").and_then(Value::as_object) {
+        let mountpoints: Map<String, Value> = mounts.iter().map(|(path, mount)| (path.clone(), mountpoint(mount))).collect();
+        facts.insert("mountpoints".to_string(), Value::Object(mountpoints));
+    }
+
+    facts.insert("saltbox".to_string(), document.clone());
+    Value::Object(facts)
+}
+
+/// Facter's mountpoints entry: sizes as `*_bytes` and human-readable strings, and
+/// `capacity` as a used percentage string. The mounts collector rounds to 0.01 GiB,
+/// so the byte counts are that precise.
+fn mountpoint(mount: &Value) -> Value {
+    let mut entry = json!({
+        "device": mount.get("device"),
+        "filesystem": mount.get("fstype"),
+        "options": mount.get("options")
+    });
+    for (source, target) in [("size_gb", "size"), ("used_gb", "used"), ("available_gb", "available")] {
+        if let Some(gib) = mount.get(source).and_then(Value::as_f64) {
+            entry[format!("{}_bytes", target)] = json!((gib * BYTES_PER_GIB).round() as u64);
+            entry[target] = json!(format!("{:.2} GiB", gib));
+        }
+    }
+    if let Some(percent) = mount.get("used_percent").and_then(Value::as_f64) {
+        entry["capacity"] = json!(format!("{:.2}%", percent));
+    }
+    entry
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::build_info;
+use crate::errors::{self, Error};
+use crate::timestamp::{format_rfc3339, now_rfc3339};
+
+/// Exit status when some collectors failed and others succeeded.
+pub const EXIT_PARTIAL: i32 = 3;
+/// Exit status when every collector that ran failed.
+pub const EXIT_ALL_FAILED: i32 = 4;
+
+/// A section's facts: a JSON tree, or typed entries that a streamed document
+/// serializes straight into the output without building one.
+pub enum Payload {
+    Json(Value),
+    Typed(Box<dyn erased_serde::Serialize>),
+}
+
+impl Payload {
+    pub fn into_json(self) -> Result<Value, serde_json::Error> {
+        match self {
+            Payload::Json(value) => Ok(value),
+            Payload::Typed(typed) => serde_json::to_value(&*typed),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Value, serde_json::Error> {
+        match self {
+            Payload::Json(value) => Ok(value.clone()),
+            Payload::Typed(typed) => serde_json::to_value(&**typed),
+        }
+    }
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Payload::Json(value) => value.serialize(serializer),
+            Payload::Typed(typed) => erased_serde::serialize(&**typed, serializer),
+        }
+    }
+}
+
+/// A section as returned by a collector.
+pub struct Section {
+    pub value: Payload,
+    /// Unix time the value was originally collected, when it was served from the cache.
+    pub cached_at: Option<u64>,
+    pub truncated: Option<Truncation>,
+    /// Set by the collector when it couldn't collect what the section is for. An
+    /// optional service that isn't there is reported in the section, not here.
+    pub failure: Option<Error>,
+}
+
+impl Section {
+    pub fn new(value: Value) -> Section {
+        Section::from_payload(Payload::Json(value))
+    }
+
+    /// A section of typed entries, serialized when the document is written.
+    pub fn typed(value: impl erased_serde::Serialize + 'static) -> Section {
+        Section::from_payload(Payload::Typed(Box::new(value)))
+    }
+
+    fn from_payload(value: Payload) -> Section {
+        Section {
+            value,
+            cached_at: None,
+            truncated: None,
+            failure: None,
+        }
+    }
+
+    /// The section as stored in the cache or passed back from a worker.
+    pub fn to_value(&self) -> Value {
+        let truncated = self
+            .truncated
+            .as_ref()
+            .map(|truncation| json!({ "limit": truncation.limit, "entries": truncation.entries }));
+        json!({ "value": self.value, "cached_at": self.cached_at, "truncated": truncated, "failure": self.failure })
+    }
+
+    pub fn from_value(value: &Value) -> Option<Section> {
+        let truncated = value.get("truncated").and_then(|truncated| {
+            Some(Truncation {
+                limit: truncated.get("limit")?.as_u64()? as usize,
+                entries: truncated.get("entries")?.as_u64()? as usize,
+            })
+        });
+        Some(Section {
+            value: Payload::Json(value.get("value")?.clone()),
+            cached_at: value.get("cached_at").and_then(Value::as_u64),
+            truncated,
+            failure: value.get("failure").and_then(|failure| Error::deserialize(failure).ok()),
+        })
+    }
+}
+
+/// A section from a collector that reports its own failure.
+impl From<(Value, Option<Error>)> for Section {
+    fn from((value, failure): (Value, Option<Error>)) -> Section {
+        Section { failure, ..Section::new(value) }
+    }
+}
+
+/// Recorded when a collector stopped at an entry limit.
+pub struct Truncation {
+    pub limit: usize,
+    /// Entries that were found, including those left out.
+    pub entries: usize,
+}
+
+/// The output document, assembled section by section.
+///
+/// Every inserted section gets a `freshness` entry recording when it was collected and
+/// whether it came from the cache, so consumers of stored or served facts can tell how
+/// stale each piece is.
+#[derive(Default)]
+pub struct Facts {
+    sections: Map<String, Value>,
+    freshness: Map<String, Value>,
+    truncated: Map<String, Value>,
+    skipped: Map<String, Value>,
+    cancelled: Map<String, Value>,
+    timed_out: Map<String, Value>,
+    failed: Map<String, Value>,
+    violations: Vec<String>,
+}
+
+impl Facts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a freshly collected section.
+    pub fn insert(&mut self, name: &str, value: Value) {
+        self.freshness.insert(
+            name.to_string(),
+            json!({ "collected_at": now_rfc3339(), "cache_hit": false }),
+        );
+        self.sections.insert(name.to_string(), value);
+    }
+
+    /// Adds a section served from the cache, originally collected at `collected_at`
+    /// (unix seconds).
+    pub fn insert_cached(&mut self, name: &str, value: Value, collected_at: u64) {
+        self.freshness.insert(
+            name.to_string(),
+            json!({
+                "collected_at": format_rfc3339(UNIX_EPOCH + Duration::from_secs(collected_at)),
+                "cache_hit": true
+            }),
+        );
+        self.sections.insert(name.to_string(), value);
+    }
+
+    /// Adds a section, building the JSON tree of a typed one.
+    pub fn insert_section(&mut self, name: &str, section: Section) -> Result<(), serde_json::Error> {
+        if let Some(truncation) = section.truncated {
+            self.truncated.insert(
+                name.to_string(),
+                json!({ "limit": truncation.limit, "entries": truncation.entries }),
+            );
+        }
+        let value = section.value.into_json()?;
+        match section.cached_at {
+            Some(collected_at) => self.insert_cached(name, value, collected_at),
+            None => self.insert(name, value),
+        }
+        Ok(())
+    }
+
+    /// Records a section already written to the output: its freshness and truncation,
+    /// and its name, so the run's exit status counts it and drop-ins can't shadow it.
+    pub fn insert_streamed(&mut self, name: &str, section: Section) {
+        let _ = self.insert_section(name, Section { value: Payload::Json(Value::Null), ..section });
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Value> {
+        self.sections.get(name)
+    }
+
+    /// Records a collector that was deliberately not run.
+    pub fn skip(&mut self, name: &str, reason: &str) {
+        self.skipped.insert(name.to_string(), json!(reason));
+    }
+
+    /// Records a collector that was stopped or never started because of `signal`.
+    pub fn cancel(&mut self, name: &str, signal: &str) {
+        self.cancelled.insert(name.to_string(), json!(signal));
+    }
+
+    /// Records a collector whose worker was killed for running too long.
+    pub fn time_out(&mut self, name: &str, reason: &str) {
+        self.timed_out.insert(name.to_string(), json!(reason));
+    }
+
+    /// Records why an inserted section counts as failed.
+    pub fn fail(&mut self, name: &str, error: &Error) {
+        self.failed.insert(name.to_string(), json!(error));
+    }
+
+    /// Names and reasons of the collectors that failed or timed out.
+    pub fn failures(&self) -> Vec<(&str, &str)> {
+        self.failed
+            .iter()
+            .chain(&self.timed_out)
+            .map(|(name, reason)| (name.as_str(), errors::message(reason).unwrap_or_default()))
+            .collect()
+    }
+
+    /// 0 when every collector that ran succeeded, [`EXIT_PARTIAL`] when some failed
+    /// and [`EXIT_ALL_FAILED`] when all did. Skipped and cancelled collectors don't count.
+    pub fn exit_code(&self) -> i32 {
+        let failed = self.failed.len() + self.timed_out.len();
+        let ran = self.sections.len() + self.timed_out.len();
+        match failed {
+            0 => 0,
+            _ if failed == ran => EXIT_ALL_FAILED,
+            _ => EXIT_PARTIAL,
+        }
+    }
+
+    /// Records collectors caught doing something they didn't declare.
+    pub fn flag_violations(&mut self, violations: Vec<String>) {
+        self.violations.extend(violations);
+    }
+
+    pub fn into_value(self, version: &str) -> Value {
+        let mut document = Map::new();
+        document.insert("saltbox_facts_version".to_string(), json!(version));
+        document.insert("meta".to_string(), build_info::meta());
+        document.extend(self.sections);
+        document.insert("freshness".to_string(), Value::Object(self.freshness));
+        // Sections cut short by an entry limit, with the limit and how many entries exist.
+        document.insert("truncated".to_string(), Value::Object(self.truncated));
+        document.insert("skipped".to_string(), Value::Object(self.skipped));
+        document.insert("cancelled".to_string(), Value::Object(self.cancelled));
+        document.insert("timed_out".to_string(), Value::Object(self.timed_out));
+        document.insert("failed".to_string(), Value::Object(self.failed));
+        document.insert("capability_violations".to_string(), json!(self.violations));
+        Value::Object(document)
+    }
+}
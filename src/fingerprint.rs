@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{Code, Error};
+
+pub const MACHINE_ID_PATHS: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+const ROUTE_FILE_PATH: &str = "/proc/net/route";
+const NET_CLASS_PATH: &str = "/sys/class/net";
+pub const DMI_SERIAL_PATHS: &[&str] = &["/sys/class/dmi/id/product_serial", "/sys/class/dmi/id/product_uuid"];
+/// Domain separation for the hash, so the fingerprint can't be matched against a
+/// plain hash of the machine-id computed by some other tool.
+const FINGERPRINT_SALT: &str = "saltbox-facts:host-fingerprint:v1";
+
+/// Placeholder serials shipped by board vendors that identify nothing.
+const BOGUS_DMI_SERIALS: &[&str] = &[
+    "",
+    "0",
+    "None",
+    "Not Specified",
+    "Default string",
+    "To be filled by O.E.M.",
+    "System Serial Number",
+    "00000000-0000-0000-0000-000000000000",
+];
+
+/// Derives a stable, non-reversible host identifier from machine-id, the primary
+/// interface's MAC and the DMI serial. Only the salted SHA-256 is emitted, never the
+/// inputs; `sources` names which inputs contributed so consumers can judge stability.
+pub fn get_host_fingerprint(user_salt: Option<&str>) -> Value {
+    let mut sources = Vec::new();
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_SALT.as_bytes());
+    if let Some(salt) = user_salt {
+        hasher.update(b"\0");
+        hasher.update(salt.as_bytes());
+    }
+
+    let components = [
+        ("machine_id", read_first(MACHINE_ID_PATHS)),
+        ("mac", primary_mac()),
+        ("dmi_serial", read_first(DMI_SERIAL_PATHS).filter(|serial| !BOGUS_DMI_SERIALS.contains(&serial.as_str()))),
+    ];
+    for (name, value) in components {
+        if let Some(value) = value {
+            hasher.update(b"\0");
+            hasher.update(name.as_bytes());
+            hasher.update(b"=");
+            hasher.update(value.as_bytes());
+            sources.push(name);
+        }
+    }
+
+    if sources.is_empty() {
+        return json!({
+            "fingerprint": Value::Null,
+            "sources": sources,
+            "error": Error::new(Code::NotFound, "host_fingerprint", "No machine-id, MAC address or DMI serial available")
+        });
+    }
+
+    let fingerprint: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    json!({
+        "fingerprint": fingerprint,
+        "sources": sources,
+        "error": Value::Null
+    })
+}
+
+fn read_first(paths: &[&str]) -> Option<String> {
+    paths
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|contents| contents.trim().to_string())
+        .find(|contents| !contents.is_empty())
+}
+
+/// MAC of the interface carrying the IPv4 default route, falling back to the first
+/// (by name) non-loopback interface with a real hardware address.
+fn primary_mac() -> Option<String> {
+    let default_iface = fs::read_to_string(ROUTE_FILE_PATH).ok().and_then(|routes| {
+        routes.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (fields.len() > 1 && fields[1] == "00000000").then(|| fields[0].to_string())
+        })
+    });
+
+    if let Some(mac) = default_iface.as_deref().and_then(interface_mac) {
+        return Some(mac);
+    }
+
+    let mut interfaces: Vec<String> = fs::read_dir(NET_CLASS_PATH)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name != "lo")
+        .collect();
+    interfaces.sort();
+    interfaces.iter().find_map(|name| interface_mac(name))
+}
+
+fn interface_mac(interface: &str) -> Option<String> {
+    let mac = fs::read_to_string(Path::new(NET_CLASS_PATH).join(interface).join("address")).ok()?;
+    let mac = mac.trim().to_lowercase();
+    (!mac.is_empty() && mac != "00:00:00:00:00:00").then_some(mac)
+}
@@ -0,0 +1,54 @@
+use serde_json::{Map, Value};
+
+/// Converts a nested document into a single-level map with dotted keys
+/// (`users.plex.uid`); array elements use their index (`locales.available.0`).
+/// Empty objects and arrays are kept as values so no key disappears.
+///
+/// Keys that would make the path ambiguous (`traefik.domains."example.com"`) are
+/// quoted as `--query` quotes them, so every flattened key names one value.
+pub fn flatten(value: &Value) -> Value {
+    match value {
+        Value::Object(_) | Value::Array(_) => {
+            let mut flat = Map::new();
+            flatten_into("", value, &mut flat);
+            Value::Object(flat)
+        }
+        scalar => scalar.clone(),
+    }
+}
+
+fn flatten_into(prefix: &str, value: &Value, flat: &mut Map<String, Value>) {
+    let join = |key: &str| {
+        let key = segment(key);
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                flatten_into(&join(key), child, flat);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (index, child) in items.iter().enumerate() {
+                flatten_into(&join(&index.to_string()), child, flat);
+            }
+        }
+        leaf => {
+            flat.insert(prefix.to_string(), leaf.clone());
+        }
+    }
+}
+
+/// A key as a path segment: quoted, with `"` and `\` escaped, when it's empty or
+/// holds a dot or quote.
+fn segment(key: &str) -> String {
+    if !key.is_empty() && !key.contains(['.', '"']) {
+        return key.to_string();
+    }
+    format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,89 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::binaries::find_in_path;
+use crate::docker;
+use crate::docker_daemon::DAEMON_JSON_PATH;
+
+const NVIDIA_DRIVER_PATH: &str = "/proc/driver/nvidia/version";
+const TOOLKIT_CONFIG_PATH: &str = "/etc/nvidia-container-runtime/config.toml";
+/// Where CDI specs are read from unless dockerd is given other `cdi-spec-dirs`.
+const CDI_SPEC_DIRS: &[&str] = &["/etc/cdi", "/var/run/cdi"];
+const TOOLKIT_BINARIES: &[&str] = &["nvidia-ctk", "nvidia-container-runtime", "nvidia-container-runtime-hook"];
+
+/// Whether containers can use the NVIDIA GPU: driver loaded, container toolkit
+/// installed, and Docker able to hand the GPU to a container, either through the
+/// `nvidia` runtime or a CDI spec for nvidia.com/gpu. `gpu_container_ready` is the
+/// verdict; `reasons` says what is missing when it is false.
+pub async fn get_gpu_container() -> Value {
+    let driver = fs::read_to_string(NVIDIA_DRIVER_PATH)
+        .ok()
+        .and_then(|version| version.lines().next().map(|line| line.trim().to_string()));
+    let toolkit: Vec<&str> = TOOLKIT_BINARIES.iter().copied().filter(|name| find_in_path(name).is_some()).collect();
+    let cdi_specs = nvidia_cdi_specs();
+
+    // The running daemon knows its runtimes for sure; daemon.json is the fallback
+    // when it can't be asked.
+    let (runtimes, runtimes_source) = match docker::get("/info").await {
+        Ok(info) => (keys(info.get("Runtimes")), "daemon"),
+        Err(_) => {
+            let config = fs::read(DAEMON_JSON_PATH).ok().and_then(|data| serde_json::from_slice::<Value>(&data).ok());
+            (keys(config.as_ref().and_then(|config| config.get("runtimes"))), "daemon.json")
+        }
+    };
+    let nvidia_runtime = runtimes.iter().any(|runtime| runtime == "nvidia");
+
+    let mut reasons = Vec::new();
+    if driver.is_none() {
+        reasons.push(format!("NVIDIA driver not loaded ({} missing)", NVIDIA_DRIVER_PATH));
+    }
+    if toolkit.is_empty() {
+        reasons.push("NVIDIA container toolkit not installed (no nvidia-ctk or nvidia-container-runtime on PATH)".to_string());
+    }
+    if !nvidia_runtime && cdi_specs.is_empty() {
+        reasons.push(format!(
+            "Docker can't pass the GPU to containers: no nvidia runtime in {} and no CDI spec for nvidia.com/gpu (run nvidia-ctk runtime configure --runtime=docker)",
+            runtimes_source
+        ));
+    }
+
+    json!({
+        "gpu_container_ready": reasons.is_empty(),
+        "reasons": reasons,
+        "driver_version": driver,
+        "toolkit_binaries": toolkit,
+        "toolkit_config": Path::new(TOOLKIT_CONFIG_PATH).exists(),
+        "cdi_specs": cdi_specs,
+        "docker_runtimes": runtimes,
+        "docker_runtimes_source": runtimes_source,
+        "nvidia_runtime": nvidia_runtime
+    })
+}
+
+/// CDI spec files (JSON or YAML) declaring the nvidia.com/gpu device kind, as
+/// `nvidia-ctk cdi generate` writes them.
+fn nvidia_cdi_specs() -> Vec<String> {
+    let mut specs = Vec::new();
+    for dir in CDI_SPEC_DIRS {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| matches!(extension.to_str(), Some("json" | "yaml" | "yml"))))
+            .collect();
+        paths.sort();
+        for path in paths {
+            if fs::read_to_string(&path).is_ok_and(|contents| contents.contains("nvidia.com/gpu")) {
+                specs.push(path.display().to_string());
+            }
+        }
+    }
+    specs
+}
+
+fn keys(value: Option<&Value>) -> Vec<String> {
+    value.and_then(Value::as_object).map(|map| map.keys().cloned().collect()).unwrap_or_default()
+}
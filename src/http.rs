@@ -0,0 +1,133 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, PROXY_AUTHORIZATION};
+use reqwest::{Certificate, Identity, Method, RequestBuilder, Url};
+use std::fs;
+use std::net::SocketAddr;
+
+use crate::capability;
+use crate::errors::{Code, Error};
+
+const USER_AGENT: &str = concat!("saltbox-facts/", env!("CARGO_PKG_VERSION"), " (+https://github.com/saltyorg/ansible-facts)");
+
+#[derive(Debug, Default)]
+pub struct HttpOptions {
+    /// PEM bundle of extra trusted CAs, for TLS-intercepting proxies or private echo endpoints.
+    pub ca_bundle: Option<String>,
+    /// PEM client certificate; may also contain the key.
+    pub client_cert: Option<String>,
+    /// PEM private key for `client_cert`, when kept in a separate file.
+    pub client_key: Option<String>,
+    pub insecure: bool,
+    /// Replaces the default `saltbox-facts/<version>` User-Agent.
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every request, e.g. a token for an internal echo service.
+    pub headers: Vec<(String, String)>,
+    /// Nameservers used instead of the system resolver; empty uses the system resolver.
+    pub dns_servers: Vec<SocketAddr>,
+}
+
+/// The HTTP client every collector shares. Requests are only built through it, and
+/// each is checked with `capability::check_network` first, so none reaches the
+/// network from a collector that didn't declare it or under `--offline`.
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::Client,
+}
+
+impl Client {
+    pub fn get(&self, url: &str) -> Result<RequestBuilder, Error> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn head(&self, url: &str) -> Result<RequestBuilder, Error> {
+        self.request(Method::HEAD, url)
+    }
+
+    pub fn post(&self, url: &str) -> Result<RequestBuilder, Error> {
+        self.request(Method::POST, url)
+    }
+
+    /// A request for `url`, refused as `check_network` refuses it. The URL is
+    /// checked and recorded without any credentials it carries.
+    pub fn request(&self, method: Method, url: &str) -> Result<RequestBuilder, Error> {
+        let parsed = Url::parse(url).map_err(|e| Error::new(Code::InvalidData, url, format!("Invalid URL {}: {}", url, e)))?;
+        let mut target = parsed.clone();
+        let _ = target.set_username("");
+        let _ = target.set_password(None);
+        capability::check_network(target.as_str())?;
+        Ok(self.client.request(method, parsed))
+    }
+}
+
+/// Parses `Name: value` as given to `--header`.
+pub fn parse_header(source: &str) -> Result<(String, String), String> {
+    let (name, value) = source
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid header {:?}: expected \"Name: value\"", source))?;
+    let name = name.trim();
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name {:?}", name))?;
+    HeaderValue::from_str(value.trim()).map_err(|_| format!("Invalid value for header {}", name))?;
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Builds the client shared by every collector that talks HTTP.
+pub fn build_client(options: &HttpOptions) -> Result<Client, String> {
+    let mut builder = reqwest::Client::builder().user_agent(options.user_agent.as_deref().unwrap_or(USER_AGENT));
+
+    if !options.headers.is_empty() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &options.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name {:?}", name))?;
+            let mut value = HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header {}", name))?;
+            // Keeps tokens out of Debug output should a request ever be logged.
+            if name == AUTHORIZATION || name == PROXY_AUTHORIZATION || name.as_str().contains("token") {
+                value.set_sensitive(true);
+            }
+            headers.append(name, value);
+        }
+        builder = builder.default_headers(headers);
+    }
+
+    if !options.dns_servers.is_empty() {
+        #[cfg(feature = "custom-dns")]
+        {
+            builder = builder.dns_resolver(std::sync::Arc::new(crate::dns::CustomResolver::new(&options.dns_servers)));
+        }
+        #[cfg(not(feature = "custom-dns"))]
+        return Err("--dns-server needs a build with the custom-dns feature".to_string());
+    }
+
+    if let Some(path) = &options.ca_bundle {
+        let pem = fs::read(path).map_err(|e| format!("Cannot read CA bundle {}: {}", path, e))?;
+        let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+        if certificates.is_empty() {
+            return Err(format!("CA bundle {} contains no certificates", path));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&options.client_cert, &options.client_key) {
+        (Some(cert_path), key_path) => {
+            let mut pem = fs::read(cert_path).map_err(|e| format!("Cannot read client certificate {}: {}", cert_path, e))?;
+            if let Some(key_path) = key_path {
+                let key = fs::read(key_path).map_err(|e| format!("Cannot read client key {}: {}", key_path, e))?;
+                pem.push(b'\n');
+                pem.extend_from_slice(&key);
+            }
+            let identity = Identity::from_pem(&pem).map_err(|e| format!("Invalid client certificate {}: {}", cert_path, e))?;
+            builder = builder.identity(identity);
+        }
+        (None, Some(_)) => return Err("--client-key requires --client-cert".to_string()),
+        (None, None) => {}
+    }
+
+    if options.insecure {
+        tracing::warn!("--insecure disables TLS certificate verification for every HTTP request.");
+        tracing::warn!("Public IP and other HTTP facts can be forged by anyone on the network path.");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    let client = builder.build().map_err(|e| format!("Cannot build HTTP client: {}", e))?;
+    Ok(Client { client })
+}
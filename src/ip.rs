@@ -0,0 +1,240 @@
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::cache::{Cache, RateLimiter};
+use crate::capability;
+use crate::echo::{self, EchoEndpoint};
+use crate::errors::{Code, Error};
+#[cfg(target_os = "linux")]
+use crate::exec;
+use crate::facts::Section;
+use crate::http::Client;
+use crate::timestamp::now_secs;
+
+pub const DEFAULT_HTTP_TIMEOUT: u64 = 3;
+const IP_CACHE_ENTRY: &str = "ip";
+const RATE_LIMIT_WINDOW_SECS: u64 = 3600;
+
+pub struct IpOptions {
+    /// Reuse a cached lookup younger than this instead of querying again.
+    pub min_requery_interval: u64,
+    /// Maximum echo-service requests per hour across all runs on this host.
+    pub rate_limit: usize,
+    pub ipv4: bool,
+    pub ipv6: bool,
+    /// Self-hosted endpoints tried before the public services.
+    pub echo_ipv4: Option<EchoEndpoint>,
+    pub echo_ipv6: Option<EchoEndpoint>,
+    /// Seconds each request to an echo service may take.
+    pub timeout: u64,
+}
+
+#[derive(Default)]
+struct Lookup {
+    ip: Option<String>,
+    asn: Option<u32>,
+    source: Option<String>,
+    error: Option<Error>,
+    attempted: bool,
+}
+
+/// Returns the ip section with, when it was served from the cache, the unix time the
+/// cached lookup was made.
+///
+/// The section fails when every lookup attempted failed; one family answering is
+/// enough. IPv6 isn't attempted without a global address, nor is a family the
+/// capability checks turned away.
+pub async fn get_ip_facts(client: &Client, cache: &Cache, options: &IpOptions) -> Section {
+    // Lookups made with a different set of families don't answer this run's question.
+    let cache_entry = match (options.ipv4, options.ipv6) {
+        (true, true) => IP_CACHE_ENTRY.to_string(),
+        (true, false) => format!("{}-ipv4", IP_CACHE_ENTRY),
+        (false, true) => format!("{}-ipv6", IP_CACHE_ENTRY),
+        (false, false) => format!("{}-none", IP_CACHE_ENTRY),
+    };
+    let cached = cache.read(&cache_entry);
+    let mut limiter = RateLimiter::load(cache, options.rate_limit, RATE_LIMIT_WINDOW_SECS);
+    if let Some((stored_at, value)) = cached {
+        // A stale answer is preferable to no answer once the request budget is spent.
+        if now_secs().saturating_sub(stored_at) < options.min_requery_interval || limiter.exhausted() {
+            tracing::debug!(
+                "Reusing the IP lookup cached at {} ({})",
+                stored_at,
+                if limiter.exhausted() { "request budget spent" } else { "younger than --min-requery-interval" }
+            );
+            return Section {
+                cached_at: Some(stored_at),
+                ..Section::new(value)
+            };
+        }
+    }
+
+    let ipv4_urls = vec![
+        "https://ipify.saltbox.dev",
+        "https://ipv4.icanhazip.com",
+    ];
+    let ipv6_urls = vec![
+        "https://ipify6.saltbox.dev",
+        "https://ipv6.icanhazip.com",
+    ];
+
+    let ipv4 = if options.ipv4 {
+        lookup(client, &mut limiter, options.echo_ipv4.as_ref(), &ipv4_urls, false, options.timeout).await
+    } else {
+        Lookup::default()
+    };
+    let (ipv6_present, ipv6_check_error) = if options.ipv6 { has_valid_ipv6().await } else { (false, None) };
+    if options.ipv6 && !ipv6_present {
+        tracing::info!("Skipping the IPv6 lookup: no global IPv6 address ({})", ipv6_check_error.as_ref().map_or("ip -6 addr", |e| e.message.as_str()));
+    }
+
+    let ipv6 = if ipv6_present {
+        lookup(client, &mut limiter, options.echo_ipv6.as_ref(), &ipv6_urls, true, options.timeout).await
+    } else {
+        Lookup::default()
+    };
+
+    let value = json!({
+        "public_ip": ipv4.ip.as_deref().unwrap_or(""),
+        "public_ipv6": ipv6.ip.as_deref().unwrap_or(""),
+        "asn_ipv4": ipv4.asn,
+        "asn_ipv6": ipv6.asn,
+        "source_ipv4": ipv4.source,
+        "source_ipv6": ipv6.source,
+        "error_ipv4": ipv4.error,
+        "error_ipv6": ipv6.error,
+        // A family that was deliberately disabled hasn't failed.
+        "failed_ipv4": options.ipv4 && ipv4.ip.is_none(),
+        "failed_ipv6": options.ipv6 && ipv6.ip.is_none(),
+        "ipv6_check_error": ipv6_check_error,
+        "enabled_ipv4": options.ipv4,
+        "enabled_ipv6": options.ipv6
+    });
+
+    if ipv4.ip.is_some() || ipv6.ip.is_some() {
+        cache.write(&cache_entry, &value);
+    }
+    let attempted: Vec<(&str, &Lookup)> = [("ipv4", &ipv4), ("ipv6", &ipv6)]
+        .into_iter()
+        .filter(|(_, lookup)| lookup.attempted && lookup.error.as_ref().is_none_or(|error| error.code != Code::Disabled))
+        .collect();
+    let failure = match attempted.iter().all(|(_, lookup)| lookup.ip.is_none()) {
+        true => Error::joined(attempted.iter().filter_map(|(family, lookup)| Some(lookup.error.clone()?.context(family))).collect()),
+        false => None,
+    };
+    Section { failure, ..Section::new(value) }
+}
+
+/// Prefers the self-hosted endpoint, falling back to the public services if it fails.
+/// Self-hosted requests don't count against the rate limit, which protects third parties.
+async fn lookup(
+    client: &Client,
+    limiter: &mut RateLimiter<'_>,
+    endpoint: Option<&EchoEndpoint>,
+    urls: &[&str],
+    is_ipv6: bool,
+    timeout_secs: u64,
+) -> Lookup {
+    if let Err(e) = capability::check_network_group(if is_ipv6 { "IPv6 echo services" } else { "IPv4 echo services" }) {
+        return Lookup {
+            error: Some(e),
+            attempted: true,
+            ..Lookup::default()
+        };
+    }
+
+    let mut echo_error = None;
+    if let Some(endpoint) = endpoint {
+        match echo::query(client, endpoint, timeout_secs, is_ipv6).await {
+            Ok(answer) => {
+                return Lookup {
+                    ip: Some(answer.ip),
+                    asn: answer.asn,
+                    source: Some(endpoint.url.clone()),
+                    error: None,
+                    attempted: true,
+                }
+            }
+            Err(e) => {
+                tracing::info!("Self-hosted echo {} failed, falling back to public services: {}", endpoint.url, e);
+                echo_error = Some(e.context("Self-hosted echo failed"));
+            }
+        }
+    }
+
+    let (ip, source, error) = get_ip(client, limiter, urls, is_ipv6, timeout_secs).await;
+    let error = match (echo_error, error) {
+        (Some(echo_error), Some(error)) => Error::joined(vec![echo_error, error]),
+        // A working fallback still reports why the preferred endpoint was skipped.
+        (echo_error, error) => error.or(echo_error),
+    };
+    Lookup {
+        ip,
+        asn: None,
+        source,
+        error,
+        attempted: true,
+    }
+}
+
+async fn get_ip(
+    client: &Client,
+    limiter: &mut RateLimiter<'_>,
+    urls: &[&str],
+    is_ipv6: bool,
+    timeout_secs: u64,
+) -> (Option<String>, Option<String>, Option<Error>) {
+    for url in urls {
+        if !limiter.try_acquire() {
+            tracing::info!("Not querying {}: outbound request rate limit reached", url);
+            return (None, None, Some(Error::new(Code::RateLimited, *url, "Outbound request rate limit reached")));
+        }
+        tracing::debug!("GET {}", url);
+        let request = match client.get(url) {
+            Ok(request) => request,
+            Err(e) => return (None, None, Some(e)),
+        };
+        match timeout(Duration::from_secs(timeout_secs), request.send()).await {
+            Ok(Ok(response)) => {
+                if response.status().is_success() {
+                    if let Ok(ip) = response.text().await {
+                        let ip = ip.trim();
+                        if validate_ip(ip, is_ipv6) {
+                            return (Some(ip.to_string()), Some(url.to_string()), None);
+                        } else {
+                            return (None, None, Some(Error::new(Code::InvalidData, *url, format!("Invalid {} address received.", if is_ipv6 { "IPv6" } else { "IPv4" }))));
+                        }
+                    }
+                } else {
+                    return (None, None, Some(Error::new(Code::of_status(response.status().as_u16()), *url, format!("HTTP {} received from {}.", response.status(), url))));
+                }
+            }
+            Ok(Err(e)) => tracing::info!("{} failed, trying the next service: {}", url, e),
+            Err(_) => tracing::info!("{} timed out after {} s, trying the next service", url, timeout_secs),
+        }
+    }
+    (None, None, Some(Error::new(Code::AllRequestsFailed, urls.join(", "), "All requests failed")))
+}
+
+fn validate_ip(ip: &str, is_ipv6: bool) -> bool {
+    if is_ipv6 {
+        ip.parse::<std::net::Ipv6Addr>().is_ok()
+    } else {
+        ip.parse::<std::net::Ipv4Addr>().is_ok()
+    }
+}
+
+/// Elsewhere there's no `ip`, so the lookup is simply attempted.
+#[cfg(not(target_os = "linux"))]
+async fn has_valid_ipv6() -> (bool, Option<Error>) {
+    (true, None)
+}
+
+#[cfg(target_os = "linux")]
+async fn has_valid_ipv6() -> (bool, Option<Error>) {
+    match exec::output("ip", ["-6", "addr", "show", "scope", "global"]).await {
+        Ok(output) => (!output.stdout.is_empty(), None),
+        Err(e) => (false, Some(Error { message: format!("Error checking IPv6: {}", e), ..Error::run("ip", &e) })),
+    }
+}
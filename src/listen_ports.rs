@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::docker;
+use crate::errors::Error;
+use crate::processes::{self, PROC_PATH};
+
+/// Socket tables under /proc/net, their protocol and the state a listening socket
+/// is in: LISTEN for TCP, and for UDP unconnected (which the kernel prints as CLOSE).
+const TABLES: &[(&str, &str, &str)] = &[("tcp", "tcp", "0A"), ("tcp6", "tcp", "0A"), ("udp", "udp", "07"), ("udp6", "udp", "07")];
+
+/// Listening TCP and bound UDP sockets on the host with the process that owns each
+/// and, where there is one, its Docker container: a process in a container's cgroup
+/// (host networking), or a published port the daemon forwards to a container.
+///
+/// Sockets inside a container's own network namespace aren't visible here; those
+/// the container publishes are. Without root, the owners of other users' sockets are
+/// unknown.
+pub async fn get_listen_ports() -> Value {
+    let mut sockets = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (table, protocol, listening) in TABLES {
+        let path = format!("{}/net/{}", PROC_PATH, table);
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                for (address, port, inode) in contents.lines().skip(1).filter_map(|line| parse_socket(line, listening)) {
+                    // SO_REUSEPORT workers share an address; they're listed once.
+                    sockets.entry((port, *protocol, address)).or_insert(inode);
+                }
+            }
+            // No IPv6 in this kernel.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => errors.push(Error::read(&path, &e)),
+        }
+    }
+
+    let owners = socket_owners();
+    let (names, published) = match docker::get("/containers/json").await {
+        Ok(containers) => (docker::container_names(&containers), published_ports(&containers)),
+        Err(e) => {
+            tracing::debug!("Not mapping ports to containers: {}", e);
+            (HashMap::new(), HashMap::new())
+        }
+    };
+    let ports: Vec<Value> = sockets
+        .into_iter()
+        .map(|((port, protocol, address), inode)| {
+            let pid = owners.get(&inode).copied();
+            let by_cgroup =
+                pid.and_then(processes::cgroup).and_then(|cgroup| docker::container_id(&cgroup).map(|id| names.get(id).cloned().unwrap_or_else(|| id[..12].to_string())));
+            let (container, mapped_by) = match (by_cgroup, published.get(&(protocol, port))) {
+                (Some(container), _) => (Some(container), Some("cgroup")),
+                (None, Some(container)) => (Some(container.clone()), Some("published")),
+                (None, None) => (None, None),
+            };
+            json!({
+                "protocol": protocol,
+                "address": address.to_string(),
+                "port": port,
+                "pid": pid,
+                "process": pid.and_then(|pid| fs::read_to_string(format!("{}/{}/comm", PROC_PATH, pid)).ok()).map(|comm| comm.trim().to_string()),
+                "container": container,
+                "mapped_by": mapped_by
+            })
+        })
+        .collect();
+    json!({ "errors": errors, "ports": ports })
+}
+
+/// `sl local_address rem_address st ... inode` with addresses as hex words in host
+/// byte order: the local address, port and inode of a socket in `listening` state.
+fn parse_socket(line: &str, listening: &str) -> Option<(IpAddr, u16, u64)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.get(3) != Some(&listening) {
+        return None;
+    }
+    let (address, port) = fields.get(1)?.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok().filter(|port| *port != 0)?;
+    let words: Vec<[u8; 4]> =
+        (0..address.len() / 8).map(|index| u32::from_str_radix(address.get(index * 8..index * 8 + 8)?, 16).ok().map(u32::to_ne_bytes)).collect::<Option<_>>()?;
+    let address = match words[..] {
+        [word] => IpAddr::V4(Ipv4Addr::from(word)),
+        [a, b, c, d] => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from([a, b, c, d].concat()).ok()?)),
+        _ => return None,
+    };
+    Some((address, port, fields.get(9)?.parse().ok()?))
+}
+
+/// The pid holding each socket inode open, from the `socket:[inode]` links in
+/// every process's fd directory.
+fn socket_owners() -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    for entry in fs::read_dir(PROC_PATH).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        for fd in fs::read_dir(entry.path().join("fd")).into_iter().flatten().filter_map(|fd| fd.ok()) {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            if let Some(inode) = target.to_str().and_then(|target| target.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()) {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
+
+/// The container each published host port forwards to, by protocol and port.
+fn published_ports(containers: &Value) -> HashMap<(&'static str, u16), String> {
+    let mut published = HashMap::new();
+    for container in containers.as_array().into_iter().flatten() {
+        let name = container.pointer("/Names/0").and_then(Value::as_str).unwrap_or_default().trim_start_matches('/');
+        for mapping in container.get("Ports").and_then(Value::as_array).into_iter().flatten() {
+            let Some(port) = mapping.get("PublicPort").and_then(Value::as_u64).and_then(|port| u16::try_from(port).ok()) else {
+                continue;
+            };
+            let protocol = match mapping.get("Type").and_then(Value::as_str) {
+                Some("udp") => "udp",
+                Some("tcp") => "tcp",
+                _ => continue,
+            };
+            published.insert((protocol, port), name.to_string());
+        }
+    }
+    published
+}
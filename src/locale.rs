@@ -0,0 +1,105 @@
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{Code, Error};
+
+pub const LOCALE_DIR_PATH: &str = "/usr/lib/locale";
+pub const LOCALE_ARCHIVE_PATH: &str = "/usr/lib/locale/locale-archive";
+
+/// `AR_MAGIC` from glibc's locarchive.h.
+const LOCALE_ARCHIVE_MAGIC: u32 = 0xde020109;
+/// Size of a `struct namehashent` (hashval, name_offset, locrec_offset).
+const NAMEHASH_ENTRY_SIZE: usize = 12;
+
+/// Enumerates compiled locales the same way `locale -a` does, without spawning it.
+pub fn get_locales() -> Value {
+    let mut locales: BTreeSet<String> = ["C", "POSIX"].iter().map(|s| s.to_string()).collect();
+    let mut errors = Vec::new();
+
+    match fs::read(LOCALE_ARCHIVE_PATH) {
+        Ok(data) => match parse_locale_archive(&data) {
+            Ok(names) => locales.extend(names),
+            Err(e) => errors.push(Error::new(Code::InvalidData, LOCALE_ARCHIVE_PATH, format!("Error parsing {}: {}", LOCALE_ARCHIVE_PATH, e))),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => tracing::debug!("No {}; only locale directories are listed", LOCALE_ARCHIVE_PATH),
+        Err(e) => errors.push(Error::new(Code::of_io(&e), LOCALE_ARCHIVE_PATH, format!("Error reading {}: {}", LOCALE_ARCHIVE_PATH, e))),
+    }
+
+    if let Ok(entries) = fs::read_dir(LOCALE_DIR_PATH) {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if is_locale_dir(&entry.path()) {
+                locales.insert(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    let has_en_us_utf8 = locales.iter().any(|name| normalize_locale(name) == "en_US.utf8");
+
+    json!({
+        "available": locales,
+        "has_en_us_utf8": has_en_us_utf8,
+        "error": Error::joined(errors)
+    })
+}
+
+/// Lowercases the codeset and strips dashes, matching glibc's name normalization
+/// (`en_US.UTF-8` and `en_US.utf8` are the same locale).
+fn normalize_locale(name: &str) -> String {
+    match name.split_once('.') {
+        Some((language, rest)) => {
+            let (codeset, modifier) = match rest.split_once('@') {
+                Some((codeset, modifier)) => (codeset, Some(modifier)),
+                None => (rest, None),
+            };
+            let codeset: String = codeset
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .map(|c| c.to_ascii_lowercase())
+                .collect();
+            match modifier {
+                Some(modifier) => format!("{}.{}@{}", language, codeset, modifier),
+                None => format!("{}.{}", language, codeset),
+            }
+        }
+        None => name.to_string(),
+    }
+}
+
+fn parse_locale_archive(data: &[u8]) -> Result<Vec<String>, String> {
+    let read_u32 = |offset: usize| -> Result<u32, String> {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(|| format!("truncated archive at offset {}", offset))
+    };
+
+    if read_u32(0)? != LOCALE_ARCHIVE_MAGIC {
+        return Err("bad magic number".to_string());
+    }
+
+    let namehash_offset = read_u32(8)? as usize;
+    let namehash_size = read_u32(16)? as usize;
+
+    let mut names = Vec::new();
+    for index in 0..namehash_size {
+        let entry = namehash_offset + index * NAMEHASH_ENTRY_SIZE;
+        let name_offset = read_u32(entry + 4)? as usize;
+        let locrec_offset = read_u32(entry + 8)?;
+        if name_offset == 0 || locrec_offset == 0 {
+            continue;
+        }
+        let name = data
+            .get(name_offset..)
+            .and_then(|rest| rest.split(|&b| b == 0).next())
+            .ok_or_else(|| format!("name offset {} out of range", name_offset))?;
+        names.push(String::from_utf8_lossy(name).into_owned());
+    }
+
+    Ok(names)
+}
+
+/// Returns true if `path` looks like a compiled locale directory rather than the archive.
+fn is_locale_dir(path: &Path) -> bool {
+    path.is_dir() && path.join("LC_CTYPE").exists()
+}
@@ -0,0 +1,108 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::cache::Cache;
+use crate::errors::Error;
+use crate::timestamp::{format_rfc3339, now_secs};
+
+/// System logs and Docker's json-file container logs.
+pub const DEFAULT_LOG_PATHS: &[&str] = &["/var/log", "/var/lib/docker/containers"];
+/// Files tracked per path; the sizes of each are kept in the cache between runs.
+const MAX_FILES: usize = 10_000;
+/// Files listed per path under `fastest_growing`.
+const FASTEST: usize = 5;
+const CACHE_ENTRY: &str = "log_growth";
+
+/// The size of each log file or directory and how fast it grew since the previous
+/// run, whose file sizes are kept in the cache, so a policy assertion can catch a
+/// runaway container log before it fills the disk.
+///
+/// A file that shrank was rotated or truncated, and one that's new was created
+/// since: either way its growth is its current size. Growth is null on the first
+/// run, with nothing to compare against.
+pub fn get_log_growth(cache: &Cache, paths: &[String]) -> Value {
+    let previous = cache.read(CACHE_ENTRY);
+    let now = now_secs();
+    let interval = previous.as_ref().map(|(stored_at, _)| now.saturating_sub(*stored_at)).filter(|interval| *interval > 0);
+    let previous_sizes = previous.as_ref().and_then(|(_, value)| value.as_object());
+    let per_hour = |growth: u64| interval.map(|interval| growth * 3600 / interval);
+
+    let mut reports = Map::new();
+    let mut sizes = Map::new();
+    for path in paths {
+        let mut files = BTreeMap::new();
+        let (exists, error, complete) = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => {
+                files.insert(path.clone(), metadata.len());
+                (true, None, true)
+            }
+            Ok(_) => (true, None, collect(Path::new(path), &mut files)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (false, None, true),
+            Err(e) => (false, Some(Error::read(path, &e)), true),
+        };
+
+        let mut growing = Vec::new();
+        for (file, &size) in &files {
+            if interval.is_some() {
+                let before = previous_sizes.and_then(|sizes| sizes.get(file)?.as_u64()).filter(|before| *before <= size).unwrap_or(0);
+                growing.push((file.as_str(), size - before));
+            }
+            sizes.insert(file.clone(), json!(size));
+        }
+        let growth_total = interval.map(|_| growing.iter().map(|(_, growth)| growth).sum::<u64>());
+        growing.retain(|(_, growth)| *growth > 0);
+        growing.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let fastest: Map<String, Value> = growing.into_iter().take(FASTEST).map(|(file, growth)| (file.to_string(), json!(per_hour(growth)))).collect();
+
+        reports.insert(
+            path.clone(),
+            json!({
+                "exists": exists,
+                "error": error,
+                "complete": complete,
+                "files": files.len(),
+                "size_bytes": files.values().sum::<u64>(),
+                "growth_bytes": growth_total,
+                "bytes_per_hour": growth_total.and_then(per_hour),
+                "fastest_growing": fastest
+            }),
+        );
+    }
+    cache.write(CACHE_ENTRY, &Value::Object(sizes));
+
+    json!({
+        "previous_run": previous.map(|(stored_at, _)| format_rfc3339(UNIX_EPOCH + Duration::from_secs(stored_at))),
+        "interval_secs": interval,
+        "paths": reports
+    })
+}
+
+/// Adds the size of every regular file below `dir`, without following symlinks, up
+/// to [`MAX_FILES`]; false when that limit cut the walk short.
+fn collect(dir: &Path, files: &mut BTreeMap<String, u64>) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+    // In name order, so a walk cut short keeps the same files every run.
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if !collect(&entry.path(), files) {
+                return false;
+            }
+        } else if metadata.is_file() {
+            if files.len() >= MAX_FILES {
+                return false;
+            }
+            files.insert(entry.path().display().to_string(), metadata.len());
+        }
+    }
+    true
+}
@@ -0,0 +1,34 @@
+//! Diagnostics on stderr, so they never mix with the document on stdout.
+
+use tracing::level_filters::LevelFilter;
+
+/// Parses `--log-level`.
+pub fn parse_level(value: &str) -> Result<LevelFilter, String> {
+    match value {
+        "off" => Ok(LevelFilter::OFF),
+        "error" => Ok(LevelFilter::ERROR),
+        "warn" => Ok(LevelFilter::WARN),
+        "info" => Ok(LevelFilter::INFO),
+        "debug" => Ok(LevelFilter::DEBUG),
+        "trace" => Ok(LevelFilter::TRACE),
+        _ => Err(format!("Unknown log level: {} (expected off, error, warn, info, debug or trace)", value)),
+    }
+}
+
+/// The level `-v` flags ask for: warnings by default, then info, debug and trace.
+pub fn verbosity_level(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+pub fn init(level: LevelFilter) {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_target(false)
+        .init();
+}
@@ -1,148 +1,429 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::process::Command;
-use std::time::Duration;
-use tokio::time::timeout;
-use reqwest::Client;
-use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::env;
-
-const TIMEOUT: u64 = 3;
-const GROUP_FILE_PATH: &str = "/etc/group";
-const PASSWD_FILE_PATH: &str = "/etc/passwd";
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+use serde_json::Value;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use facts::Facts;
+use output::OutputFormat;
+
+mod accounts;
+mod ansible;
+mod bench;
+mod binaries;
+mod build_info;
+mod cache;
+mod capability;
+mod cli;
+mod clock;
+mod cloudflared;
+mod collectors;
+mod compare;
+mod completions;
+mod config;
+mod connectivity;
+mod container_restarts;
+mod coredumps;
+mod databases;
+mod dbus;
+mod derived;
+mod deterministic;
+mod dhcp_leases;
+mod diff;
+mod dir_sizes;
+mod dns;
+mod docker;
+mod docker_daemon;
+mod docker_images;
+mod docker_networks;
+mod docker_stats;
+mod document_schema;
+mod doctor;
+mod download_clients;
+mod drop_ins;
+mod echo;
+mod environment;
+mod errors;
+mod exec;
+mod expr;
+mod facter;
+mod facts;
+mod fingerprint;
+mod flatten;
+mod gpu;
+mod http;
+mod ip;
+mod listen_ports;
+mod locale;
+mod log_growth;
+mod logging;
+mod media_servers;
+mod mmap;
+mod mounts;
+mod nagios;
+mod netplan;
+mod network_managers;
+mod output;
+mod ownership;
+mod platform;
+mod policy;
+mod processes;
+mod progress;
+mod query;
+mod region;
+mod remote;
+mod schema;
+mod schema_version;
+mod section_cache;
+mod serve;
+mod shares;
+mod shutdown;
+mod sso;
+mod static_facts;
+mod swap;
+mod systemd_mounts;
+mod timestamp;
+mod timezone;
+mod tool_versions;
+mod traefik;
+mod validate;
+mod vpn_gateways;
+mod wake_on_lan;
+mod wireguard;
+mod worker;
+
+use build_info::VERSION;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let client = Client::new();
-
-    let ipv4_urls = vec![
-        "https://ipify.saltbox.dev",
-        "https://ipv4.icanhazip.com",
-    ];
-    let ipv6_urls = vec![
-        "https://ipify6.saltbox.dev",
-        "https://ipv6.icanhazip.com",
-    ];
-
-    let (ipv4, ipv4_error) = get_ip(&client, &ipv4_urls, false).await;
-    let (ipv6_present, ipv6_check_error) = has_valid_ipv6();
-
-    let (ipv6, ipv6_error) = if ipv6_present {
-        get_ip(&client, &ipv6_urls, true).await
-    } else {
-        (None, None)
+async fn main() {
+    let command = cli::Command::parse().unwrap_or_else(|e| e.exit());
+
+    let level = match &command {
+        cli::Command::Gather(args) | cli::Command::Worker(_, args) => args.log_level(),
+        cli::Command::Bench(args) => args.gather.log_level(),
+        cli::Command::Serve(args) => args.gather.log_level(),
+        _ => logging::verbosity_level(0),
     };
+    logging::init(level);
 
-    let groups_data = parse_file(GROUP_FILE_PATH, 3)?;
-    let users_data = parse_file(PASSWD_FILE_PATH, 7)?;
-    let timezone_data = get_timezone()?;
-
-    let result = json!({
-        "saltbox_facts_version": VERSION,
-        "ip": {
-            "public_ip": ipv4.as_deref().unwrap_or(""),
-            "public_ipv6": ipv6.as_deref().unwrap_or(""),
-            "error_ipv4": ipv4_error,
-            "error_ipv6": ipv6_error,
-            "failed_ipv4": ipv4.is_none(),
-            "failed_ipv6": ipv6.is_none(),
-            "ipv6_check_error": ipv6_check_error
-        },
-        "groups": groups_data,
-        "users": users_data,
-        "timezone": timezone_data
-    });
-
-    println!("{}", serde_json::to_string(&result)?);
-    Ok(())
+    let result = match command {
+        cli::Command::Gather(args) => run(*args).await,
+        cli::Command::Remote(args) => remote::run(args).await,
+        cli::Command::Compare(args) => compare::run(args),
+        cli::Command::Validate(args) => validate::run(args),
+        cli::Command::Bench(args) => bench::run(*args).await,
+        cli::Command::Doctor(args) => doctor::run(args).await,
+        cli::Command::Schema(schema_version) => print_schema(schema_version),
+        cli::Command::Cache(args) => cache::run(args),
+        cli::Command::Serve(args) => serve::run(*args).await,
+        cli::Command::Completions(shell) => completions::run(shell),
+        cli::Command::Worker(collector, args) => worker::run(collector, *args).await,
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
 
-async fn get_ip(client: &Client, urls: &[&str], is_ipv6: bool) -> (Option<String>, Option<String>) {
-    for url in urls {
-        match timeout(Duration::from_secs(TIMEOUT), client.get(*url).send()).await {
-            Ok(Ok(response)) => {
-                if response.status().is_success() {
-                    if let Ok(ip) = response.text().await {
-                        let ip = ip.trim();
-                        if validate_ip(ip, is_ipv6) {
-                            return (Some(ip.to_string()), None);
-                        } else {
-                            return (None, Some(format!("Invalid {} address received.", if is_ipv6 { "IPv6" } else { "IPv4" })));
+async fn run(mut args: cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.list_collectors {
+        collectors::print_list()?;
+        return Ok(());
+    }
+
+    capability::restrict(args.no_exec, args.offline);
+    let context = collectors::Context::new(&args)?;
+    let mut shutdown = shutdown::Shutdown::listen();
+    let selected: Vec<&collectors::Collector> = collectors::COLLECTORS.iter().filter(|collector| args.selects(collector)).collect();
+    let names: Vec<&str> = selected.iter().map(|collector| collector.name).collect();
+    let mut progress = progress::Progress::start(args.progress.as_ref(), &names);
+    let mut facts = Facts::new();
+    let mut timings = serde_json::Map::new();
+    let deadline = (args.timeout > 0).then(|| tokio::time::Instant::now() + Duration::from_secs(args.timeout));
+    let expired = format!("--timeout of {} s reached", args.timeout);
+    // When nothing after the collectors needs the whole document, each section is
+    // written as its collector finishes, and the rest of the document after them.
+    let mut stream = match streams(&args) {
+        true => Some(output::ObjectWriter::new(destination(&args)?, pretty(&args))),
+        false => None,
+    };
+    for collector in selected {
+        let started = Instant::now();
+        let status = if let Some(signal) = shutdown.received() {
+            facts.cancel(collector.name, shutdown::signal_name(signal));
+            "cancelled"
+        } else if let Some(reason) = collectors::skip_reason(collector, &args) {
+            facts.skip(collector.name, reason);
+            "skipped"
+        } else if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+            facts.time_out(collector.name, &expired);
+            "timed_out"
+        } else {
+            progress.running(collector.name);
+            tokio::select! {
+                section = collectors::collect(collector.name, &context) => match section {
+                    Ok(section) => {
+                        let failure = section.failure.clone();
+                        let status = if section.cached_at.is_some() { "cached" } else { "ok" };
+                        match stream.as_mut() {
+                            Some(stream) => {
+                                write_section(stream, collector.name, &section.value, &args)?;
+                                facts.insert_streamed(collector.name, section);
+                            }
+                            None => facts.insert_section(collector.name, section)?,
+                        }
+                        match failure {
+                            Some(reason) => {
+                                tracing::warn!("{} failed: {}", collector.name, reason);
+                                facts.fail(collector.name, &reason);
+                                "failed"
+                            }
+                            None => status,
                         }
                     }
-                } else {
-                    return (None, Some(format!("HTTP {} received from {}.", response.status(), url)));
+                    Err(e) if e.is::<worker::TimedOut>() => {
+                        facts.time_out(collector.name, &e.to_string());
+                        "timed_out"
+                    }
+                    Err(e) => {
+                        progress.finished(collector.name, "failed", started.elapsed());
+                        return Err(e);
+                    }
+                },
+                signal = shutdown.wait() => {
+                    facts.cancel(collector.name, shutdown::signal_name(signal));
+                    "cancelled"
+                }
+                _ = expire(deadline) => {
+                    facts.time_out(collector.name, &expired);
+                    "timed_out"
                 }
             }
-            _ => continue,
+        };
+        tracing::info!("{}: {} in {} ms", collector.name, status, started.elapsed().as_millis());
+        progress.finished(collector.name, status, started.elapsed());
+        let (commands, endpoints) = capability::take_usage();
+        if args.profile {
+            timings.insert(
+                collector.name.to_string(),
+                serde_json::json!({
+                    "status": status,
+                    "duration_ms": started.elapsed().as_millis() as u64,
+                    "commands": commands,
+                    "endpoints": endpoints,
+                    "files": collector.sources
+                }),
+            );
+        }
+        if let OutputFormat::Ndjson = args.format {
+            write_ndjson_section(collector.name, status, facts.section(collector.name), &args)?;
         }
     }
-    (None, Some("All requests failed".to_string()))
-}
+    facts.flag_violations(capability::take_violations());
 
-fn validate_ip(ip: &str, is_ipv6: bool) -> bool {
-    if is_ipv6 {
-        ip.parse::<std::net::Ipv6Addr>().is_ok()
-    } else {
-        ip.parse::<std::net::Ipv4Addr>().is_ok()
+    // Nothing is printed, so an existing --output or --facts-d file keeps the last
+    // complete facts.
+    let failures = facts.failures();
+    if args.strict && !failures.is_empty() {
+        let failures: Vec<String> = failures.iter().map(|(name, reason)| format!("{}: {}", name, reason)).collect();
+        return Err(format!("Collectors failed (--strict): {}", failures.join("; ")).into());
+    }
+    let exit_code = facts.exit_code();
+    let mut result = facts.into_value(VERSION);
+    schema_version::shape(&mut result, args.schema_version);
+    if args.profile {
+        result["timings"] = Value::Object(timings);
+    }
+
+    let drop_ins = drop_ins::apply(&mut result, std::path::Path::new(&args.drop_in_dir));
+    if args.schema_version >= 2 {
+        result["drop_ins"] = drop_ins;
+    }
+
+    if let Some(path) = &args.static_facts {
+        static_facts::apply(&mut result, std::path::Path::new(path), &args.static_precedence)?;
+    }
+
+    let mut definitions = match &args.derived_facts {
+        Some(path) => derived::load_definitions(std::path::Path::new(path))?,
+        None => Vec::new(),
+    };
+    definitions.extend(std::mem::take(&mut args.derive));
+    if !definitions.is_empty() || args.schema_version >= 2 {
+        derived::apply(&mut result, &definitions);
+    }
+
+    let mut rules = match &args.assertions_file {
+        Some(path) => policy::load_rules(std::path::Path::new(path))?,
+        None => Vec::new(),
+    };
+    rules.extend(std::mem::take(&mut args.assertions));
+    let failed_assertions = if rules.is_empty() { 0 } else { policy::apply(&mut result, &rules) };
+
+    if args.deterministic {
+        deterministic::normalize(&mut result);
+    }
+
+    if let Some(mut stream) = stream {
+        for (key, value) in result.as_object().into_iter().flatten().filter(|(key, _)| collectors::find(key).is_none()) {
+            stream.entry(key, value)?;
+        }
+        stream.finish()?.commit()?;
+        return finish(shutdown, exit_code);
+    }
+
+    // The sections are out already; the last line is everything else.
+    if let (OutputFormat::Ndjson, Value::Object(document)) = (&args.format, &mut result) {
+        document.retain(|key, _| collectors::find(key).is_none());
+        result = serde_json::json!({ "category": "envelope", "status": "ok", "facts": result });
     }
+
+    if let OutputFormat::Nagios = args.format {
+        let (code, report) = nagios::report(&result, &args.checks);
+        print!("{}", report);
+        std::process::exit(code);
+    }
+
+    if args.facter {
+        result = facter::to_facter(&result);
+    }
+    if let Some(namespace) = &args.namespace {
+        result = serde_json::json!({ namespace.as_str(): result });
+    }
+
+    if let Some(path) = &args.diff {
+        let previous = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let previous: Value = serde_json::from_str(&previous).map_err(|e| format!("Cannot parse {}: {}", path, e))?;
+        result = diff::render(&diff::diff(&previous, &result), &args.diff_format);
+    }
+
+    if let Some(query) = &args.query {
+        result = query.evaluate(&result);
+    }
+    if args.flatten {
+        result = flatten::flatten(&result);
+    }
+
+    let mut destination = destination(&args)?;
+    write_output(&mut destination, &result, &args.format, args.query.is_some(), args.only.as_deref(), pretty(&args))?;
+    destination.commit()?;
+
+    if args.strict && failed_assertions > 0 {
+        return Err(format!("{} assertion{} failed", failed_assertions, if failed_assertions == 1 { "" } else { "s" }).into());
+    }
+    finish(shutdown, exit_code)
 }
 
-fn has_valid_ipv6() -> (bool, Option<String>) {
-    match Command::new("ip").args(&["-6", "addr", "show", "scope", "global"]).output() {
-        Ok(output) => (!output.stdout.is_empty(), None),
-        Err(e) => (false, Some(format!("Error checking IPv6: {}", e))),
+/// Exits as a run that printed its document should: with the signal's status when
+/// one interrupted it, else with `exit_code`.
+fn finish(shutdown: shutdown::Shutdown, exit_code: i32) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(signal) = shutdown.received() {
+        tracing::warn!("Interrupted by {}; printed partial facts", shutdown::signal_name(signal));
+        std::process::exit(shutdown::exit_code(signal));
     }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
 }
 
-fn parse_file(file_path: &str, min_tokens: usize) -> Result<Value, Box<dyn std::error::Error>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut data = HashMap::new();
-
-    for line in reader.lines() {
-        let line = line?;
-        let tokens: Vec<&str> = line.split(':').collect();
-        if tokens.len() >= min_tokens {
-            let value = if file_path == GROUP_FILE_PATH {
-                json!({
-                    "gid": tokens[2],
-                    "group-list": tokens.get(3).map_or(Vec::new(), |&s| s.split(',').map(String::from).collect::<Vec<_>>())
-                })
-            } else {
-                json!({
-                    "uid": tokens[2],
-                    "gid": tokens[3],
-                    "comment": tokens[4],
-                    "home": tokens[5],
-                    "shell": tokens[6],
-                })
-            };
-            data.insert(tokens[0].to_string(), value);
-        }
+/// Whether the document can be written a section at a time: it's JSON, and nothing
+/// between collecting and printing works on the document as a whole. `--strict`
+/// prints nothing when a collector fails, so it waits for them all.
+fn streams(args: &cli::Args) -> bool {
+    matches!(args.format, OutputFormat::Json)
+        && !args.strict
+        && args.query.is_none()
+        && args.diff.is_none()
+        && !args.flatten
+        && !args.facter
+        && args.namespace.is_none()
+        && args.static_facts.is_none()
+        && args.derived_facts.is_none()
+        && args.derive.is_empty()
+        && args.assertions_file.is_none()
+        && args.assertions.is_empty()
+}
+
+/// `--output`, else the `--facts-d` file, else stdout.
+fn destination(args: &cli::Args) -> Result<output::Destination, Box<dyn std::error::Error>> {
+    if let Some(dir) = args.facts_d.as_deref().and_then(|path| std::path::Path::new(path).parent()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
     }
+    // A fact file must not be executable, or Ansible runs it instead of reading it;
+    // AtomicFile creates it afresh with default permissions.
+    Ok(match args.output.as_ref().or(args.facts_d.as_ref()) {
+        Some(path) => output::Destination::File(output::AtomicFile::create(std::path::Path::new(path))?),
+        None => output::Destination::Stdout(std::io::stdout().lock()),
+    })
+}
 
-    Ok(json!(data))
+/// JSON is indented on a terminal, unless `--compact`.
+fn pretty(args: &cli::Args) -> bool {
+    args.output.is_none() && args.facts_d.is_none() && !args.compact && std::io::stdout().is_terminal()
+}
+
+fn print_schema(schema_version: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = document_schema::document_schema(VERSION, schema_version);
+    output::write_json(std::io::stdout().lock(), &schema, std::io::stdout().is_terminal())?;
+    Ok(())
 }
 
-fn get_timezone() -> Result<Value, Box<dyn std::error::Error>> {
-    if let Ok(tz) = env::var("TZ") {
-        return Ok(json!({ "timezone": tz }));
+/// Writes the final document; a `--query` result prints strings raw, and `pretty`
+/// indents JSON.
+fn write_output(
+    writer: &mut dyn Write,
+    result: &Value,
+    format: &OutputFormat,
+    queried: bool,
+    only: Option<&str>,
+    pretty: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match (format, queried) {
+        (OutputFormat::Nagios, _) => unreachable!("nagios output is printed before --diff and --query"),
+        (OutputFormat::Csv, _) => writer.write_all(output::render_csv(result, only.unwrap_or_default())?.as_bytes())?,
+        (OutputFormat::Json, true) => writeln!(writer, "{}", query::render_raw(result))?,
+        (OutputFormat::Json, false) => output::write_json(writer, result, pretty)?,
+        (OutputFormat::Yaml, _) => output::write_yaml(writer, result)?,
+        (OutputFormat::Msgpack, _) => output::write_msgpack(writer, result)?,
+        (OutputFormat::Env, _) => output::write_env(writer, result)?,
+        (OutputFormat::Ndjson, _) => output::write_json(writer, result, false)?,
     }
+    Ok(())
+}
 
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg("cat /etc/timezone 2>/dev/null || ls -l /etc/localtime | sed 's/.* -> //' | sed 's/^.*zoneinfo\\///'")
-        .output()?;
+/// Prints one collector's `--format ndjson` line as soon as it finishes; `facts` is
+/// null unless it produced a section.
+fn write_ndjson_section(name: &str, status: &str, facts: Option<&Value>, args: &cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    let line = serde_json::json!({ "category": name, "status": status, "facts": shaped(name, facts.cloned().unwrap_or_default(), args) });
+    output::write_json(std::io::stdout().lock(), &line, false)?;
+    Ok(())
+}
 
-    if output.status.success() {
-        let tz = String::from_utf8(output.stdout)?.trim().to_string();
-        if !tz.is_empty() {
-            return Ok(json!({ "timezone": tz }));
-        }
+/// Writes a section into a streamed document, serializing it straight into the
+/// output unless it needs shaping or normalizing first.
+fn write_section<W: Write>(stream: &mut output::ObjectWriter<W>, name: &str, value: &facts::Payload, args: &cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.schema_version >= 2 && !args.deterministic {
+        stream.entry(name, value)?;
+    } else {
+        stream.entry(name, &shaped(name, value.to_json()?, args))?;
+    }
+    Ok(())
+}
+
+/// A section as the document prints it: shaped for `--schema-version`, and
+/// normalized with `--deterministic`.
+fn shaped(name: &str, value: Value, args: &cli::Args) -> Value {
+    let mut section = serde_json::json!({ name: value });
+    schema_version::shape_section(&mut section[name], args.schema_version);
+    if args.deterministic {
+        deterministic::normalize(&mut section);
     }
+    section[name].take()
+}
 
-    Ok(json!({ "timezone": "Etc/UTC" }))
+/// Resolves at the `--timeout` deadline, or never without one.
+async fn expire(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
 }
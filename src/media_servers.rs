@@ -0,0 +1,211 @@
+use reqwest::header::ACCEPT;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::time::Duration;
+
+use crate::errors::{Code, Error};
+use crate::http::Client;
+
+pub const DEFAULT_PLEX_URL: &str = "http://127.0.0.1:32400";
+pub const DEFAULT_PLEX_PREFERENCES_PATH: &str = "/opt/plex/Library/Application Support/Plex Media Server/Preferences.xml";
+pub const DEFAULT_JELLYFIN_URL: &str = "http://127.0.0.1:8096";
+pub const DEFAULT_EMBY_URL: &str = "http://127.0.0.1:8096";
+
+const API_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Plex Media Server: version and claim state from `/identity` (no token needed),
+/// then, with the `PlexOnlineToken` from Preferences.xml, the transcoder settings
+/// and item counts per library.
+pub async fn get_plex(client: &Client, url: &str, preferences_path: &str) -> (Value, Option<Error>) {
+    let url = url.trim_end_matches('/');
+    let mut section = json!({
+        "available": false,
+        "error": Value::Null,
+        "url": url,
+        "version": Value::Null,
+        "claimed": Value::Null,
+        "machine_identifier": Value::Null,
+        "transcoder": Value::Null,
+        "libraries": Value::Null,
+        "auth_error": Value::Null
+    });
+    let identity = match get_json(client, &format!("{}/identity", url), None).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            let failure = e.as_failure();
+            section["error"] = json!(e);
+            return (section, failure);
+        }
+    };
+    let identity = &identity["MediaContainer"];
+    section["available"] = json!(true);
+    section["version"] = json!(identity.get("version"));
+    section["claimed"] = json!(identity.get("claimed"));
+    section["machine_identifier"] = json!(identity.get("machineIdentifier"));
+
+    let token = match fs::read_to_string(preferences_path) {
+        Ok(preferences) => match xml_attribute(&preferences, "PlexOnlineToken") {
+            Some(token) => token,
+            None => return set_auth_error(section, Error::new(Code::Unauthorized, preferences_path, format!("No PlexOnlineToken in {}; the server isn't claimed", preferences_path))),
+        },
+        Err(e) => return set_auth_error(section, Error::read(preferences_path, &e)),
+    };
+    let auth = Some(("X-Plex-Token", token.as_str()));
+
+    match get_json(client, &format!("{}/:/prefs", url), auth).await {
+        Ok(prefs) => {
+            let transcoder: Map<String, Value> = prefs
+                .pointer("/MediaContainer/Setting")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|setting| Some((setting.get("id")?.as_str()?, setting.get("value")?)))
+                .filter(|(id, _)| id.starts_with("Transcode") || id.starts_with("HardwareAccelerated"))
+                .map(|(id, value)| (id.to_string(), value.clone()))
+                .collect();
+            section["transcoder"] = Value::Object(transcoder);
+        }
+        Err(e) => return set_auth_error(section, e),
+    }
+
+    let sections = match get_json(client, &format!("{}/library/sections", url), auth).await {
+        Ok(sections) => sections,
+        Err(e) => return set_auth_error(section, e),
+    };
+    let mut libraries = Vec::new();
+    for library in sections.pointer("/MediaContainer/Directory").and_then(Value::as_array).into_iter().flatten() {
+        let key = library.get("key").and_then(Value::as_str).unwrap_or_default();
+        // A zero-size page still reports the library's totalSize.
+        let count = get_json(client, &format!("{}/library/sections/{}/all?X-Plex-Container-Start=0&X-Plex-Container-Size=0", url, key), auth)
+            .await
+            .ok()
+            .and_then(|page| page.pointer("/MediaContainer/totalSize").cloned());
+        libraries.push(json!({ "title": library.get("title"), "type": library.get("type"), "items": count }));
+    }
+    section["libraries"] = json!(libraries);
+    (section, None)
+}
+
+/// A Jellyfin server: see [`get_emby_like`].
+pub async fn get_jellyfin(client: &Client, url: &str, token_file: Option<&str>) -> (Value, Option<Error>) {
+    get_emby_like(client, url, token_file, "Jellyfin").await
+}
+
+/// An Emby server: see [`get_emby_like`].
+pub async fn get_emby(client: &Client, url: &str, token_file: Option<&str>) -> (Value, Option<Error>) {
+    get_emby_like(client, url, token_file, "Emby").await
+}
+
+/// Jellyfin and Emby share their API. Version and whether the startup wizard has
+/// been completed come from `/System/Info/Public`; the encoding settings and
+/// libraries need an API key (Dashboard > API Keys) in `token_file`.
+///
+/// Both default to port 8096, so a server that turns out to be the other product
+/// is reported as unavailable rather than under the wrong name.
+async fn get_emby_like(client: &Client, url: &str, token_file: Option<&str>, product: &str) -> (Value, Option<Error>) {
+    let url = url.trim_end_matches('/');
+    let mut section = json!({
+        "available": false,
+        "error": Value::Null,
+        "url": url,
+        "product": Value::Null,
+        "server_name": Value::Null,
+        "server_id": Value::Null,
+        "version": Value::Null,
+        "setup_complete": Value::Null,
+        "transcoder": Value::Null,
+        "libraries": Value::Null,
+        "auth_error": Value::Null
+    });
+    let info = match get_json(client, &format!("{}/System/Info/Public", url), None).await {
+        Ok(info) => info,
+        Err(e) => {
+            let failure = e.as_failure();
+            section["error"] = json!(e);
+            return (section, failure);
+        }
+    };
+    // Jellyfin says "Jellyfin Server"; Emby leaves ProductName out or says "Emby Server".
+    let product_name = info.get("ProductName").and_then(Value::as_str).unwrap_or("Emby Server");
+    section["product"] = json!(product_name);
+    if !product_name.contains(product) {
+        // The other product answering on the port means this one isn't there.
+        section["error"] = json!(Error::new(Code::NotFound, url, format!("{} is {}, not {}", url, product_name, product)));
+        return (section, None);
+    }
+    section["available"] = json!(true);
+    section["server_name"] = json!(info.get("ServerName"));
+    section["server_id"] = json!(info.get("Id"));
+    section["version"] = json!(info.get("Version"));
+    section["setup_complete"] = json!(info.get("StartupWizardCompleted"));
+
+    let Some(token_file) = token_file else {
+        let option = format!("--{}-token-file", product.to_ascii_lowercase());
+        return set_auth_error(section, Error::new(Code::Unauthorized, &option, format!("No API key given ({})", option)));
+    };
+    let token = match fs::read_to_string(token_file) {
+        Ok(token) if token.trim().is_empty() => return set_auth_error(section, Error::new(Code::InvalidData, token_file, format!("{} is empty", token_file))),
+        Ok(token) => token.trim().to_string(),
+        Err(e) => return set_auth_error(section, Error::read(token_file, &e)),
+    };
+    let auth = Some(("X-Emby-Token", token.as_str()));
+
+    match get_json(client, &format!("{}/System/Configuration/encoding", url), auth).await {
+        Ok(encoding) => {
+            section["transcoder"] = json!({
+                "hardware_acceleration": encoding.get("HardwareAccelerationType"),
+                "hardware_encoding": encoding.get("EnableHardwareEncoding"),
+                "hardware_decoding_codecs": encoding.get("HardwareDecodingCodecs"),
+                "temp_path": encoding.get("TranscodingTempPath"),
+                "throttling": encoding.get("EnableThrottling")
+            })
+        }
+        Err(e) => return set_auth_error(section, e),
+    }
+
+    let folders = match get_json(client, &format!("{}/Library/VirtualFolders", url), auth).await {
+        Ok(folders) => folders,
+        Err(e) => return set_auth_error(section, e),
+    };
+    let mut libraries = Vec::new();
+    for folder in folders.as_array().into_iter().flatten() {
+        let id = folder.get("ItemId").and_then(Value::as_str).unwrap_or_default();
+        let count = get_json(client, &format!("{}/Items?ParentId={}&Recursive=true&Limit=0", url, id), auth)
+            .await
+            .ok()
+            .and_then(|items| items.get("TotalRecordCount").cloned());
+        libraries.push(json!({ "title": folder.get("Name"), "type": folder.get("CollectionType"), "items": count }));
+    }
+    section["libraries"] = json!(libraries);
+    (section, None)
+}
+
+/// What was gathered without a token stays; the rest is explained by `auth_error`.
+/// The server answered, so the collector hasn't failed.
+fn set_auth_error(mut section: Value, error: Error) -> (Value, Option<Error>) {
+    section["auth_error"] = json!(error);
+    (section, None)
+}
+
+/// GETs a JSON endpoint, sending `auth` as a header; Plex answers in XML unless
+/// asked for JSON.
+async fn get_json(client: &Client, url: &str, auth: Option<(&str, &str)>) -> Result<Value, Error> {
+    let mut request = client.get(url)?.header(ACCEPT, "application/json").timeout(API_TIMEOUT);
+    if let Some((header, token)) = auth {
+        request = request.header(header, token);
+    }
+    let response = request.send().await.map_err(|e| Error::http(url, &e))?;
+    if !response.status().is_success() {
+        return Err(Error::status(url, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| Error::http(url, &e))?;
+    serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: {}", url, e)))
+}
+
+/// The value of `name="..."` in an XML document with a single element of interest,
+/// as Plex's Preferences.xml is.
+fn xml_attribute(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = xml[start..].find('"')?;
+    Some(xml[start..start + end].to_string()).filter(|value| !value.is_empty())
+}
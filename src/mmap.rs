@@ -0,0 +1,60 @@
+use memmap2::Mmap;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+
+/// A file's contents, memory-mapped when possible so large files are parsed in
+/// place instead of being copied through a read buffer.
+///
+/// Files reporting a size of zero (procfs and sysfs) are read instead, as are files
+/// that fail to map. The mapping is read-only; files such as /etc/passwd are
+/// replaced by rename, which leaves an existing mapping intact.
+pub enum Contents {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+pub fn read(path: &str) -> io::Result<Contents> {
+    let file = fs::File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return fs::read(path).map(Contents::Read);
+    }
+    // SAFETY: the mapping is only read while `Contents` lives. Truncating the file in
+    // place meanwhile would fault, which isn't worth guarding against for the files
+    // mapped here.
+    match unsafe { Mmap::map(&file) } {
+        Ok(mapping) => Ok(Contents::Mapped(mapping)),
+        Err(_) => fs::read(path).map(Contents::Read),
+    }
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(mapping) => mapping,
+            Contents::Read(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_mapped_and_empty_ones_read() {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-mmap", std::process::id()));
+        fs::write(&path, b"root:x:0:0:root:/root:/bin/bash\n").unwrap();
+        let mapped = read(path.to_str().unwrap()).unwrap();
+        assert!(matches!(mapped, Contents::Mapped(_)));
+        assert_eq!(&*mapped, b"root:x:0:0:root:/root:/bin/bash\n");
+
+        fs::write(&path, b"").unwrap();
+        let empty = read(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(empty, Contents::Read(ref bytes) if bytes.is_empty()));
+        assert!(read("/nonexistent/passwd").is_err());
+    }
+}
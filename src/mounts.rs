@@ -0,0 +1,123 @@
+use serde_json::{json, Map, Value};
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs;
+
+use crate::errors::{Code, Error};
+
+pub const MOUNTS_FILE_PATH: &str = "/proc/self/mounts";
+
+/// Kernel and runtime pseudo filesystems that never hold user data.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Reports real filesystems keyed by mount point, with capacity from statvfs.
+pub fn get_mounts() -> Result<Value, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(MOUNTS_FILE_PATH)?;
+    let mut mounts = Map::new();
+
+    for line in contents.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || PSEUDO_FILESYSTEMS.contains(&fields[2]) {
+            continue;
+        }
+        let mountpoint = unescape_mount_field(fields[1]);
+        let mut value = json!({
+            "device": unescape_mount_field(fields[0]),
+            "fstype": fields[2],
+            "options": fields[3].split(',').collect::<Vec<_>>(),
+        });
+        match statvfs(&mountpoint) {
+            Ok((total, available, free)) => {
+                let used = total.saturating_sub(free);
+                value["size_gb"] = json!(to_gb(total));
+                value["used_gb"] = json!(to_gb(used));
+                value["available_gb"] = json!(to_gb(available));
+                // Matches df: used / (used + available to unprivileged users).
+                value["used_percent"] = json!(if used + available > 0 {
+                    (used as f64 * 10000.0 / (used + available) as f64).round() / 100.0
+                } else {
+                    0.0
+                });
+                value["error"] = Value::Null;
+            }
+            Err(e) => {
+                value["error"] = json!(Error::new(Code::of_io(&e), &mountpoint, format!("statvfs failed: {}", e)));
+            }
+        }
+        // Later entries overmount earlier ones at the same path.
+        mounts.insert(mountpoint, value);
+    }
+
+    Ok(Value::Object(mounts))
+}
+
+fn to_gb(bytes: u64) -> f64 {
+    (bytes as f64 / BYTES_PER_GB * 100.0).round() / 100.0
+}
+
+/// Returns (total, available to unprivileged users, free) in bytes.
+#[cfg(not(unix))]
+fn statvfs(_path: &str) -> std::io::Result<(u64, u64, u64)> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn statvfs(path: &str) -> std::io::Result<(u64, u64, u64)> {
+    let c_path = CString::new(path).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid, writable statvfs.
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let fragment = stat.f_frsize as u64;
+    Ok((
+        stat.f_blocks as u64 * fragment,
+        stat.f_bavail as u64 * fragment,
+        stat.f_bfree as u64 * fragment,
+    ))
+}
+
+/// /proc/mounts (and /proc/swaps) encode space, tab, newline and backslash as octal
+/// escapes.
+pub fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let code = bytes[i + 1..i + 4].iter().fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+            out.push(code as u8);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
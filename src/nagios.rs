@@ -0,0 +1,253 @@
+use serde_json::Value;
+
+use crate::errors;
+
+/// A monitoring check evaluated against the collected facts by `--check`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Check {
+    /// Used space on a mount point, in percent.
+    Disk { mount: String, warn: f64, crit: Option<f64> },
+    /// Whether the public address lookup for a family succeeded.
+    Ip { family: &'static str },
+    /// Whether outbound TCP works for a family.
+    Connectivity { family: &'static str },
+}
+
+impl Check {
+    /// The collector whose section the check reads.
+    pub fn collector(&self) -> &'static str {
+        match self {
+            Check::Disk { .. } => "mounts",
+            Check::Ip { .. } => "ip",
+            Check::Connectivity { .. } => "connectivity",
+        }
+    }
+}
+
+/// Parses `disk:MOUNT:WARN[:CRIT]`, `ip:ipv4|ipv6` or `connectivity:ipv4|ipv6`.
+pub fn parse_check(source: &str) -> Result<Check, String> {
+    let invalid = || {
+        format!(
+            "Invalid check {:?}: expected disk:MOUNT:WARN[:CRIT], ip:ipv4|ipv6 or connectivity:ipv4|ipv6",
+            source
+        )
+    };
+    let (kind, rest) = source.split_once(':').ok_or_else(invalid)?;
+    let family = |rest: &str| match rest {
+        "ipv4" => Ok("ipv4"),
+        "ipv6" => Ok("ipv6"),
+        _ => Err(invalid()),
+    };
+    match kind {
+        "disk" => {
+            let mut parts: Vec<&str> = rest.split(':').collect();
+            let mut thresholds = Vec::new();
+            while parts.len() > 1 && thresholds.len() < 2 {
+                match parts.last().and_then(|part| part.parse::<f64>().ok()) {
+                    Some(threshold) => {
+                        thresholds.insert(0, threshold);
+                        parts.pop();
+                    }
+                    None => break,
+                }
+            }
+            let mount = parts.join(":");
+            match thresholds[..] {
+                [warn] => Ok(Check::Disk { mount, warn, crit: None }),
+                [warn, crit] => Ok(Check::Disk { mount, warn, crit: Some(crit) }),
+                _ => Err(invalid()),
+            }
+        }
+        "ip" => Ok(Check::Ip { family: family(rest)? }),
+        "connectivity" => Ok(Check::Connectivity { family: family(rest)? }),
+        _ => Err(invalid()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum State {
+    Ok,
+    Unknown,
+    Warning,
+    Critical,
+}
+
+impl State {
+    fn label(self) -> &'static str {
+        match self {
+            State::Ok => "OK",
+            State::Unknown => "UNKNOWN",
+            State::Warning => "WARNING",
+            State::Critical => "CRITICAL",
+        }
+    }
+
+    /// The plugin exit code Nagios and Icinga expect.
+    fn exit_code(self) -> i32 {
+        match self {
+            State::Ok => 0,
+            State::Warning => 1,
+            State::Critical => 2,
+            State::Unknown => 3,
+        }
+    }
+}
+
+/// Evaluates `checks` against the facts document and returns the plugin exit code
+/// with the standard one-line output: `FACTS STATE - summaries | perfdata`.
+pub fn report(document: &Value, checks: &[Check]) -> (i32, String) {
+    let mut worst = State::Ok;
+    let mut summaries = Vec::new();
+    let mut perfdata = Vec::new();
+    for check in checks {
+        let (state, summary, perf) = evaluate(document, check);
+        worst = worst.max(state);
+        summaries.push(summary);
+        perfdata.extend(perf);
+    }
+    let mut line = format!("FACTS {} - {}", worst.label(), summaries.join(", "));
+    if !perfdata.is_empty() {
+        line.push_str(" | ");
+        line.push_str(&perfdata.join(" "));
+    }
+    line.push('\n');
+    (worst.exit_code(), line)
+}
+
+fn evaluate(document: &Value, check: &Check) -> (State, String, Option<String>) {
+    match check {
+        Check::Disk { mount, warn, crit } => {
+            let name = format!("disk {}", mount);
+            let Some(entry) = document.get("mounts").and_then(|mounts| mounts.get(mount)) else {
+                return (State::Unknown, format!("{}: not mounted or not collected", name), None);
+            };
+            if let Some(error) = entry.get("error").and_then(errors::message) {
+                return (State::Unknown, format!("{}: {}", name, error), None);
+            }
+            let Some(used) = entry.get("used_percent").and_then(Value::as_f64) else {
+                return (State::Unknown, format!("{}: usage unknown", name), None);
+            };
+            let state = match crit {
+                Some(crit) if used >= *crit => State::Critical,
+                _ if used >= *warn => State::Warning,
+                _ => State::Ok,
+            };
+            let crit = crit.map(|crit| crit.to_string()).unwrap_or_default();
+            let perf = format!("'{}'={}%;{};{};0;100", mount, used, warn, crit);
+            (state, format!("{}: {}% used", name, used), Some(perf))
+        }
+        Check::Ip { family } => {
+            let name = format!("ip {}", family);
+            let Some(ip) = document.get("ip") else {
+                return (State::Unknown, format!("{}: not collected", name), None);
+            };
+            let field = |prefix: &str| ip.get(format!("{}_{}", prefix, family));
+            if field("enabled").and_then(Value::as_bool) != Some(true) {
+                return (State::Unknown, format!("{}: lookup disabled", name), None);
+            }
+            if field("failed").and_then(Value::as_bool) != Some(false) {
+                let error = field("error").and_then(errors::message).unwrap_or("unknown error");
+                return (State::Critical, format!("{}: lookup failed ({})", name, error), None);
+            }
+            let address = if *family == "ipv4" { ip.get("public_ip") } else { ip.get("public_ipv6") };
+            (State::Ok, format!("{}: {}", name, address.and_then(Value::as_str).unwrap_or_default()), None)
+        }
+        Check::Connectivity { family } => {
+            let name = format!("connectivity {}", family);
+            let Some(entry) = document.get("connectivity").and_then(|connectivity| connectivity.get(family)) else {
+                return (State::Unknown, format!("{}: not collected", name), None);
+            };
+            let verdict = entry.get("verdict").and_then(Value::as_str).unwrap_or("unknown");
+            match entry.get("latency_ms").and_then(Value::as_u64) {
+                Some(latency) if verdict == "ok" => (
+                    State::Ok,
+                    format!("{}: ok", name),
+                    Some(format!("'{}_latency'={}ms;;;0", family, latency)),
+                ),
+                _ => (State::Critical, format!("{}: {}", name, verdict), None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn disk(mount: &str, warn: f64, crit: Option<f64>) -> Check {
+        Check::Disk { mount: mount.to_string(), warn, crit }
+    }
+
+    fn report_for(checks: &[&str]) -> (i32, String) {
+        let checks: Vec<Check> = checks.iter().map(|check| parse_check(check).unwrap()).collect();
+        report(&document(), &checks)
+    }
+
+    fn document() -> Value {
+        json!({
+            "mounts": {
+                "/": { "used_percent": 50.0, "error": null },
+                "/full": { "used_percent": 95.5, "error": null },
+                "/mnt/remote": { "used_percent": null, "error": { "code": "timeout", "message": "statvfs timed out", "source": "/mnt/remote" } }
+            },
+            "ip": {
+                "enabled_ipv4": true, "failed_ipv4": false, "public_ip": "203.0.113.7",
+                "enabled_ipv6": true, "failed_ipv6": true, "error_ipv6": "no route"
+            },
+            "connectivity": {
+                "ipv4": { "verdict": "ok", "latency_ms": 12 },
+                "ipv6": { "verdict": "blocked", "latency_ms": null }
+            }
+        })
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(parse_check("disk:/:90"), Ok(disk("/", 90.0, None)));
+        assert_eq!(parse_check("disk:/opt:80:90.5"), Ok(disk("/opt", 80.0, Some(90.5))));
+        // Only the last two numeric parts are thresholds; the rest is the mount.
+        assert_eq!(parse_check("disk:/mnt/a:1:80:90"), Ok(disk("/mnt/a:1", 80.0, Some(90.0))));
+        assert_eq!(parse_check("ip:ipv6"), Ok(Check::Ip { family: "ipv6" }));
+        assert_eq!(parse_check("connectivity:ipv4"), Ok(Check::Connectivity { family: "ipv4" }));
+        for invalid in ["disk:/", "disk:/:high", "disk:90", "ip:ipv5", "load:1", "disk"] {
+            assert!(parse_check(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn ok() {
+        assert_eq!(
+            report_for(&["disk:/:80:90", "ip:ipv4", "connectivity:ipv4"]),
+            (0, "FACTS OK - disk /: 50% used, ip ipv4: 203.0.113.7, connectivity ipv4: ok | '/'=50%;80;90;0;100 'ipv4_latency'=12ms;;;0\n".to_string())
+        );
+    }
+
+    #[test]
+    fn warning() {
+        assert_eq!(report_for(&["disk:/:40"]), (1, "FACTS WARNING - disk /: 50% used | '/'=50%;40;;0;100\n".to_string()));
+        // Without a critical threshold, any use past the warning one only warns.
+        assert_eq!(report_for(&["disk:/full:40"]).0, 1);
+    }
+
+    #[test]
+    fn critical() {
+        assert_eq!(report_for(&["disk:/full:80:90"]), (2, "FACTS CRITICAL - disk /full: 95.5% used | '/full'=95.5%;80;90;0;100\n".to_string()));
+        assert_eq!(report_for(&["ip:ipv6"]), (2, "FACTS CRITICAL - ip ipv6: lookup failed (no route)\n".to_string()));
+        assert_eq!(report_for(&["connectivity:ipv6"]), (2, "FACTS CRITICAL - connectivity ipv6: blocked\n".to_string()));
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(report_for(&["disk:/srv:90"]), (3, "FACTS UNKNOWN - disk /srv: not mounted or not collected\n".to_string()));
+        assert_eq!(report_for(&["disk:/mnt/remote:90"]), (3, "FACTS UNKNOWN - disk /mnt/remote: statvfs timed out\n".to_string()));
+        assert_eq!(report(&json!({}), &[Check::Ip { family: "ipv4" }]), (3, "FACTS UNKNOWN - ip ipv4: not collected\n".to_string()));
+    }
+
+    #[test]
+    fn worst_state_wins() {
+        // A real warning outranks a check that couldn't be evaluated.
+        assert_eq!(report_for(&["disk:/srv:90", "disk:/:40"]).0, 1);
+        assert_eq!(report_for(&["disk:/:40", "ip:ipv6", "disk:/srv:90"]).0, 2);
+    }
+}
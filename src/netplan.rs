@@ -0,0 +1,387 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+
+use crate::errors::{Code, Error};
+
+/// Directories netplan reads, lowest precedence first: a file in a later one
+/// shadows the file of the same name in an earlier one.
+const NETPLAN_DIRS: &[&str] = &["/lib/netplan", "/etc/netplan", "/run/netplan"];
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+/// The device types under `network:`.
+const DEVICE_KINDS: &[&str] = &[
+    "ethernets", "wifis", "bonds", "bridges", "vlans", "tunnels", "vrfs", "modems", "dummy-devices", "virtual-ethernets", "nm-devices",
+];
+
+/// The netplan configuration: the renderer, each interface's DHCP and static
+/// settings, and `drift` where the interface as it is now doesn't match, such as a
+/// static address that isn't assigned or an address on a static-only interface
+/// that no configuration gives it. `name` and `present` are null for definitions
+/// matching interfaces by a glob or driver.
+///
+/// Files are merged as netplan does, in file name order across the directories:
+/// mappings merge key by key and anything else in a later file replaces the
+/// earlier value. The files are usually readable only by root.
+pub fn get_netplan() -> Value {
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    for dir in NETPLAN_DIRS {
+        for entry in fs::read_dir(dir).into_iter().flatten().filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(".yaml") {
+                files.insert(name, entry.path().display().to_string());
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    let mut config = Value::Object(Map::new());
+    for path in files.values() {
+        let parsed = fs::read_to_string(path)
+            .map_err(|e| Error::read(path, &e))
+            .and_then(|contents| serde_yaml::from_str::<Value>(&contents).map_err(|e| Error::new(Code::InvalidData, path, format!("Cannot read {}: {}", path, e))));
+        match parsed {
+            // An empty file parses as null and configures nothing.
+            Ok(Value::Null) => {}
+            Ok(value) => merge(&mut config, value),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let network = config.get("network").cloned().unwrap_or(Value::Null);
+    let renderer = network.get("renderer").and_then(Value::as_str);
+    let actual = actual_addresses();
+    let (interfaces, drift) = interfaces(&network, renderer, &actual);
+
+    json!({
+        "files": files.into_values().collect::<Vec<_>>(),
+        "errors": errors,
+        "renderer": renderer,
+        "interfaces": interfaces,
+        "drift": drift
+    })
+}
+
+/// Each device definition under `network:`, by ID, and the drift between the
+/// definitions and `actual`, the addresses each existing interface has.
+fn interfaces(network: &Value, renderer: Option<&str>, actual: &BTreeMap<String, Vec<(IpAddr, u8)>>) -> (Map<String, Value>, Vec<Value>) {
+    let mut interfaces = Map::new();
+    let mut drift = Vec::new();
+    for kind in DEVICE_KINDS {
+        for (id, device) in network.get(*kind).and_then(Value::as_object).into_iter().flatten() {
+            let name = resolve_name(id, device);
+            let dhcp4 = flag(device.get("dhcp4"));
+            let dhcp6 = flag(device.get("dhcp6"));
+            let addresses: Vec<String> = device
+                .get("addresses")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|address| match address {
+                    Value::String(address) => Some(address.clone()),
+                    // `- 10.0.0.5/24: {lifetime: 0}`
+                    Value::Object(address) => address.keys().next().cloned(),
+                    _ => None,
+                })
+                .collect();
+            let assigned = name.as_ref().and_then(|name| actual.get(name));
+            if let Some(name) = &name {
+                match assigned {
+                    None if !flag(device.get("optional")) => drift.push(json!({ "interface": name, "kind": "missing_interface", "address": Value::Null })),
+                    None => {}
+                    Some(assigned) => {
+                        let configured: Vec<(IpAddr, u8)> = addresses.iter().filter_map(|address| parse_cidr(address)).collect();
+                        for address in &addresses {
+                            if parse_cidr(address).is_some_and(|address| !assigned.contains(&address)) {
+                                drift.push(json!({ "interface": name, "kind": "missing_address", "address": address }));
+                            }
+                        }
+                        // Dynamic addresses are expected with DHCP, and for IPv6 with
+                        // router advertisements, which netplan accepts by default.
+                        let accept_ra = device.get("accept-ra").is_none_or(|accept| flag(Some(accept)));
+                        for (ip, prefix) in assigned {
+                            let dynamic = if ip.is_ipv4() { dhcp4 } else { dhcp6 || accept_ra };
+                            if !dynamic && !configured.contains(&(*ip, *prefix)) {
+                                drift.push(json!({ "interface": name, "kind": "unexpected_address", "address": format!("{}/{}", ip, prefix) }));
+                            }
+                        }
+                    }
+                }
+            }
+            let routes: Vec<Value> = device
+                .get("routes")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|route| json!({ "to": scalar(route.get("to")), "via": scalar(route.get("via")), "metric": route.get("metric").and_then(Value::as_u64) }))
+                .collect();
+            let nameservers = device.get("nameservers");
+            let strings = |value: Option<&Value>| value.and_then(Value::as_array).into_iter().flatten().filter_map(|item| scalar(Some(item))).collect::<Vec<String>>();
+            interfaces.insert(
+                id.clone(),
+                json!({
+                    "kind": kind.trim_end_matches('s'),
+                    "name": name,
+                    "renderer": device.get("renderer").and_then(Value::as_str).or(renderer),
+                    "dhcp4": dhcp4,
+                    "dhcp6": dhcp6,
+                    "addresses": addresses,
+                    "gateway4": scalar(device.get("gateway4")),
+                    "gateway6": scalar(device.get("gateway6")),
+                    "routes": routes,
+                    "nameservers": {
+                        "addresses": strings(nameservers.and_then(|nameservers| nameservers.get("addresses"))),
+                        "search": strings(nameservers.and_then(|nameservers| nameservers.get("search")))
+                    },
+                    "present": name.as_ref().map(|_| assigned.is_some()),
+                    "actual_addresses": assigned.map(|assigned| assigned.iter().map(|(ip, prefix)| format!("{}/{}", ip, prefix)).collect::<Vec<_>>())
+                }),
+            );
+        }
+    }
+    (interfaces, drift)
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// The kernel name a device definition applies to: the ID itself unless it has a
+/// `match`, then `set-name`, an exact `match: name` or the interface with the
+/// matched MAC address. None when the match is a glob or by driver.
+fn resolve_name(id: &str, device: &Value) -> Option<String> {
+    let Some(matching) = device.get("match") else {
+        return Some(id.to_string());
+    };
+    if let Some(name) = device.get("set-name").and_then(Value::as_str) {
+        return Some(name.to_string());
+    }
+    if let Some(name) = matching.get("name").and_then(Value::as_str).filter(|name| !name.contains(['*', '?', '['])) {
+        return Some(name.to_string());
+    }
+    let mac = matching.get("macaddress").and_then(Value::as_str)?.to_ascii_lowercase();
+    fs::read_dir(SYS_CLASS_NET_PATH).into_iter().flatten().filter_map(|entry| entry.ok()).find_map(|entry| {
+        let address = fs::read_to_string(entry.path().join("address")).ok()?;
+        (address.trim() == mac).then(|| entry.file_name().to_string_lossy().into_owned())
+    })
+}
+
+/// A YAML boolean; netplan also takes `yes`/`no` and `on`/`off`, which the YAML
+/// parser leaves as strings.
+fn flag(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::Bool(value)) => *value,
+        Some(Value::String(value)) => matches!(value.to_ascii_lowercase().as_str(), "true" | "yes" | "on" | "y"),
+        _ => false,
+    }
+}
+
+/// A string or number, as a string: ports and metrics may be written either way.
+fn scalar(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_cidr(address: &str) -> Option<(IpAddr, u8)> {
+    let (ip, prefix) = address.split_once('/')?;
+    Some((ip.parse().ok()?, prefix.parse().ok()?))
+}
+
+/// Global addresses assigned to each interface that exists, with their prefix
+/// lengths; link-local ones are left out, as netplan never configures them.
+#[cfg(unix)]
+fn actual_addresses() -> BTreeMap<String, Vec<(IpAddr, u8)>> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut addresses: BTreeMap<String, Vec<(IpAddr, u8)>> = BTreeMap::new();
+    for entry in fs::read_dir(SYS_CLASS_NET_PATH).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        addresses.entry(entry.file_name().to_string_lossy().into_owned()).or_default();
+    }
+    let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills `list` on success, which is freed below.
+    if unsafe { libc::getifaddrs(&mut list) } != 0 {
+        return addresses;
+    }
+    let mut cursor = list;
+    while !cursor.is_null() {
+        // SAFETY: every node of the list getifaddrs returned stays valid until freeifaddrs.
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null() || entry.ifa_netmask.is_null() {
+            continue;
+        }
+        // SAFETY: ifa_name is a nul-terminated string, and ifa_addr and ifa_netmask
+        // point to sockaddrs of the family in sa_family.
+        let (name, address) = unsafe {
+            let name = CStr::from_ptr(entry.ifa_name).to_string_lossy().into_owned();
+            let address = match i32::from((*entry.ifa_addr).sa_family) {
+                libc::AF_INET => {
+                    let ip = Ipv4Addr::from(u32::from_be((*(entry.ifa_addr as *const libc::sockaddr_in)).sin_addr.s_addr));
+                    let mask = u32::from_be((*(entry.ifa_netmask as *const libc::sockaddr_in)).sin_addr.s_addr);
+                    Some((IpAddr::V4(ip), mask.count_ones() as u8))
+                }
+                libc::AF_INET6 => {
+                    let ip = Ipv6Addr::from((*(entry.ifa_addr as *const libc::sockaddr_in6)).sin6_addr.s6_addr);
+                    let mask = (*(entry.ifa_netmask as *const libc::sockaddr_in6)).sin6_addr.s6_addr;
+                    Some((IpAddr::V6(ip), mask.iter().map(|byte| byte.count_ones()).sum::<u32>() as u8))
+                }
+                _ => None,
+            };
+            (name, address)
+        };
+        let Some((ip, prefix)) = address else {
+            continue;
+        };
+        let link_local = match ip {
+            IpAddr::V4(ip) => ip.is_link_local(),
+            IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+        };
+        if !link_local && !ip.is_loopback() {
+            addresses.entry(name).or_default().push((ip, prefix));
+        }
+    }
+    // SAFETY: `list` came from a successful getifaddrs and is freed once.
+    unsafe { libc::freeifaddrs(list) };
+    addresses
+}
+
+#[cfg(not(unix))]
+fn actual_addresses() -> BTreeMap<String, Vec<(IpAddr, u8)>> {
+    BTreeMap::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(source: &str) -> Value {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn later_files_merge_mappings_and_replace_everything_else() {
+        let mut config = yaml("network:\n  renderer: networkd\n  ethernets:\n    eth0:\n      dhcp4: true\n      addresses: [10.0.0.5/24]\n");
+        merge(&mut config, yaml("network:\n  ethernets:\n    eth0:\n      addresses: [10.0.0.6/24]\n    eth1:\n      dhcp4: true\n"));
+        assert_eq!(
+            config,
+            yaml("network:\n  renderer: networkd\n  ethernets:\n    eth0:\n      dhcp4: true\n      addresses: [10.0.0.6/24]\n    eth1:\n      dhcp4: true\n")
+        );
+    }
+
+    #[test]
+    fn flags_scalars_and_addresses() {
+        assert!(flag(Some(&json!(true))));
+        assert!(flag(Some(&json!("yes"))));
+        assert!(flag(Some(&json!("On"))));
+        assert!(!flag(Some(&json!("no"))));
+        assert!(!flag(None));
+        assert_eq!(scalar(Some(&json!(100))).as_deref(), Some("100"));
+        assert_eq!(scalar(Some(&json!("default"))).as_deref(), Some("default"));
+        assert_eq!(scalar(Some(&json!([]))), None);
+        assert_eq!(parse_cidr("10.0.0.5/24"), Some(("10.0.0.5".parse().unwrap(), 24)));
+        assert_eq!(parse_cidr("2001:db8::5/64"), Some(("2001:db8::5".parse().unwrap(), 64)));
+        assert_eq!(parse_cidr("10.0.0.5"), None);
+    }
+
+    #[test]
+    fn names_come_from_set_name_or_an_exact_match() {
+        assert_eq!(resolve_name("eth0", &json!({})).as_deref(), Some("eth0"));
+        assert_eq!(resolve_name("lan", &json!({ "match": { "macaddress": "00:11:22:33:44:55" }, "set-name": "lan0" })).as_deref(), Some("lan0"));
+        assert_eq!(resolve_name("uplink", &json!({ "match": { "name": "enp3s0" } })).as_deref(), Some("enp3s0"));
+        assert_eq!(resolve_name("wired", &json!({ "match": { "name": "en*" } })), None);
+        assert_eq!(resolve_name("nics", &json!({ "match": { "driver": "ixgbe" } })), None);
+    }
+
+    #[test]
+    fn interfaces_report_settings_and_drift_from_the_live_addresses() {
+        let network = yaml(
+            r#"
+renderer: networkd
+ethernets:
+  eth0:
+    accept-ra: false
+    addresses:
+      - 10.0.0.5/24
+      - 10.0.0.9/24:
+          lifetime: 0
+    routes:
+      - to: default
+        via: 10.0.0.1
+        metric: 100
+    nameservers:
+      addresses: [1.1.1.1]
+      search: [lan]
+  lan:
+    match:
+      macaddress: "00:11:22:33:44:55"
+    set-name: lan0
+    dhcp4: "yes"
+  spare:
+    optional: true
+  gone:
+    dhcp4: true
+  wired:
+    match:
+      name: "en*"
+    dhcp4: true
+bridges:
+  br0:
+    renderer: NetworkManager
+    dhcp6: "on"
+"#,
+        );
+        let cidr = |address: &str| parse_cidr(address).unwrap();
+        let actual = BTreeMap::from([
+            ("eth0".to_string(), vec![cidr("10.0.0.5/24"), cidr("192.168.1.7/24")]),
+            ("lan0".to_string(), vec![cidr("192.168.1.50/24")]),
+            ("br0".to_string(), vec![cidr("2001:db8::5/64")]),
+        ]);
+
+        let (interfaces, drift) = interfaces(&network, Some("networkd"), &actual);
+        assert_eq!(
+            interfaces["eth0"],
+            json!({
+                "kind": "ethernet",
+                "name": "eth0",
+                "renderer": "networkd",
+                "dhcp4": false,
+                "dhcp6": false,
+                "addresses": ["10.0.0.5/24", "10.0.0.9/24"],
+                "gateway4": null,
+                "gateway6": null,
+                "routes": [{ "to": "default", "via": "10.0.0.1", "metric": 100 }],
+                "nameservers": { "addresses": ["1.1.1.1"], "search": ["lan"] },
+                "present": true,
+                "actual_addresses": ["10.0.0.5/24", "192.168.1.7/24"]
+            })
+        );
+        assert_eq!((&interfaces["lan"]["name"], &interfaces["lan"]["dhcp4"]), (&json!("lan0"), &json!(true)));
+        assert_eq!((&interfaces["br0"]["kind"], &interfaces["br0"]["renderer"]), (&json!("bridge"), &json!("NetworkManager")));
+        assert_eq!((&interfaces["wired"]["name"], &interfaces["wired"]["present"]), (&Value::Null, &Value::Null));
+        assert_eq!(interfaces["spare"]["present"], json!(false));
+        assert_eq!(
+            drift,
+            [
+                json!({ "interface": "eth0", "kind": "missing_address", "address": "10.0.0.9/24" }),
+                json!({ "interface": "eth0", "kind": "unexpected_address", "address": "192.168.1.7/24" }),
+                json!({ "interface": "gone", "kind": "missing_interface", "address": null }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::binaries::find_in_path;
+use crate::errors::{Code, Error};
+use crate::exec;
+use crate::processes::PROC_PATH;
+
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+const NETWORKD_LINKS_PATH: &str = "/run/systemd/netif/links";
+const INTERFACES_FILE_PATH: &str = "/etc/network/interfaces";
+/// Interfaces ifupdown has brought up, as `name=logical-name` lines.
+const IFSTATE_PATH: &str = "/run/network/ifstate";
+const IFUP_PATHS: &[&str] = &["/usr/sbin/ifup", "/sbin/ifup"];
+/// Process names as the kernel truncates them to 15 characters.
+const NETWORK_MANAGER_PROCESS: &str = "NetworkManager";
+const NETWORKD_PROCESS: &str = "systemd-network";
+/// Nested `source` directives followed before giving up on a loop.
+const MAX_SOURCE_DEPTH: usize = 8;
+
+/// Which of NetworkManager, systemd-networkd and ifupdown manages each interface,
+/// so network-tuning roles configure the one in charge instead of fighting it.
+///
+/// An interface counts as managed by NetworkManager when NetworkManager is running
+/// and doesn't list it as unmanaged, by networkd when networkd is running and
+/// has a `.network` file for it, and by ifupdown when /etc/network/interfaces (or a
+/// file it sources) has an `iface` stanza for it. Interfaces claimed by more than
+/// one are listed in `conflicts`.
+pub async fn get_network_managers() -> Value {
+    let running = running_processes();
+    let (network_manager, devices) = network_manager_devices(running.iter().any(|name| name == NETWORK_MANAGER_PROCESS)).await;
+    let networkd_running = running.iter().any(|name| name == NETWORKD_PROCESS);
+    let links = networkd_links();
+    let mut ifupdown_errors = Vec::new();
+    let stanzas = ifupdown_stanzas(Path::new(INTERFACES_FILE_PATH), 0, &mut ifupdown_errors);
+    let up: Vec<String> = fs::read_to_string(IFSTATE_PATH)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim().to_string()))
+        .collect();
+
+    let mut names: Vec<String> = fs::read_dir(SYS_CLASS_NET_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    let mut interfaces = Map::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        let device = devices.get(&name);
+        let link = ifindex(&name).and_then(|index| links.get(&index));
+        let stanza = stanzas.get(&name);
+        let mut managed_by = Vec::new();
+        if network_manager.running && device.is_some_and(|device| device.state != "unmanaged") {
+            managed_by.push("network_manager");
+        }
+        if networkd_running && link.is_some_and(|link| link.network_file.is_some()) {
+            managed_by.push("networkd");
+        }
+        if stanza.is_some() {
+            managed_by.push("ifupdown");
+        }
+        if managed_by.len() > 1 {
+            conflicts.push(name.clone());
+        }
+        let value = json!({
+            "managed_by": managed_by,
+            "network_manager": device.map(|device| json!({ "type": device.kind, "state": device.state, "connection": device.connection })),
+            "networkd": link.map(|link| json!({ "admin_state": link.admin_state, "oper_state": link.oper_state, "network_file": link.network_file })),
+            "ifupdown": stanza.map(|stanza| json!({
+                "methods": stanza.methods,
+                "auto": stanza.auto,
+                "hotplug": stanza.hotplug,
+                "up": up.contains(&name),
+                "file": stanza.file
+            }))
+        });
+        interfaces.insert(name, value);
+    }
+
+    json!({
+        "managers": {
+            "network_manager": { "installed": network_manager.installed, "running": network_manager.running, "error": network_manager.error },
+            "networkd": { "running": networkd_running },
+            "ifupdown": {
+                "installed": IFUP_PATHS.iter().any(|path| Path::new(path).exists()),
+                "configured": !stanzas.is_empty(),
+                "errors": ifupdown_errors
+            }
+        },
+        "interfaces": interfaces,
+        "conflicts": conflicts
+    })
+}
+
+/// The names of running processes, from /proc/<pid>/comm.
+fn running_processes() -> Vec<String> {
+    fs::read_dir(PROC_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_digit())))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_string())
+        .collect()
+}
+
+fn ifindex(name: &str) -> Option<u32> {
+    fs::read_to_string(Path::new(SYS_CLASS_NET_PATH).join(name).join("ifindex")).ok()?.trim().parse().ok()
+}
+
+struct NetworkManager {
+    installed: bool,
+    running: bool,
+    error: Option<Error>,
+}
+
+struct Device {
+    kind: String,
+    state: String,
+    connection: Option<String>,
+}
+
+/// NetworkManager's devices from `nmcli`, which is only asked when the daemon is
+/// running: without it every device would read as unavailable.
+async fn network_manager_devices(running: bool) -> (NetworkManager, BTreeMap<String, Device>) {
+    let mut status = NetworkManager { installed: find_in_path("nmcli").is_some(), running, error: None };
+    let mut devices = BTreeMap::new();
+    if !running || !status.installed {
+        return (status, devices);
+    }
+    let output = exec::output("nmcli", ["--terse", "--fields", "DEVICE,TYPE,STATE,CONNECTION", "device", "status"]).await;
+    match output {
+        Err(e) => status.error = Some(Error::run("nmcli", &e)),
+        Ok(output) if !output.status.success() => status.error = Some(Error::exited("nmcli", &output)),
+        Ok(output) => {
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                let fields = terse_fields(line);
+                let [name, kind, state, connection] = &fields[..] else {
+                    continue;
+                };
+                devices.insert(
+                    name.clone(),
+                    Device {
+                        kind: kind.clone(),
+                        state: state.clone(),
+                        connection: Some(connection.clone()).filter(|connection| !connection.is_empty() && connection != "--"),
+                    },
+                );
+            }
+        }
+    }
+    (status, devices)
+}
+
+/// nmcli's terse output: fields separated by `:`, with `:` and `\` inside a field
+/// escaped by a backslash.
+fn terse_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().unwrap().extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+struct Link {
+    admin_state: Option<String>,
+    oper_state: Option<String>,
+    network_file: Option<String>,
+}
+
+/// networkd's per-link state files, named by interface index.
+fn networkd_links() -> BTreeMap<u32, Link> {
+    let mut links = BTreeMap::new();
+    for entry in fs::read_dir(NETWORKD_LINKS_PATH).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let Some(index) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        let Ok(contents) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let value = |key: &str| {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        links.insert(index, Link { admin_state: value("ADMIN_STATE"), oper_state: value("OPER_STATE"), network_file: value("NETWORK_FILE") });
+    }
+    links
+}
+
+#[derive(Default)]
+struct Stanza {
+    /// `inet dhcp`, `inet6 static` and so on, one per `iface` stanza.
+    methods: Vec<String>,
+    auto: bool,
+    hotplug: bool,
+    file: String,
+}
+
+/// The interfaces configured in an ifupdown interfaces file and the files it
+/// sources, by name.
+fn ifupdown_stanzas(path: &Path, depth: usize, errors: &mut Vec<Error>) -> BTreeMap<String, Stanza> {
+    let mut stanzas: BTreeMap<String, Stanza> = BTreeMap::new();
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && depth == 0 => return stanzas,
+        Err(e) => {
+            errors.push(Error::read(path.display(), &e));
+            return stanzas;
+        }
+    };
+    let file = path.display().to_string();
+    let mut included = Vec::new();
+    // Continuation lines end with a backslash.
+    for line in contents.replace("\\\n", " ").lines() {
+        let words: Vec<&str> = line.split('#').next().unwrap_or_default().split_whitespace().collect();
+        match words[..] {
+            ["iface", name, family, method, ..] => {
+                let stanza = stanzas.entry(name.to_string()).or_default();
+                stanza.methods.push(format!("{} {}", family, method));
+                stanza.file = file.clone();
+            }
+            ["auto" | "allow-auto", ref names @ ..] => names.iter().for_each(|name| stanzas.entry(name.to_string()).or_default().auto = true),
+            ["allow-hotplug", ref names @ ..] => names.iter().for_each(|name| stanzas.entry(name.to_string()).or_default().hotplug = true),
+            ["source", pattern] => included.extend(source_files(path, pattern)),
+            ["source-directory", dir] => included.extend(source_directory(path, dir)),
+            _ => {}
+        }
+    }
+
+    for source in included {
+        if depth >= MAX_SOURCE_DEPTH {
+            errors.push(Error::new(
+                Code::Unsupported,
+                source.display().to_string(),
+                format!("Not following {}: sources nested more than {} deep", source.display(), MAX_SOURCE_DEPTH),
+            ));
+            break;
+        }
+        for (name, nested) in ifupdown_stanzas(&source, depth + 1, errors) {
+            let stanza = stanzas.entry(name).or_default();
+            stanza.methods.extend(nested.methods);
+            stanza.auto |= nested.auto;
+            stanza.hotplug |= nested.hotplug;
+            if !nested.file.is_empty() {
+                stanza.file = nested.file;
+            }
+        }
+    }
+    // `auto` alone, without an `iface` stanza anywhere, configures nothing.
+    stanzas.retain(|_, stanza| !stanza.methods.is_empty());
+    stanzas
+}
+
+/// The files a `source` line names, relative to the including file's directory
+/// when not absolute. A `*` in the last component matches any run of characters.
+fn source_files(including: &Path, pattern: &str) -> Vec<std::path::PathBuf> {
+    let pattern = including.parent().unwrap_or(Path::new("/")).join(pattern);
+    let Some(file_pattern) = pattern.file_name().and_then(|name| name.to_str()).filter(|name| name.contains('*')) else {
+        return vec![pattern];
+    };
+    let mut files: Vec<_> = fs::read_dir(pattern.parent().unwrap_or(Path::new("/")))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| wildcard_match(file_pattern, name)))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+/// The files `source-directory` includes: those named like run-parts scripts.
+fn source_directory(including: &Path, dir: &str) -> Vec<std::path::PathBuf> {
+    let dir = including.parent().unwrap_or(Path::new("/")).join(dir);
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        if index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
@@ -0,0 +1,430 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Yaml,
+    Msgpack,
+    Env,
+    Ndjson,
+    Nagios,
+}
+
+/// Serializes `value` straight into `writer` through a buffer, followed by a
+/// newline, so a large document is never held as one string.
+pub fn write_json<W: Write>(writer: W, value: &Value, pretty: bool) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, value)?;
+    } else {
+        serde_json::to_writer(&mut writer, value)?;
+    }
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Writes a JSON object an entry at a time, each flushed as soon as it's known, laid
+/// out as `write_json` lays out the whole object.
+pub struct ObjectWriter<W: Write> {
+    writer: BufWriter<W>,
+    pretty: bool,
+    entries: usize,
+}
+
+impl<W: Write> ObjectWriter<W> {
+    pub fn new(writer: W, pretty: bool) -> ObjectWriter<W> {
+        ObjectWriter {
+            writer: BufWriter::new(writer),
+            pretty,
+            entries: 0,
+        }
+    }
+
+    pub fn entry(&mut self, key: &str, value: &impl serde::Serialize) -> io::Result<()> {
+        self.writer.write_all(if self.entries == 0 { b"{" } else { b"," })?;
+        if self.pretty {
+            self.writer.write_all(b"\n  ")?;
+            serde_json::to_writer(&mut self.writer, key)?;
+            self.writer.write_all(b": ")?;
+            serde_json::to_writer_pretty(Indented(&mut self.writer), value)?;
+        } else {
+            serde_json::to_writer(&mut self.writer, key)?;
+            self.writer.write_all(b":")?;
+            serde_json::to_writer(&mut self.writer, value)?;
+        }
+        self.entries += 1;
+        self.writer.flush()
+    }
+
+    /// Closes the object and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let end: &[u8] = match (self.entries, self.pretty) {
+            (0, _) => b"{}\n",
+            (_, true) => b"\n}\n",
+            (_, false) => b"}\n",
+        };
+        self.writer.write_all(end)?;
+        self.writer.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+/// Indents every line after the first by one level; JSON strings never hold a raw
+/// newline, so only the layout's own are indented.
+struct Indented<W: Write>(W);
+
+impl<W: Write> Write for Indented<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (index, line) in buf.split(|&byte| byte == b'\n').enumerate() {
+            if index > 0 {
+                self.0.write_all(b"\n  ")?;
+            }
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Where a document is written: an `AtomicFile`, or stdout.
+pub enum Destination {
+    File(AtomicFile),
+    Stdout(io::StdoutLock<'static>),
+}
+
+impl Destination {
+    /// Renames a file into place; stdout has nothing to do.
+    pub fn commit(self) -> Result<(), String> {
+        match self {
+            Destination::File(file) => file.commit(),
+            Destination::Stdout(_) => Ok(()),
+        }
+    }
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Destination::File(file) => file.write(buf),
+            Destination::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Destination::File(file) => file.flush(),
+            Destination::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+/// A file written under a temporary name next to its destination and renamed over
+/// it on `commit`, so readers see either the old contents or the complete new ones.
+/// Dropped without committing, the temporary file is removed. A file that already
+/// exists keeps its permissions.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: Option<File>,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> Result<AtomicFile, String> {
+        let name = path.file_name().ok_or_else(|| format!("Cannot write {}: not a file path", path.display()))?;
+        let temp = path.with_file_name(format!(".{}.tmp.{}", name.to_string_lossy(), std::process::id()));
+        let file = File::create(&temp).map_err(|e| format!("Cannot write {}: {}", temp.display(), e))?;
+        if let Ok(metadata) = fs::metadata(path) {
+            if let Err(e) = file.set_permissions(metadata.permissions()) {
+                let _ = fs::remove_file(&temp);
+                return Err(format!("Cannot write {}: {}", temp.display(), e));
+            }
+        }
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            temp,
+            file: Some(file),
+        })
+    }
+
+    /// Flushes the contents to disk, renames the file into place, then flushes the
+    /// directory so the rename itself survives a crash.
+    pub fn commit(mut self) -> Result<(), String> {
+        let file = self.file.take().expect("AtomicFile committed twice");
+        if let Err(e) = file.sync_all() {
+            drop(file);
+            let _ = fs::remove_file(&self.temp);
+            return Err(format!("Cannot write {}: {}", self.temp.display(), e));
+        }
+        drop(file);
+        if let Err(e) = fs::rename(&self.temp, &self.path) {
+            let _ = fs::remove_file(&self.temp);
+            return Err(format!("Cannot write {}: {}", self.path.display(), e));
+        }
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(directory)
+            .and_then(|directory| directory.sync_all())
+            .map_err(|e| format!("Cannot write {}: {}", directory.display(), e))
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.as_mut().expect("AtomicFile used after commit").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().expect("AtomicFile used after commit").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Treats stdout's reader going away, as with `| head`, as the normal end of the
+/// output rather than an error.
+pub fn closed_pipe_is_ok(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Writes `value` as a YAML document through a buffer.
+pub fn write_yaml<W: Write>(writer: W, value: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(writer);
+    serde_yaml::to_writer(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `value` as a single MessagePack object through a buffer. Maps keep
+/// their string keys, so the structure is the same as the JSON document's.
+pub fn write_msgpack<W: Write>(writer: W, value: &Value) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(writer);
+    rmp_serde::encode::write_named(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `value` as `SALTBOX_IP_PUBLIC_IP='203.0.113.7'` lines that a shell can
+/// `source` or `eval`. Nested objects become underscore-joined names; arrays, and
+/// objects that are empty, are kept whole as JSON strings. Values are single-quoted,
+/// so nothing in them is expanded.
+///
+/// Keys that differ only in characters a variable name can't hold (`a-b` and `a_b`,
+/// `/var/log` and `/var_log`) would give the same name; the first in document order
+/// keeps it and the others get `_2`, `_3`, ... appended.
+pub fn write_env<W: Write>(writer: W, value: &Value) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let mut name = String::from("SALTBOX");
+    write_env_into(&mut writer, &mut name, &mut HashSet::new(), value)?;
+    writer.flush()
+}
+
+fn write_env_into<W: Write>(writer: &mut W, name: &mut String, written: &mut HashSet<String>, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let length = name.len();
+                name.push('_');
+                name.extend(key.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }));
+                write_env_into(writer, name, written, child)?;
+                name.truncate(length);
+            }
+            Ok(())
+        }
+        leaf => {
+            let text = match leaf {
+                Value::Null => String::new(),
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            let mut unique = name.clone();
+            let mut suffix = 2;
+            while !written.insert(unique.clone()) {
+                unique = format!("{}_{}", name, suffix);
+                suffix += 1;
+            }
+            writeln!(writer, "{}='{}'", unique, text.replace('\'', "'\\''"))
+        }
+    }
+}
+
+/// Format of human-oriented reports from subcommands such as `compare` and `bench`.
+#[derive(Clone, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// Sections that are maps of records and can be exported as CSV, with the column
+/// holding the map key first and the record fields after it.
+const CSV_SECTIONS: &[(&str, &str, &[&str])] = &[
+    ("users", "name", &["uid", "gid", "comment", "home", "shell"]),
+    ("groups", "name", &["gid", "group-list"]),
+    (
+        "mounts",
+        "mountpoint",
+        &["device", "fstype", "options", "size_gb", "used_gb", "available_gb", "used_percent"],
+    ),
+];
+
+pub fn csv_section_names() -> Vec<&'static str> {
+    CSV_SECTIONS.iter().map(|(name, _, _)| *name).collect()
+}
+
+/// Renders one tabular section of the document as RFC 4180 CSV with a header row.
+/// List fields (group members, mount options) are joined with commas inside the cell.
+/// Cells a spreadsheet would run as a formula get a leading `'`; see `guard_formula`.
+pub fn render_csv(document: &Value, section: &str) -> Result<String, String> {
+    let (_, key_column, columns) = CSV_SECTIONS
+        .iter()
+        .find(|(name, _, _)| *name == section)
+        .ok_or_else(|| format!("Section {} cannot be exported as CSV (expected one of: {})", section, csv_section_names().join(", ")))?;
+    let records = document
+        .get(section)
+        .and_then(Value::as_object)
+        .ok_or_else(|| format!("Section {} is not present in the output", section))?;
+
+    let mut writer = csv::WriterBuilder::new().terminator(csv::Terminator::CRLF).from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Cannot write {} as CSV: {}", section, e);
+    writer.write_record(std::iter::once(*key_column).chain(columns.iter().copied())).map_err(error)?;
+    for (key, record) in records {
+        let cells = std::iter::once(key.clone()).chain(columns.iter().map(|column| cell(record.get(*column))));
+        writer.write_record(cells.map(guard_formula)).map_err(error)?;
+    }
+
+    let out = writer.into_inner().map_err(|e| error(e.into_error().into()))?;
+    String::from_utf8(out).map_err(|e| format!("Cannot write {} as CSV: {}", section, e))
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(items)) => items.iter().map(|item| cell(Some(item))).collect::<Vec<_>>().join(","),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Prefixes `'` to a cell starting with `=`, `+`, `-` or `@`, which spreadsheets
+/// would otherwise evaluate as a formula: a GECOS comment or share name is
+/// whatever a local user chose to put there.
+fn guard_formula(cell: String) -> String {
+    if cell.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn scratch(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("saltbox-facts-output-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn object_writer_lays_out_entries_as_write_json() {
+        let document = serde_json::json!({ "a": { "b": [1, "two\nlines"], "c": {} }, "d": null, "e": [] });
+        for pretty in [false, true] {
+            let mut expected = Vec::new();
+            write_json(&mut expected, &document, pretty).unwrap();
+            let mut writer = ObjectWriter::new(Vec::new(), pretty);
+            for (key, value) in document.as_object().unwrap() {
+                writer.entry(key, value).unwrap();
+            }
+            assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), String::from_utf8(expected).unwrap());
+        }
+        for pretty in [false, true] {
+            assert_eq!(ObjectWriter::new(Vec::new(), pretty).finish().unwrap(), b"{}\n");
+        }
+    }
+
+    #[test]
+    fn atomic_file_replaces_contents_and_keeps_permissions() {
+        let directory = scratch("atomic");
+        let path = directory.join("facts.json");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        file.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn env_names_that_collide_get_a_suffix() {
+        let value = serde_json::json!({ "mounts": { "/var/log": 1, "/var_log": 2, "_var_log": 3 }, "a-b": "x", "a_b": "y" });
+        let mut out = Vec::new();
+        write_env(&mut out, &value).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "SALTBOX_A_B='x'\nSALTBOX_A_B_2='y'\nSALTBOX_MOUNTS__VAR_LOG='1'\nSALTBOX_MOUNTS__VAR_LOG_2='2'\nSALTBOX_MOUNTS__VAR_LOG_3='3'\n"
+        );
+    }
+
+    #[test]
+    fn env_values_are_quoted() {
+        let value = serde_json::json!({ "comment": "it's $HOME", "list": [1], "none": null });
+        let mut out = Vec::new();
+        write_env(&mut out, &value).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "SALTBOX_COMMENT='it'\\''s $HOME'\nSALTBOX_LIST='[1]'\nSALTBOX_NONE=''\n");
+    }
+
+    #[test]
+    fn atomic_file_dropped_without_commit_leaves_nothing() {
+        let directory = scratch("dropped");
+        let path = directory.join("facts.json");
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn csv_cells_are_quoted_and_formulas_defused() {
+        let document = serde_json::json!({
+            "users": {
+                "=cmd": { "uid": "1001", "gid": "-1", "comment": "Seed, \"the\" user\nsecond line", "home": "@home", "shell": "+x" },
+                "root": { "uid": "0", "gid": "0", "comment": null, "home": "/root", "shell": "/bin/bash" }
+            },
+            "groups": { "docker": { "gid": "998", "group-list": ["seed", "plex"] } }
+        });
+        assert_eq!(
+            render_csv(&document, "users").unwrap(),
+            "name,uid,gid,comment,home,shell\r\n'=cmd,1001,'-1,\"Seed, \"\"the\"\" user\nsecond line\",'@home,'+x\r\nroot,0,0,,/root,/bin/bash\r\n"
+        );
+        assert_eq!(render_csv(&document, "groups").unwrap(), "name,gid,group-list\r\ndocker,998,\"seed,plex\"\r\n");
+        assert!(render_csv(&document, "mounts").unwrap_err().contains("not present"));
+        assert!(render_csv(&document, "ip").unwrap_err().contains("cannot be exported as CSV"));
+    }
+}
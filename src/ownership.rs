@@ -0,0 +1,192 @@
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::accounts::{self, GROUP_FILE_PATH, PASSWD_FILE_PATH};
+use crate::errors::{Code, Error};
+
+pub const DEFAULT_OWNERSHIP_ROOTS: &[&str] = &["/opt", "/srv"];
+pub const DEFAULT_MAX_FILES: usize = 500_000;
+/// Mismatched paths listed per application directory.
+const MAX_SAMPLES: usize = 5;
+
+/// Per application directory (each directory directly under a root), how many
+/// entries are owned by someone other than the expected user or group, with a few
+/// examples and a count per unexpected owner. Only metadata is read.
+///
+/// The expected owner is `user` and its primary group when given, else the owner
+/// and group of the application directory itself. The walk doesn't follow symlinks
+/// or cross into other filesystems, and stops after `max_files` entries per root
+/// (0 for no limit), setting `complete` to false. Symlinks aren't counted.
+pub fn get_ownership(roots: &[String], user: Option<&str>, max_files: usize) -> Result<Value, Error> {
+    let users = user_names();
+    let groups = group_names();
+    let expected = user.map(expected_ids).transpose()?;
+    let name = |names: &HashMap<u32, String>, id: u32| names.get(&id).cloned().unwrap_or_else(|| id.to_string());
+
+    let mut reports = Map::new();
+    for root in roots {
+        let mut walk = Walk { max_files, scanned: 0, complete: true };
+        let mut apps = Map::new();
+        let entries = match fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                let error = Error::read(root, &e);
+                reports.insert(root.clone(), json!({ "error": error, "apps": {}, "entries": 0, "complete": false, "mismatched_owner": 0 }));
+                continue;
+            }
+        };
+        let mut dirs: Vec<_> = entries.filter_map(|entry| entry.ok()).filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir())).collect();
+        dirs.sort_by_key(|entry| entry.file_name());
+        let mut mismatched_total = 0;
+        for dir in dirs {
+            let Some(owner) = metadata::ids(&dir.path()) else {
+                continue;
+            };
+            let (uid, gid) = expected.unwrap_or((owner.uid, owner.gid));
+            let mut audit = Audit { uid, gid, dev: owner.dev, ..Audit::default() };
+            audit.visit(&dir.path(), &mut walk);
+            mismatched_total += audit.mismatched_owner;
+            let owners: Map<String, Value> = audit.owners.iter().map(|(id, count)| (name(&users, *id), json!(count))).collect();
+            apps.insert(
+                dir.file_name().to_string_lossy().into_owned(),
+                json!({
+                    "owner": name(&users, owner.uid),
+                    "group": name(&groups, owner.gid),
+                    "expected_owner": name(&users, uid),
+                    "expected_group": name(&groups, gid),
+                    "entries": audit.entries,
+                    "mismatched_owner": audit.mismatched_owner,
+                    "mismatched_group": audit.mismatched_group,
+                    "unexpected_owners": owners,
+                    "samples": audit.samples
+                }),
+            );
+            if !walk.complete {
+                break;
+            }
+        }
+        reports.insert(
+            root.clone(),
+            json!({ "error": Value::Null, "apps": apps, "entries": walk.scanned, "complete": walk.complete, "mismatched_owner": mismatched_total }),
+        );
+    }
+    Ok(json!({ "expected_user": user, "roots": reports }))
+}
+
+struct Walk {
+    max_files: usize,
+    scanned: usize,
+    complete: bool,
+}
+
+#[derive(Default)]
+struct Audit {
+    uid: u32,
+    gid: u32,
+    dev: u64,
+    entries: u64,
+    mismatched_owner: u64,
+    mismatched_group: u64,
+    owners: BTreeMap<u32, u64>,
+    samples: Vec<String>,
+}
+
+impl Audit {
+    fn visit(&mut self, dir: &Path, walk: &mut Walk) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        // In name order, so the samples and where --ownership-max-files stops don't
+        // depend on the order the filesystem lists entries in.
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            if walk.max_files > 0 && walk.scanned >= walk.max_files {
+                walk.complete = false;
+                return;
+            }
+            let path = entry.path();
+            let Some(ids) = metadata::ids(&path).filter(|ids| !ids.symlink) else {
+                continue;
+            };
+            walk.scanned += 1;
+            self.entries += 1;
+            if ids.uid != self.uid {
+                self.mismatched_owner += 1;
+                *self.owners.entry(ids.uid).or_default() += 1;
+            }
+            if ids.gid != self.gid {
+                self.mismatched_group += 1;
+            }
+            if (ids.uid != self.uid || ids.gid != self.gid) && self.samples.len() < MAX_SAMPLES {
+                self.samples.push(path.display().to_string());
+            }
+            if ids.dir && ids.dev == self.dev {
+                self.visit(&path, walk);
+            }
+        }
+    }
+}
+
+/// User names by uid.
+fn user_names() -> HashMap<u32, String> {
+    let Ok(contents) = accounts::read(PASSWD_FILE_PATH) else {
+        return HashMap::new();
+    };
+    let Ok((users, _)) = accounts::users(&contents, PASSWD_FILE_PATH, 0) else {
+        return HashMap::new();
+    };
+    users.into_iter().filter_map(|(name, user)| Some((user.uid.parse().ok()?, name.to_string()))).collect()
+}
+
+/// Group names by gid.
+fn group_names() -> HashMap<u32, String> {
+    let Ok(contents) = accounts::read(GROUP_FILE_PATH) else {
+        return HashMap::new();
+    };
+    let Ok((groups, _)) = accounts::groups(&contents, GROUP_FILE_PATH, 0) else {
+        return HashMap::new();
+    };
+    groups.into_iter().filter_map(|(name, group)| Some((group.gid.parse().ok()?, name.to_string()))).collect()
+}
+
+/// The uid and primary gid of `user`, by name or number.
+fn expected_ids(user: &str) -> Result<(u32, u32), Error> {
+    let contents = accounts::read(PASSWD_FILE_PATH).map_err(|e| Error::new(Code::Unknown, PASSWD_FILE_PATH, e.to_string()))?;
+    let (users, _) = accounts::users(&contents, PASSWD_FILE_PATH, 0).map_err(|e| Error::new(Code::Unknown, PASSWD_FILE_PATH, e.to_string()))?;
+    let ids = |entry: &accounts::User| Some((entry.uid.parse().ok()?, entry.gid.parse().ok()?));
+    users
+        .get(user)
+        .and_then(ids)
+        .or_else(|| users.values().filter_map(ids).find(|(uid, _): &(u32, u32)| uid.to_string() == user))
+        .ok_or_else(|| Error::new(Code::NotFound, user, format!("Unknown user: {}", user)))
+}
+
+mod metadata {
+    use std::path::Path;
+
+    pub struct Ids {
+        pub uid: u32,
+        pub gid: u32,
+        pub dev: u64,
+        pub dir: bool,
+        pub symlink: bool,
+    }
+
+    /// Owner, group and device of `path` itself; symlinks aren't followed.
+    #[cfg(unix)]
+    pub fn ids(path: &Path) -> Option<Ids> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        Some(Ids { uid: metadata.uid(), gid: metadata.gid(), dev: metadata.dev(), dir: metadata.is_dir(), symlink: metadata.is_symlink() })
+    }
+
+    #[cfg(not(unix))]
+    pub fn ids(_path: &Path) -> Option<Ids> {
+        None
+    }
+}
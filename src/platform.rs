@@ -0,0 +1,12 @@
+/// Whether the process has full privileges: root on Unix. Always false elsewhere,
+/// where no collector reads privileged sources yet.
+pub fn is_root() -> bool {
+    #[cfg(unix)]
+    {
+        unsafe { libc::geteuid() == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
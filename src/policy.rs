@@ -0,0 +1,138 @@
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::expr::{is_truthy, Expr};
+use crate::static_facts::load_file;
+
+/// A named assertion that must hold for a facts document.
+#[derive(Clone)]
+pub struct Rule {
+    pub name: String,
+    pub expr: Expr,
+    pub message: Option<String>,
+}
+
+/// Parses an `--assert` expression; the rule is named after its source.
+pub fn parse_rule(source: &str) -> Result<Rule, String> {
+    Ok(Rule {
+        name: source.trim().to_string(),
+        expr: Expr::parse(source).map_err(|e| format!("Assertion {:?}: {}", source, e))?,
+        message: None,
+    })
+}
+
+/// Loads a JSON/YAML mapping of rule names to either an expression string or
+/// `{assert: EXPR, message: TEXT}`.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let value = load_file(path)?;
+    let map = value
+        .as_object()
+        .ok_or_else(|| format!("{} must contain a mapping of rule names to assertions", path.display()))?;
+    map.iter()
+        .map(|(name, rule)| {
+            let (source, message) = match rule {
+                Value::String(source) => (source.as_str(), None),
+                Value::Object(rule) => (
+                    rule.get("assert")
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| format!("{}: rule {} needs an \"assert\" expression", path.display(), name))?,
+                    rule.get("message").and_then(Value::as_str).map(String::from),
+                ),
+                _ => return Err(format!("{}: rule {} must be an expression or a mapping", path.display(), name)),
+            };
+            Ok(Rule {
+                name: name.clone(),
+                expr: Expr::parse(source).map_err(|e| format!("{}: rule {}: {}", path.display(), name, e))?,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Returns `<rule>: <problem>` for every rule that is falsy or can't be evaluated.
+pub fn check(document: &Value, rules: &[Rule]) -> Vec<String> {
+    rules
+        .iter()
+        .filter_map(|rule| failure(document, rule).map(|problem| format!("{}: {}", rule.name, problem)))
+        .collect()
+}
+
+/// Evaluates the rules into the `assertions` section, `{passed: [names], failed:
+/// {name: problem}}`, and returns how many failed.
+pub fn apply(document: &mut Value, rules: &[Rule]) -> usize {
+    let mut passed = Vec::new();
+    let mut failed = Map::new();
+    for rule in rules {
+        match failure(document, rule) {
+            Some(problem) => {
+                failed.insert(rule.name.clone(), json!(problem));
+            }
+            None => passed.push(rule.name.clone()),
+        }
+    }
+    let count = failed.len();
+    document["assertions"] = json!({ "passed": passed, "failed": failed });
+    count
+}
+
+fn failure(document: &Value, rule: &Rule) -> Option<String> {
+    match rule.expr.evaluate(document) {
+        Ok(value) if is_truthy(&value) => None,
+        Ok(_) => Some(rule.message.clone().unwrap_or_else(|| "assertion failed".to_string())),
+        Err(e) => Some(format!("cannot evaluate: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn document() -> Value {
+        json!({ "mounts": { "/": { "available_gb": 42 } }, "users": { "root": {}, "seed": {} } })
+    }
+
+    #[test]
+    fn passing_failing_and_missing_paths() {
+        let rules = [
+            parse_rule("mounts[\"/\"].available_gb > 10").unwrap(),
+            parse_rule("len(users) > 2").unwrap(),
+            parse_rule("docker.version").unwrap(),
+            parse_rule("-users").unwrap(),
+        ];
+        assert_eq!(
+            check(&document(), &rules),
+            ["len(users) > 2: assertion failed", "docker.version: assertion failed", "-users: cannot evaluate: Cannot negate {\"root\":{},\"seed\":{}}"]
+        );
+
+        let mut document = document();
+        assert_eq!(apply(&mut document, &rules[..3]), 2);
+        assert_eq!(
+            document["assertions"],
+            json!({ "passed": ["mounts[\"/\"].available_gb > 10"], "failed": { "len(users) > 2": "assertion failed", "docker.version": "assertion failed" } })
+        );
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert!(parse_rule("len(users) >").err().unwrap().starts_with("Assertion \"len(users) >\": "));
+    }
+
+    #[test]
+    fn rule_files_take_expressions_or_mappings_with_messages() {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-rules.yml", std::process::id()));
+        fs::write(&path, "space: mounts[\"/\"].available_gb > 100\ndocker:\n  assert: exists(docker)\n  message: Docker is not installed\n").unwrap();
+        let rules = load_rules(&path);
+        fs::write(&path, "broken:\n  message: no expression\n").unwrap();
+        let missing_assert = load_rules(&path).err();
+        fs::write(&path, "- not a mapping\n").unwrap();
+        let not_a_mapping = load_rules(&path).err();
+        fs::remove_file(&path).unwrap();
+
+        let rules = rules.unwrap();
+        assert_eq!(rules.iter().map(|rule| rule.name.as_str()).collect::<Vec<_>>(), ["docker", "space"]);
+        assert_eq!(check(&document(), &rules), ["docker: Docker is not installed", "space: assertion failed"]);
+        assert!(missing_assert.unwrap().ends_with("rule broken needs an \"assert\" expression"));
+        assert!(not_a_mapping.unwrap().ends_with("must contain a mapping of rule names to assertions"));
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::accounts::{self, PASSWD_FILE_PATH};
+use crate::docker;
+use crate::errors::Error;
+
+pub const PROC_PATH: &str = "/proc";
+pub const DEFAULT_CPU_THRESHOLD: u64 = 10;
+pub const DEFAULT_MEMORY_THRESHOLD_MB: u64 = 512;
+/// CPU use is measured between two reads this far apart.
+const SAMPLE: Duration = Duration::from_millis(500);
+/// Clock ticks per second in /proc/<pid>/stat: USER_HZ, 100 on every architecture
+/// Saltbox runs on.
+const TICKS_PER_SEC: f64 = 100.0;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+struct Process {
+    pid: u32,
+    name: String,
+    uid: Option<u32>,
+    rss_bytes: u64,
+    ticks: u64,
+}
+
+/// Processes using at least `cpu_threshold` percent of a CPU (measured over a short
+/// sample) or `memory_threshold_mb` of resident memory, busiest first, with their
+/// user, cgroup and Docker container: a "what's hogging the box" snapshot without
+/// a shell on it.
+///
+/// Only stat, status and cgroup are read; command lines, which can hold secrets,
+/// aren't. Processes that start or exit during the sample are left out.
+pub async fn get_processes(cpu_threshold: u64, memory_threshold_mb: u64) -> Value {
+    let before = match scan() {
+        Ok(processes) => processes,
+        Err(e) => {
+            return json!({
+                "error": Error::read(PROC_PATH, &e),
+                "sample_ms": 0,
+                "cpu_threshold_percent": cpu_threshold,
+                "memory_threshold_bytes": memory_threshold_mb * BYTES_PER_MB,
+                "scanned": 0,
+                "processes": []
+            })
+        }
+    };
+    let started = Instant::now();
+    tokio::time::sleep(SAMPLE).await;
+    let after = scan().unwrap_or_default();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let ticks_before: HashMap<u32, u64> = before.iter().map(|process| (process.pid, process.ticks)).collect();
+    let memory_total = memory_total_bytes();
+    let users = user_names();
+    let mut listed: Vec<(f64, Process)> = after
+        .into_iter()
+        .filter_map(|process| {
+            let ticks = process.ticks.saturating_sub(*ticks_before.get(&process.pid)?);
+            Some((ticks as f64 / TICKS_PER_SEC / elapsed * 100.0, process))
+        })
+        .filter(|(cpu, process)| *cpu >= cpu_threshold as f64 || process.rss_bytes >= memory_threshold_mb * BYTES_PER_MB)
+        .collect();
+    listed.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.rss_bytes.cmp(&a.1.rss_bytes)).then_with(|| a.1.pid.cmp(&b.1.pid)));
+
+    let cgroups: Vec<Option<String>> = listed.iter().map(|(_, process)| cgroup(process.pid)).collect();
+    let names = if cgroups.iter().flatten().any(|cgroup| docker::container_id(cgroup).is_some()) {
+        docker::get("/containers/json").await.map(|containers| docker::container_names(&containers)).unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
+    let processes: Vec<Value> = listed
+        .into_iter()
+        .zip(cgroups)
+        .map(|((cpu, process), cgroup)| {
+            let container = cgroup.as_deref().and_then(docker::container_id).map(|id| names.get(id).cloned().unwrap_or_else(|| id[..12].to_string()));
+            json!({
+                "pid": process.pid,
+                "name": process.name,
+                "uid": process.uid,
+                "user": process.uid.and_then(|uid| users.get(&uid)),
+                "cgroup": cgroup,
+                "container": container,
+                "rss_bytes": process.rss_bytes,
+                "memory_percent": memory_total.filter(|total| *total > 0).map(|total| round(process.rss_bytes as f64 * 100.0 / total as f64)),
+                "cpu_percent": round(cpu)
+            })
+        })
+        .collect();
+    json!({
+        "error": Value::Null,
+        "sample_ms": (elapsed * 1000.0).round() as u64,
+        "cpu_threshold_percent": cpu_threshold,
+        "memory_threshold_bytes": memory_threshold_mb * BYTES_PER_MB,
+        "scanned": before.len(),
+        "processes": processes
+    })
+}
+
+/// Every process readable in /proc, kernel threads included: they have no resident
+/// memory but a busy one still counts.
+fn scan() -> std::io::Result<Vec<Process>> {
+    let mut processes = Vec::new();
+    for entry in fs::read_dir(PROC_PATH)?.filter_map(|entry| entry.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // `pid (comm) state ppid ...`: comm may hold spaces and parentheses, so the
+        // fields after it are found from the last `)`.
+        let Some((head, rest)) = stat.rsplit_once(')') else {
+            continue;
+        };
+        let name = head.split_once('(').map_or("", |(_, name)| name).to_string();
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        // utime and stime are fields 14 and 15 of stat, 12 and 13 after comm.
+        let ticks = fields.get(11).and_then(|utime| utime.parse::<u64>().ok()).unwrap_or(0)
+            + fields.get(12).and_then(|stime| stime.parse::<u64>().ok()).unwrap_or(0);
+        let status = fs::read_to_string(entry.path().join("status")).unwrap_or_default();
+        let field = |key: &str| status.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(':').map(str::trim));
+        processes.push(Process {
+            pid,
+            name,
+            uid: field("Uid").and_then(|uids| uids.split_whitespace().next()?.parse().ok()),
+            rss_bytes: field("VmRSS").and_then(|rss| rss.strip_suffix("kB")?.trim().parse::<u64>().ok()).map_or(0, |kib| kib * 1024),
+            ticks,
+        });
+    }
+    Ok(processes)
+}
+
+/// The unified (v2) cgroup path, or on a v1 host the one systemd tracks the
+/// process in, which names the service or container scope.
+pub fn cgroup(pid: u32) -> Option<String> {
+    let cgroups = fs::read_to_string(format!("{}/{}/cgroup", PROC_PATH, pid)).ok()?;
+    // `hierarchy-id:controllers:path`, with no controllers for the unified hierarchy.
+    let path = |wanted: &str| {
+        cgroups.lines().find_map(|line| {
+            let (_, rest) = line.split_once(':')?;
+            let (controllers, path) = rest.split_once(':')?;
+            (controllers == wanted).then(|| path.to_string())
+        })
+    };
+    path("").or_else(|| path("name=systemd"))
+}
+
+fn memory_total_bytes() -> Option<u64> {
+    let meminfo = fs::read_to_string(format!("{}/meminfo", PROC_PATH)).ok()?;
+    meminfo.lines().find_map(|line| line.strip_prefix("MemTotal:")?.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()).map(|kib| kib * 1024)
+}
+
+fn user_names() -> HashMap<u32, String> {
+    let Ok(contents) = accounts::read(PASSWD_FILE_PATH) else {
+        return HashMap::new();
+    };
+    let Ok((users, _)) = accounts::users(&contents, PASSWD_FILE_PATH, 0) else {
+        return HashMap::new();
+    };
+    users.into_iter().filter_map(|(name, user)| Some((user.uid.parse().ok()?, name.to_string()))).collect()
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -0,0 +1,68 @@
+use serde_json::json;
+use std::time::Duration;
+
+use crate::output::ReportFormat;
+use crate::timestamp::now_rfc3339;
+
+/// `--progress` lines on stderr, so someone running the full collector suite by hand
+/// can see what it is waiting on. Does nothing when no format was asked for.
+pub struct Progress<'a> {
+    format: Option<&'a ReportFormat>,
+    total: usize,
+    position: usize,
+    running: bool,
+}
+
+impl<'a> Progress<'a> {
+    /// Starts reporting and announces every collector as pending.
+    pub fn start(format: Option<&'a ReportFormat>, collectors: &[&str]) -> Progress<'a> {
+        if let Some(ReportFormat::Json) = format {
+            emit(json!({ "event": "pending", "collectors": collectors }));
+        }
+        Progress {
+            format,
+            total: collectors.len(),
+            position: 0,
+            running: false,
+        }
+    }
+
+    pub fn running(&mut self, collector: &str) {
+        self.position += 1;
+        self.running = true;
+        match self.format {
+            Some(ReportFormat::Text) => eprintln!("[{:>2}/{}] {}...", self.position, self.total, collector),
+            Some(ReportFormat::Json) => emit(json!({ "event": "running", "collector": collector })),
+            None => {}
+        }
+    }
+
+    /// Reports `collector` as done: ok, cached or failed after running, or skipped or
+    /// cancelled without.
+    pub fn finished(&mut self, collector: &str, status: &str, elapsed: Duration) {
+        if !std::mem::take(&mut self.running) {
+            self.position += 1;
+        }
+        let elapsed_ms = elapsed.as_millis() as u64;
+        match self.format {
+            Some(ReportFormat::Text) if status == "skipped" || status == "cancelled" => {
+                eprintln!("[{:>2}/{}] {}: {}", self.position, self.total, collector, status)
+            }
+            Some(ReportFormat::Text) => {
+                eprintln!("[{:>2}/{}] {}: {} in {} ms", self.position, self.total, collector, status, elapsed_ms)
+            }
+            Some(ReportFormat::Json) => emit(json!({
+                "event": "done",
+                "collector": collector,
+                "status": status,
+                "elapsed_ms": elapsed_ms
+            })),
+            None => {}
+        }
+    }
+}
+
+fn emit(mut event: serde_json::Value) {
+    event["time"] = json!(now_rfc3339());
+    eprintln!("{}", event);
+}
@@ -0,0 +1,356 @@
+use serde_json::Value;
+
+/// A compiled query in the JMESPath subset understood by `--query`:
+///
+/// - `ip.public_ip`                 sub-expressions
+/// - `users."systemd-network".uid`  quoted identifiers for keys that aren't plain words
+/// - `locales.available[0]`, `[-1]` indexes, negative from the end
+/// - `users.*.shell`, `list[*].x`   object and list projections (nulls are dropped)
+/// - `{ip: ip.public_ip}`, `[a, b]`  multiselect hashes and lists
+/// - `users.*.shell | [0]`          pipes, which end a projection
+#[derive(Clone)]
+pub struct Query {
+    stages: Vec<Vec<Step>>,
+}
+
+#[derive(Clone)]
+enum Step {
+    Field(String),
+    Index(i64),
+    ObjectProjection,
+    ListProjection,
+    MultiselectHash(Vec<(String, Query)>),
+    MultiselectList(Vec<Query>),
+}
+
+impl Query {
+    pub fn parse(source: &str) -> Result<Query, String> {
+        let mut parser = Parser { source, chars: source.chars().collect(), pos: 0 };
+        let query = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(query)
+    }
+
+    /// The key the query starts with, if it starts with one.
+    pub fn first_field(&self) -> Option<&str> {
+        match self.stages.first()?.first()? {
+            Step::Field(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn evaluate(&self, document: &Value) -> Value {
+        let mut stages = self.stages.iter();
+        let first = stages.next().map_or_else(|| document.clone(), |steps| evaluate(steps, document));
+        stages.fold(first, |value, steps| evaluate(steps, &value))
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("Invalid query {:?} at position {}: {}", self.source, self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Pipe-separated stages, up to the end or a `,`, `}` or `]` closing an
+    /// enclosing multiselect.
+    fn expression(&mut self) -> Result<Query, String> {
+        let mut stages = vec![self.stage()?];
+        loop {
+            self.skip_whitespace();
+            if self.chars.get(self.pos) != Some(&'|') {
+                return Ok(Query { stages });
+            }
+            self.pos += 1;
+            stages.push(self.stage()?);
+        }
+    }
+
+    fn stage(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        let mut expect_segment = true;
+
+        loop {
+            self.skip_whitespace();
+            let Some(&c) = self.chars.get(self.pos) else {
+                break;
+            };
+            match c {
+                '[' => {
+                    let close = self.chars[self.pos..]
+                        .iter()
+                        .position(|&c| c == ']')
+                        .map(|offset| self.pos + offset)
+                        .ok_or_else(|| self.error("unclosed '['"))?;
+                    let inner: String = self.chars[self.pos + 1..close].iter().collect();
+                    let inner = inner.trim();
+                    if inner == "*" {
+                        steps.push(Step::ListProjection);
+                        self.pos = close + 1;
+                    } else if let Ok(index) = inner.parse::<i64>() {
+                        steps.push(Step::Index(index));
+                        self.pos = close + 1;
+                    } else if expect_segment {
+                        self.pos += 1;
+                        steps.push(Step::MultiselectList(self.list()?));
+                    } else {
+                        self.pos += 1;
+                        return Err(self.error("expected an index or '*'"));
+                    }
+                    expect_segment = false;
+                }
+                '{' if expect_segment => {
+                    self.pos += 1;
+                    steps.push(Step::MultiselectHash(self.hash()?));
+                    expect_segment = false;
+                }
+                '.' if !expect_segment => {
+                    self.pos += 1;
+                    expect_segment = true;
+                }
+                '*' if expect_segment => {
+                    steps.push(Step::ObjectProjection);
+                    self.pos += 1;
+                    expect_segment = false;
+                }
+                '"' if expect_segment => {
+                    steps.push(Step::Field(self.identifier()?));
+                    expect_segment = false;
+                }
+                c if expect_segment && (c.is_ascii_alphabetic() || c == '_') => {
+                    steps.push(Step::Field(self.identifier()?));
+                    expect_segment = false;
+                }
+                '|' | ',' | '}' | ']' if !expect_segment => break,
+                _ => return Err(self.error("unexpected character")),
+            }
+        }
+
+        if steps.is_empty() || expect_segment {
+            return Err(self.error("expected an identifier"));
+        }
+        Ok(steps)
+    }
+
+    /// `[a, b]` after its `[`.
+    fn list(&mut self) -> Result<Vec<Query>, String> {
+        let mut items = Vec::new();
+        loop {
+            items.push(self.expression()?);
+            if self.close(']')? {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// `{key: a, other: b}` after its `{`.
+    fn hash(&mut self) -> Result<Vec<(String, Query)>, String> {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let key = self.identifier()?;
+            self.skip_whitespace();
+            if self.chars.get(self.pos) != Some(&':') {
+                return Err(self.error("expected ':'"));
+            }
+            self.pos += 1;
+            entries.push((key, self.expression()?));
+            if self.close('}')? {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// Consumes the `,` before another multiselect item (false) or the `closing`
+    /// delimiter (true).
+    fn close(&mut self, closing: char) -> Result<bool, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some(',') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            Some(&c) if c == closing => {
+                self.pos += 1;
+                Ok(true)
+            }
+            _ => Err(self.error(&format!("expected ',' or '{}'", closing))),
+        }
+    }
+
+    /// A plain or quoted identifier.
+    fn identifier(&mut self) -> Result<String, String> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            let start = self.pos;
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                self.pos += 1;
+            }
+            if start == self.pos {
+                return Err(self.error("expected an identifier"));
+            }
+            return Ok(self.chars[start..self.pos].iter().collect());
+        }
+        let mut name = String::new();
+        self.pos += 1;
+        loop {
+            match self.chars.get(self.pos) {
+                Some('"') => break,
+                Some('\\') => {
+                    match self.chars.get(self.pos + 1) {
+                        Some(&c) => name.push(c),
+                        None => return Err(self.error("unterminated escape")),
+                    }
+                    self.pos += 2;
+                }
+                Some(&c) => {
+                    name.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated quoted identifier")),
+            }
+        }
+        self.pos += 1;
+        Ok(name)
+    }
+}
+
+fn evaluate(steps: &[Step], value: &Value) -> Value {
+    let Some((step, rest)) = steps.split_first() else {
+        return value.clone();
+    };
+
+    match step {
+        Step::Field(name) => match value.get(name) {
+            Some(child) => evaluate(rest, child),
+            None => Value::Null,
+        },
+        Step::Index(index) => match value.as_array() {
+            Some(items) => {
+                let resolved = if *index < 0 { items.len() as i64 + index } else { *index };
+                match usize::try_from(resolved).ok().and_then(|i| items.get(i)) {
+                    Some(child) => evaluate(rest, child),
+                    None => Value::Null,
+                }
+            }
+            None => Value::Null,
+        },
+        Step::ObjectProjection => match value.as_object() {
+            Some(map) => project(rest, map.values()),
+            None => Value::Null,
+        },
+        Step::ListProjection => match value.as_array() {
+            Some(items) => project(rest, items.iter()),
+            None => Value::Null,
+        },
+        // Like JMESPath, a multiselect of nothing is nothing rather than nulls.
+        Step::MultiselectHash(_) | Step::MultiselectList(_) if value.is_null() => Value::Null,
+        Step::MultiselectHash(entries) => {
+            let selected = entries.iter().map(|(key, query)| (key.clone(), query.evaluate(value))).collect();
+            evaluate(rest, &Value::Object(selected))
+        }
+        Step::MultiselectList(items) => evaluate(rest, &Value::Array(items.iter().map(|query| query.evaluate(value)).collect())),
+    }
+}
+
+fn project<'a>(rest: &[Step], values: impl Iterator<Item = &'a Value>) -> Value {
+    Value::Array(
+        values
+            .map(|value| evaluate(rest, value))
+            .filter(|value| !value.is_null())
+            .collect(),
+    )
+}
+
+/// Strings are printed without quotes so shell consumers don't need jq; everything
+/// else is printed as JSON.
+pub fn render_raw(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(source: &str, document: &Value) -> Value {
+        Query::parse(source).unwrap().evaluate(document)
+    }
+
+    fn document() -> Value {
+        json!({
+            "ip": { "public_ip": "203.0.113.7" },
+            "users": {
+                "root": { "uid": "0", "shell": "/bin/bash" },
+                "systemd-network": { "uid": "998", "shell": "/usr/sbin/nologin" },
+                "nobody": { "uid": "65534" }
+            },
+            "locales": { "available": ["C.UTF-8", "en_US.UTF-8"] },
+            "mounts": [{ "path": "/", "size": 10 }, { "path": "/opt" }]
+        })
+    }
+
+    #[test]
+    fn fields_and_quoted_identifiers() {
+        let document = document();
+        assert_eq!(query("ip.public_ip", &document), json!("203.0.113.7"));
+        assert_eq!(query("users.\"systemd-network\".uid", &document), json!("998"));
+        assert_eq!(query("users.missing.uid", &document), Value::Null);
+        assert_eq!(Query::parse("users.\"systemd-network\"").unwrap().first_field(), Some("users"));
+    }
+
+    #[test]
+    fn indexes_count_from_either_end() {
+        let document = document();
+        assert_eq!(query("locales.available[0]", &document), json!("C.UTF-8"));
+        assert_eq!(query("locales.available[-1]", &document), json!("en_US.UTF-8"));
+        assert_eq!(query("locales.available[5]", &document), Value::Null);
+    }
+
+    #[test]
+    fn projections_drop_nulls_and_pipes_end_them() {
+        let document = document();
+        assert_eq!(query("users.*.shell", &document), json!(["/bin/bash", "/usr/sbin/nologin"]));
+        assert_eq!(query("mounts[*].size", &document), json!([10]));
+        assert_eq!(query("mounts[*].path | [0]", &document), json!("/"));
+        assert_eq!(query("ip.*", &document), json!(["203.0.113.7"]));
+        assert_eq!(query("ip[*]", &document), Value::Null);
+    }
+
+    #[test]
+    fn multiselects() {
+        let document = document();
+        assert_eq!(query("{ip: ip.public_ip, root: users.root.uid}", &document), json!({ "ip": "203.0.113.7", "root": "0" }));
+        assert_eq!(query("[ip.public_ip, users.root.uid]", &document), json!(["203.0.113.7", "0"]));
+        assert_eq!(query("missing.{a: b}", &document), Value::Null);
+    }
+
+    #[test]
+    fn malformed_queries_are_rejected() {
+        for source in ["", "ip.", "users.\"unterminated", "locales.available[", "locales.available[x]", "{ip ip}", "[a,", "ip public_ip", "a | "] {
+            assert!(Query::parse(source).is_err(), "{:?} should not parse", source);
+        }
+    }
+
+    #[test]
+    fn raw_rendering_unquotes_strings_only() {
+        assert_eq!(render_raw(&json!("text")), "text");
+        assert_eq!(render_raw(&json!({ "a": 1 })), "{\"a\":1}");
+    }
+}
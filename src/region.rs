@@ -0,0 +1,89 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::capability;
+use crate::errors::{Code, Error};
+
+/// Regional (not anycast) endpoints, so connect latency says roughly how far away
+/// each continent is. Only TCP handshakes are made; nothing is sent.
+const ANCHORS: &[(&str, &str)] = &[
+    ("north_america", "ec2.us-east-1.amazonaws.com:443"),
+    ("north_america", "ec2.us-west-2.amazonaws.com:443"),
+    ("south_america", "ec2.sa-east-1.amazonaws.com:443"),
+    ("europe", "ec2.eu-central-1.amazonaws.com:443"),
+    ("europe", "ec2.eu-west-1.amazonaws.com:443"),
+    ("africa", "ec2.af-south-1.amazonaws.com:443"),
+    ("asia", "ec2.ap-northeast-1.amazonaws.com:443"),
+    ("asia", "ec2.ap-southeast-1.amazonaws.com:443"),
+    ("oceania", "ec2.ap-southeast-2.amazonaws.com:443"),
+];
+
+/// Handshakes per anchor; the fastest counts, filtering out a slow first SYN.
+const SAMPLES: usize = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A coarse region hint from the continent with the lowest connect latency,
+/// without asking a geolocation service about this host's address.
+pub async fn get_region_hint() -> Value {
+    let targets: Vec<&str> = ANCHORS.iter().map(|(_, target)| *target).collect();
+    if let Err(e) = capability::check_network_group(&targets.join(", ")) {
+        return json!({ "hint": Value::Null, "latency_ms": {}, "errors": [e] });
+    }
+
+    let mut probes = JoinSet::new();
+    for (region, target) in ANCHORS {
+        capability::record_endpoint(target);
+        probes.spawn(async move { (*region, probe(target).await) });
+    }
+
+    let mut latencies: BTreeMap<&str, Option<u64>> = ANCHORS.iter().map(|(region, _)| (*region, None)).collect();
+    let mut errors = Vec::new();
+    while let Some(result) = probes.join_next().await {
+        match result {
+            Ok((region, Ok(latency))) => {
+                let best = latencies.entry(region).or_default();
+                *best = Some(best.map_or(latency, |best| best.min(latency)));
+            }
+            Ok((_, Err(e))) => errors.push(e),
+            Err(e) => errors.push(Error::new(Code::Unknown, "region", format!("probe failed: {}", e))),
+        }
+    }
+    errors.sort_by(|a, b| a.message.cmp(&b.message));
+
+    let hint = latencies
+        .iter()
+        .filter_map(|(region, latency)| latency.map(|latency| (latency, *region)))
+        .min()
+        .map(|(_, region)| region);
+    let latency_ms: Map<String, Value> = latencies.into_iter().map(|(region, latency)| (region.to_string(), json!(latency))).collect();
+    json!({ "hint": hint, "latency_ms": latency_ms, "errors": errors })
+}
+
+/// The fastest of `SAMPLES` handshakes with `target`, in ms. Name resolution is done
+/// once up front so it doesn't count towards the latency.
+async fn probe(target: &str) -> Result<u64, Error> {
+    // A name that doesn't resolve can't be reached; the I/O error says no more.
+    let address = lookup_host(target)
+        .await
+        .map_err(|e| Error::new(Code::Unreachable, target, format!("{}: {}", target, e)))?
+        .next()
+        .ok_or_else(|| Error::new(Code::Unreachable, target, format!("{}: no addresses", target)))?;
+    let mut best: Option<Duration> = None;
+    let mut last_error = None;
+    for _ in 0..SAMPLES {
+        let started = Instant::now();
+        match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+            Ok(Ok(_)) => best = Some(best.map_or(started.elapsed(), |best| best.min(started.elapsed()))),
+            Ok(Err(e)) => last_error = Some(Error::new(Code::of_io(&e), target, format!("{}: {}", target, e))),
+            Err(_) => last_error = Some(Error::new(Code::Timeout, target, format!("{}: timed out after {}s", target, CONNECT_TIMEOUT.as_secs()))),
+        }
+    }
+    match (best, last_error) {
+        (Some(best), _) => Ok(best.as_millis() as u64),
+        (None, error) => Err(error.unwrap_or_else(|| Error::new(Code::Unknown, target, format!("{}: no samples", target)))),
+    }
+}
@@ -0,0 +1,257 @@
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::cli::RemoteArgs;
+use crate::{facts, output};
+use crate::timestamp::now_rfc3339;
+
+/// Options every connection gets: never prompt (a prompt would hang the run) and
+/// give up on dead hosts quickly.
+const SSH_OPTIONS: &[&str] = &["BatchMode=yes", "ConnectTimeout=10"];
+
+/// What each host task needs, shared between the tasks.
+struct Plan {
+    binary: Option<Vec<u8>>,
+    binary_sha256: String,
+    remote_path: String,
+    ssh_options: Vec<String>,
+    collector_args: Vec<String>,
+}
+
+/// Collects facts from every host in the inventory over SSH and prints or writes
+/// one document with a `hosts` map of per-host facts and an `errors` map of hosts
+/// that couldn't be collected.
+pub async fn run(args: RemoteArgs) -> Result<(), Box<dyn Error>> {
+    let inventory = fs::read_to_string(&args.inventory).map_err(|e| format!("Cannot read {}: {}", args.inventory, e))?;
+    let hosts = parse_inventory(&inventory)?;
+    if hosts.is_empty() {
+        return Err(format!("No hosts in {}", args.inventory).into());
+    }
+
+    let binary = if args.no_copy {
+        None
+    } else {
+        let path = match &args.binary {
+            Some(path) => path.into(),
+            None => std::env::current_exe().map_err(|e| format!("Cannot locate the running binary: {}", e))?,
+        };
+        Some(fs::read(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?)
+    };
+    let plan = Arc::new(Plan {
+        binary_sha256: binary.as_deref().map(sha256_hex).unwrap_or_default(),
+        binary,
+        remote_path: args.remote_path,
+        ssh_options: args.ssh_options,
+        collector_args: args.collector_args,
+    });
+
+    let permits = Arc::new(Semaphore::new(args.parallel.max(1)));
+    let mut tasks = JoinSet::new();
+    for host in hosts {
+        let plan = plan.clone();
+        let permits = permits.clone();
+        let host_timeout = Duration::from_secs(args.timeout);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("semaphore is never closed");
+            let result = match timeout(host_timeout, collect(&host, &plan)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("timed out after {}s", host_timeout.as_secs())),
+            };
+            (host, result)
+        });
+    }
+
+    let mut facts = Map::new();
+    let mut errors = Map::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (host, result) = joined?;
+        match result {
+            Ok(value) => {
+                facts.insert(host, value);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", host, e);
+                errors.insert(host, Value::String(e));
+            }
+        }
+    }
+
+    let collected = facts.len();
+    let total = collected + errors.len();
+    let document = json!({
+        "saltbox_facts_version": crate::VERSION,
+        "collected_at": now_rfc3339(),
+        "hosts": facts,
+        "errors": errors
+    });
+
+    match &args.output {
+        Some(path) => {
+            let mut file = output::AtomicFile::create(Path::new(path))?;
+            output::write_json(&mut file, &document, false)?;
+            file.commit()?;
+        }
+        None => output::write_json(std::io::stdout().lock(), &document, false)?,
+    }
+    if collected == 0 {
+        return Err(format!("None of the {} hosts could be collected", total).into());
+    }
+    if collected < total {
+        eprintln!("Collected {} of {} hosts", collected, total);
+    }
+    Ok(())
+}
+
+/// One SSH destination per line, as the first whitespace-separated word so
+/// `host ansible_user=...` lines work; `#` comments and `[group]` headers are skipped.
+fn parse_inventory(contents: &str) -> Result<BTreeSet<String>, String> {
+    let mut hosts = BTreeSet::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some(host) = line.split_whitespace().next() else {
+            continue;
+        };
+        if host.starts_with('[') {
+            continue;
+        }
+        // ssh would take it as an option, e.g. -oProxyCommand=...
+        if host.starts_with('-') {
+            return Err(format!("Invalid host in inventory: {}", host));
+        }
+        hosts.insert(host.to_string());
+    }
+    Ok(hosts)
+}
+
+async fn collect(host: &str, plan: &Plan) -> Result<Value, String> {
+    if let Some(binary) = &plan.binary {
+        // Reuse the installed binary when it's already this exact build.
+        let probe = format!("uname -m; sha256sum -- {} 2>/dev/null || echo missing", shell_quote(&plan.remote_path));
+        let output = ssh(host, plan, &probe, None, &[]).await?;
+        let mut lines = output.lines();
+        let machine = lines.next().unwrap_or_default().trim();
+        let arch = rust_arch(machine);
+        if arch != std::env::consts::ARCH {
+            return Err(format!(
+                "host is {}, local binary is {}; pass --binary or install saltbox-facts and use --no-copy",
+                machine,
+                std::env::consts::ARCH
+            ));
+        }
+        let remote_sha256 = lines.next().and_then(|line| line.split_whitespace().next()).unwrap_or_default();
+        if remote_sha256 != plan.binary_sha256 {
+            let path = shell_quote(&plan.remote_path);
+            let dir = Path::new(&plan.remote_path).parent().map(|dir| dir.to_string_lossy().into_owned());
+            let mkdir = match dir.as_deref() {
+                Some(dir) if !dir.is_empty() => format!("mkdir -p -- {} && ", shell_quote(dir)),
+                _ => String::new(),
+            };
+            let upload = format!("{mkdir}cat > {path}.tmp.$$ && chmod 0755 {path}.tmp.$$ && mv -f {path}.tmp.$$ {path}");
+            ssh(host, plan, &upload, Some(binary), &[]).await?;
+        }
+    }
+
+    let mut command = shell_quote(&plan.remote_path);
+    for arg in &plan.collector_args {
+        command.push(' ');
+        command.push_str(&shell_quote(arg));
+    }
+    // A partial document is still a document; its failed section says what's missing.
+    let output = ssh(host, plan, &command, None, &[facts::EXIT_PARTIAL, facts::EXIT_ALL_FAILED]).await?;
+    serde_json::from_str(&output).map_err(|e| format!("invalid facts document: {}", e))
+}
+
+/// Runs `command` on `host` and returns its stdout, feeding `stdin` if given. Exit
+/// statuses other than 0 are errors unless listed in `accepted`.
+async fn ssh(host: &str, plan: &Plan, command: &str, stdin: Option<&[u8]>, accepted: &[i32]) -> Result<String, String> {
+    let mut ssh = Command::new("ssh");
+    for option in SSH_OPTIONS.iter().copied().chain(plan.ssh_options.iter().map(String::as_str)) {
+        ssh.arg("-o").arg(option);
+    }
+    ssh.arg("--")
+        .arg(host)
+        .arg(command)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = ssh.spawn().map_err(|e| format!("Error running ssh: {}", e))?;
+    if let (Some(data), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(data).await.map_err(|e| format!("Error writing to ssh: {}", e))?;
+    }
+    let output = child.wait_with_output().await.map_err(|e| format!("Error running ssh: {}", e))?;
+    if !output.status.success() && !output.status.code().is_some_and(|code| accepted.contains(&code)) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no error output");
+        return Err(format!("{} ({})", reason.trim(), output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|_| "output is not valid UTF-8".to_string())
+}
+
+/// The `std::env::consts::ARCH` name for what `uname -m` prints, which names
+/// several architectures differently, e.g. `armv7l` for `arm` and, on macOS and
+/// the BSDs, `arm64` for `aarch64`.
+fn rust_arch(machine: &str) -> &str {
+    match machine {
+        "amd64" => "x86_64",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "arm64" => "aarch64",
+        machine if machine.starts_with("armv") => "arm",
+        "ppc64le" => "powerpc64",
+        machine => machine,
+    }
+}
+
+/// Quotes `value` as a single POSIX shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inventories_list_the_first_word_of_each_host_line() {
+        let hosts = parse_inventory("[servers]\nalpha ansible_user=seed\n  beta  # comment\n# gamma\n\nseed@delta:2222\nalpha\n").unwrap();
+        assert_eq!(hosts.into_iter().collect::<Vec<_>>(), ["alpha", "beta", "seed@delta:2222"]);
+        assert!(parse_inventory("").unwrap().is_empty());
+        assert_eq!(parse_inventory("-oProxyCommand=evil\n").unwrap_err(), "Invalid host in inventory: -oProxyCommand=evil");
+    }
+
+    #[test]
+    fn values_are_quoted_as_one_shell_word() {
+        assert_eq!(shell_quote("/usr/local/bin/saltbox-facts"), "'/usr/local/bin/saltbox-facts'");
+        assert_eq!(shell_quote("it's $(here)"), "'it'\\''s $(here)'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn uname_machines_match_rust_arch_names() {
+        assert_eq!(rust_arch("x86_64"), "x86_64");
+        assert_eq!(rust_arch("amd64"), "x86_64");
+        assert_eq!(rust_arch("aarch64"), "aarch64");
+        assert_eq!(rust_arch("arm64"), "aarch64");
+        assert_eq!(rust_arch("armv7l"), "arm");
+        assert_eq!(rust_arch("armv6l"), "arm");
+        assert_eq!(rust_arch("i686"), "x86");
+        assert_eq!(rust_arch("riscv64"), "riscv64");
+    }
+}
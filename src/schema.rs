@@ -0,0 +1,81 @@
+use jsonschema::Validator;
+use serde_json::Value;
+
+/// A JSON Schema, validated by the jsonschema crate. `$schema` picks the draft,
+/// 2020-12 when there's none; `$ref`s resolve within the schema only, as nothing
+/// is fetched.
+pub struct Schema {
+    validator: Validator,
+}
+
+impl Schema {
+    pub fn new(root: Value) -> Result<Schema, String> {
+        let validator = jsonschema::validator_for(&root).map_err(|e| format!("invalid schema: {}", e))?;
+        Ok(Schema { validator })
+    }
+
+    /// Returns every violation as `<JSON Pointer>: <problem>`.
+    pub fn validate(&self, instance: &Value) -> Vec<String> {
+        self.validator
+            .iter_errors(instance)
+            .map(|error| {
+                let path = error.instance_path().as_str();
+                format!("{}: {}", if path.is_empty() { "/" } else { path }, error)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn problems(schema: Value, instance: Value) -> Vec<String> {
+        Schema::new(schema).unwrap().validate(&instance)
+    }
+
+    #[test]
+    fn types_and_required_properties() {
+        let schema = json!({
+            "type": "object",
+            "required": ["ip"],
+            "properties": { "ip": { "type": "object", "properties": { "public_ip": { "type": "string" } } } }
+        });
+        assert!(problems(schema.clone(), json!({ "ip": { "public_ip": "203.0.113.7" } })).is_empty());
+        assert_eq!(problems(schema.clone(), json!({ "ip": { "public_ip": 7 } })), ["/ip/public_ip: 7 is not of type \"string\""]);
+        assert_eq!(problems(schema.clone(), json!({})), ["/: \"ip\" is a required property"]);
+        assert_eq!(problems(schema, json!([])).len(), 1);
+    }
+
+    #[test]
+    fn additional_properties() {
+        let schema = json!({ "type": "object", "properties": { "a": true }, "additionalProperties": { "type": "integer" } });
+        assert!(problems(schema.clone(), json!({ "a": "x", "b": 1 })).is_empty());
+        let found = problems(schema, json!({ "a": "x", "b": "y" }));
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("/b: "), "{:?}", found);
+        let closed = json!({ "type": "object", "properties": { "a": true }, "additionalProperties": false });
+        assert_eq!(problems(closed, json!({ "a": 1, "extra": 2 })).len(), 1);
+    }
+
+    #[test]
+    fn local_refs_and_one_of() {
+        let schema = json!({
+            "$defs": { "error": { "type": "object", "required": ["code", "message"] } },
+            "type": "object",
+            "properties": { "error": { "oneOf": [{ "type": "null" }, { "$ref": "#/$defs/error" }] } }
+        });
+        assert!(problems(schema.clone(), json!({ "error": null })).is_empty());
+        assert!(problems(schema.clone(), json!({ "error": { "code": "timeout", "message": "slow" } })).is_empty());
+        let found = problems(schema, json!({ "error": { "code": "timeout" } }));
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("/error: "), "{:?}", found);
+    }
+
+    #[test]
+    fn invalid_schemas_are_rejected() {
+        assert!(Schema::new(json!({ "type": "strnig" })).is_err());
+        assert!(Schema::new(json!({ "$ref": "#/$defs/missing" })).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! The shape of the document, numbered separately from the binary's version so
+//! roles can depend on it.
+//!
+//! Within a schema version the document only grows: new collectors, new keys in a
+//! section and new enum values may appear in any release, but a key is never
+//! renamed, removed or given another type. A change that would break a consumer
+//! bumps `CURRENT` and is listed in `CHANGES`, and `--schema-version` keeps every
+//! earlier shape available: documents are built in the current shape and `shape`
+//! brings them back to the requested one.
+
+use serde_json::{json, Value};
+
+use crate::collectors;
+use crate::errors;
+
+/// The shape documents are printed in unless `--schema-version` asks otherwise.
+pub const CURRENT: u32 = 2;
+
+/// What each version changed from the one before.
+pub const CHANGES: &[(u32, &str)] = &[
+    (1, "The version and the sections alone, as the first releases printed them; error fields and `errors` entries are strings"),
+    (
+        2,
+        "Adds the envelope (schema_version, meta, freshness, truncated, skipped, cancelled, timed_out, failed, capability_violations, drop_ins, derived); error fields, `errors` entries and `failed` reasons are {code, message, source} objects",
+    ),
+];
+
+/// A `--schema-version` value: one of the versions in `CHANGES`.
+pub fn parse(value: &str) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|version| (1..=CURRENT).contains(version))
+        .ok_or_else(|| format!("Unknown schema version: {} (supported: 1 to {})", value, CURRENT))
+}
+
+/// Records `version` in a document built in the current shape and converts it to
+/// that version's shape. Version 1 has no envelope, nor the key recording it.
+pub fn shape(document: &mut Value, version: u32) {
+    if version < 2 {
+        errors::to_messages(document);
+    }
+    let Some(document) = document.as_object_mut() else {
+        return;
+    };
+    if version < 2 {
+        document.retain(|key, _| key == "saltbox_facts_version" || collectors::find(key).is_some());
+    } else {
+        document.insert("schema_version".to_string(), json!(version));
+    }
+}
+
+/// `shape` for one collector's section, as `--format ndjson` prints it.
+pub fn shape_section(section: &mut Value, version: u32) {
+    if version < 2 {
+        errors::messages_in_section(section);
+    }
+}
+
+/// The `schema_version` property of the document schema, describing what each
+/// version changed.
+pub fn property(version: u32) -> Value {
+    let changes: Vec<String> = CHANGES.iter().map(|(version, change)| format!("{}: {}", version, change)).collect();
+    json!({ "const": version, "description": format!("Shape of the document (--schema-version). {}", changes.join(". ")) })
+}
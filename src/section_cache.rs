@@ -0,0 +1,199 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+
+use crate::cache::Cache;
+use crate::collectors;
+use crate::facts::Section;
+use crate::timestamp::now_secs;
+
+/// How long a collector's section may be reused, as given to `--cache-ttl`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ttl {
+    Secs(u64),
+    Forever,
+}
+
+impl Ttl {
+    fn max_age(self) -> Option<u64> {
+        match self {
+            Ttl::Secs(secs) => Some(secs),
+            Ttl::Forever => None,
+        }
+    }
+}
+
+/// Parses `COLLECTOR=DURATION`, where DURATION is `forever` or a number of seconds
+/// with an optional s, m, h or d suffix.
+pub fn parse_ttl(source: &str) -> Result<(String, Ttl), String> {
+    let (name, duration) = source
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid cache TTL {:?}: expected COLLECTOR=DURATION", source))?;
+    let name = name.trim();
+    if collectors::find(name).is_none() {
+        return Err(format!("Unknown collector: {} (expected one of {})", name, collectors::names().join(", ")));
+    }
+    let duration = duration.trim();
+    if duration == "forever" {
+        return Ok((name.to_string(), Ttl::Forever));
+    }
+    let (number, unit) = match duration.char_indices().last() {
+        Some((index, 's')) => (&duration[..index], 1),
+        Some((index, 'm')) => (&duration[..index], 60),
+        Some((index, 'h')) => (&duration[..index], 3600),
+        Some((index, 'd')) => (&duration[..index], 86400),
+        _ => (duration, 1),
+    };
+    let secs = number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .ok_or_else(|| format!("Invalid duration for {}: {} (expected e.g. 90, 10m, 1h, 7d or forever)", name, duration))?;
+    Ok((name.to_string(), Ttl::Secs(secs)))
+}
+
+/// Identifies the state of a collector's input files without reading them. The
+/// change time is included because, unlike mtime, it can't be set back by tools
+/// that preserve timestamps.
+pub fn fingerprint(sources: &[&str]) -> Value {
+    let files: Vec<Value> = sources
+        .iter()
+        .map(|path| match fs::metadata(path) {
+            Ok(metadata) => file_state(path, &metadata),
+            Err(_) => json!([path, null]),
+        })
+        .collect();
+    json!({ "files": files })
+}
+
+#[cfg(unix)]
+fn file_state(path: &str, metadata: &fs::Metadata) -> Value {
+    use std::os::unix::fs::MetadataExt;
+    json!([
+        path,
+        metadata.dev(),
+        metadata.ino(),
+        metadata.len(),
+        metadata.mtime(),
+        metadata.mtime_nsec(),
+        metadata.ctime(),
+        metadata.ctime_nsec()
+    ])
+}
+
+#[cfg(not(unix))]
+fn file_state(path: &str, metadata: &fs::Metadata) -> Value {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos().to_string());
+    json!([path, metadata.len(), modified])
+}
+
+/// The section stored for `collector` under `options`, if it is younger than `ttl`
+/// (when given) and its inputs are unchanged since (when a fingerprint is given).
+pub fn lookup(cache: &Cache, collector: &str, options: &str, fingerprint: Option<&Value>, ttl: Option<Ttl>) -> Option<Section> {
+    let Some((stored_at, entry)) = cache.read(&entry_name(collector, options)) else {
+        tracing::debug!("{}: no cached section", collector);
+        return None;
+    };
+    if fingerprint.is_some_and(|fingerprint| entry.get("fingerprint") != Some(fingerprint)) {
+        tracing::debug!("{}: source files changed since the cached section", collector);
+        return None;
+    }
+    if let Some(max_age) = ttl.and_then(Ttl::max_age) {
+        if now_secs().saturating_sub(stored_at) >= max_age {
+            tracing::debug!("{}: cached section is older than its TTL ({} s)", collector, max_age);
+            return None;
+        }
+    }
+    tracing::debug!("{}: reusing section cached at {}", collector, stored_at);
+    let section = Section::from_value(entry.get("section")?)?;
+    Some(Section {
+        // A section that was itself served from a cache keeps its original time.
+        cached_at: section.cached_at.or(Some(stored_at)),
+        ..section
+    })
+}
+
+/// Stores a collected section for `collector` under `options`. A failed section
+/// isn't stored, so one transient failure isn't replayed for the whole TTL.
+pub fn store(cache: &Cache, collector: &str, options: &str, fingerprint: Option<&Value>, section: &Section) {
+    if section.failure.is_some() {
+        tracing::debug!("{}: not caching a failed section", collector);
+        return;
+    }
+    cache.write(
+        &entry_name(collector, options),
+        &json!({ "fingerprint": fingerprint, "section": section.to_value() }),
+    );
+}
+
+/// Sections collected with other options are stored apart. The options are hashed,
+/// since they may hold credentials.
+fn entry_name(collector: &str, options: &str) -> String {
+    let digest: String = Sha256::digest(options).iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    format!("section-{}-{}", collector, digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{Code, Error};
+
+    #[test]
+    fn ttl_units() {
+        assert_eq!(parse_ttl("binaries=90").unwrap(), ("binaries".to_string(), Ttl::Secs(90)));
+        assert_eq!(parse_ttl("binaries=90s").unwrap().1, Ttl::Secs(90));
+        assert_eq!(parse_ttl("binaries=10m").unwrap().1, Ttl::Secs(600));
+        assert_eq!(parse_ttl("binaries=1h").unwrap().1, Ttl::Secs(3600));
+        assert_eq!(parse_ttl("binaries=7d").unwrap().1, Ttl::Secs(604_800));
+        assert_eq!(parse_ttl(" mounts = forever ").unwrap(), ("mounts".to_string(), Ttl::Forever));
+        assert_eq!(parse_ttl("mounts=0").unwrap().1, Ttl::Secs(0));
+    }
+
+    #[test]
+    fn invalid_ttls() {
+        for (source, expected) in [
+            ("binaries", "expected COLLECTOR=DURATION"),
+            ("nonexistent=1h", "Unknown collector: nonexistent"),
+            ("binaries=", "Invalid duration for binaries"),
+            ("binaries=1w", "Invalid duration for binaries"),
+            ("binaries=-5", "Invalid duration for binaries"),
+            ("binaries=h", "Invalid duration for binaries"),
+            ("binaries=99999999999999999999d", "Invalid duration for binaries"),
+        ] {
+            let error = parse_ttl(source).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", source, error);
+        }
+    }
+
+    fn temp_cache(name: &str) -> (Cache, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("saltbox-facts-section-cache-{}-{}", std::process::id(), name));
+        (Cache::open(dir.to_str()), dir)
+    }
+
+    #[test]
+    fn sections_are_kept_per_options() {
+        let (cache, dir) = temp_cache("options");
+        store(&cache, "binaries", "[\"git\"]", None, &Section::new(json!({ "git": "/usr/bin/git" })));
+        assert!(lookup(&cache, "binaries", "[\"curl\"]", None, None).is_none());
+        let section = lookup(&cache, "binaries", "[\"git\"]", None, None).unwrap();
+        assert_eq!(section.value.into_json().unwrap(), json!({ "git": "/usr/bin/git" }));
+        assert!(section.cached_at.is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_sections_are_not_stored() {
+        let (cache, dir) = temp_cache("failed");
+        let section = Section {
+            failure: Some(Error::new(Code::Timeout, "traefik", "timed out")),
+            ..Section::new(json!({ "available": false }))
+        };
+        store(&cache, "traefik", "", None, &section);
+        assert!(lookup(&cache, "traefik", "", None, None).is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
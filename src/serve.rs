@@ -0,0 +1,203 @@
+use serde_json::json;
+use std::error::Error;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+
+use crate::cli::ServeArgs;
+
+/// Longest request head read before the request is refused.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Exit codes of a run that still printed a document: every collector succeeded,
+/// some failed, or all did.
+const DOCUMENT_EXIT_CODES: &[i32] = &[0, 3, 4];
+
+/// Answers `GET /facts` and `GET /facts/<PATH>` by running this binary as a gather
+/// or `get` with the collector arguments, so each response reflects the host at
+/// that moment and a run that crashes or hangs can't take the server with it.
+///
+/// Each connection is answered in its own task. At most `--max-connections` are
+/// accepted at once; the rest wait in the listen backlog.
+pub async fn run(args: ServeArgs) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(args.listen).await.map_err(|e| format!("Cannot listen on {}: {}", args.listen, e))?;
+    tracing::info!("Serving facts on http://{}/facts", args.listen);
+    let argv: Arc<[String]> = args.gather.argv.into();
+    let permits = Arc::new(Semaphore::new(args.max_connections.max(1)));
+    loop {
+        let permit = permits.clone().acquire_owned().await.expect("semaphore is never closed");
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                tracing::warn!("Cannot accept a connection: {}", e);
+                continue;
+            }
+        };
+        let argv = argv.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            if let Err(e) = respond(stream, &argv).await {
+                tracing::warn!("{}: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, argv: &[String]) -> std::io::Result<()> {
+    let target = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(target))) => target,
+        Ok(Ok(None)) => return write_response(&mut stream, "405 Method Not Allowed", "text/plain", "Only GET is supported\n").await,
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return write_response(&mut stream, "408 Request Timeout", "text/plain", "").await,
+    };
+    let Some((run_args, content_type)) = route(&target, argv) else {
+        return write_response(&mut stream, "404 Not Found", "text/plain", "Try /facts or /facts/<PATH>\n").await;
+    };
+
+    let output = tokio::process::Command::new(std::env::current_exe()?)
+        .args(&run_args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    match output.status.code() {
+        Some(code) if DOCUMENT_EXIT_CODES.contains(&code) && !output.stdout.is_empty() => {
+            let body = String::from_utf8_lossy(&output.stdout);
+            tracing::info!("{}: exit {}, {} bytes", target, code, body.len());
+            write_response(&mut stream, "200 OK", content_type, &body).await
+        }
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no output").trim_start_matches("Error: ");
+            tracing::warn!("{}: run failed with {}: {}", target, output.status, error);
+            let body = json!({ "error": error, "exit_code": output.status.code() }).to_string();
+            write_response(&mut stream, "500 Internal Server Error", "application/json", &body).await
+        }
+    }
+}
+
+/// The arguments a request target runs this binary with, and the response's
+/// content type; None for targets other than /facts and /facts/<PATH>. The path
+/// comes after `--`, so one starting with `-` is a query rather than an option.
+fn route(target: &str, argv: &[String]) -> Option<(Vec<String>, &'static str)> {
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    match path.trim_end_matches('/') {
+        "/facts" => Some((argv.to_vec(), "application/json")),
+        field => {
+            let query = percent_decode(field.strip_prefix("/facts/")?).filter(|query| !query.is_empty())?;
+            let run_args = ["get"].into_iter().map(String::from).chain(argv.iter().cloned()).chain(["--".to_string(), query]).collect();
+            Some((run_args, "text/plain; charset=utf-8"))
+        }
+    }
+}
+
+/// The request target of a GET, or None for any other method. The rest of the
+/// head is read and discarded.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || head.len() + read > MAX_REQUEST_BYTES {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "incomplete or oversized request"));
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    Ok(match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Decodes `%XX` escapes, as a client sends the quotes and brackets of a path like
+/// `mounts["/"].available_gb`; None if the result isn't UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%').then(|| value.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())).flatten();
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("mounts%5B%22%2F%22%5D.available_gb").as_deref(), Some("mounts[\"/\"].available_gb"));
+        assert_eq!(percent_decode("ip.public_ip").as_deref(), Some("ip.public_ip"));
+        // Incomplete or non-hex escapes are kept as they are.
+        assert_eq!(percent_decode("100%").as_deref(), Some("100%"));
+        assert_eq!(percent_decode("%zz%4").as_deref(), Some("%zz%4"));
+        assert_eq!(percent_decode("%ff"), None);
+    }
+
+    #[test]
+    fn targets_route_to_a_gather_or_get() {
+        let argv = vec!["--no-ipv6".to_string()];
+        assert_eq!(route("/facts", &argv), Some((argv.clone(), "application/json")));
+        assert_eq!(route("/facts/?pretty", &argv), Some((argv.clone(), "application/json")));
+        let get = |query: &str| Some((vec!["get".to_string(), "--no-ipv6".to_string(), "--".to_string(), query.to_string()], "text/plain; charset=utf-8"));
+        assert_eq!(route("/facts/ip.public_ip", &argv), get("ip.public_ip"));
+        assert_eq!(route("/facts/-v", &argv), get("-v"));
+        assert_eq!(route("/facts/", &argv).map(|(run_args, _)| run_args), Some(argv.clone()));
+        assert_eq!(route("/", &argv), None);
+        assert_eq!(route("/factsheet", &argv), None);
+        assert_eq!(route("/facts/%ff", &argv), None);
+    }
+
+    /// Sends `request` to `read_request` over a local connection.
+    async fn read(request: impl Into<Vec<u8>>) -> std::io::Result<Option<String>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let request = request.into();
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let _ = stream.write_all(&request).await;
+            let _ = stream.shutdown().await;
+        });
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let target = read_request(&mut stream).await;
+        client.await.unwrap();
+        target
+    }
+
+    #[tokio::test]
+    async fn requests_are_read_up_to_their_head() {
+        assert_eq!(read(b"GET /facts/ip HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap().as_deref(), Some("/facts/ip"));
+        assert_eq!(read(b"POST /facts HTTP/1.1\r\n\r\n").await.unwrap(), None);
+        assert!(read(b"GET /facts HTTP/1.1\r\nHost: localhost\r\n").await.is_err());
+        assert!(read(format!("GET /facts HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_REQUEST_BYTES))).await.is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{Code, Error};
+
+const SMB_CONF_PATH: &str = "/etc/samba/smb.conf";
+const SMBD_PATHS: &[&str] = &["/usr/sbin/smbd", "/sbin/smbd"];
+const EXPORTS_PATH: &str = "/etc/exports";
+const EXPORTS_D_PATH: &str = "/etc/exports.d";
+const EXPORTFS_PATHS: &[&str] = &["/usr/sbin/exportfs", "/sbin/exportfs"];
+/// Nested `include` directives followed before giving up on a loop.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Samba shares and NFS exports as configured: each share's path and access
+/// settings from smb.conf (and the files it includes), and each export's path and
+/// per-client options from /etc/exports and /etc/exports.d. Every file read is
+/// listed with whether it carries an `Ansible managed` marker, so a role can tell
+/// a hand-edited file from one it templated before overwriting it.
+///
+/// `read_only`, `browseable` and `guest_ok` are Samba's effective values, with its
+/// defaults for settings a share leaves out; `options` has every setting as
+/// written, with names lowercased and spaces collapsed.
+pub fn get_shares() -> Value {
+    json!({ "samba": samba(), "nfs": nfs() })
+}
+
+fn samba() -> Value {
+    let installed = SMBD_PATHS.iter().any(|path| Path::new(path).exists());
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut sections: Vec<(String, String, Map<String, Value>)> = Vec::new();
+    if Path::new(SMB_CONF_PATH).exists() || installed {
+        read_smb_conf(Path::new(SMB_CONF_PATH), 0, &mut files, &mut errors, &mut sections);
+    }
+
+    let (global, shares) = effective_shares(sections);
+
+    json!({
+        "installed": installed,
+        "files": files,
+        "errors": errors,
+        "global": global,
+        "shares": shares
+    })
+}
+
+/// The `[global]` settings and each share with Samba's effective values for the
+/// settings it reports, from the sections in the order they were read.
+fn effective_shares(sections: Vec<(String, String, Map<String, Value>)>) -> (Map<String, Value>, Map<String, Value>) {
+    let mut global = Map::new();
+    let mut shares = Map::new();
+    for (name, file, options) in sections {
+        if name.eq_ignore_ascii_case("global") {
+            global.extend(options);
+            continue;
+        }
+        // A section appearing again adds to the one before.
+        let share = shares.entry(name).or_insert_with(|| json!({ "file": file, "options": {} }));
+        if let Some(existing) = share["options"].as_object_mut() {
+            existing.extend(options);
+        }
+    }
+    for share in shares.values_mut() {
+        let options = share["options"].clone();
+        let option = |names: &[&str]| names.iter().find_map(|name| options.get(*name).and_then(Value::as_str));
+        let writable = option(&["writeable", "writable", "write ok"]).and_then(boolean).map(|writable| !writable);
+        share["path"] = json!(option(&["path", "directory"]));
+        share["comment"] = json!(option(&["comment"]));
+        share["read_only"] = json!(option(&["read only"]).and_then(boolean).or(writable).unwrap_or(true));
+        share["browseable"] = json!(option(&["browseable", "browsable"]).and_then(boolean).unwrap_or(true));
+        share["guest_ok"] = json!(option(&["guest ok", "public"]).and_then(boolean).unwrap_or(false));
+        share["valid_users"] = json!(list(option(&["valid users"])));
+        share["write_list"] = json!(list(option(&["write list"])));
+    }
+    (global, shares)
+}
+
+/// Reads an smb.conf-style file into `sections`, following `include = FILE` where
+/// it names a plain path; includes using `%` substitutions depend on the client
+/// and are left alone.
+fn read_smb_conf(path: &Path, depth: usize, files: &mut Vec<Value>, errors: &mut Vec<Error>, sections: &mut Vec<(String, String, Map<String, Value>)>) {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => return errors.push(Error::read(path.display(), &e)),
+    };
+    let file = path.display().to_string();
+    files.push(json!({ "path": file, "ansible_managed": ansible_managed(&contents) }));
+
+    for line in logical_lines(&contents) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            sections.push((name.trim().to_string(), file.clone(), Map::new()));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.split_whitespace().collect::<Vec<_>>().join(" ").to_ascii_lowercase();
+        let value = value.trim();
+        if key == "include" && !value.contains('%') {
+            if depth >= MAX_INCLUDE_DEPTH {
+                errors.push(Error::new(Code::Unsupported, value, format!("Not following include of {} from {}: nested too deeply", value, file)));
+            } else {
+                read_smb_conf(Path::new(value), depth + 1, files, errors, sections);
+            }
+            continue;
+        }
+        // Settings before the first section header are global.
+        if sections.is_empty() {
+            sections.push(("global".to_string(), file.clone(), Map::new()));
+        }
+        if let Some((_, _, options)) = sections.last_mut() {
+            options.insert(key, json!(value));
+        }
+    }
+}
+
+fn nfs() -> Value {
+    let installed = EXPORTFS_PATHS.iter().any(|path| Path::new(path).exists());
+    let mut paths = vec![Path::new(EXPORTS_PATH).to_path_buf()];
+    let mut drop_ins: Vec<_> = fs::read_dir(EXPORTS_D_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "exports"))
+        .collect();
+    drop_ins.sort();
+    paths.extend(drop_ins);
+
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut exports = Vec::new();
+    for path in paths {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                errors.push(Error::read(path.display(), &e));
+                continue;
+            }
+        };
+        let file = path.display().to_string();
+        files.push(json!({ "path": file, "ansible_managed": ansible_managed(&contents) }));
+        read_exports(&contents, &file, &mut exports, &mut errors);
+    }
+
+    json!({ "installed": installed, "files": files, "errors": errors, "exports": exports })
+}
+
+/// Adds the exports in an exports-format file to `exports`, and an error for each
+/// line that isn't one.
+fn read_exports(contents: &str, file: &str, exports: &mut Vec<Value>, errors: &mut Vec<Error>) {
+    for line in logical_lines(contents) {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_export(line) {
+            Some((export_path, clients)) => exports.push(json!({ "path": export_path, "file": file, "clients": clients })),
+            None => errors.push(Error::parse(file, format_args!("unexpected line {:?}", line))),
+        }
+    }
+}
+
+/// `/srv/media -ro 10.0.0.0/24(rw,no_subtree_check) host.lan`: the path (quoted
+/// when it has spaces), an optional `-options` default for clients listed without
+/// their own, then the clients. A path alone is exported to everyone.
+fn parse_export(line: &str) -> Option<(String, Vec<Value>)> {
+    let (path, rest) = match line.strip_prefix('"') {
+        Some(quoted) => {
+            let (path, rest) = quoted.split_once('"')?;
+            (path.to_string(), rest)
+        }
+        None => {
+            let (path, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            (path.to_string(), rest)
+        }
+    };
+    let mut defaults: Vec<String> = Vec::new();
+    let mut clients = Vec::new();
+    for token in rest.split_whitespace() {
+        if let Some(options) = token.strip_prefix('-') {
+            defaults = options.split(',').map(String::from).collect();
+            continue;
+        }
+        let (client, options) = match token.split_once('(') {
+            Some((client, options)) => (client, options.strip_suffix(')')?.split(',').filter(|option| !option.is_empty()).map(String::from).collect()),
+            None => (token, defaults.clone()),
+        };
+        clients.push(json!({ "client": if client.is_empty() { "*" } else { client }, "options": options }));
+    }
+    if clients.is_empty() {
+        clients.push(json!({ "client": "*", "options": defaults }));
+    }
+    Some((path, clients))
+}
+
+/// Lines with `\` continuations joined, as both smb.conf and exports allow.
+fn logical_lines(contents: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in contents.lines() {
+        match line.strip_suffix('\\') {
+            Some(continued) => current.push_str(continued),
+            None => {
+                current.push_str(line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Whether a comment in the file carries Ansible's default `ansible_managed`
+/// marker.
+fn ansible_managed(contents: &str) -> bool {
+    contents.lines().map(str::trim).filter(|line| line.starts_with(['#', ';'])).any(|line| line.to_ascii_lowercase().contains("ansible managed"))
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "true" | "1" | "on" => Some(true),
+        "no" | "false" | "0" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// A Samba user list, separated by commas or spaces.
+fn list(value: Option<&str>) -> Vec<String> {
+    value.unwrap_or_default().split([',', ' ', '\t']).filter(|item| !item.is_empty()).map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn smb_conf_sections_includes_and_effective_values() {
+        let included = temp_file("smb-media.conf", "[media]\n   write list = seed\n");
+        let conf = temp_file(
+            "smb.conf",
+            &format!(
+                "# Ansible managed\nworkgroup = WORKGROUP\n[global]\n   Server  String = saltbox\n\n[media]\n   path = /mnt/unionfs/Media\n   comment = Media \\\n     library\n   writeable = yes\n   valid users = seed, @media\n; [disabled]\ninclude = {}\ninclude = /etc/samba/%m.conf\n[printers]\n   browsable = no\n   public = yes\n",
+                included.display()
+            ),
+        );
+        let (mut files, mut errors, mut sections) = (Vec::new(), Vec::new(), Vec::new());
+        read_smb_conf(&conf, 0, &mut files, &mut errors, &mut sections);
+        let (global, shares) = effective_shares(sections);
+        fs::remove_file(&conf).unwrap();
+        fs::remove_file(&included).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            files,
+            [json!({ "path": conf.display().to_string(), "ansible_managed": true }), json!({ "path": included.display().to_string(), "ansible_managed": false })]
+        );
+        assert_eq!(Value::Object(global), json!({ "workgroup": "WORKGROUP", "server string": "saltbox" }));
+        assert_eq!(
+            shares["media"],
+            json!({
+                "file": conf.display().to_string(),
+                "options": {
+                    "path": "/mnt/unionfs/Media",
+                    "comment": "Media      library",
+                    "writeable": "yes",
+                    "valid users": "seed, @media",
+                    "write list": "seed",
+                    "include": "/etc/samba/%m.conf"
+                },
+                "path": "/mnt/unionfs/Media",
+                "comment": "Media      library",
+                "read_only": false,
+                "browseable": true,
+                "guest_ok": false,
+                "valid_users": ["seed", "@media"],
+                "write_list": ["seed"]
+            })
+        );
+        assert_eq!(
+            (&shares["printers"]["read_only"], &shares["printers"]["browseable"], &shares["printers"]["guest_ok"], &shares["printers"]["path"]),
+            (&json!(true), &json!(false), &json!(true), &Value::Null)
+        );
+        assert!(!shares.contains_key("disabled"));
+    }
+
+    #[test]
+    fn smb_conf_include_loops_and_missing_files_are_errors() {
+        let path = std::env::temp_dir().join(format!("saltbox-facts-{}-smb-loop.conf", std::process::id()));
+        fs::write(&path, format!("include = {}\n", path.display())).unwrap();
+        let (mut files, mut errors, mut sections) = (Vec::new(), Vec::new(), Vec::new());
+        read_smb_conf(&path, 0, &mut files, &mut errors, &mut sections);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(files.len(), MAX_INCLUDE_DEPTH + 1);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].code, Code::Unsupported), "{}", errors[0].message);
+
+        let (mut files, mut errors) = (Vec::new(), Vec::new());
+        read_smb_conf(Path::new("/nonexistent/smb.conf"), 0, &mut files, &mut errors, &mut Vec::new());
+        assert!(files.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn exports_paths_clients_and_default_options() {
+        let (mut exports, mut errors) = (Vec::new(), Vec::new());
+        read_exports(
+            "# /etc/exports\n/srv/media -ro,sync 10.0.0.0/24(rw,no_subtree_check) host.lan\n\"/srv/with space\" (ro)\n/srv/all # everyone\n/srv/long \\\n  *.lan(rw)\n/srv/bad host(rw\n",
+            "/etc/exports",
+            &mut exports,
+            &mut errors,
+        );
+        assert_eq!(
+            exports,
+            [
+                json!({ "path": "/srv/media", "file": "/etc/exports", "clients": [
+                    { "client": "10.0.0.0/24", "options": ["rw", "no_subtree_check"] },
+                    { "client": "host.lan", "options": ["ro", "sync"] }
+                ] }),
+                json!({ "path": "/srv/with space", "file": "/etc/exports", "clients": [{ "client": "*", "options": ["ro"] }] }),
+                json!({ "path": "/srv/all", "file": "/etc/exports", "clients": [{ "client": "*", "options": [] }] }),
+                json!({ "path": "/srv/long", "file": "/etc/exports", "clients": [{ "client": "*.lan", "options": ["rw"] }] }),
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("/srv/bad host(rw"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn booleans_lists_and_markers() {
+        assert_eq!((boolean("Yes"), boolean("off"), boolean("maybe")), (Some(true), Some(false), None));
+        assert_eq!(list(Some("seed,@media  plex\tjellyfin")), ["seed", "@media", "plex", "jellyfin"]);
+        assert!(list(None).is_empty());
+        assert!(ansible_managed("; Ansible managed: do not edit\n"));
+        assert!(!ansible_managed("comment = Ansible managed\n"));
+        assert_eq!(logical_lines("a \\\nb\nc\\"), ["a b", "c"]);
+    }
+}
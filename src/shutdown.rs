@@ -0,0 +1,86 @@
+use tokio::sync::watch;
+
+/// SIGTERM/SIGINT received during a gather run.
+///
+/// The first signal asks the run to stop collecting and still print what it has, so
+/// a consumer killed by an Ansible timeout gets a valid document. A second signal
+/// exits immediately.
+pub struct Shutdown {
+    receiver: watch::Receiver<Option<i32>>,
+}
+
+impl Shutdown {
+    /// Installs the handlers. Must be called from within the runtime.
+    pub fn listen() -> Shutdown {
+        let (sender, receiver) = watch::channel(None);
+        install(sender);
+        Shutdown { receiver }
+    }
+
+    /// The signal received so far, if any.
+    pub fn received(&self) -> Option<i32> {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once a signal arrives; never resolves if the handlers couldn't be installed.
+    pub async fn wait(&mut self) -> i32 {
+        loop {
+            if let Some(signal) = *self.receiver.borrow_and_update() {
+                return signal;
+            }
+            if self.receiver.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn install(sender: watch::Sender<Option<i32>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+        (Ok(mut terminate), Ok(mut interrupt)) => {
+            tokio::spawn(async move {
+                loop {
+                    let signal = tokio::select! {
+                        _ = terminate.recv() => libc::SIGTERM,
+                        _ = interrupt.recv() => libc::SIGINT,
+                    };
+                    if sender.borrow().is_some() {
+                        std::process::exit(exit_code(signal));
+                    }
+                    let _ = sender.send(Some(signal));
+                }
+            });
+        }
+        _ => tracing::warn!("Cannot install signal handlers; a SIGTERM will discard partial output"),
+    }
+}
+
+/// Windows only delivers Ctrl+C (and Ctrl+Break) to console programs.
+#[cfg(not(unix))]
+fn install(sender: watch::Sender<Option<i32>>) {
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if sender.borrow().is_some() {
+                std::process::exit(exit_code(libc::SIGINT));
+            }
+            let _ = sender.send(Some(libc::SIGINT));
+        }
+    });
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGINT => "SIGINT",
+        _ => "signal",
+    }
+}
+
+/// The shell convention for a process ended by `signal`, so callers can tell an
+/// interrupted run (130 for SIGINT, 143 for SIGTERM) from a failed one.
+pub fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
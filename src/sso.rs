@@ -0,0 +1,110 @@
+use serde_json::{json, Value};
+use std::fs;
+
+use crate::errors::Error;
+use crate::http::Client;
+use crate::{docker, traefik};
+
+pub const DEFAULT_AUTHELIA_CONFIG_PATH: &str = "/opt/authelia/configuration.yml";
+
+/// Image name fragments identifying each SSO provider.
+const PROVIDERS: &[(&str, &[&str])] = &[("authelia", &["authelia/authelia"]), ("authentik", &["goauthentik/"])];
+
+/// The SSO provider in use (Authelia or Authentik), from its running container,
+/// Authelia's session domain from its configuration, and the Traefik forwardAuth
+/// middlewares that send requests to either provider.
+pub async fn get_sso(client: &Client, traefik_api: &str, authelia_config: &str) -> Value {
+    let (containers, docker_error) = match docker::get("/containers/json").await {
+        Ok(containers) => (provider_containers(&containers), None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let (middlewares, traefik_error) = match traefik::fetch(client, &format!("{}/api/http/middlewares", traefik_api.trim_end_matches('/'))).await {
+        Ok(middlewares) => (forward_auth_middlewares(&middlewares), None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let (domains, config_error) = match fs::read_to_string(authelia_config) {
+        Ok(config) => match serde_yaml::from_str::<Value>(&config) {
+            Ok(config) => (authelia_domains(&config), None),
+            Err(e) => (Vec::new(), Some(Error::parse(authelia_config, e))),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Vec::new(), None),
+        Err(e) => (Vec::new(), Some(Error::read(authelia_config, &e))),
+    };
+
+    // A running container is the strongest signal; a middleware pointing at a
+    // provider on another host still means apps here are wired to it.
+    let provider = containers
+        .iter()
+        .chain(&middlewares)
+        .find_map(|entry| entry.get("provider").and_then(Value::as_str))
+        .or_else(|| (!domains.is_empty()).then_some("authelia"));
+
+    json!({
+        "provider": provider,
+        "containers": containers,
+        "authelia_config": authelia_config,
+        "domains": domains,
+        "middlewares": middlewares,
+        "docker_error": docker_error,
+        "traefik_error": traefik_error,
+        "config_error": config_error
+    })
+}
+
+fn provider_of(text: &str) -> Option<&'static str> {
+    let text = text.to_ascii_lowercase();
+    PROVIDERS
+        .iter()
+        .find(|(_, fragments)| fragments.iter().any(|fragment| text.contains(fragment)))
+        .map(|(provider, _)| *provider)
+}
+
+fn provider_containers(containers: &Value) -> Vec<Value> {
+    containers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|container| {
+            let image = container.get("Image").and_then(Value::as_str)?;
+            let provider = provider_of(image)?;
+            let name = container.pointer("/Names/0").and_then(Value::as_str).unwrap_or_default().trim_start_matches('/');
+            Some(json!({ "name": name, "image": image, "provider": provider, "state": container.get("State") }))
+        })
+        .collect()
+}
+
+/// forwardAuth middlewares whose address names a provider, e.g.
+/// `http://authelia:9091/api/verify?rd=...` or Authentik's outpost endpoint.
+fn forward_auth_middlewares(middlewares: &Value) -> Vec<Value> {
+    middlewares
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|middleware| {
+            let address = middleware.pointer("/forwardAuth/address").and_then(Value::as_str)?;
+            let lowercase = address.to_ascii_lowercase();
+            let provider = PROVIDERS.iter().map(|(provider, _)| *provider).find(|provider| lowercase.contains(provider))?;
+            Some(json!({
+                "name": middleware.get("name"),
+                "provider": provider,
+                "address": address,
+                "status": middleware.get("status")
+            }))
+        })
+        .collect()
+}
+
+/// Cookie domains Authelia protects: `session.cookies[].domain` since 4.38, or the
+/// single `session.domain` before that.
+fn authelia_domains(config: &Value) -> Vec<String> {
+    let session = config.get("session");
+    let mut domains: Vec<String> = session
+        .and_then(|session| session.get("cookies"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|cookie| cookie.get("domain").and_then(Value::as_str).map(String::from))
+        .collect();
+    domains.extend(session.and_then(|session| session.get("domain")).and_then(Value::as_str).map(String::from));
+    domains
+}
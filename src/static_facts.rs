@@ -0,0 +1,56 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, clap::ValueEnum)]
+pub enum Precedence {
+    /// Static values replace collected ones.
+    Override,
+    /// Static values are only used where the collected value is missing, null or an
+    /// empty string (how collectors report "no value", e.g. a failed `public_ip`).
+    FillMissing,
+}
+
+/// Loads a JSON or YAML document, chosen by the file extension.
+pub fn load_file(path: &Path) -> Result<Value, String> {
+    tracing::debug!("Reading {}", path.display());
+    let contents = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yml") | Some("yaml") => {
+            serde_yaml::from_str(&contents).map_err(|e| format!("Cannot parse {}: {}", path.display(), e))
+        }
+        _ => serde_json::from_str(&contents).map_err(|e| format!("Cannot parse {}: {}", path.display(), e)),
+    }
+}
+
+/// Deep-merges `overlay` into `base`: objects are merged key by key, anything else
+/// is resolved according to `precedence`.
+pub fn deep_merge(base: &mut Value, overlay: Value, precedence: &Precedence) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(base_value) => deep_merge(base_value, overlay_value, precedence),
+                    None => {
+                        base_map.insert(key, overlay_value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => match precedence {
+            Precedence::Override => *base = overlay,
+            Precedence::FillMissing if base.is_null() || base.as_str() == Some("") => *base = overlay,
+            Precedence::FillMissing => {}
+        },
+    }
+}
+
+/// Applies the static facts file to the collected document.
+pub fn apply(document: &mut Value, path: &Path, precedence: &Precedence) -> Result<(), String> {
+    let overlay = load_file(path)?;
+    if !overlay.is_object() {
+        return Err(format!("{} must contain a mapping at the top level", path.display()));
+    }
+    deep_merge(document, overlay, precedence);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::Error;
+use crate::mounts;
+
+pub const SWAPS_PATH: &str = "/proc/swaps";
+pub const MEMINFO_PATH: &str = "/proc/meminfo";
+pub const SWAPPINESS_PATH: &str = "/proc/sys/vm/swappiness";
+pub const ZRAM_GENERATOR_CONFIGS: &[&str] = &["/etc/systemd/zram-generator.conf", "/usr/lib/systemd/zram-generator.conf"];
+const SYS_BLOCK: &str = "/sys/block";
+
+/// Active swap areas with their sizes, usage and priorities, zram devices whether
+/// or not they're used as swap, and overall swap usage, so a role deciding whether
+/// to create a swapfile sees what already exists, including zram-only setups.
+pub fn get_swap() -> Value {
+    let swaps = match fs::read_to_string(SWAPS_PATH) {
+        Ok(swaps) => swaps,
+        Err(e) => {
+            return json!({
+                "error": Error::read(SWAPS_PATH, &e),
+                "total_bytes": Value::Null,
+                "used_bytes": Value::Null,
+                "swappiness": Value::Null,
+                "devices": {},
+                "zram": {},
+                "zram_generator_config": Value::Null,
+                "zram_only": false
+            })
+        }
+    };
+
+    let mut devices = Map::new();
+    // Filename Type Size Used Priority, sizes in KiB; the header comes first.
+    for line in swaps.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, kind, size, used, priority] = fields[..] else {
+            continue;
+        };
+        let name = mounts::unescape_mount_field(name);
+        devices.insert(
+            name.clone(),
+            json!({
+                "type": kind,
+                "size_bytes": size.parse::<u64>().ok().map(|kib| kib * 1024),
+                "used_bytes": used.parse::<u64>().ok().map(|kib| kib * 1024),
+                "priority": priority.parse::<i64>().ok(),
+                "zram": is_zram(&name)
+            }),
+        );
+    }
+
+    let mut zram = Map::new();
+    for entry in fs::read_dir(SYS_BLOCK).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("zram") {
+            continue;
+        }
+        let read = |file: &str| fs::read_to_string(entry.path().join(file)).ok().map(|value| value.trim().to_string());
+        // mm_stat: orig_data_size compr_data_size mem_used_total ..., in bytes.
+        let mm_stat: Vec<u64> = read("mm_stat").unwrap_or_default().split_whitespace().filter_map(|value| value.parse().ok()).collect();
+        zram.insert(
+            name.clone(),
+            json!({
+                "disksize_bytes": read("disksize").and_then(|size| size.parse::<u64>().ok()),
+                "algorithm": read("comp_algorithm").as_deref().and_then(selected_algorithm),
+                "orig_data_bytes": mm_stat.first(),
+                "compr_data_bytes": mm_stat.get(1),
+                "mem_used_bytes": mm_stat.get(2),
+                "swap": devices.contains_key(&format!("/dev/{}", name))
+            }),
+        );
+    }
+
+    let meminfo = fs::read_to_string(MEMINFO_PATH).unwrap_or_default();
+    let meminfo_bytes = |key: &str| {
+        meminfo.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(':')?.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()).map(|kib| kib * 1024)
+    };
+    let total = meminfo_bytes("SwapTotal");
+    let used = total.zip(meminfo_bytes("SwapFree")).map(|(total, free)| total.saturating_sub(free));
+
+    json!({
+        "error": Value::Null,
+        "total_bytes": total,
+        "used_bytes": used,
+        "swappiness": fs::read_to_string(SWAPPINESS_PATH).ok().and_then(|value| value.trim().parse::<u64>().ok()),
+        "zram_only": !devices.is_empty() && devices.keys().all(|name| is_zram(name)),
+        "devices": devices,
+        "zram": zram,
+        "zram_generator_config": ZRAM_GENERATOR_CONFIGS.iter().find(|path| Path::new(path).exists())
+    })
+}
+
+fn is_zram(name: &str) -> bool {
+    name.starts_with("/dev/zram")
+}
+
+/// `lzo lzo-rle [zstd] lz4`: the bracketed one is in use.
+fn selected_algorithm(algorithms: &str) -> Option<String> {
+    algorithms.split_whitespace().find_map(|algorithm| algorithm.strip_prefix('[')?.strip_suffix(']')).map(String::from)
+}
@@ -0,0 +1,105 @@
+use serde_json::{json, Map, Value};
+
+use crate::errors::Error;
+use crate::exec;
+
+const PROPERTIES: &str =
+    "Id,LoadState,ActiveState,SubState,UnitFileState,FragmentPath,SourcePath,Where,What,Type,Options,TimeoutIdleUSec,TriggeredBy,Triggers";
+const FSTAB_PATH: &str = "/etc/fstab";
+const GENERATOR_DIRS: &[&str] = &["/run/systemd/generator/", "/run/systemd/generator.early/", "/run/systemd/generator.late/"];
+
+/// The `.mount` and `.automount` units systemd has loaded, with their state, where
+/// they came from and, for automounts, the idle timeout, so a role turning rclone
+/// mounts into units sees what's already defined and by whom.
+///
+/// `origin` is `fstab` for units systemd-fstab-generator made from /etc/fstab,
+/// `generator` for other generated units, `unit_file` for units written by hand or
+/// shipped by a package, and `runtime` for mounts made outside systemd, which it
+/// only tracks.
+pub async fn get_systemd_mounts() -> Value {
+    let property = format!("--property={}", PROPERTIES);
+    let output = match exec::output("systemctl", ["show", "--all", "--no-pager", &property, "--", "*.mount", "*.automount"]).await {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => return json!({ "error": Error::exited("systemctl", &output), "units": {} }),
+        Err(e) => return json!({ "error": Error::run("systemctl", &e), "units": {} }),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut units = Map::new();
+    // One block of Key=Value lines per unit, separated by blank lines.
+    for block in stdout.split("\n\n") {
+        let property = |key: &str| {
+            block
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let Some(id) = property("Id") else {
+            continue;
+        };
+        let Some((_, kind)) = id.rsplit_once('.').filter(|(_, kind)| matches!(*kind, "mount" | "automount")) else {
+            continue;
+        };
+        let fragment = property("FragmentPath");
+        let origin = if property("SourcePath") == Some(FSTAB_PATH) {
+            "fstab"
+        } else if fragment.is_some_and(|path| GENERATOR_DIRS.iter().any(|dir| path.starts_with(dir))) {
+            "generator"
+        } else if fragment.is_some() {
+            "unit_file"
+        } else {
+            "runtime"
+        };
+        let list = |key: &str| property(key).map_or_else(Vec::new, |value| value.split_whitespace().collect());
+        units.insert(
+            id.to_string(),
+            json!({
+                "kind": kind,
+                "load_state": property("LoadState"),
+                "active_state": property("ActiveState"),
+                "sub_state": property("SubState"),
+                "unit_file_state": property("UnitFileState"),
+                "origin": origin,
+                "fragment_path": fragment,
+                "source_path": property("SourcePath"),
+                "where": property("Where"),
+                "what": property("What"),
+                "type": property("Type"),
+                "options": property("Options"),
+                "idle_timeout_secs": if kind == "automount" { property("TimeoutIdleUSec").and_then(timespan_secs) } else { None },
+                "triggered_by": list("TriggeredBy"),
+                "triggers": list("Triggers")
+            }),
+        );
+    }
+    json!({ "error": Value::Null, "units": units })
+}
+
+/// Whole seconds in a timespan as systemctl prints it (`0`, `5min`, `1h 30min`,
+/// `500ms`); none for `infinity` or anything unrecognised.
+fn timespan_secs(timespan: &str) -> Option<u64> {
+    if timespan == "0" {
+        return Some(0);
+    }
+    let mut micros: u64 = 0;
+    for part in timespan.split_whitespace() {
+        let split = part.find(|c: char| !c.is_ascii_digit())?;
+        let (number, unit) = part.split_at(split);
+        let number: u64 = number.parse().ok()?;
+        let scale: u64 = match unit {
+            "us" => 1,
+            "ms" => 1_000,
+            "s" => 1_000_000,
+            "min" => 60_000_000,
+            "h" => 3_600_000_000,
+            "d" => 86_400_000_000,
+            "w" => 604_800_000_000,
+            "M" => 2_629_800_000_000,
+            "y" => 31_557_600_000_000,
+            _ => return None,
+        };
+        micros = micros.checked_add(number.checked_mul(scale)?)?;
+    }
+    Some(micros / 1_000_000)
+}
@@ -0,0 +1,132 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::time::timeout;
+
+use crate::binaries::find_in_path;
+use crate::errors::{Code, Error};
+use crate::exec;
+
+const PROBE_TIMEOUT: u64 = 2;
+/// Upper bound on bytes read from each of stdout/stderr of a `--version` probe.
+const PROBE_OUTPUT_LIMIT: u64 = 16 * 1024;
+const RAW_LINE_LIMIT: usize = 200;
+
+/// Runs `<command> --version` for each command concurrently and extracts a semantic version.
+pub async fn get_tool_versions(commands: &[String]) -> Value {
+    let mut tasks = tokio::task::JoinSet::new();
+    for command in commands {
+        let command = command.clone();
+        tasks.spawn(async move {
+            let value = probe(&command).await;
+            (command, value)
+        });
+    }
+
+    let mut versions = BTreeMap::new();
+    while let Some(result) = tasks.join_next().await {
+        if let Ok((command, value)) = result {
+            versions.insert(command, value);
+        }
+    }
+
+    json!(versions)
+}
+
+async fn probe(command: &str) -> Value {
+    let Some(path) = find_in_path(command) else {
+        return version_error(Error::new(Code::CommandNotFound, command, format!("{} not found on PATH", command)));
+    };
+
+    let probe = format!("{} --version", command);
+    let output = match timeout(Duration::from_secs(PROBE_TIMEOUT), run_version(&path)).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return version_error(Error::run(&probe, &e)),
+        Err(_) => return version_error(Error::new(Code::Timeout, &probe, format!("{} timed out after {}s", probe, PROBE_TIMEOUT))),
+    };
+
+    let Some((line, version)) = output.lines().find_map(|line| extract_version(line).map(|v| (line, v))) else {
+        return version_error(Error::new(Code::InvalidData, &probe, format!("No version found in {} output", probe)));
+    };
+
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    json!({
+        "version": version,
+        "major": parts.next().flatten(),
+        "minor": parts.next().flatten(),
+        "patch": parts.next().flatten(),
+        "raw": line.trim().chars().take(RAW_LINE_LIMIT).collect::<String>(),
+        "error": Value::Null
+    })
+}
+
+fn version_error(error: Error) -> Value {
+    json!({
+        "version": Value::Null,
+        "major": Value::Null,
+        "minor": Value::Null,
+        "patch": Value::Null,
+        "raw": "",
+        "error": error
+    })
+}
+
+/// Runs the probe with a bounded read of both output streams; some tools
+/// (older Java, Python 2) print their version on stderr.
+pub async fn run_version(path: &Path) -> std::io::Result<String> {
+    let mut child = exec::async_command(path)?
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut group = exec::ProcessGroup::of(&child);
+
+    let mut stdout = child.stdout.take().expect("stdout is piped").take(PROBE_OUTPUT_LIMIT);
+    let mut stderr = child.stderr.take().expect("stderr is piped").take(PROBE_OUTPUT_LIMIT);
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let (out_result, err_result) = tokio::join!(stdout.read_to_end(&mut out), stderr.read_to_end(&mut err));
+    out_result?;
+    err_result?;
+
+    group.kill();
+    let _ = child.wait().await;
+
+    out.push(b'\n');
+    out.extend_from_slice(&err);
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Finds the first `MAJOR.MINOR[.PATCH]` token in a line, ignoring any `v` prefix
+/// and trailing build metadata (`24.0.7,` -> `24.0.7`, `v1.2.3-rc1` -> `1.2.3`).
+pub fn extract_version(line: &str) -> Option<String> {
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        if bytes[start].is_ascii_digit() && (start == 0 || !bytes[start - 1].is_ascii_alphanumeric() || bytes[start - 1] == b'v') {
+            let mut end = start;
+            let mut components = 1;
+            while end < bytes.len() {
+                if bytes[end].is_ascii_digit() {
+                    end += 1;
+                } else if bytes[end] == b'.' && end + 1 < bytes.len() && bytes[end + 1].is_ascii_digit() && components < 3 {
+                    components += 1;
+                    end += 1;
+                } else {
+                    break;
+                }
+            }
+            if components >= 2 {
+                return Some(line[start..end].to_string());
+            }
+            start = end.max(start + 1);
+        } else {
+            start += 1;
+        }
+    }
+    None
+}
@@ -0,0 +1,198 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Duration;
+
+use crate::errors::{Code, Error};
+use crate::http::Client;
+
+pub const DEFAULT_TRAEFIK_API: &str = "http://127.0.0.1:8080";
+pub const DEFAULT_ACME_PATH: &str = "/opt/traefik/acme.json";
+
+const API_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Routers, services and middlewares from Traefik's API (when it is enabled),
+/// which domains are routed and how, and the domains in the ACME store.
+///
+/// A domain is `routed` when an enabled router matches it by `Host(...)` and that
+/// router's service has at least one server Traefik reports as UP (or, for
+/// services without health checks, any server at all).
+pub async fn get_traefik(client: &Client, api: &str, acme_path: &str) -> (Value, Option<Error>) {
+    let acme = acme_summary(acme_path);
+    let api = api.trim_end_matches('/');
+    let (routers, services, middlewares) = match (
+        fetch(client, &format!("{}/api/http/routers", api)).await,
+        fetch(client, &format!("{}/api/http/services", api)).await,
+        fetch(client, &format!("{}/api/http/middlewares", api)).await,
+    ) {
+        (Ok(routers), Ok(services), Ok(middlewares)) => (routers, services, middlewares),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            let failure = e.as_failure();
+            return (json!({ "available": false, "error": e, "routers": {}, "services": {}, "middlewares": [], "domains": {}, "acme": acme }), failure);
+        }
+    };
+
+    let mut service_status = Map::new();
+    for service in services.as_array().into_iter().flatten() {
+        let Some(name) = service.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let servers: Vec<&str> = service
+            .pointer("/loadBalancer/servers")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|server| server.get("url").and_then(Value::as_str))
+            .collect();
+        // serverStatus is only filled in when the service has a health check.
+        let up = match service.get("serverStatus").and_then(Value::as_object) {
+            Some(statuses) => statuses.values().filter(|status| status.as_str() == Some("UP")).count(),
+            None => servers.len(),
+        };
+        service_status.insert(
+            name.to_string(),
+            json!({
+                "status": service.get("status"),
+                "type": service.get("type"),
+                "servers": servers,
+                "servers_up": up,
+                "healthy": up > 0 || service.get("type").and_then(Value::as_str) != Some("loadbalancer"),
+            }),
+        );
+    }
+
+    let mut router_summary = Map::new();
+    let mut domains: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for router in routers.as_array().into_iter().flatten() {
+        let Some(name) = router.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let rule = router.get("rule").and_then(Value::as_str).unwrap_or_default();
+        let service = qualified_service(router.get("service").and_then(Value::as_str).unwrap_or_default(), name);
+        let enabled = router.get("status").and_then(Value::as_str) == Some("enabled");
+        let healthy = service_status.get(&service).and_then(|status| status.get("healthy")).and_then(Value::as_bool) == Some(true);
+        let tls = router.get("tls").is_some();
+        for host in rule_hosts(rule) {
+            domains.entry(host).or_default().push(json!({
+                "router": name,
+                "service": service,
+                "routed": enabled && healthy,
+                "tls": tls,
+                "entry_points": router.get("entryPoints"),
+            }));
+        }
+        router_summary.insert(
+            name.to_string(),
+            json!({
+                "rule": rule,
+                "status": router.get("status"),
+                "service": service,
+                "entry_points": router.get("entryPoints"),
+                "middlewares": router.get("middlewares"),
+                "tls": tls,
+                "cert_resolver": router.pointer("/tls/certResolver"),
+                // Traefik's own reasons the router isn't serving.
+                "errors": router.get("error").and_then(Value::as_array).map(|errors| {
+                    errors.iter().filter_map(Value::as_str).map(|error| Error::new(Code::Unknown, name, error)).collect::<Vec<_>>()
+                }),
+            }),
+        );
+    }
+    let domains: Map<String, Value> = domains
+        .into_iter()
+        .map(|(domain, routes)| {
+            let routed = routes.iter().any(|route| route.get("routed") == Some(&Value::Bool(true)));
+            (domain, json!({ "routed": routed, "routes": routes }))
+        })
+        .collect();
+    let middlewares: Vec<&str> = middlewares
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|middleware| middleware.get("name").and_then(Value::as_str))
+        .collect();
+
+    let value = json!({
+        "available": true,
+        "error": Value::Null,
+        "routers": router_summary,
+        "services": service_status,
+        "middlewares": middlewares,
+        "domains": domains,
+        "acme": acme
+    });
+    (value, None)
+}
+
+/// GETs a Traefik API endpoint and parses the JSON body.
+pub async fn fetch(client: &Client, url: &str) -> Result<Value, Error> {
+    let response = client.get(url)?.timeout(API_TIMEOUT).send().await.map_err(|e| Error::http(url, &e))?;
+    if !response.status().is_success() {
+        return Err(Error::status(url, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| Error::http(url, &e))?;
+    serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: {}", url, e)))
+}
+
+/// Routers refer to services of their own provider without the `@provider`
+/// suffix the services list uses.
+fn qualified_service(service: &str, router: &str) -> String {
+    match (service.contains('@'), router.split_once('@')) {
+        (false, Some((_, provider))) => format!("{}@{}", service, provider),
+        _ => service.to_string(),
+    }
+}
+
+/// The hostnames in a rule's `Host(...)` matchers, in v2 form (``Host(`a`, `b`)``)
+/// or v3 form (one host per matcher). HostRegexp and HostSNI are not expanded.
+fn rule_hosts(rule: &str) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut rest = rule;
+    while let Some(start) = rest.find("Host(") {
+        let after = &rest[start + "Host(".len()..];
+        let end = after.find(')').unwrap_or(after.len());
+        hosts.extend(
+            after[..end]
+                .split(',')
+                .map(|host| host.trim().trim_matches(|c| c == '`' || c == '"' || c == '\'').to_ascii_lowercase())
+                .filter(|host| !host.is_empty()),
+        );
+        rest = &after[end..];
+    }
+    hosts
+}
+
+/// Domains with certificates in the ACME store, per resolver. The store is Traefik's
+/// acme.json: `{resolver: {Certificates: [{domain: {main, sans}}]}}`.
+fn acme_summary(path: &str) -> Value {
+    let store = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return json!({ "path": path, "error": Value::Null, "resolvers": {} }),
+        Err(e) => return json!({ "path": path, "error": Error::read(path, &e), "resolvers": {} }),
+    };
+    let store: Value = match serde_json::from_slice(&store) {
+        Ok(store) => store,
+        Err(e) => return json!({ "path": path, "error": Error::parse(path, e), "resolvers": {} }),
+    };
+    let mut resolvers = Map::new();
+    for (resolver, contents) in store.as_object().into_iter().flatten() {
+        let certificates = contents.get("Certificates").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        let mut domains = Vec::new();
+        for certificate in certificates {
+            let domain = certificate.get("domain");
+            domains.extend(domain.and_then(|domain| domain.get("main")).and_then(Value::as_str));
+            domains.extend(
+                domain
+                    .and_then(|domain| domain.get("sans"))
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str),
+            );
+        }
+        domains.sort_unstable();
+        domains.dedup();
+        resolvers.insert(resolver.clone(), json!({ "certificates": certificates.len(), "domains": domains }));
+    }
+    json!({ "path": path, "error": Value::Null, "resolvers": resolvers })
+}
@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::cli::ValidateArgs;
+use crate::policy;
+use crate::schema::Schema;
+use crate::static_facts::load_file;
+
+/// Checks a facts document against a JSON Schema and policy rules, printing each
+/// problem and failing if there are any.
+pub fn run(args: ValidateArgs) -> Result<(), Box<dyn Error>> {
+    let document = load_file(Path::new(&args.file))?;
+    let mut problems = Vec::new();
+
+    if !document.is_object() {
+        problems.push("document: a facts document must be a JSON object".to_string());
+    }
+    if let Some(path) = &args.schema {
+        let schema = Schema::new(load_file(Path::new(path))?).map_err(|e| format!("{}: {}", path, e))?;
+        problems.extend(schema.validate(&document).into_iter().map(|problem| format!("schema: {}", problem)));
+    }
+    for path in &args.policies {
+        let rules = policy::load_rules(Path::new(path))?;
+        problems.extend(policy::check(&document, &rules).into_iter().map(|problem| format!("policy: {}", problem)));
+    }
+
+    if problems.is_empty() {
+        println!("{}: valid", args.file);
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    Err(format!("{}: {} problem{}", args.file, problems.len(), if problems.len() == 1 { "" } else { "s" }).into())
+}
@@ -0,0 +1,126 @@
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use crate::errors::{Code, Error};
+use crate::http::Client;
+use crate::{docker, download_clients};
+
+/// Image name fragments of containers that other containers use as a VPN gateway.
+const GATEWAY_IMAGES: &[&str] = &["gluetun", "transmission-openvpn", "openvpn-client", "vpn-client", "qbittorrentvpn", "delugevpn", "sabnzbdvpn"];
+/// gluetun's control server port inside its container.
+const GLUETUN_CONTROL_PORT: u16 = 8000;
+const API_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Running VPN gateway containers (gluetun and similar), the egress address and
+/// forwarded port gluetun's control server reports, and which containers share a
+/// gateway's network namespace (`network_mode: service:gluetun` or
+/// `container:...`) and so leave through the VPN.
+///
+/// The control server is reached on the gateway's own container addresses, then on
+/// `gluetun_api` (e.g. a port published on the host).
+pub async fn get_vpn_gateways(client: &Client, gluetun_api: &str) -> Value {
+    let containers = match docker::get("/containers/json").await {
+        Ok(containers) => containers,
+        Err(e) => return json!({ "docker_error": e, "gateways": {}, "routed_containers": {} }),
+    };
+    let containers = containers.as_array().map_or(&[][..], Vec::as_slice);
+    let name_of = |container: &Value| container.pointer("/Names/0").and_then(Value::as_str).unwrap_or_default().trim_start_matches('/').to_string();
+
+    let mut gateways = Map::new();
+    let mut gateway_ids = Vec::new();
+    for container in containers {
+        let image = container.get("Image").and_then(Value::as_str).unwrap_or_default();
+        if !GATEWAY_IMAGES.iter().any(|fragment| image.to_ascii_lowercase().contains(fragment)) {
+            continue;
+        }
+        let name = name_of(container);
+        let mut gateway = json!({
+            "image": image,
+            "state": container.get("State"),
+            "control_api": Value::Null,
+            "api_error": Value::Null,
+            "public_ip": Value::Null,
+            "country": Value::Null,
+            "region": Value::Null,
+            "vpn_status": Value::Null,
+            "forwarded_port": Value::Null,
+            "routed_containers": []
+        });
+        if image.contains("gluetun") {
+            query_gluetun(client, &mut gateway, &control_apis(container, gluetun_api)).await;
+        }
+        gateway_ids.push((container.get("Id").and_then(Value::as_str).unwrap_or_default().to_string(), name.clone()));
+        gateways.insert(name, gateway);
+    }
+
+    // Compose resolves service:NAME to container:ID; plain docker run keeps the name.
+    let mut routed_containers = Map::new();
+    for container in containers {
+        let Some(target) = container.pointer("/HostConfig/NetworkMode").and_then(Value::as_str).and_then(|mode| mode.strip_prefix("container:")) else {
+            continue;
+        };
+        let Some((_, gateway)) = gateway_ids.iter().find(|(id, name)| name == target || (target.len() >= 12 && id.starts_with(target))) else {
+            continue;
+        };
+        let name = name_of(container);
+        if let Some(Value::Array(routed)) = gateways.get_mut(gateway).and_then(|gateway| gateway.get_mut("routed_containers")) {
+            routed.push(json!(name));
+        }
+        routed_containers.insert(name, json!(gateway));
+    }
+
+    json!({ "docker_error": Value::Null, "gateways": gateways, "routed_containers": routed_containers })
+}
+
+/// The control server on each of the container's network addresses, then the
+/// configured one.
+fn control_apis(container: &Value, gluetun_api: &str) -> Vec<String> {
+    let mut apis: Vec<String> = container
+        .pointer("/NetworkSettings/Networks")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, network)| network.get("IPAddress").and_then(Value::as_str).filter(|address| !address.is_empty()))
+        .map(|address| format!("http://{}:{}", address, GLUETUN_CONTROL_PORT))
+        .collect();
+    apis.push(gluetun_api.trim_end_matches('/').to_string());
+    apis
+}
+
+/// Fills in the egress facts from the first control server that answers
+/// `/v1/publicip/ip`; the VPN status and forwarded port are best effort.
+async fn query_gluetun(client: &Client, gateway: &mut Value, apis: &[String]) {
+    let mut errors = Vec::new();
+    for api in apis {
+        let public_ip = match fetch(client, &format!("{}/v1/publicip/ip", api)).await {
+            Ok(public_ip) => public_ip,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        gateway["control_api"] = json!(api);
+        gateway["public_ip"] = json!(public_ip.get("public_ip").and_then(Value::as_str).filter(|address| !address.is_empty()));
+        gateway["country"] = json!(public_ip.get("country"));
+        gateway["region"] = json!(public_ip.get("region"));
+        // /v1/vpn/status since 3.39; /v1/openvpn/status before.
+        for path in ["/v1/vpn/status", "/v1/openvpn/status"] {
+            if let Ok(status) = fetch(client, &format!("{}{}", api, path)).await {
+                gateway["vpn_status"] = json!(status.get("status"));
+                break;
+            }
+        }
+        gateway["forwarded_port"] = json!(download_clients::gluetun_forwarded_port(client, api).await.ok().flatten());
+        return;
+    }
+    gateway["api_error"] = json!(Error::joined(errors));
+}
+
+async fn fetch(client: &Client, url: &str) -> Result<Value, Error> {
+    let response = client.get(url)?.timeout(API_TIMEOUT).send().await.map_err(|e| Error::http(url, &e))?;
+    if !response.status().is_success() {
+        return Err(Error::status(url, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| Error::http(url, &e))?;
+    serde_json::from_slice(&body).map_err(|e| Error::new(Code::InvalidData, url, format!("{}: {}", url, e)))
+}
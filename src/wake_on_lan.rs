@@ -0,0 +1,84 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::binaries::find_in_path;
+use crate::errors::Error;
+use crate::exec;
+
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+/// Where distributions install ethtool, which isn't on a regular user's PATH
+/// everywhere.
+const ETHTOOL_PATHS: &[&str] = &["/usr/sbin/ethtool", "/sbin/ethtool"];
+/// ethtool's Wake-on flag letters and what each wakes the machine on.
+const MODES: &[(char, &str)] = &[
+    ('p', "phy"),
+    ('u', "unicast"),
+    ('m', "multicast"),
+    ('b', "broadcast"),
+    ('a', "arp"),
+    ('g', "magic"),
+    ('s', "secureon"),
+    ('f', "filter"),
+];
+
+/// Wake-on-LAN per physical interface: the modes the NIC supports and the ones
+/// armed, from `ethtool <iface>`, and whether the device may wake the system
+/// from suspend at all (`power/wakeup` in sysfs). `armed` lists the interfaces a
+/// magic packet wakes, the usual suspend/wake setup.
+///
+/// Virtual interfaces have no `device` link in sysfs and are left out. A driver
+/// without Wake-on-LAN support prints no Wake-on lines, so its flags are null, as
+/// they are everywhere when ethtool isn't installed.
+pub async fn get_wake_on_lan() -> Value {
+    let ethtool = find_in_path("ethtool").or_else(|| ETHTOOL_PATHS.iter().map(Path::new).find(|path| path.exists()).map(Path::to_path_buf));
+
+    let mut names: Vec<String> = fs::read_dir(SYS_CLASS_NET_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("device").exists())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    let mut interfaces = Map::new();
+    let mut armed = Vec::new();
+    for name in names {
+        let device_wakeup = fs::read_to_string(Path::new(SYS_CLASS_NET_PATH).join(&name).join("device/power/wakeup")).ok().map(|value| value.trim().to_string());
+        let (supported_flags, flags, error) = match &ethtool {
+            None => (None, None, None),
+            Some(ethtool) => match exec::output(ethtool, [&name]).await {
+                Err(e) => (None, None, Some(Error::run(ethtool.display(), &e))),
+                Ok(output) if !output.status.success() => (None, None, Some(Error::exited("ethtool", &output))),
+                Ok(output) => {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let field = |key: &str| stdout.lines().find_map(|line| line.trim().strip_prefix(key)?.strip_prefix(':')).map(|value| value.trim().to_string());
+                    (field("Supports Wake-on"), field("Wake-on"), None)
+                }
+            },
+        };
+        if flags.as_deref().is_some_and(|flags| flags.contains('g')) {
+            armed.push(name.clone());
+        }
+        interfaces.insert(
+            name,
+            json!({
+                "supported_flags": supported_flags,
+                "flags": flags,
+                "supported": modes(supported_flags.as_deref()),
+                "enabled": modes(flags.as_deref()),
+                "magic_packet": flags.as_deref().map(|flags| flags.contains('g')),
+                "device_wakeup": device_wakeup,
+                "error": error
+            }),
+        );
+    }
+
+    json!({ "ethtool_installed": ethtool.is_some(), "interfaces": interfaces, "armed": armed })
+}
+
+/// The mode names of a flag string; `d` (disabled) has none.
+fn modes(flags: Option<&str>) -> Vec<&'static str> {
+    MODES.iter().filter(|(letter, _)| flags.is_some_and(|flags| flags.contains(*letter))).map(|(_, mode)| *mode).collect()
+}
@@ -0,0 +1,301 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::{Code, Error};
+use crate::timestamp::format_rfc3339;
+
+const NET_CLASS_DIR: &str = "/sys/class/net";
+/// Where userspace implementations (wireguard-go, boringtun) put their UAPI sockets.
+const UAPI_SOCKET_DIR: &str = "/var/run/wireguard";
+/// A peer rekeys every 2 minutes while traffic flows; after REJECT_AFTER_TIME
+/// (180 s) without a handshake its session is dead.
+pub const DEFAULT_STALE_AFTER_SECS: u64 = 180;
+
+/// A WireGuard device as read from the kernel or a UAPI socket.
+#[derive(Default)]
+struct Device {
+    public_key: Option<String>,
+    listen_port: Option<u16>,
+    peers: Vec<Peer>,
+}
+
+#[derive(Default)]
+struct Peer {
+    public_key: String,
+    endpoint: Option<String>,
+    allowed_ips: Vec<String>,
+    /// Unix seconds of the last handshake; None when there never was one.
+    last_handshake: Option<u64>,
+    persistent_keepalive: Option<u16>,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+/// Every WireGuard interface with its peers, their endpoints and allowed IPs, and
+/// how long ago each last completed a handshake. A peer is `stale` when that was
+/// more than `stale_after` seconds ago, or never: configured but not actually up.
+///
+/// Kernel interfaces are read over generic netlink, which needs CAP_NET_ADMIN;
+/// userspace ones over their UAPI socket. Private and preshared keys are never
+/// reported.
+pub fn get_wireguard(stale_after: u64) -> Value {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut interfaces = Map::new();
+    let mut stale_peers = Vec::new();
+    for (name, source) in interface_names() {
+        let device = match source {
+            "uapi" => uapi_device(&name),
+            _ => netlink::device(&name),
+        };
+        let mut report = json!({
+            "source": source,
+            "up": interface_up(&name),
+            "public_key": Value::Null,
+            "listen_port": Value::Null,
+            "error": Value::Null,
+            "peers": {}
+        });
+        match device {
+            Ok(device) => {
+                let mut peers = Map::new();
+                for peer in device.peers {
+                    let age = peer.last_handshake.map(|handshake| now.saturating_sub(handshake));
+                    let stale = age.is_none_or(|age| age > stale_after);
+                    if stale {
+                        stale_peers.push(format!("{}/{}", name, peer.public_key));
+                    }
+                    peers.insert(
+                        peer.public_key,
+                        json!({
+                            "endpoint": peer.endpoint,
+                            "allowed_ips": peer.allowed_ips,
+                            "last_handshake": peer.last_handshake.map(|secs| format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs))),
+                            "handshake_age_secs": age,
+                            "stale": stale,
+                            "persistent_keepalive": peer.persistent_keepalive,
+                            "rx_bytes": peer.rx_bytes,
+                            "tx_bytes": peer.tx_bytes
+                        }),
+                    );
+                }
+                report["public_key"] = json!(device.public_key);
+                report["listen_port"] = json!(device.listen_port);
+                report["peers"] = Value::Object(peers);
+            }
+            Err(e) => report["error"] = json!(e),
+        }
+        interfaces.insert(name, report);
+    }
+    json!({ "interfaces": interfaces, "stale_after_secs": stale_after, "stale_peers": stale_peers })
+}
+
+/// Kernel interfaces (DEVTYPE=wireguard) and UAPI sockets, sorted by name; an
+/// interface with a socket is a userspace one.
+fn interface_names() -> Vec<(String, &'static str)> {
+    let mut names: Vec<(String, &'static str)> = fs::read_dir(UAPI_SOCKET_DIR)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".sock").map(String::from))
+        .map(|name| (name, "uapi"))
+        .collect();
+    for entry in fs::read_dir(NET_CLASS_DIR).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let Some(name) = entry.file_name().to_str().map(String::from) else {
+            continue;
+        };
+        let kernel = fs::read_to_string(entry.path().join("uevent")).is_ok_and(|uevent| uevent.lines().any(|line| line == "DEVTYPE=wireguard"));
+        if kernel && !names.iter().any(|(known, _)| *known == name) {
+            names.push((name, "netlink"));
+        }
+    }
+    names.sort();
+    names
+}
+
+/// IFF_UP from the interface flags; WireGuard's operstate stays "unknown" when up.
+fn interface_up(name: &str) -> Option<bool> {
+    let flags = fs::read_to_string(format!("{}/{}/flags", NET_CLASS_DIR, name)).ok()?;
+    let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok()?;
+    Some(flags & 1 != 0)
+}
+
+/// Speaks the cross-platform UAPI (`get=1`), whose keys are hex.
+#[cfg(unix)]
+fn uapi_device(name: &str) -> Result<Device, Error> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let path = format!("{}/{}.sock", UAPI_SOCKET_DIR, name);
+    let mut stream = UnixStream::connect(&path).map_err(|e| Error::new(Code::of_io(&e), &path, format!("Cannot connect to {}: {}", path, e)))?;
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    stream.write_all(b"get=1\n\n").map_err(|e| Error::new(Code::of_io(&e), &path, format!("Cannot write to {}: {}", path, e)))?;
+    let mut device = Device::default();
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|e| Error::new(Code::of_io(&e), &path, format!("Cannot read from {}: {}", path, e)))?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let peer = device.peers.last_mut();
+        match (key, peer) {
+            ("errno", _) if value != "0" => return Err(Error::new(Code::Unknown, &path, format!("{}: errno {}", path, value))),
+            // The device's own public key isn't in the UAPI, only the private key it
+            // derives from, which is skipped; public_key starts a peer.
+            ("listen_port", _) => device.listen_port = value.parse().ok(),
+            ("public_key", _) => {
+                let public_key = hex_key(value).ok_or_else(|| Error::new(Code::InvalidData, &path, format!("Malformed key in UAPI output: {}", value)))?;
+                device.peers.push(Peer { public_key, ..Peer::default() })
+            }
+            ("endpoint", Some(peer)) => peer.endpoint = Some(value.to_string()),
+            ("allowed_ip", Some(peer)) => peer.allowed_ips.push(value.to_string()),
+            ("persistent_keepalive_interval", Some(peer)) => peer.persistent_keepalive = value.parse().ok().filter(|interval| *interval != 0),
+            ("rx_bytes", Some(peer)) => peer.rx_bytes = value.parse().unwrap_or(0),
+            ("tx_bytes", Some(peer)) => peer.tx_bytes = value.parse().unwrap_or(0),
+            ("last_handshake_time_sec", Some(peer)) => peer.last_handshake = value.parse().ok().filter(|secs| *secs != 0),
+            _ => {}
+        }
+    }
+    Ok(device)
+}
+
+#[cfg(not(unix))]
+fn uapi_device(name: &str) -> Result<Device, Error> {
+    Err(Error::new(Code::Unsupported, name, "WireGuard UAPI sockets are only supported on Unix"))
+}
+
+/// Keys are shown in base64, as `wg` and configuration files show them.
+fn hex_key(hex: &str) -> Option<String> {
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect();
+    bytes.map(|bytes| BASE64_STANDARD.encode(bytes))
+}
+
+/// The kernel's generic netlink interface (`WG_CMD_GET_DEVICE`), as `wg show` uses
+/// it, through wireguard-uapi.
+#[cfg(target_os = "linux")]
+mod netlink {
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use std::io;
+    use wireguard_uapi::err::{ConnectError, GetDeviceError};
+    use wireguard_uapi::{get, DeviceInterface, WgSocket};
+
+    use super::{Device, Peer};
+    use crate::errors::{Code, Error};
+
+    pub fn device(name: &str) -> Result<Device, Error> {
+        let mut socket = WgSocket::connect().map_err(|e| match e {
+            ConnectError::ResolveFamilyError(e) if os_error(&e).is_some_and(|e| e.kind() == io::ErrorKind::NotFound) => {
+                Error::new(Code::NotFound, "wireguard", "The wireguard kernel module isn't loaded")
+            }
+            ConnectError::ResolveFamilyError(e) => Error::new(os_code(&e), "wireguard", format!("Cannot resolve the wireguard netlink family: {}", e)),
+            ConnectError::NlError(e) => Error::new(os_code(&e), "netlink", format!("Cannot open a netlink socket: {}", e)),
+        })?;
+        let device = socket.get_device(DeviceInterface::from_name(name)).map_err(|e| match e {
+            // The kernel's error reply, which wireguard-uapi doesn't pass on, is
+            // EPERM here: the interface was just listed, so it exists.
+            GetDeviceError::AccessError => Error::new(Code::PermissionDenied, name, format!("{}: reading WireGuard devices needs CAP_NET_ADMIN (run as root)", name)),
+            GetDeviceError::NlError(e) => Error::new(os_code(&e), name, format!("{}: {}", name, e)),
+            GetDeviceError::ParseDeviceError(e) => Error::new(Code::InvalidData, name, format!("{}: {}", name, e)),
+            e => Error::new(Code::Unknown, name, format!("{}: {}", name, e)),
+        })?;
+        Ok(device_of(device))
+    }
+
+    /// The OS error behind a netlink one: an error reply's errno, or a socket error.
+    fn os_error<T, P>(error: &neli::err::NlError<T, P>) -> Option<io::Error> {
+        match error {
+            neli::err::NlError::Nlmsgerr(reply) => Some(io::Error::from_raw_os_error(-reply.error)),
+            neli::err::NlError::Wrapped(neli::err::WrappedError::IOError(e)) => Some(io::Error::new(e.kind(), e.to_string())),
+            _ => None,
+        }
+    }
+
+    fn os_code<T, P>(error: &neli::err::NlError<T, P>) -> Code {
+        os_error(error).map_or(Code::Unknown, |e| Code::of_io(&e))
+    }
+
+    pub(super) fn device_of(device: get::Device) -> Device {
+        Device {
+            public_key: device.public_key.map(|key| BASE64_STANDARD.encode(key)),
+            listen_port: Some(device.listen_port),
+            peers: device
+                .peers
+                .into_iter()
+                .map(|peer| Peer {
+                    public_key: BASE64_STANDARD.encode(peer.public_key),
+                    endpoint: peer.endpoint.map(|endpoint| endpoint.to_string()),
+                    allowed_ips: peer.allowed_ips.iter().map(|allowed_ip| format!("{}/{}", allowed_ip.ipaddr, allowed_ip.cidr_mask)).collect(),
+                    // All zero when there was no handshake yet.
+                    last_handshake: Some(peer.last_handshake_time.as_secs()).filter(|secs| *secs != 0),
+                    persistent_keepalive: Some(peer.persistent_keepalive_interval).filter(|interval| *interval != 0),
+                    rx_bytes: peer.rx_bytes,
+                    tx_bytes: peer.tx_bytes,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod netlink {
+    use super::Device;
+    use crate::errors::{Code, Error};
+
+    pub fn device(name: &str) -> Result<Device, Error> {
+        Err(Error::new(Code::Unsupported, name, "Kernel WireGuard devices can only be read on Linux"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_keys_are_shown_in_base64() {
+        let hex = "e84b5a6d2717c1003a13b431570353dbaca9146cf150c5f8575680feba52027a";
+        assert_eq!(hex_key(hex).as_deref(), Some("6EtabScXwQA6E7QxVwNT26ypFGzxUMX4V1aA/rpSAno="));
+        assert_eq!(hex_key("zz"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn kernel_devices_are_reported_like_uapi_ones() {
+        use wireguard_uapi::get;
+
+        let peer = |key: u8, handshake: u64, keepalive: u16| get::Peer {
+            public_key: [key; 32],
+            preshared_key: [0; 32],
+            endpoint: Some("203.0.113.7:51820".parse().unwrap()),
+            persistent_keepalive_interval: keepalive,
+            last_handshake_time: Duration::from_secs(handshake),
+            rx_bytes: 10,
+            tx_bytes: 20,
+            allowed_ips: vec!["10.0.0.2/32".parse().unwrap(), "fd00::2/128".parse().unwrap()],
+            protocol_version: 1,
+        };
+        let device = netlink::device_of(get::Device {
+            ifindex: 4,
+            ifname: "wg0".into(),
+            private_key: Some([9; 32]),
+            public_key: Some([1; 32]),
+            listen_port: 51820,
+            fwmark: 0,
+            peers: vec![peer(2, 1_700_000_000, 25), peer(3, 0, 0)],
+        });
+        assert_eq!(device.public_key.as_deref(), Some(BASE64_STANDARD.encode([1; 32]).as_str()));
+        assert_eq!(device.listen_port, Some(51820));
+        assert_eq!(device.peers[0].public_key, BASE64_STANDARD.encode([2; 32]));
+        assert_eq!(device.peers[0].endpoint.as_deref(), Some("203.0.113.7:51820"));
+        assert_eq!(device.peers[0].allowed_ips, ["10.0.0.2/32", "fd00::2/128"]);
+        assert_eq!(device.peers[0].last_handshake, Some(1_700_000_000));
+        assert_eq!(device.peers[0].persistent_keepalive, Some(25));
+        assert_eq!((device.peers[0].rx_bytes, device.peers[0].tx_bytes), (10, 20));
+        assert_eq!(device.peers[1].last_handshake, None);
+        assert_eq!(device.peers[1].persistent_keepalive, None);
+    }
+}
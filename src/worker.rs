@@ -0,0 +1,105 @@
+//! Runs collectors that touch subsystems prone to hanging (statvfs on a dead NFS
+//! server can block in D state indefinitely) in a child process of this binary,
+//! with resource limits and a wall-clock timeout. A hung worker is killed and the
+//! run moves on without its section.
+
+#[cfg(unix)]
+use rlimit::Resource;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::process::Stdio;
+use std::time::Duration;
+
+use crate::capability;
+use crate::cli::Args;
+use crate::collectors::{self, Context};
+use crate::facts::Section;
+
+/// Hidden subcommand a worker is started with: `__worker <COLLECTOR> <GATHER ARGS>...`.
+pub const SUBCOMMAND: &str = "__worker";
+
+/// Address space a worker may map; the collectors that run isolated need a few MiB.
+const MEMORY_LIMIT_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Returned when a worker didn't finish within `--worker-timeout`.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub secs: u64,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "killed after {} s", self.secs)
+    }
+}
+
+impl Error for TimedOut {}
+
+/// Collects `name` in a worker process started with the same gather arguments.
+pub async fn collect(name: &str, context: &Context<'_>) -> Result<Section, Box<dyn Error>> {
+    let secs = context.args.worker_timeout;
+    let mut command = tokio::process::Command::new(std::env::current_exe()?);
+    command
+        .arg(SUBCOMMAND)
+        .arg(name)
+        .args(&context.args.argv)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // SAFETY: only async-signal-safe calls (setrlimit) run between fork and exec.
+    #[cfg(unix)]
+    unsafe {
+        command.pre_exec(move || {
+            set_limit(Resource::AS, MEMORY_LIMIT_BYTES)?;
+            set_limit(Resource::CPU, secs + 1)?;
+            set_limit(Resource::CORE, 0)
+        });
+    }
+    tracing::debug!("Starting a worker for {} (killed after {} s)", name, secs);
+    let child = command.spawn().map_err(|e| format!("Cannot start {} worker: {}", name, e))?;
+
+    // Dropping the child on timeout sends SIGKILL. A worker stuck in D state only
+    // dies once the kernel returns from the blocking call, so it isn't waited for.
+    let output = match tokio::time::timeout(Duration::from_secs(secs), child.wait_with_output()).await {
+        Ok(output) => output?,
+        Err(_) => return Err(Box::new(TimedOut { secs })),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} worker failed ({}): {}", name, output.status, stderr.trim()).into());
+    }
+    // The worker's own diagnostics.
+    io::stderr().write_all(&output.stderr)?;
+    let result: Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("Invalid output from {} worker: {}", name, e))?;
+    if let Some(violations) = result.get("violations").and_then(Value::as_array) {
+        capability::record_violations(violations.iter().filter_map(Value::as_str).map(String::from));
+    }
+    let strings = |key: &str| result.get(key).and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).map(String::from).collect::<Vec<_>>();
+    capability::record_usage(strings("commands"), strings("endpoints"));
+    result
+        .get("section")
+        .and_then(Section::from_value)
+        .ok_or_else(|| format!("Invalid output from {} worker: no section", name).into())
+}
+
+/// The worker side: collects one section and prints it for the parent.
+pub async fn run(collector: String, args: Args) -> Result<(), Box<dyn Error>> {
+    capability::restrict(args.no_exec, args.offline);
+    let mut context = Context::new(&args)?;
+    context.isolate = false;
+    let section = collectors::collect(&collector, &context).await?;
+    let (commands, endpoints) = capability::take_usage();
+    println!(
+        "{}",
+        json!({ "section": section.to_value(), "violations": capability::take_violations(), "commands": commands, "endpoints": endpoints })
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_limit(resource: Resource, limit: u64) -> io::Result<()> {
+    rlimit::setrlimit(resource, limit, limit)
+}
@@ -0,0 +1,25 @@
+//! Output to a reader that has already gone away, as with `| head`, ends the
+//! command quietly instead of failing it.
+
+use std::process::{Command, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_saltbox-facts");
+
+/// Runs the binary with stdout a pipe whose reading end is already closed, so every
+/// write fails with EPIPE, and returns its exit code and stderr.
+fn run_into_closed_pipe(args: &[&str]) -> (Option<i32>, String) {
+    let (reader, writer) = std::io::pipe().unwrap();
+    drop(reader);
+    let output = Command::new(BIN).args(args).stdout(Stdio::from(writer)).stderr(Stdio::piped()).output().unwrap();
+    (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn collector_list_ends_quietly() {
+    assert_eq!(run_into_closed_pipe(&["--list-collectors"]), (Some(0), String::new()));
+}
+
+#[test]
+fn completion_script_ends_quietly() {
+    assert_eq!(run_into_closed_pipe(&["completions", "bash"]), (Some(0), String::new()));
+}
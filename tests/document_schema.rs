@@ -0,0 +1,54 @@
+//! Documents printed by a gather run validate against what `schema` publishes.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_saltbox-facts");
+
+/// Runs the binary and returns its stdout, failing the test on exit statuses
+/// other than those a document is printed with.
+fn output(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(BIN).args(args).output().unwrap();
+    assert!(matches!(output.status.code(), Some(0 | 3 | 4)), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("saltbox-facts-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Validates the document `gather` prints against `schema --schema-version <version>`.
+/// Runs are offline so the lookups fail the same way everywhere, with error fields.
+fn assert_valid(name: &str, version: &str, gather: &[&str]) {
+    let schema = temp_file(&format!("{}-schema.json", name), &output(&["schema", "--schema-version", version]));
+    let mut args = vec!["--no-config", "--offline", "--schema-version", version];
+    args.extend(gather);
+    let document = temp_file(&format!("{}-document.json", name), &output(&args));
+
+    let validate = Command::new(BIN).arg("validate").arg("--schema").arg(&schema).arg(&document).output().unwrap();
+    std::fs::remove_file(schema).unwrap();
+    std::fs::remove_file(document).unwrap();
+    assert!(validate.status.success(), "{}", String::from_utf8_lossy(&validate.stdout));
+}
+
+#[test]
+fn default_document_matches_schema() {
+    assert_valid("default", "2", &[]);
+}
+
+#[test]
+fn every_collector_matches_schema() {
+    assert_valid("all", "2", &["--facts", "all"]);
+}
+
+#[test]
+fn version_1_document_matches_its_schema() {
+    assert_valid("v1", "1", &["--facts", "all"]);
+}
+
+#[test]
+fn deterministic_document_matches_schema() {
+    assert_valid("deterministic", "2", &["--facts", "all", "--deterministic"]);
+}
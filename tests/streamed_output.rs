@@ -0,0 +1,26 @@
+//! A document written a section at a time holds what the assembled one does.
+
+use serde_json::Value;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_saltbox-facts");
+
+fn document(args: &[&str]) -> Value {
+    let output = Command::new(BIN).args(["--no-config", "--offline", "--deterministic", "--facts", "all"]).args(args).output().unwrap();
+    assert!(matches!(output.status.code(), Some(0 | 3 | 4)), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn streamed_document_matches_assembled_one() {
+    // Derived facts need the whole document, so any definitions file, even an empty
+    // one, makes the run assemble it before printing.
+    let definitions = std::env::temp_dir().join(format!("saltbox-facts-{}-no-definitions.json", std::process::id()));
+    std::fs::write(&definitions, "{}").unwrap();
+    for version in ["1", "2"] {
+        let streamed = document(&["--schema-version", version]);
+        let assembled = document(&["--schema-version", version, "--derived-facts", definitions.to_str().unwrap()]);
+        assert_eq!(streamed, assembled, "schema version {}", version);
+    }
+    std::fs::remove_file(definitions).unwrap();
+}